@@ -0,0 +1,253 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains the address book, which maps names to address prefixes
+//!
+//! The address book is a YAML mapping of names to prefixes or addresses, read from the file named
+//! by the SABRE_ADDRESS_BOOK environment variable, or from ~/.sabre/address_book.yaml. Wherever
+//! the CLI accepts a prefix or address, `@name` is replaced by the entry for `name`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use sabre_sdk::protocol::namespaces::validate_prefix;
+use sabre_sdk::protocol::state::NamespaceRegistry;
+use yaml_rust::{yaml::Hash, Yaml, YamlEmitter, YamlLoader};
+
+use crate::error::CliError;
+use crate::paths;
+
+/// The environment variable which names the address book file
+pub const ADDRESS_BOOK_ENV_VAR: &str = "SABRE_ADDRESS_BOOK";
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct AddressBook {
+    path: PathBuf,
+    entries: BTreeMap<String, String>,
+}
+
+impl AddressBook {
+    /// Loads the user's address book, which is empty if the file does not exist yet.
+    pub fn load() -> Result<AddressBook, CliError> {
+        let path = match std::env::var(ADDRESS_BOOK_ENV_VAR) {
+            Ok(path) => PathBuf::from(path),
+            Err(_) => paths::config_file("address_book.yaml")?,
+        };
+
+        AddressBook::load_from(&path)
+    }
+
+    fn load_from(path: &Path) -> Result<AddressBook, CliError> {
+        let entries = if path.exists() {
+            let contents = fs::read_to_string(path).map_err(|e| {
+                CliError::User(format!(
+                    "Could not load address book \"{}\": {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+            parse_entries(&contents).map_err(|msg| {
+                CliError::User(format!(
+                    "Malformed address book \"{}\": {}",
+                    path.display(),
+                    msg
+                ))
+            })?
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(AddressBook {
+            path: path.to_path_buf(),
+            entries,
+        })
+    }
+
+    pub fn save(&self) -> Result<(), CliError> {
+        let mut hash = Hash::new();
+        for (name, address) in &self.entries {
+            hash.insert(Yaml::String(name.clone()), Yaml::String(address.clone()));
+        }
+        let mut contents = String::new();
+        YamlEmitter::new(&mut contents)
+            .dump(&Yaml::Hash(hash))
+            .map_err(|e| CliError::User(format!("Unable to write address book: {:?}", e)))?;
+        contents.push('\n');
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, contents).map_err(|e| {
+            CliError::User(format!(
+                "Could not save address book \"{}\": {}",
+                self.path.display(),
+                e
+            ))
+        })
+    }
+
+    pub fn entries(&self) -> &BTreeMap<String, String> {
+        &self.entries
+    }
+
+    /// Adds or replaces the entry for `name`.
+    pub fn add(&mut self, name: &str, address: &str) -> Result<(), CliError> {
+        if name.is_empty() || name.starts_with('@') {
+            return Err(CliError::User(format!(
+                "invalid address book name: {}",
+                name
+            )));
+        }
+        if address.is_empty() {
+            return Err(CliError::User(format!(
+                "empty address or prefix for {}",
+                name
+            )));
+        }
+        validate_prefix(address).map_err(|err| {
+            CliError::User(format!("invalid address or prefix for {}: {}", name, err))
+        })?;
+
+        self.entries
+            .insert(name.to_string(), address.to_ascii_lowercase());
+        Ok(())
+    }
+
+    /// Adds an entry naming each namespace after the contracts with write permission for it,
+    /// keeping existing entries. Returns the names added.
+    pub fn populate(&mut self, namespace_registries: &[NamespaceRegistry]) -> Vec<String> {
+        let mut added = Vec::new();
+        for registry in namespace_registries {
+            for permission in registry.permissions().iter().filter(|p| p.write()) {
+                let name = permission.contract_name();
+                if !self.entries.contains_key(name) {
+                    self.entries
+                        .insert(name.to_string(), registry.namespace().to_string());
+                    added.push(name.to_string());
+                }
+            }
+        }
+        added
+    }
+
+    /// Returns the value with `@name` replaced by the entry for `name`.
+    pub fn resolve(&self, value: &str) -> Result<String, CliError> {
+        match value.strip_prefix('@') {
+            Some(name) => self
+                .entries
+                .get(name)
+                .cloned()
+                .ok_or_else(|| CliError::User(format!("'{}' is not in the address book", name))),
+            None => Ok(value.to_string()),
+        }
+    }
+}
+
+/// Returns the value with `@name` replaced by the entry for `name` in the user's address book,
+/// which is only loaded if it is needed.
+pub fn resolve(value: &str) -> Result<String, CliError> {
+    if value.starts_with('@') {
+        AddressBook::load()?.resolve(value)
+    } else {
+        Ok(value.to_string())
+    }
+}
+
+fn parse_entries(contents: &str) -> Result<BTreeMap<String, String>, String> {
+    let docs = YamlLoader::load_from_str(contents).map_err(|err| err.to_string())?;
+    let hash = match docs.get(0) {
+        None | Some(Yaml::Null) => return Ok(BTreeMap::new()),
+        Some(doc) => doc
+            .as_hash()
+            .ok_or_else(|| "expected a mapping of names to addresses".to_string())?,
+    };
+
+    hash.iter()
+        .map(|(name, address)| {
+            let name = name
+                .as_str()
+                .ok_or_else(|| "contains non-string names".to_string())?;
+            let address = address
+                .as_str()
+                .ok_or_else(|| format!("address of {} must be a string", name))?;
+            Ok((name.to_string(), address.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use sabre_sdk::protocol::state::{NamespaceRegistryBuilder, PermissionBuilder};
+
+    use super::*;
+
+    #[test]
+    // Asserts that entries survive saving and loading, and that @name is resolved to its entry
+    fn test_cli_address_book() {
+        let path = std::env::temp_dir().join("sabre_test_cli_address_book.yaml");
+        let _ = fs::remove_file(&path);
+
+        let mut book = AddressBook::load_from(&path).unwrap();
+        book.add("intkey", "1CF126").unwrap();
+        assert!(book.add("bad", "xyz").is_err());
+        book.save().unwrap();
+
+        let book = AddressBook::load_from(&path).unwrap();
+        assert_eq!(book.resolve("@intkey").unwrap(), "1cf126");
+        assert_eq!(book.resolve("cad11d").unwrap(), "cad11d");
+        assert!(book.resolve("@other").is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    // Asserts that namespaces are named after the contracts which may write to them, without
+    // replacing existing entries
+    fn test_cli_address_book_populate() {
+        let registries = vec![NamespaceRegistryBuilder::new()
+            .with_namespace("1cf126".to_string())
+            .with_owners(vec!["owner".to_string()])
+            .with_permissions(
+                [
+                    ("intkey_multiply", true),
+                    ("reader", false),
+                    ("existing", true),
+                ]
+                .iter()
+                .map(|(name, write)| {
+                    PermissionBuilder::new()
+                        .with_contract_name(name.to_string())
+                        .with_read(true)
+                        .with_write(*write)
+                        .build()
+                        .unwrap()
+                })
+                .collect(),
+            )
+            .build()
+            .unwrap()];
+
+        let mut book = AddressBook {
+            path: PathBuf::new(),
+            entries: BTreeMap::new(),
+        };
+        book.add("existing", "cad11d").unwrap();
+
+        assert_eq!(book.populate(&registries), vec!["intkey_multiply"]);
+        assert_eq!(book.resolve("@intkey_multiply").unwrap(), "1cf126");
+        assert_eq!(book.resolve("@existing").unwrap(), "cad11d");
+    }
+}
@@ -0,0 +1,348 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains the report of the contracts which may write to a set of namespaces, and the audit
+//! log of the Sabre transactions committed to the chain
+
+use sabre_sdk::protocol::payload::{Action, SabrePayload};
+use sabre_sdk::protocol::permissions::Access;
+use sabre_sdk::protocol::state::{ContractRegistry, NamespaceRegistry};
+use sabre_sdk::protos::FromBytes;
+
+use crate::blocks::CommittedTransaction;
+use crate::error::CliError;
+
+/// A contract with write permission for a namespace
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct WriteAccess {
+    /// The namespace the permission is granted on
+    pub namespace: String,
+    pub contract: String,
+    /// The registered versions of the contract, which all share the permission
+    pub versions: Vec<String>,
+    /// The owners of the contract's registry, who may upload new versions
+    pub owners: Vec<String>,
+    /// Whether any owner of the contract's registry is not an administrator
+    pub non_admin_owners: bool,
+}
+
+/// Returns the write permissions granted on namespaces which overlap any of the prefixes, in the
+/// order of the namespace registries.
+pub fn find_write_access(
+    prefixes: &[String],
+    namespace_registries: &[NamespaceRegistry],
+    contract_registries: &[ContractRegistry],
+    administrators: &[String],
+) -> Vec<WriteAccess> {
+    namespace_registries
+        .iter()
+        .filter(|registry| {
+            prefixes.iter().any(|prefix| {
+                registry.namespace().starts_with(prefix.as_str())
+                    || prefix.starts_with(registry.namespace().as_str())
+            })
+        })
+        .flat_map(|registry| {
+            registry
+                .permissions()
+                .iter()
+                .filter(|permission| permission.write())
+                .map(move |permission| {
+                    let contract_registry = contract_registries.iter().find(|contract_registry| {
+                        contract_registry.name() == permission.contract_name()
+                    });
+                    let versions = contract_registry
+                        .map(|contract_registry| {
+                            contract_registry
+                                .versions()
+                                .iter()
+                                .map(|version| version.version().to_string())
+                                .collect()
+                        })
+                        .unwrap_or_default();
+                    let owners = contract_registry
+                        .map(|contract_registry| contract_registry.owners().to_vec())
+                        .unwrap_or_default();
+                    let non_admin_owners =
+                        owners.iter().any(|owner| !administrators.contains(owner));
+
+                    WriteAccess {
+                        namespace: registry.namespace().to_string(),
+                        contract: permission.contract_name().to_string(),
+                        versions,
+                        owners,
+                        non_admin_owners,
+                    }
+                })
+        })
+        .collect()
+}
+
+/// A committed Sabre transaction, and the entities its action affected
+#[derive(Serialize, Debug, Default, PartialEq, Eq)]
+pub struct AuditRecord {
+    pub block_num: u64,
+    pub block_id: String,
+    pub transaction_id: String,
+    /// The public key which signed the transaction
+    pub signer: String,
+    pub action: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contract: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    /// The owners an action sets
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owners: Option<Vec<String>>,
+    /// The access a permission action grants
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub permission: Option<String>,
+}
+
+/// Decodes the action of a committed Sabre transaction into its audit record.
+pub fn audit_record(transaction: &CommittedTransaction) -> Result<AuditRecord, CliError> {
+    let payload = SabrePayload::from_bytes(&transaction.payload).map_err(|err| {
+        CliError::User(format!(
+            "Unable to decode the payload of transaction {}: {}",
+            transaction.id, err
+        ))
+    })?;
+
+    let record = AuditRecord {
+        block_num: transaction.block_num,
+        block_id: transaction.block_id.clone(),
+        transaction_id: transaction.id.clone(),
+        signer: transaction.signer_public_key.clone(),
+        ..AuditRecord::default()
+    };
+
+    Ok(match payload.action() {
+        Action::CreateContract(action) => AuditRecord {
+            action: "create_contract",
+            contract: Some(action.name().into()),
+            version: Some(action.version().into()),
+            ..record
+        },
+        Action::DeleteContract(action) => AuditRecord {
+            action: "delete_contract",
+            contract: Some(action.name().into()),
+            version: Some(action.version().into()),
+            ..record
+        },
+        Action::ExecuteContract(action) => AuditRecord {
+            action: "execute_contract",
+            contract: Some(action.name().into()),
+            version: Some(action.version().into()),
+            ..record
+        },
+        Action::CreateContractRegistry(action) => AuditRecord {
+            action: "create_contract_registry",
+            contract: Some(action.name().into()),
+            owners: Some(action.owners().to_vec()),
+            ..record
+        },
+        Action::DeleteContractRegistry(action) => AuditRecord {
+            action: "delete_contract_registry",
+            contract: Some(action.name().into()),
+            ..record
+        },
+        Action::UpdateContractRegistryOwners(action) => AuditRecord {
+            action: "update_contract_registry_owners",
+            contract: Some(action.name().into()),
+            owners: Some(action.owners().to_vec()),
+            ..record
+        },
+        Action::CreateNamespaceRegistry(action) => AuditRecord {
+            action: "create_namespace_registry",
+            namespace: Some(action.namespace().into()),
+            owners: Some(action.owners().to_vec()),
+            ..record
+        },
+        Action::DeleteNamespaceRegistry(action) => AuditRecord {
+            action: "delete_namespace_registry",
+            namespace: Some(action.namespace().into()),
+            ..record
+        },
+        Action::UpdateNamespaceRegistryOwners(action) => AuditRecord {
+            action: "update_namespace_registry_owners",
+            namespace: Some(action.namespace().into()),
+            owners: Some(action.owners().to_vec()),
+            ..record
+        },
+        Action::CreateNamespaceRegistryPermission(action) => AuditRecord {
+            action: "create_namespace_registry_permission",
+            contract: Some(action.contract_name().into()),
+            namespace: Some(action.namespace().into()),
+            permission: Some(
+                Access {
+                    read: action.read(),
+                    write: action.write(),
+                }
+                .to_string(),
+            ),
+            ..record
+        },
+        Action::DeleteNamespaceRegistryPermission(action) => AuditRecord {
+            action: "delete_namespace_registry_permission",
+            contract: Some(action.contract_name().into()),
+            namespace: Some(action.namespace().into()),
+            ..record
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use sabre_sdk::protocol::payload::{
+        CreateNamespaceRegistryPermissionActionBuilder, UpdateContractRegistryOwnersActionBuilder,
+    };
+    use sabre_sdk::protocol::state::{
+        ContractRegistryBuilder, NamespaceRegistryBuilder, PermissionBuilder, VersionBuilder,
+    };
+    use sabre_sdk::protos::IntoBytes;
+
+    use super::*;
+
+    #[test]
+    // Asserts that only write permissions on overlapping namespaces are reported, with the
+    // versions and owners of each contract
+    fn test_cli_find_write_access() {
+        let permission = |contract: &str, write: bool| {
+            PermissionBuilder::new()
+                .with_contract_name(contract.to_string())
+                .with_read(true)
+                .with_write(write)
+                .build()
+                .unwrap()
+        };
+        let namespace_registries = vec![
+            NamespaceRegistryBuilder::new()
+                .with_namespace("1cf126".to_string())
+                .with_owners(vec!["admin".to_string()])
+                .with_permissions(vec![
+                    permission("intkey_multiply", true),
+                    permission("reader", false),
+                ])
+                .build()
+                .unwrap(),
+            NamespaceRegistryBuilder::new()
+                .with_namespace("cad11d".to_string())
+                .with_owners(vec!["admin".to_string()])
+                .with_permissions(vec![permission("other", true)])
+                .build()
+                .unwrap(),
+        ];
+        let contract_registries = vec![ContractRegistryBuilder::new()
+            .with_name("intkey_multiply".to_string())
+            .with_versions(vec![VersionBuilder::new()
+                .with_version("1.0".to_string())
+                .with_contract_sha512("sha512".to_string())
+                .with_creator("creator".to_string())
+                .build()
+                .unwrap()])
+            .with_owners(vec!["admin".to_string(), "owner".to_string()])
+            .build()
+            .unwrap()];
+
+        let report = find_write_access(
+            &["1cf1".to_string()],
+            &namespace_registries,
+            &contract_registries,
+            &["admin".to_string()],
+        );
+
+        assert_eq!(
+            report,
+            vec![WriteAccess {
+                namespace: "1cf126".into(),
+                contract: "intkey_multiply".into(),
+                versions: vec!["1.0".into()],
+                owners: vec!["admin".into(), "owner".into()],
+                non_admin_owners: true,
+            }]
+        );
+    }
+
+    #[test]
+    // Asserts that each committed transaction is recorded with its signer, its action, and the
+    // entities the action affects
+    fn test_cli_audit_record() {
+        let transaction = |payload: SabrePayload| CommittedTransaction {
+            block_num: 7,
+            block_id: "block".into(),
+            id: "transaction".into(),
+            signer_public_key: "signer".into(),
+            payload: payload.into_bytes().unwrap(),
+        };
+
+        let record = audit_record(&transaction(
+            UpdateContractRegistryOwnersActionBuilder::new()
+                .with_name("intkey_multiply".into())
+                .with_owners(vec!["owner".into()])
+                .into_payload_builder()
+                .unwrap()
+                .build()
+                .unwrap(),
+        ))
+        .unwrap();
+        assert_eq!(
+            record,
+            AuditRecord {
+                block_num: 7,
+                block_id: "block".into(),
+                transaction_id: "transaction".into(),
+                signer: "signer".into(),
+                action: "update_contract_registry_owners",
+                contract: Some("intkey_multiply".into()),
+                owners: Some(vec!["owner".into()]),
+                ..AuditRecord::default()
+            }
+        );
+
+        let record = audit_record(&transaction(
+            CreateNamespaceRegistryPermissionActionBuilder::new()
+                .with_namespace("1cf126".into())
+                .with_contract_name("intkey_multiply".into())
+                .with_read(true)
+                .into_payload_builder()
+                .unwrap()
+                .build()
+                .unwrap(),
+        ))
+        .unwrap();
+        assert_eq!(
+            serde_json::to_string(&record).unwrap(),
+            "{\"block_num\":7,\"block_id\":\"block\",\"transaction_id\":\"transaction\",\
+             \"signer\":\"signer\",\"action\":\"create_namespace_registry_permission\",\
+             \"contract\":\"intkey_multiply\",\"namespace\":\"1cf126\",\"permission\":\"read\"}"
+        );
+
+        assert!(audit_record(&CommittedTransaction {
+            payload: b"not a payload".to_vec(),
+            ..transaction(
+                UpdateContractRegistryOwnersActionBuilder::new()
+                    .with_name("intkey_multiply".into())
+                    .with_owners(vec!["owner".into()])
+                    .into_payload_builder()
+                    .unwrap()
+                    .build()
+                    .unwrap()
+            )
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,283 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains the packing of transactions into batches small enough for validators to accept
+
+use cylinder::Signer;
+use protobuf::rt::{compute_raw_varint64_size, tag_size};
+use sabre_sdk::protocol::size::{
+    estimate_transaction, DEFAULT_MAX_BATCH_LIST_BYTES, DEFAULT_MAX_BATCH_TRANSACTIONS,
+};
+use sawtooth::transact::protocol::{
+    batch::{Batch, BatchBuilder},
+    transaction::Transaction,
+};
+
+use crate::error::CliError;
+
+/// The length of a hex encoded secp256k1 public key, which the batch signer's key is measured as
+const PUBLIC_KEY_HEX_LENGTH: u64 = 66;
+
+/// The length of a hex encoded secp256k1 signature, which the batch signature is measured as
+const SIGNATURE_HEX_LENGTH: u64 = 128;
+
+/// The limits on the size of each batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchLimits {
+    /// The most bytes of a batch list holding only the batch, as encoded
+    pub max_bytes: usize,
+    /// The most transactions in a batch
+    pub max_transactions: usize,
+}
+
+impl Default for BatchLimits {
+    fn default() -> Self {
+        BatchLimits {
+            max_bytes: DEFAULT_MAX_BATCH_LIST_BYTES,
+            max_transactions: DEFAULT_MAX_BATCH_TRANSACTIONS,
+        }
+    }
+}
+
+/// Splits the transactions into groups within the limits, each of which can be built into a
+/// batch.
+///
+/// The transactions keep their order, both within and across groups, so a transaction is never
+/// placed in a batch before a transaction it depends on.
+pub fn pack_transactions(
+    transactions: Vec<Transaction>,
+    limits: &BatchLimits,
+) -> Result<Vec<Vec<Transaction>>, CliError> {
+    if limits.max_transactions == 0 {
+        return Err(CliError::User(
+            "batches must allow at least one transaction".into(),
+        ));
+    }
+
+    let mut groups = Vec::new();
+    let mut group: Vec<Transaction> = Vec::new();
+    let mut group_size = EncodedBatchSize::default();
+    for transaction in transactions {
+        let size = transaction_size(&transaction)?;
+        let alone = EncodedBatchSize::default().with(&transaction, size).bytes();
+        if alone > limits.max_bytes {
+            return Err(CliError::User(format!(
+                "a batch of transaction {} alone is {} bytes, which exceeds the batch limit of \
+                 {} bytes",
+                transaction.header_signature(),
+                alone,
+                limits.max_bytes
+            )));
+        }
+
+        let grown = group_size.with(&transaction, size);
+        if !group.is_empty()
+            && (group.len() == limits.max_transactions || grown.bytes() > limits.max_bytes)
+        {
+            groups.push(std::mem::take(&mut group));
+            group_size = EncodedBatchSize::default().with(&transaction, size);
+        } else {
+            group_size = grown;
+        }
+
+        group.push(transaction);
+    }
+    if !group.is_empty() {
+        groups.push(group);
+    }
+
+    Ok(groups)
+}
+
+/// Packs the transactions into as few batches as the limits allow, in order.
+pub fn build_batches(
+    transactions: Vec<Transaction>,
+    limits: &BatchLimits,
+    signer: &dyn Signer,
+) -> Result<Vec<Batch>, CliError> {
+    pack_transactions(transactions, limits)?
+        .into_iter()
+        .map(|group| {
+            BatchBuilder::new()
+                .with_transactions(group)
+                .build(signer)
+                .map_err(CliError::from)
+        })
+        .collect()
+}
+
+/// Returns the size of the transaction's message, as encoded
+fn transaction_size(transaction: &Transaction) -> Result<u64, CliError> {
+    Ok(estimate_transaction(transaction)?.bytes as u64)
+}
+
+/// Returns the encoded size of a length delimited protobuf field holding `length` bytes
+fn field_size(field_number: u32, length: u64) -> u64 {
+    u64::from(tag_size(field_number) + compute_raw_varint64_size(length)) + length
+}
+
+/// The encoded size of a batch list holding a single batch, tracked as transactions are added.
+/// The batch is measured before it is signed, with a header and signature the size of a
+/// secp256k1 signer's.
+#[derive(Debug, Clone, Copy)]
+struct EncodedBatchSize {
+    /// The size of the batch header's fields
+    header: u64,
+    /// The size of the batch's transactions fields
+    transactions: u64,
+}
+
+impl Default for EncodedBatchSize {
+    fn default() -> Self {
+        EncodedBatchSize {
+            header: field_size(1, PUBLIC_KEY_HEX_LENGTH),
+            transactions: 0,
+        }
+    }
+}
+
+impl EncodedBatchSize {
+    /// Returns the size with the transaction, whose message is `size` bytes, added to the batch.
+    fn with(self, transaction: &Transaction, size: u64) -> Self {
+        EncodedBatchSize {
+            header: self.header + field_size(2, transaction.header_signature().len() as u64),
+            transactions: self.transactions + field_size(3, size),
+        }
+    }
+
+    /// Returns the size of the batch list.
+    fn bytes(&self) -> usize {
+        let batch =
+            field_size(1, self.header) + field_size(2, SIGNATURE_HEX_LENGTH) + self.transactions;
+        field_size(1, batch) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use cylinder::{secp256k1::Secp256k1Context, Context};
+    use sawtooth::protos::IntoBytes;
+    use sawtooth::transact::protocol::transaction::{HashMethod, TransactionBuilder};
+
+    fn new_signer() -> Box<dyn Signer> {
+        let context = Secp256k1Context::new();
+        let key = context.new_random_private_key();
+        context.new_signer(key)
+    }
+
+    fn transactions(signer: &dyn Signer, count: usize) -> Vec<Transaction> {
+        (0..count)
+            .map(|i| {
+                TransactionBuilder::new()
+                    .with_family_name("test_family".to_string())
+                    .with_family_version("0.1".to_string())
+                    .with_inputs(vec![])
+                    .with_outputs(vec![])
+                    .with_nonce(format!("{:08}", i).into_bytes())
+                    .with_payload_hash_method(HashMethod::Sha512)
+                    .with_payload(vec![0; 64])
+                    .build(signer)
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    fn header_signatures(groups: &[Vec<Transaction>]) -> Vec<Vec<String>> {
+        groups
+            .iter()
+            .map(|group| {
+                group
+                    .iter()
+                    .map(|transaction| transaction.header_signature().to_string())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    // Asserts that a batch may be filled to exactly the byte limit, but not past it, and that
+    // transactions keep their order
+    fn test_cli_pack_transactions_bytes() {
+        let signer = new_signer();
+        let transactions = transactions(&*signer, 3);
+        let batch_size = |count: usize| {
+            transactions[..count]
+                .iter()
+                .fold(EncodedBatchSize::default(), |size, transaction| {
+                    size.with(transaction, transaction_size(transaction).unwrap())
+                })
+                .bytes()
+        };
+        let ids = header_signatures(&[transactions.clone()]).remove(0);
+
+        let limits = BatchLimits {
+            max_bytes: batch_size(2),
+            max_transactions: 10,
+        };
+        assert_eq!(
+            header_signatures(&pack_transactions(transactions.clone(), &limits).unwrap()),
+            vec![ids[0..2].to_vec(), ids[2..3].to_vec()]
+        );
+
+        let limits = BatchLimits {
+            max_bytes: batch_size(2) - 1,
+            max_transactions: 10,
+        };
+        assert_eq!(
+            header_signatures(&pack_transactions(transactions.clone(), &limits).unwrap()),
+            vec![ids[0..1].to_vec(), ids[1..2].to_vec(), ids[2..3].to_vec()]
+        );
+
+        let limits = BatchLimits {
+            max_bytes: batch_size(1) - 1,
+            max_transactions: 10,
+        };
+        assert!(pack_transactions(transactions.clone(), &limits).is_err());
+
+        // The measured size is that of the signed batch list
+        let batch = BatchBuilder::new()
+            .with_transactions(transactions[..2].to_vec())
+            .build(&*signer)
+            .unwrap();
+        assert_eq!(vec![batch].into_bytes().unwrap().len(), batch_size(2));
+    }
+
+    #[test]
+    // Asserts that a batch holds at most the transaction limit, and that no transactions pack
+    // into no batches
+    fn test_cli_pack_transactions_count() {
+        let signer = new_signer();
+        let limits = BatchLimits {
+            max_bytes: DEFAULT_MAX_BATCH_LIST_BYTES,
+            max_transactions: 2,
+        };
+
+        let groups = pack_transactions(transactions(&*signer, 5), &limits).unwrap();
+        assert_eq!(
+            groups.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![2, 2, 1]
+        );
+
+        assert!(pack_transactions(vec![], &limits).unwrap().is_empty());
+        assert_eq!(
+            build_batches(transactions(&*signer, 4), &limits, &*signer)
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+}
@@ -0,0 +1,239 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains reading the transactions of committed blocks from the REST API
+//!
+//! The REST API lists blocks from the head down, so blocks are paged through until one below
+//! the first requested block is reached, and the transactions are then put in chain order.
+
+use reqwest::{header::ACCEPT, Url};
+use sabre_sdk::trace::TRACEPARENT;
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+
+use crate::error::CliError;
+use crate::rest_api::{
+    authorize, check_scheme, client, parse_response, with_failover, JSON_MEDIA_TYPE,
+};
+use crate::trace;
+
+/// The number of blocks requested in each page
+const PAGE_LIMIT: usize = 100;
+
+/// A transaction committed in a block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommittedTransaction {
+    pub block_num: u64,
+    pub block_id: String,
+    pub id: String,
+    pub signer_public_key: String,
+    pub payload: Vec<u8>,
+}
+
+/// Returns the transactions of the given family committed in the blocks from `from_block` to
+/// the head, in the order they were applied.
+pub fn get_transactions_since(
+    url: &str,
+    family_name: &str,
+    from_block: u64,
+) -> Result<Vec<CommittedTransaction>, CliError> {
+    let mut transactions = Vec::new();
+    let mut start = None;
+
+    loop {
+        let page = with_failover(url, |url| get_blocks_from(url, start.as_deref()))?;
+        let mut reached_start = page.data.is_empty();
+
+        // Blocks are listed from the head down, and their batches and transactions in order,
+        // so each block's transactions are added in reverse to reverse the whole list at the end
+        for block in page.data {
+            if block.header.block_num < from_block {
+                reached_start = true;
+                break;
+            }
+
+            for batch in block.batches.into_iter().rev() {
+                for transaction in batch.transactions.into_iter().rev() {
+                    if transaction.header.family_name != family_name {
+                        continue;
+                    }
+
+                    let payload = base64::decode(&transaction.payload).map_err(|_| {
+                        CliError::User(format!(
+                            "Unable to decode the payload of transaction {}",
+                            transaction.header_signature
+                        ))
+                    })?;
+                    transactions.push(CommittedTransaction {
+                        block_num: block.header.block_num,
+                        block_id: block.header_signature.clone(),
+                        id: transaction.header_signature,
+                        signer_public_key: transaction.header.signer_public_key,
+                        payload,
+                    });
+                }
+            }
+        }
+
+        match page.paging.next_position {
+            Some(next_position) if !reached_start => start = Some(next_position),
+            _ => break,
+        }
+    }
+
+    transactions.reverse();
+    Ok(transactions)
+}
+
+fn get_blocks_from(url: &str, start: Option<&str>) -> Result<BlockPage, CliError> {
+    let url = Url::parse(&format!(
+        "{url}/blocks?limit={limit}{start}",
+        url = url,
+        limit = PAGE_LIMIT,
+        start = start
+            .map(|start| format!("&start={}", start))
+            .unwrap_or_default()
+    ))
+    .map_err(|e| CliError::User(format!("Invalid URL: {}: {}", e, url)))?;
+
+    check_scheme(&url)?;
+
+    let response = authorize(client(url.as_str())?.get(url))
+        .header(ACCEPT, JSON_MEDIA_TYPE)
+        .header(TRACEPARENT, trace::new_span().to_string())
+        .send()?;
+
+    parse_response::<BlockPage>(response)
+}
+
+#[derive(Deserialize, Debug)]
+struct BlockPage {
+    data: Vec<JsonBlock>,
+    paging: Paging,
+}
+
+#[derive(Deserialize, Debug)]
+struct Paging {
+    next_position: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct JsonBlock {
+    header: JsonBlockHeader,
+    header_signature: String,
+    batches: Vec<JsonBatch>,
+}
+
+#[derive(Deserialize, Debug)]
+struct JsonBlockHeader {
+    #[serde(deserialize_with = "deserialize_block_num")]
+    block_num: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct JsonBatch {
+    transactions: Vec<JsonTransaction>,
+}
+
+#[derive(Deserialize, Debug)]
+struct JsonTransaction {
+    header: JsonTransactionHeader,
+    header_signature: String,
+    payload: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct JsonTransactionHeader {
+    family_name: String,
+    signer_public_key: String,
+}
+
+/// Block numbers are 64 bit integers, which the REST API renders as strings
+fn deserialize_block_num<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Value::deserialize(deserializer)? {
+        Value::Number(num) => num.as_u64(),
+        Value::String(num) => num.parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| serde::de::Error::custom("block_num is not an unsigned integer"))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use mockito;
+
+    use super::*;
+
+    fn block(num: u64, transactions: &[(&str, &str, &str)]) -> String {
+        let transactions = transactions
+            .iter()
+            .map(|(id, family_name, payload)| {
+                format!(
+                    "{{\"header\": {{\"family_name\": \"{}\", \"signer_public_key\": \"signer\"}}, \
+                     \"header_signature\": \"{}\", \"payload\": \"{}\"}}",
+                    family_name,
+                    id,
+                    base64::encode(payload.as_bytes())
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+
+        format!(
+            "{{\"header\": {{\"block_num\": \"{num}\"}}, \"header_signature\": \"block{num}\", \
+             \"batches\": [{{\"transactions\": [{transactions}]}}]}}",
+            num = num,
+            transactions = transactions
+        )
+    }
+
+    #[test]
+    // Asserts that the transactions of the family are returned in chain order across pages,
+    // stopping at the first requested block
+    fn test_cli_get_transactions_since() {
+        let url = mockito::server_url();
+        let _m1 = mockito::mock("GET", "/blocks?limit=100")
+            .with_header("content-type", "application/json")
+            .with_body(format!(
+                "{{\"data\": [{}, {}], \"paging\": {{\"next_position\": \"0x0000000000000001\"}}}}",
+                block(3, &[("t3", "sabre", "3")]),
+                block(2, &[("t2a", "sabre", "2a"), ("t2b", "intkey", "2b")]),
+            ))
+            .create();
+        let _m2 = mockito::mock("GET", "/blocks?limit=100&start=0x0000000000000001")
+            .with_header("content-type", "application/json")
+            .with_body(format!(
+                "{{\"data\": [{}, {}], \"paging\": {{\"next_position\": null}}}}",
+                block(1, &[("t1a", "sabre", "1a"), ("t1b", "sabre", "1b")]),
+                block(0, &[("t0", "sabre", "0")]),
+            ))
+            .create();
+
+        let transactions = get_transactions_since(&url, "sabre", 1).unwrap();
+
+        assert_eq!(
+            transactions
+                .iter()
+                .map(|transaction| (transaction.block_num, transaction.id.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(1, "t1a"), (1, "t1b"), (2, "t2a"), (3, "t3")]
+        );
+        assert_eq!(transactions[0].payload, b"1a".to_vec());
+        assert_eq!(transactions[0].block_id, "block1");
+    }
+}
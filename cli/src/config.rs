@@ -0,0 +1,612 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Resolves the CLI's settings from command line flags, environment variables and defaults.
+//!
+//! A flag takes precedence over an environment variable, which takes precedence over the
+//! default. An environment variable set to the empty string is treated as unset.
+
+use std::fmt;
+
+use sabre_sdk::protocol::size::SizeLimits;
+
+use crate::address_book::ADDRESS_BOOK_ENV_VAR;
+use crate::error::CliError;
+use crate::paths::CONFIG_DIR_ENV_VAR;
+use crate::rest_api::{Authorization, ProxySettings, TlsSettings};
+use crate::trace::TRACEPARENT_ENV_VAR;
+
+pub const DEFAULT_REST_API_ENDPOINT: &str = "http://localhost:8008/";
+
+/// The environment variable which gives the REST API URL, if --url is not given
+pub const URL_ENV_VAR: &str = "SABRE_URL";
+
+/// The environment variable which names the signing key, if --key is not given
+pub const KEY_ENV_VAR: &str = "SABRE_KEY";
+
+/// The environment variable which names the policy file, if --policy is not given
+pub const POLICY_ENV_VAR: &str = "SABRE_POLICY_FILE";
+
+/// The environment variable which gives Basic credentials for the REST API, if --auth is not
+/// given
+pub const AUTH_ENV_VAR: &str = "SABRE_AUTH";
+
+/// The environment variable which gives a bearer token for the REST API, if --token is not given
+pub const TOKEN_ENV_VAR: &str = "SABRE_TOKEN";
+
+/// The environment variable which gives the proxy for REST API requests, if --proxy is not given
+pub const PROXY_ENV_VAR: &str = "SABRE_PROXY";
+
+/// The environment variable which gives Basic credentials for the proxy, if --proxy-auth is not
+/// given
+pub const PROXY_AUTH_ENV_VAR: &str = "SABRE_PROXY_AUTH";
+
+/// The environment variable which names the CA certificates to trust, if --ca-file is not given
+pub const CA_FILE_ENV_VAR: &str = "SABRE_CA_FILE";
+
+/// The environment variable which names the client certificate, if --client-cert is not given
+pub const CLIENT_CERT_ENV_VAR: &str = "SABRE_CLIENT_CERT";
+
+/// The environment variable which names the client certificate's key, if --client-key is not
+/// given
+pub const CLIENT_KEY_ENV_VAR: &str = "SABRE_CLIENT_KEY";
+
+/// The environment variable which gives the size limit of a submitted batch list, if
+/// --max-batch-bytes is not given
+pub const MAX_BATCH_BYTES_ENV_VAR: &str = "SABRE_MAX_BATCH_BYTES";
+
+/// The environment variable which gives the most transactions in a batch, if
+/// --max-batch-transactions is not given
+pub const MAX_BATCH_TRANSACTIONS_ENV_VAR: &str = "SABRE_MAX_BATCH_TRANSACTIONS";
+
+/// Where the value of a setting came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Flag(&'static str),
+    Env(&'static str),
+    Default,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Flag(flag) => write!(f, "--{}", flag),
+            Source::Env(var) => write!(f, "${}", var),
+            Source::Default => f.write_str("default"),
+        }
+    }
+}
+
+/// A setting and the sources it may be given by, highest precedence first
+struct Definition {
+    name: &'static str,
+    /// The argument name and long flag
+    flag: Option<(&'static str, &'static str)>,
+    env: Option<&'static str>,
+    default: Option<&'static str>,
+    /// Describes the behavior when the setting has no value
+    unset: &'static str,
+    /// Whether the value is a credential, which is not displayed
+    secret: bool,
+}
+
+const DEFINITIONS: [Definition; 17] = [
+    Definition {
+        name: "url",
+        flag: Some(("url", "url")),
+        env: Some(URL_ENV_VAR),
+        default: Some(DEFAULT_REST_API_ENDPOINT),
+        unset: "",
+        secret: false,
+    },
+    Definition {
+        name: "key",
+        flag: Some(("key", "key")),
+        env: Some(KEY_ENV_VAR),
+        default: None,
+        unset: "(the current user's key)",
+        secret: false,
+    },
+    Definition {
+        name: "key-algo",
+        flag: Some(("key_algo", "key-algo")),
+        env: None,
+        default: None,
+        unset: "(detected from the key file)",
+        secret: false,
+    },
+    Definition {
+        name: "policy",
+        flag: Some(("policy", "policy")),
+        env: Some(POLICY_ENV_VAR),
+        default: None,
+        unset: "(no policy)",
+        secret: false,
+    },
+    Definition {
+        name: "auth",
+        flag: Some(("auth", "auth")),
+        env: Some(AUTH_ENV_VAR),
+        default: None,
+        unset: "(no Basic credentials)",
+        secret: true,
+    },
+    Definition {
+        name: "token",
+        flag: Some(("token", "token")),
+        env: Some(TOKEN_ENV_VAR),
+        default: None,
+        unset: "(no bearer token)",
+        secret: true,
+    },
+    Definition {
+        name: "proxy",
+        flag: Some(("proxy", "proxy")),
+        env: Some(PROXY_ENV_VAR),
+        default: None,
+        unset: "($HTTP_PROXY and $HTTPS_PROXY)",
+        secret: false,
+    },
+    Definition {
+        name: "proxy-auth",
+        flag: Some(("proxy_auth", "proxy-auth")),
+        env: Some(PROXY_AUTH_ENV_VAR),
+        default: None,
+        unset: "",
+        secret: true,
+    },
+    Definition {
+        name: "ca-file",
+        flag: Some(("ca_file", "ca-file")),
+        env: Some(CA_FILE_ENV_VAR),
+        default: None,
+        unset: "(the built-in root certificates)",
+        secret: false,
+    },
+    Definition {
+        name: "client-cert",
+        flag: Some(("client_cert", "client-cert")),
+        env: Some(CLIENT_CERT_ENV_VAR),
+        default: None,
+        unset: "",
+        secret: false,
+    },
+    Definition {
+        name: "client-key",
+        flag: Some(("client_key", "client-key")),
+        env: Some(CLIENT_KEY_ENV_VAR),
+        default: None,
+        unset: "(the key in the client certificate file)",
+        secret: false,
+    },
+    Definition {
+        name: "max-batch-bytes",
+        flag: Some(("max_batch_bytes", "max-batch-bytes")),
+        env: Some(MAX_BATCH_BYTES_ENV_VAR),
+        default: Some("10485760"),
+        unset: "",
+        secret: false,
+    },
+    Definition {
+        name: "max-batch-transactions",
+        flag: Some(("max_batch_transactions", "max-batch-transactions")),
+        env: Some(MAX_BATCH_TRANSACTIONS_ENV_VAR),
+        default: Some("100"),
+        unset: "",
+        secret: false,
+    },
+    Definition {
+        name: "progress",
+        flag: Some(("progress", "progress")),
+        env: None,
+        default: Some("none"),
+        unset: "",
+        secret: false,
+    },
+    Definition {
+        name: "address-book",
+        flag: None,
+        env: Some(ADDRESS_BOOK_ENV_VAR),
+        default: None,
+        unset: "(address_book.yaml in the configuration directory)",
+        secret: false,
+    },
+    Definition {
+        name: "config-dir",
+        flag: None,
+        env: Some(CONFIG_DIR_ENV_VAR),
+        default: None,
+        unset: "(the platform's configuration directory)",
+        secret: false,
+    },
+    Definition {
+        name: "traceparent",
+        flag: None,
+        env: Some(TRACEPARENT_ENV_VAR),
+        default: None,
+        unset: "(a new trace for each invocation)",
+        secret: false,
+    },
+];
+
+/// The resolved value of a setting
+#[derive(Debug, PartialEq, Eq)]
+pub struct Setting {
+    pub name: &'static str,
+    pub value: Option<String>,
+    pub source: Source,
+    /// Values given by lower precedence sources, which were ignored
+    pub overridden: Vec<(Source, String)>,
+}
+
+impl Setting {
+    /// Returns the value, or a description of the behavior when there is none.
+    pub fn display_value(&self) -> &str {
+        match &self.value {
+            Some(value) => self.display(value),
+            None => definition(self.name).unset,
+        }
+    }
+
+    /// Returns a value given for the setting as it may be displayed, hiding credentials.
+    pub fn display<'a>(&self, value: &'a str) -> &'a str {
+        if definition(self.name).secret {
+            "(hidden)"
+        } else {
+            value
+        }
+    }
+}
+
+fn definition(name: &str) -> &'static Definition {
+    DEFINITIONS
+        .iter()
+        .find(|definition| definition.name == name)
+        .expect("setting is not defined")
+}
+
+fn resolve_with<F>(definition: &Definition, matches: &clap::ArgMatches, lookup: F) -> Setting
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut given = Vec::new();
+    if let Some((arg, long)) = definition.flag {
+        if let Some(value) = matches.value_of(arg) {
+            given.push((Source::Flag(long), value.to_string()));
+        }
+    }
+    if let Some(var) = definition.env {
+        if let Some(value) = lookup(var).filter(|value| !value.is_empty()) {
+            given.push((Source::Env(var), value));
+        }
+    }
+
+    let mut given = given.into_iter();
+    match given.next() {
+        Some((source, value)) => Setting {
+            name: definition.name,
+            value: Some(value),
+            source,
+            overridden: given.collect(),
+        },
+        None => Setting {
+            name: definition.name,
+            value: definition.default.map(String::from),
+            source: Source::Default,
+            overridden: vec![],
+        },
+    }
+}
+
+fn resolve_setting(name: &str, matches: &clap::ArgMatches) -> Setting {
+    resolve_with(definition(name), matches, |var| std::env::var(var).ok())
+}
+
+/// Resolves every setting, in the order they are defined.
+pub fn resolve_all(matches: &clap::ArgMatches) -> Vec<Setting> {
+    DEFINITIONS
+        .iter()
+        .map(|definition| resolve_with(definition, matches, |var| std::env::var(var).ok()))
+        .collect()
+}
+
+/// Returns the URL of the REST API to use.
+pub fn rest_api_url(matches: &clap::ArgMatches) -> String {
+    resolve_setting("url", matches)
+        .value
+        .unwrap_or_else(|| DEFAULT_REST_API_ENDPOINT.into())
+}
+
+/// Returns whether a REST API URL was given, rather than falling back to the default.
+pub fn rest_api_url_given(matches: &clap::ArgMatches) -> bool {
+    resolve_setting("url", matches).source != Source::Default
+}
+
+/// Returns the name or path of the signing key, if one is configured.
+pub fn signing_key(matches: &clap::ArgMatches) -> Option<String> {
+    resolve_setting("key", matches).value
+}
+
+/// Returns the path of the policy file, if one is configured. --policy may be given before or
+/// after the subcommand.
+pub fn policy_file(
+    step_matches: Option<&clap::ArgMatches>,
+    matches: &clap::ArgMatches,
+) -> Option<String> {
+    step_matches
+        .and_then(|step_matches| step_matches.value_of("policy"))
+        .map(String::from)
+        .or_else(|| resolve_setting("policy", matches).value)
+}
+
+/// Returns the credentials to send to the REST API, if any are configured. --auth and --token
+/// may be given before or after the subcommand, but only one of them may be set.
+pub fn authorization(
+    step_matches: Option<&clap::ArgMatches>,
+    matches: &clap::ArgMatches,
+) -> Result<Option<Authorization>, CliError> {
+    let resolve = |name| {
+        step_matches
+            .map(|step_matches| resolve_setting(name, step_matches))
+            .filter(|setting| setting.value.is_some())
+            .unwrap_or_else(|| resolve_setting(name, matches))
+            .value
+    };
+
+    match (resolve("auth"), resolve("token")) {
+        (Some(_), Some(_)) => Err(CliError::User(format!(
+            "Only one of --auth (${}) and --token (${}) may be set",
+            AUTH_ENV_VAR, TOKEN_ENV_VAR
+        ))),
+        (Some(credentials), None) => Authorization::basic(&credentials).map(Some),
+        (None, Some(token)) => Ok(Some(Authorization::Bearer(token))),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Returns the proxies to send requests through. --proxy and --proxy-auth may be given before or
+/// after the subcommand; a proxy given by either is used for both http and https requests.
+pub fn proxy_settings(
+    step_matches: Option<&clap::ArgMatches>,
+    matches: &clap::ArgMatches,
+) -> Result<ProxySettings, CliError> {
+    proxy_settings_with(step_matches, matches, |var| std::env::var(var).ok())
+}
+
+fn proxy_settings_with<F>(
+    step_matches: Option<&clap::ArgMatches>,
+    matches: &clap::ArgMatches,
+    lookup: F,
+) -> Result<ProxySettings, CliError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let resolve = |name| {
+        step_matches
+            .map(|step_matches| resolve_with(definition(name), step_matches, &lookup))
+            .filter(|setting| setting.value.is_some())
+            .unwrap_or_else(|| resolve_with(definition(name), matches, &lookup))
+            .value
+    };
+    let env = |vars: &[&str]| {
+        vars.iter()
+            .filter_map(|var| lookup(var))
+            .find(|value| !value.is_empty())
+    };
+
+    let (http, https) = match resolve("proxy") {
+        Some(proxy) => (Some(proxy.clone()), Some(proxy)),
+        None => (
+            env(&["HTTP_PROXY", "http_proxy", "ALL_PROXY", "all_proxy"]),
+            env(&["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"]),
+        ),
+    };
+    let no_proxy = env(&["NO_PROXY", "no_proxy"])
+        .map(|hosts| {
+            hosts
+                .split(',')
+                .map(|host| host.trim().to_lowercase())
+                .filter(|host| !host.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    let auth = match resolve("proxy-auth") {
+        Some(credentials) => match Authorization::basic(&credentials)? {
+            Authorization::Basic { username, password } => Some((username, password)),
+            Authorization::Bearer(_) => None,
+        },
+        None => None,
+    };
+
+    Ok(ProxySettings {
+        http,
+        https,
+        no_proxy,
+        auth,
+    })
+}
+
+/// Returns the certificate files TLS connections use. The files may be given before or after the
+/// subcommand.
+pub fn tls_settings(
+    step_matches: Option<&clap::ArgMatches>,
+    matches: &clap::ArgMatches,
+) -> TlsSettings {
+    let resolve = |name| {
+        step_matches
+            .map(|step_matches| resolve_setting(name, step_matches))
+            .filter(|setting| setting.value.is_some())
+            .unwrap_or_else(|| resolve_setting(name, matches))
+            .value
+    };
+
+    TlsSettings {
+        ca_file: resolve("ca-file"),
+        client_cert: resolve("client-cert"),
+        client_key: resolve("client-key"),
+    }
+}
+
+/// Returns the size limits submissions are estimated against. The limits may be given before or
+/// after the subcommand.
+pub fn size_limits(
+    step_matches: Option<&clap::ArgMatches>,
+    matches: &clap::ArgMatches,
+) -> Result<SizeLimits, CliError> {
+    let resolve = |name| -> Result<usize, CliError> {
+        let setting = step_matches
+            .map(|step_matches| resolve_setting(name, step_matches))
+            .filter(|setting| setting.source != Source::Default)
+            .unwrap_or_else(|| resolve_setting(name, matches));
+        let value = setting.value.unwrap_or_default();
+        value.parse().map_err(|_| {
+            CliError::User(format!(
+                "{} ({}) must be a number, not '{}'",
+                name, setting.source, value
+            ))
+        })
+    };
+
+    Ok(SizeLimits {
+        max_batch_list_bytes: resolve("max-batch-bytes")?,
+        max_batch_transactions: resolve("max-batch-transactions")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use clap::{App, Arg};
+
+    fn matches(args: &[&str]) -> clap::ArgMatches<'static> {
+        App::new("test")
+            .arg(Arg::with_name("url").long("url").takes_value(true))
+            .arg(Arg::with_name("key").long("key").takes_value(true))
+            .get_matches_from(args)
+    }
+
+    #[test]
+    // check that a flag takes precedence over an environment variable, which takes precedence
+    // over the default, and that the ignored values are reported
+    fn test_cli_config_precedence() {
+        let env = |var: &str| match var {
+            URL_ENV_VAR => Some("http://env:8008".to_string()),
+            KEY_ENV_VAR => Some("".to_string()),
+            _ => None,
+        };
+
+        let setting = resolve_with(
+            definition("url"),
+            &matches(&["test", "--url", "http://flag:8008"]),
+            env,
+        );
+        assert_eq!(setting.value, Some("http://flag:8008".to_string()));
+        assert_eq!(setting.source, Source::Flag("url"));
+        assert_eq!(
+            setting.overridden,
+            vec![(Source::Env(URL_ENV_VAR), "http://env:8008".to_string())]
+        );
+
+        let setting = resolve_with(definition("url"), &matches(&["test"]), env);
+        assert_eq!(setting.value, Some("http://env:8008".to_string()));
+        assert_eq!(setting.source, Source::Env(URL_ENV_VAR));
+        assert!(setting.overridden.is_empty());
+
+        // An empty environment variable is unset
+        let setting = resolve_with(definition("key"), &matches(&["test"]), env);
+        assert_eq!(setting.value, None);
+        assert_eq!(setting.source, Source::Default);
+        assert_eq!(setting.display_value(), "(the current user's key)");
+    }
+
+    #[test]
+    // check that credentials are not displayed, and that only one kind may be given
+    fn test_cli_config_authorization() {
+        let matches = App::new("test")
+            .arg(Arg::with_name("auth").long("auth").takes_value(true))
+            .arg(Arg::with_name("token").long("token").takes_value(true))
+            .get_matches_from(&["test", "--auth", "user:secret"]);
+
+        let setting = resolve_with(definition("auth"), &matches, |_| None);
+        assert_eq!(setting.value, Some("user:secret".to_string()));
+        assert_eq!(setting.display_value(), "(hidden)");
+
+        assert_eq!(
+            Authorization::basic("user:pass:word").unwrap(),
+            Authorization::Basic {
+                username: "user".into(),
+                password: "pass:word".into()
+            }
+        );
+        assert!(Authorization::basic("user").is_err());
+        assert!(Authorization::basic(":secret").is_err());
+
+        let both = App::new("test")
+            .arg(Arg::with_name("auth").long("auth").takes_value(true))
+            .arg(Arg::with_name("token").long("token").takes_value(true))
+            .get_matches_from(&["test", "--auth", "user:secret", "--token", "abc"]);
+        assert!(authorization(None, &both).is_err());
+    }
+
+    #[test]
+    // check that --proxy is used for both schemes in place of the standard environment
+    // variables, which are otherwise used per scheme, and that the proxy credentials are hidden
+    fn test_cli_config_proxy() {
+        let app = || {
+            App::new("test")
+                .arg(Arg::with_name("proxy").long("proxy").takes_value(true))
+                .arg(
+                    Arg::with_name("proxy_auth")
+                        .long("proxy-auth")
+                        .takes_value(true),
+                )
+        };
+        let env = |var: &str| match var {
+            "HTTP_PROXY" => Some("http://http-proxy:3128".to_string()),
+            "https_proxy" => Some("http://https-proxy:3128".to_string()),
+            "NO_PROXY" => Some("internal.example.com, .corp,".to_string()),
+            _ => None,
+        };
+
+        let settings = proxy_settings_with(None, &app().get_matches_from(&["test"]), env).unwrap();
+        assert_eq!(
+            settings,
+            ProxySettings {
+                http: Some("http://http-proxy:3128".into()),
+                https: Some("http://https-proxy:3128".into()),
+                no_proxy: vec!["internal.example.com".into(), ".corp".into()],
+                auth: None,
+            }
+        );
+
+        let matches = app().get_matches_from(&[
+            "test",
+            "--proxy",
+            "http://proxy:3128",
+            "--proxy-auth",
+            "user:secret",
+        ]);
+        let settings = proxy_settings_with(None, &matches, env).unwrap();
+        assert_eq!(settings.http, Some("http://proxy:3128".into()));
+        assert_eq!(settings.https, Some("http://proxy:3128".into()));
+        assert_eq!(settings.auth, Some(("user".into(), "secret".into())));
+
+        let setting = resolve_with(definition("proxy-auth"), &matches, |_| None);
+        assert_eq!(setting.display_value(), "(hidden)");
+
+        let matches = app().get_matches_from(&["test", "--proxy-auth", "user"]);
+        assert!(proxy_settings_with(None, &matches, |_| None).is_err());
+    }
+}
@@ -0,0 +1,662 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains the conformance probes run by `sabre contract test` against a compiled contract
+//!
+//! The static probes inspect the module: it may import only the functions the Sabre interpreter
+//! provides, must export `entrypoint`, and must not start with more memory than the limit.
+//!
+//! The other probes install the contract in state held in memory and execute it with the Sabre
+//! transaction handler, which runs contracts in the same interpreter as the transaction
+//! processor. Its transactions are applied without admin key verification, so the registries
+//! and permissions the contract needs are created without Sawtooth settings.
+//!
+//! The interpreter is itself deterministic, so the determinism probe varies what may differ
+//! between validators: each payload is executed a second time with the state entries the
+//! contract reads returned in reverse order. Floating point instructions also fail the probe.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+
+use cylinder::{secp256k1::Secp256k1Context, Context, Signer};
+use sabre_sdk::protocol::payload::{
+    CreateContractActionBuilder, CreateContractRegistryActionBuilder,
+    CreateNamespaceRegistryActionBuilder, CreateNamespaceRegistryPermissionActionBuilder,
+    ExecuteContractActionBuilder, SabrePayloadBuilder,
+};
+use sawtooth::families::sabre::admin::AllowAllAdminPermission;
+use sawtooth::families::sabre::handler::SabreTransactionHandler;
+use sawtooth::transact::handler::{
+    ApplyError, ContextError, TransactionContext, TransactionHandler,
+};
+
+use crate::error::CliError;
+use crate::lint;
+use crate::upload::ContractDefinition;
+
+/// The most 64 KiB pages a contract's memory may start with, unless another limit is given
+pub const DEFAULT_MAX_MEMORY_PAGES: u64 = 256;
+
+/// The module the Sabre interpreter's functions are imported from
+const HOST_MODULE: &str = "env";
+
+/// The functions the Sabre interpreter provides to contracts
+const HOST_FUNCTIONS: [&str; 14] = [
+    "get_state",
+    "set_state",
+    "delete_state",
+    "add_event",
+    "get_ptr_len",
+    "alloc",
+    "read_byte",
+    "write_byte",
+    "get_ptr_collection_len",
+    "get_ptr_from_collection",
+    "add_to_collection",
+    "create_collection",
+    "log_buffer",
+    "log_level",
+];
+
+/// The function the Sabre interpreter calls to execute a contract
+const ENTRYPOINT: &str = "entrypoint";
+
+/// The length of a namespace registry's namespace
+const NAMESPACE_LENGTH: usize = 6;
+
+/// The outcome of a probe
+#[derive(Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Pass(String),
+    Fail(String),
+}
+
+/// A probe and its outcome
+#[derive(Debug, PartialEq, Eq)]
+pub struct Probe {
+    pub name: &'static str,
+    pub outcome: Outcome,
+}
+
+/// A contract payload and the name it is reported by
+pub struct Payload {
+    pub name: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Runs every probe against the contract, executing it with the given payloads, each of which
+/// it should apply, and with malformed payloads, which it should reject. The probes which
+/// execute the contract are skipped if it cannot be installed.
+pub fn run_probes(
+    definition: &ContractDefinition,
+    wasm: &[u8],
+    payloads: &[Payload],
+    max_memory_pages: u64,
+) -> Vec<Probe> {
+    let mut probes = vec![];
+
+    match lint::interface(wasm) {
+        Ok(interface) => {
+            probes.push(Probe {
+                name: "Imports",
+                outcome: check_imports(&interface.imports),
+            });
+            probes.push(Probe {
+                name: "Entrypoint export",
+                outcome: check_entrypoint(&interface.exports),
+            });
+            probes.push(Probe {
+                name: "Memory",
+                outcome: check_memory(interface.memory, max_memory_pages),
+            });
+        }
+        Err(msg) => probes.push(Probe {
+            name: "Module",
+            outcome: Outcome::Fail(format!("unable to read the compiled contract: {}", msg)),
+        }),
+    }
+
+    let context = Secp256k1Context::new();
+    let signer = context.new_signer(context.new_random_private_key());
+    let installed = match install(definition, wasm, &*signer) {
+        Ok(installed) => {
+            probes.push(Probe {
+                name: "Install",
+                outcome: Outcome::Pass(format!(
+                    "installed {}:{} in local state",
+                    definition.name, definition.version
+                )),
+            });
+            installed
+        }
+        Err(err) => {
+            probes.push(Probe {
+                name: "Install",
+                outcome: Outcome::Fail(err.to_string()),
+            });
+            return probes;
+        }
+    };
+
+    let malformed = malformed_payloads(payloads);
+    let mut executions = vec![];
+    for payload in payloads.iter().chain(malformed.iter()) {
+        let execution =
+            execute(definition, &installed, payload, &*signer, false).and_then(|first| {
+                execute(definition, &installed, payload, &*signer, true)
+                    .map(|second| (first, second))
+            });
+        match execution {
+            Ok(execution) => executions.push((payload, execution)),
+            Err(err) => {
+                probes.push(Probe {
+                    name: "Execute",
+                    outcome: Outcome::Fail(format!(
+                        "unable to execute payload {}: {}",
+                        payload.name, err
+                    )),
+                });
+                return probes;
+            }
+        }
+    }
+    let (valid, malformed) = executions.split_at(payloads.len());
+
+    if !valid.is_empty() {
+        probes.push(Probe {
+            name: "Valid payloads",
+            outcome: check_valid(valid),
+        });
+    }
+    probes.push(Probe {
+        name: "Malformed payloads",
+        outcome: check_malformed(malformed),
+    });
+    probes.push(Probe {
+        name: "Determinism",
+        outcome: check_determinism(wasm, &executions),
+    });
+
+    probes
+}
+
+fn check_imports(imports: &[(String, String)]) -> Outcome {
+    let disallowed = imports
+        .iter()
+        .filter(|(module, name)| module != HOST_MODULE || !HOST_FUNCTIONS.contains(&name.as_str()))
+        .map(|(module, name)| format!("{}.{}", module, name))
+        .collect::<Vec<_>>();
+
+    if disallowed.is_empty() {
+        Outcome::Pass(format!(
+            "{} imports, all provided by the Sabre interpreter",
+            imports.len()
+        ))
+    } else {
+        Outcome::Fail(format!(
+            "imports functions the Sabre interpreter does not provide: {}",
+            disallowed.join(", ")
+        ))
+    }
+}
+
+fn check_entrypoint(exports: &[String]) -> Outcome {
+    if exports.iter().any(|export| export == ENTRYPOINT) {
+        Outcome::Pass(format!("exports {}", ENTRYPOINT))
+    } else {
+        Outcome::Fail(format!(
+            "does not export {}; define it with sabre_entrypoint!",
+            ENTRYPOINT
+        ))
+    }
+}
+
+fn check_memory(memory: Option<(u64, Option<u64>)>, max_pages: u64) -> Outcome {
+    match memory {
+        None => Outcome::Pass("has no memory".into()),
+        Some((initial, _)) if initial > max_pages => Outcome::Fail(format!(
+            "starts with {} pages of memory, more than the limit of {}",
+            initial, max_pages
+        )),
+        Some((initial, Some(maximum))) if maximum > max_pages => Outcome::Fail(format!(
+            "starts with {} pages of memory, but may grow to {}, more than the limit of {}",
+            initial, maximum, max_pages
+        )),
+        Some((initial, Some(maximum))) => Outcome::Pass(format!(
+            "starts with {} pages of memory and may grow to {}",
+            initial, maximum
+        )),
+        Some((initial, None)) => Outcome::Pass(format!(
+            "starts with {} pages of memory, with no maximum",
+            initial
+        )),
+    }
+}
+
+fn check_valid(executions: &[(&Payload, (Execution, Execution))]) -> Outcome {
+    for (payload, (execution, _)) in executions {
+        match &execution.result {
+            Verdict::Applied => (),
+            Verdict::Invalid(msg) | Verdict::InternalError(msg) => {
+                return Outcome::Fail(format!("payload {} was not applied: {}", payload.name, msg))
+            }
+        }
+    }
+
+    Outcome::Pass(format!("{} payloads applied", executions.len()))
+}
+
+fn check_malformed(executions: &[(&Payload, (Execution, Execution))]) -> Outcome {
+    for (payload, (execution, _)) in executions {
+        if let Verdict::InternalError(msg) = &execution.result {
+            return Outcome::Fail(format!(
+                "payload {} caused an internal error instead of an invalid transaction: {}",
+                payload.name, msg
+            ));
+        }
+    }
+
+    Outcome::Pass(format!(
+        "{} malformed payloads applied or rejected as invalid",
+        executions.len()
+    ))
+}
+
+fn check_determinism(wasm: &[u8], executions: &[(&Payload, (Execution, Execution))]) -> Outcome {
+    match lint::float_instructions(wasm) {
+        Ok(0) => (),
+        Ok(count) => {
+            return Outcome::Fail(format!(
+                "contains {} floating point instructions, whose results may differ between \
+                 executors",
+                count
+            ))
+        }
+        Err(msg) => {
+            return Outcome::Fail(format!(
+                "unable to check for floating point instructions: {}",
+                msg
+            ))
+        }
+    }
+
+    for (payload, (first, second)) in executions {
+        if first != second {
+            return Outcome::Fail(format!(
+                "payload {} had a different result when the state it read was returned in \
+                 another order",
+                payload.name
+            ));
+        }
+    }
+
+    Outcome::Pass(format!(
+        "{} payloads had the same result when executed again",
+        executions.len()
+    ))
+}
+
+/// Returns payloads a contract should reject: an empty payload, a single byte, noise, and half
+/// of each of the given payloads.
+fn malformed_payloads(payloads: &[Payload]) -> Vec<Payload> {
+    // A fixed xorshift sequence, so that a failure can be reproduced
+    let mut seed: u32 = 0x9e37_79b9;
+    let noise = (0..4096)
+        .map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed as u8
+        })
+        .collect();
+
+    let mut malformed = vec![
+        Payload {
+            name: "(empty)".into(),
+            bytes: vec![],
+        },
+        Payload {
+            name: "(one byte)".into(),
+            bytes: vec![0xff],
+        },
+        Payload {
+            name: "(4 KiB of noise)".into(),
+            bytes: noise,
+        },
+    ];
+    malformed.extend(
+        payloads
+            .iter()
+            .filter(|payload| payload.bytes.len() > 1)
+            .map(|payload| Payload {
+                name: format!("{} (truncated)", payload.name),
+                bytes: payload.bytes[..payload.bytes.len() / 2].to_vec(),
+            }),
+    );
+
+    malformed
+}
+
+/// The state after the contract registry, the namespace registries of the contract's inputs and
+/// outputs, and the contract itself are created
+fn install(
+    definition: &ContractDefinition,
+    wasm: &[u8],
+    signer: &dyn Signer,
+) -> Result<BTreeMap<String, Vec<u8>>, CliError> {
+    let owner = signer
+        .public_key()
+        .map_err(|err| CliError::Signing(err.to_string()))?
+        .as_hex();
+    let namespaces = definition
+        .inputs
+        .iter()
+        .chain(definition.outputs.iter())
+        .map(|namespace| {
+            namespace
+                .get(..NAMESPACE_LENGTH)
+                .unwrap_or(namespace)
+                .to_string()
+        })
+        .collect::<BTreeSet<_>>();
+
+    let mut payloads = vec![CreateContractRegistryActionBuilder::new()
+        .with_name(definition.name.clone())
+        .with_owners(vec![owner.clone()])
+        .into_payload_builder()?];
+    for namespace in namespaces {
+        payloads.push(
+            CreateNamespaceRegistryActionBuilder::new()
+                .with_namespace(namespace.clone())
+                .with_owners(vec![owner.clone()])
+                .into_payload_builder()?,
+        );
+        payloads.push(
+            CreateNamespaceRegistryPermissionActionBuilder::new()
+                .with_namespace(namespace)
+                .with_contract_name(definition.name.clone())
+                .with_read(true)
+                .with_write(true)
+                .into_payload_builder()?,
+        );
+    }
+    payloads.push(
+        CreateContractActionBuilder::new()
+            .with_name(definition.name.clone())
+            .with_version(definition.version.clone())
+            .with_inputs(definition.inputs.clone())
+            .with_outputs(definition.outputs.clone())
+            .with_contract(wasm.to_vec())
+            .into_payload_builder()?,
+    );
+
+    let mut context = MemoryContext::new(BTreeMap::new(), false);
+    for payload in payloads {
+        if let Verdict::Invalid(msg) | Verdict::InternalError(msg) =
+            apply(payload, &mut context, signer)?
+        {
+            return Err(CliError::User(format!(
+                "unable to install the contract: {}",
+                msg
+            )));
+        }
+    }
+
+    Ok(context.state.into_inner())
+}
+
+/// Executes the contract with the payload in a copy of the installed state.
+fn execute(
+    definition: &ContractDefinition,
+    installed: &BTreeMap<String, Vec<u8>>,
+    payload: &Payload,
+    signer: &dyn Signer,
+    reverse_reads: bool,
+) -> Result<Execution, CliError> {
+    let payload = ExecuteContractActionBuilder::new()
+        .with_name(definition.name.clone())
+        .with_version(definition.version.clone())
+        .with_inputs(definition.inputs.clone())
+        .with_outputs(definition.outputs.clone())
+        .with_payload(payload.bytes.clone())
+        .into_payload_builder()?;
+
+    let mut context = MemoryContext::new(installed.clone(), reverse_reads);
+    let result = apply(payload, &mut context, signer)?;
+
+    Ok(Execution {
+        result,
+        state: context.state.into_inner(),
+        events: context.events.into_inner(),
+    })
+}
+
+fn apply(
+    payload: SabrePayloadBuilder,
+    context: &mut MemoryContext,
+    signer: &dyn Signer,
+) -> Result<Verdict, CliError> {
+    let transaction = payload
+        .into_transaction_builder()?
+        .with_nonce(b"sabre contract test".to_vec())
+        .build(signer)?
+        .into_pair()?;
+
+    let handler = SabreTransactionHandler::new(Box::new(AllowAllAdminPermission::default()));
+    Ok(match handler.apply(&transaction, context) {
+        Ok(()) => Verdict::Applied,
+        Err(ApplyError::InvalidTransaction(msg)) => Verdict::Invalid(msg),
+        Err(ApplyError::InternalError(msg)) => Verdict::InternalError(msg),
+    })
+}
+
+/// How the Sabre transaction handler applied a transaction
+#[derive(Debug, PartialEq, Eq)]
+enum Verdict {
+    Applied,
+    Invalid(String),
+    InternalError(String),
+}
+
+/// An event added by a contract: its type, attributes and data
+type Event = (String, Vec<(String, String)>, Vec<u8>);
+
+/// The result of executing a contract, and the state and events it left
+#[derive(Debug, PartialEq, Eq)]
+struct Execution {
+    result: Verdict,
+    state: BTreeMap<String, Vec<u8>>,
+    events: Vec<Event>,
+}
+
+/// A transaction context holding state in memory
+struct MemoryContext {
+    state: RefCell<BTreeMap<String, Vec<u8>>>,
+    events: RefCell<Vec<Event>>,
+    /// Whether state entries are returned in reverse address order
+    reverse_reads: bool,
+}
+
+impl MemoryContext {
+    fn new(state: BTreeMap<String, Vec<u8>>, reverse_reads: bool) -> Self {
+        MemoryContext {
+            state: RefCell::new(state),
+            events: RefCell::new(vec![]),
+            reverse_reads,
+        }
+    }
+}
+
+impl TransactionContext for MemoryContext {
+    fn get_state_entry(&self, address: &str) -> Result<Option<Vec<u8>>, ContextError> {
+        Ok(self.state.borrow().get(address).cloned())
+    }
+
+    fn get_state_entries(
+        &self,
+        addresses: &[String],
+    ) -> Result<Vec<(String, Vec<u8>)>, ContextError> {
+        let state = self.state.borrow();
+        let mut addresses = addresses
+            .iter()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        if self.reverse_reads {
+            addresses.reverse();
+        }
+
+        Ok(addresses
+            .into_iter()
+            .filter_map(|address| {
+                state
+                    .get(address)
+                    .map(|data| (address.clone(), data.clone()))
+            })
+            .collect())
+    }
+
+    fn set_state_entry(&self, address: String, data: Vec<u8>) -> Result<(), ContextError> {
+        self.set_state_entries(vec![(address, data)])
+    }
+
+    fn set_state_entries(&self, entries: Vec<(String, Vec<u8>)>) -> Result<(), ContextError> {
+        self.state.borrow_mut().extend(entries);
+        Ok(())
+    }
+
+    fn delete_state_entry(&self, address: &str) -> Result<Option<String>, ContextError> {
+        Ok(self
+            .delete_state_entries(&[address.to_owned()])?
+            .into_iter()
+            .next())
+    }
+
+    fn delete_state_entries(&self, addresses: &[String]) -> Result<Vec<String>, ContextError> {
+        let mut state = self.state.borrow_mut();
+        Ok(addresses
+            .iter()
+            .filter(|address| state.remove(*address).is_some())
+            .cloned()
+            .collect())
+    }
+
+    fn add_receipt_data(&self, _data: Vec<u8>) -> Result<(), ContextError> {
+        Ok(())
+    }
+
+    fn add_event(
+        &self,
+        event_type: String,
+        attributes: Vec<(String, String)>,
+        data: Vec<u8>,
+    ) -> Result<(), ContextError> {
+        self.events
+            .borrow_mut()
+            .push((event_type, attributes, data));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // Asserts that only the Sabre interpreter's functions may be imported, that the entrypoint
+    // must be exported, and that memory beyond the limit fails
+    fn test_cli_contract_test_static_probes() {
+        let import = |module: &str, name: &str| (module.to_string(), name.to_string());
+
+        assert!(matches!(
+            check_imports(&[import("env", "get_state"), import("env", "log_level")]),
+            Outcome::Pass(_)
+        ));
+        assert_eq!(
+            check_imports(&[
+                import("env", "get_state"),
+                import("env", "now"),
+                import("wasi_snapshot_preview1", "fd_write"),
+            ]),
+            Outcome::Fail(
+                "imports functions the Sabre interpreter does not provide: env.now, \
+                 wasi_snapshot_preview1.fd_write"
+                    .into()
+            )
+        );
+
+        assert!(matches!(
+            check_entrypoint(&["memory".into(), "entrypoint".into()]),
+            Outcome::Pass(_)
+        ));
+        assert!(matches!(
+            check_entrypoint(&["main".into()]),
+            Outcome::Fail(_)
+        ));
+
+        assert!(matches!(check_memory(None, 16), Outcome::Pass(_)));
+        assert!(matches!(
+            check_memory(Some((16, None)), 16),
+            Outcome::Pass(_)
+        ));
+        assert!(matches!(
+            check_memory(Some((16, Some(16))), 16),
+            Outcome::Pass(_)
+        ));
+        assert!(matches!(
+            check_memory(Some((17, None)), 16),
+            Outcome::Fail(_)
+        ));
+        assert!(matches!(
+            check_memory(Some((1, Some(17))), 16),
+            Outcome::Fail(_)
+        ));
+    }
+
+    #[test]
+    // Asserts that state entries are returned once each, in address order or its reverse
+    fn test_cli_contract_test_memory_context() {
+        let state = vec![
+            ("a".to_string(), vec![1]),
+            ("b".to_string(), vec![2]),
+            ("c".to_string(), vec![3]),
+        ]
+        .into_iter()
+        .collect::<BTreeMap<_, _>>();
+        let addresses = [
+            "c".to_string(),
+            "a".to_string(),
+            "x".to_string(),
+            "a".to_string(),
+        ];
+
+        let context = MemoryContext::new(state.clone(), false);
+        assert_eq!(
+            context.get_state_entries(&addresses).unwrap(),
+            vec![("a".to_string(), vec![1]), ("c".to_string(), vec![3])]
+        );
+
+        let context = MemoryContext::new(state, true);
+        assert_eq!(
+            context.get_state_entries(&addresses).unwrap(),
+            vec![("c".to_string(), vec![3]), ("a".to_string(), vec![1])]
+        );
+        assert_eq!(
+            context
+                .delete_state_entries(&["b".to_string(), "x".to_string()])
+                .unwrap(),
+            vec!["b".to_string()]
+        );
+    }
+}
@@ -0,0 +1,271 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains the dependencies a contract definition may declare on other contracts, and the
+//! order in which contracts must be uploaded to satisfy them.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use yaml_rust::Yaml;
+
+/// A contract which must be uploaded before the contract declaring it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    pub name: String,
+    pub requirement: VersionRequirement,
+}
+
+impl Dependency {
+    /// Returns whether the contract version satisfies the dependency.
+    pub fn is_satisfied_by(&self, name: &str, version: &str) -> bool {
+        self.name == name && self.requirement.matches(version)
+    }
+}
+
+impl fmt::Display for Dependency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.name, self.requirement)
+    }
+}
+
+/// Parses the "dependencies" array of a contract definition, which may be absent. Each
+/// dependency has a "name" and an optional "version" requirement, such as ">=1.0, <2.0".
+pub fn parse_dependencies(doc: &Yaml) -> Result<Vec<Dependency>, String> {
+    let dependencies = match &doc["dependencies"] {
+        Yaml::BadValue | Yaml::Null => return Ok(vec![]),
+        Yaml::Array(dependencies) => dependencies,
+        _ => return Err("\"dependencies\" is not an array".into()),
+    };
+
+    dependencies
+        .iter()
+        .map(|dependency| {
+            let name = dependency["name"]
+                .as_str()
+                .ok_or("dependency is missing string field \"name\"")?;
+            let requirement = match &dependency["version"] {
+                Yaml::BadValue | Yaml::Null => VersionRequirement::default(),
+                Yaml::String(version) => VersionRequirement::parse(version)?,
+                _ => {
+                    return Err(format!(
+                        "version of dependency \"{}\" is not a string",
+                        name
+                    ))
+                }
+            };
+
+            Ok(Dependency {
+                name: name.to_string(),
+                requirement,
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Eq,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+/// A set of comparisons a contract version must all satisfy; an empty requirement matches any
+/// version.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VersionRequirement {
+    comparators: Vec<(Operator, String)>,
+}
+
+impl VersionRequirement {
+    pub fn parse(requirement: &str) -> Result<Self, String> {
+        let comparators = requirement
+            .split(',')
+            .map(str::trim)
+            .filter(|comparator| !comparator.is_empty() && *comparator != "*")
+            .map(|comparator| {
+                let (operator, version) = [
+                    (">=", Operator::Ge),
+                    ("<=", Operator::Le),
+                    (">", Operator::Gt),
+                    ("<", Operator::Lt),
+                    ("=", Operator::Eq),
+                ]
+                .iter()
+                .find(|(symbol, _)| comparator.starts_with(symbol))
+                .map(|(symbol, operator)| (*operator, comparator[symbol.len()..].trim()))
+                .unwrap_or((Operator::Eq, comparator));
+
+                if version.is_empty() {
+                    return Err(format!("invalid version requirement \"{}\"", requirement));
+                }
+                Ok((operator, version.to_string()))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(VersionRequirement { comparators })
+    }
+
+    pub fn matches(&self, version: &str) -> bool {
+        self.comparators.iter().all(|(operator, required)| {
+            let ordering = compare_versions(version, required);
+            match operator {
+                Operator::Eq => ordering == Ordering::Equal,
+                Operator::Gt => ordering == Ordering::Greater,
+                Operator::Ge => ordering != Ordering::Less,
+                Operator::Lt => ordering == Ordering::Less,
+                Operator::Le => ordering != Ordering::Greater,
+            }
+        })
+    }
+}
+
+impl fmt::Display for VersionRequirement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.comparators.is_empty() {
+            return f.write_str("*");
+        }
+
+        let comparators = self
+            .comparators
+            .iter()
+            .map(|(operator, version)| {
+                let symbol = match operator {
+                    Operator::Eq => "=",
+                    Operator::Gt => ">",
+                    Operator::Ge => ">=",
+                    Operator::Lt => "<",
+                    Operator::Le => "<=",
+                };
+                format!("{}{}", symbol, version)
+            })
+            .collect::<Vec<_>>();
+        f.write_str(&comparators.join(", "))
+    }
+}
+
+/// Compares contract versions by their dot separated components, numerically where both
+/// components are numbers. Missing components are treated as 0, so "1.0" equals "1".
+pub fn compare_versions(left: &str, right: &str) -> Ordering {
+    let left = left.split('.').collect::<Vec<_>>();
+    let right = right.split('.').collect::<Vec<_>>();
+
+    for i in 0..left.len().max(right.len()) {
+        let l = left.get(i).copied().unwrap_or("0");
+        let r = right.get(i).copied().unwrap_or("0");
+        let ordering = match (l.parse::<u64>(), r.parse::<u64>()) {
+            (Ok(l), Ok(r)) => l.cmp(&r),
+            _ => l.cmp(r),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    Ordering::Equal
+}
+
+/// Returns the order in which to upload contracts, given as (name, version, dependencies), so
+/// that each comes after the contracts it depends on. Dependencies no contract satisfies are
+/// ignored, as they must already be uploaded. Contracts are otherwise kept in the order given.
+pub fn install_order(contracts: &[(&str, &str, &[Dependency])]) -> Result<Vec<usize>, String> {
+    let mut order = Vec::with_capacity(contracts.len());
+    let mut placed = vec![false; contracts.len()];
+
+    while order.len() < contracts.len() {
+        let next = (0..contracts.len()).find(|&i| {
+            !placed[i]
+                && contracts[i].2.iter().all(|dependency| {
+                    contracts.iter().enumerate().all(|(j, (name, version, _))| {
+                        placed[j] || j == i || !dependency.is_satisfied_by(name, version)
+                    })
+                })
+        });
+
+        match next {
+            Some(i) => {
+                placed[i] = true;
+                order.push(i);
+            }
+            None => {
+                let cycle = (0..contracts.len())
+                    .filter(|&i| !placed[i])
+                    .map(|i| format!("{}:{}", contracts[i].0, contracts[i].1))
+                    .collect::<Vec<_>>();
+                return Err(format!(
+                    "contracts depend on each other: {}",
+                    cycle.join(", ")
+                ));
+            }
+        }
+    }
+
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    // check that version requirements compare versions numerically by component
+    fn test_cli_version_requirement() {
+        let requirement = VersionRequirement::parse(">=1.2, <2").unwrap();
+        assert!(requirement.matches("1.2"));
+        assert!(requirement.matches("1.10"));
+        assert!(!requirement.matches("1.1"));
+        assert!(!requirement.matches("2.0"));
+
+        assert!(VersionRequirement::parse("1.0").unwrap().matches("1"));
+        assert!(VersionRequirement::parse("*").unwrap().matches("0.1"));
+        assert!(VersionRequirement::parse(">=").is_err());
+    }
+
+    #[test]
+    // check that dependencies are parsed from a contract definition, and that contracts are
+    // ordered after the contracts they depend on
+    fn test_cli_install_order() {
+        let doc = &YamlLoader::load_from_str(
+            "name: app\n\
+             dependencies:\n  \
+               - name: lib\n    \
+                 version: \">=1.0\"\n",
+        )
+        .unwrap()[0];
+        let app_dependencies = parse_dependencies(doc).unwrap();
+        assert_eq!(app_dependencies[0].to_string(), "lib >=1.0");
+
+        let lib_dependencies = vec![Dependency {
+            name: "external".into(),
+            requirement: VersionRequirement::default(),
+        }];
+        let contracts: Vec<(&str, &str, &[Dependency])> = vec![
+            ("app", "1.0", &app_dependencies),
+            ("lib", "1.1", &lib_dependencies),
+        ];
+        assert_eq!(install_order(&contracts).unwrap(), vec![1, 0]);
+
+        let cyclic = vec![Dependency {
+            name: "app".into(),
+            requirement: VersionRequirement::default(),
+        }];
+        let contracts: Vec<(&str, &str, &[Dependency])> =
+            vec![("app", "1.0", &app_dependencies), ("lib", "1.1", &cyclic)];
+        assert!(install_order(&contracts).is_err());
+    }
+}
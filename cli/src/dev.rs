@@ -0,0 +1,213 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains starting and stopping local single node networks for development
+//!
+//! Each network is a docker compose project named sabre-dev-<name>. Its compose file, which is
+//! embedded in the CLI, is written with a .env file holding its settings to dev/<name> in the
+//! configuration directory, so the network can be stopped without repeating them.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::CliError;
+use crate::paths;
+use crate::watch::get_head;
+
+/// The compose file of the network
+const COMPOSE_FILE: &str = include_str!("../dev/docker-compose.yaml");
+
+/// The image the Sabre transaction processor is run from, unless another is given
+pub const DEFAULT_TP_IMAGE: &str = "hyperledger/sawtooth-sabre-tp:latest";
+
+/// How often the REST API is polled while waiting for the network to start
+const READY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A local network, which may or may not be running
+pub struct DevNetwork {
+    name: String,
+    dir: PathBuf,
+}
+
+impl DevNetwork {
+    /// Returns the network with the given name. Names may contain lowercase letters, digits,
+    /// '-' and '_'.
+    pub fn new(name: &str) -> Result<Self, CliError> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+        {
+            return Err(CliError::User(format!(
+                "Invalid network name '{}': names may only contain lowercase letters, digits, \
+                 '-' and '_'",
+                name
+            )));
+        }
+
+        Ok(DevNetwork {
+            name: name.to_string(),
+            dir: paths::config_dir()?.join("dev").join(name),
+        })
+    }
+
+    /// Starts the network, with the public key as the network's administrator, and waits until
+    /// its REST API, bound to the port on localhost, answers. Returns the URL of the REST API.
+    pub fn up(
+        &self,
+        admin_key: &str,
+        port: u16,
+        tp_image: &str,
+        timeout: Duration,
+    ) -> Result<String, CliError> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.dir.join("docker-compose.yaml"), COMPOSE_FILE)?;
+        fs::write(
+            self.dir.join(".env"),
+            format!(
+                "SABRE_DEV_ADMIN_KEY={}\nSABRE_DEV_PORT={}\nSABRE_DEV_TP_IMAGE={}\n",
+                admin_key, port, tp_image
+            ),
+        )?;
+
+        self.compose(&["up", "--detach"])?;
+
+        let url = format!("http://localhost:{}", port);
+        wait_until_ready(&url, timeout).map_err(|err| {
+            CliError::User(format!(
+                "{}; the network is still running, stop it with 'sabre dev down --name {}'",
+                err, self.name
+            ))
+        })?;
+
+        Ok(url)
+    }
+
+    /// Stops the network, removing its containers and volumes, and its files.
+    pub fn down(&self) -> Result<(), CliError> {
+        if !self.dir.join("docker-compose.yaml").is_file() {
+            return Err(CliError::User(format!(
+                "There is no network named '{}'",
+                self.name
+            )));
+        }
+
+        self.compose(&["down", "--volumes", "--remove-orphans"])?;
+        fs::remove_dir_all(&self.dir)?;
+
+        Ok(())
+    }
+
+    /// Runs docker compose with the arguments against the network's project.
+    fn compose(&self, args: &[&str]) -> Result<(), CliError> {
+        let status = Command::new("docker")
+            .arg("compose")
+            .arg("--project-name")
+            .arg(format!("sabre-dev-{}", self.name))
+            .arg("--file")
+            .arg(self.dir.join("docker-compose.yaml"))
+            .args(args)
+            .status()
+            .map_err(|err| match err.kind() {
+                ErrorKind::NotFound => CliError::User(
+                    "docker was not found; a local network requires docker compose".into(),
+                ),
+                _ => CliError::Io(err),
+            })?;
+
+        if !status.success() {
+            return Err(CliError::User(format!(
+                "docker compose {} failed ({})",
+                args.join(" "),
+                status
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Waits until the REST API at the URL returns a head block, which it does once the genesis
+/// block, holding the administrators setting, is committed.
+fn wait_until_ready(url: &str, timeout: Duration) -> Result<(), CliError> {
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        let err = match get_head(url) {
+            Ok(_) => return Ok(()),
+            Err(err) => err,
+        };
+
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(CliError::User(format!(
+                "The network was not ready within {}s: {}",
+                timeout.as_secs(),
+                err
+            )));
+        }
+        thread::sleep(READY_INTERVAL.min(deadline - now));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use yaml_rust::YamlLoader;
+
+    use super::*;
+
+    #[test]
+    // Asserts that only names which are valid in a compose project name are accepted
+    fn test_cli_dev_network_name() {
+        assert!(DevNetwork::new("default").is_ok());
+        assert!(DevNetwork::new("ci-2_a").is_ok());
+        assert!(DevNetwork::new("").is_err());
+        assert!(DevNetwork::new("Default").is_err());
+        assert!(DevNetwork::new("../default").is_err());
+    }
+
+    #[test]
+    // Asserts that the embedded compose file runs the Sabre transaction processor, and takes
+    // its settings from the variables written to the .env file
+    fn test_cli_dev_compose_file() {
+        let compose = &YamlLoader::load_from_str(COMPOSE_FILE).unwrap()[0];
+        let services = &compose["services"];
+        for service in &[
+            "validator",
+            "rest-api",
+            "settings-tp",
+            "devmode-rust",
+            "sabre-tp",
+        ] {
+            assert!(!services[*service].is_badvalue(), "{} is missing", service);
+        }
+
+        assert_eq!(
+            services["sabre-tp"]["image"].as_str(),
+            Some("${SABRE_DEV_TP_IMAGE}")
+        );
+        assert_eq!(
+            services["rest-api"]["ports"][0].as_str(),
+            Some("127.0.0.1:${SABRE_DEV_PORT}:9708")
+        );
+        assert!(services["validator"]["entrypoint"]
+            .as_str()
+            .unwrap()
+            .contains("sawtooth.swa.administrators=${SABRE_DEV_ADMIN_KEY}"));
+    }
+}
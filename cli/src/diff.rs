@@ -0,0 +1,170 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains the comparison of the state of two networks
+
+use std::collections::BTreeMap;
+
+use sabre_sdk::protocol::settings::Setting;
+use sabre_sdk::protocol::state::{ContractList, ContractRegistryList, NamespaceRegistryList};
+use sabre_sdk::protos::FromBytes;
+
+use crate::state::{address_kind, StateEntry};
+
+/// A difference between the state of the left and right networks at an address
+#[derive(Debug, PartialEq, Eq)]
+pub enum StateDifference {
+    /// The address is only set on the right network
+    Added(StateEntry),
+    /// The address is only set on the left network
+    Removed(StateEntry),
+    /// The address is set to different data on each network
+    Changed { left: StateEntry, right: StateEntry },
+}
+
+impl StateDifference {
+    pub fn address(&self) -> &str {
+        match self {
+            StateDifference::Added(entry) | StateDifference::Removed(entry) => &entry.address,
+            StateDifference::Changed { left, .. } => &left.address,
+        }
+    }
+}
+
+/// Compares the entries of the left and right networks, returning the differences ordered by
+/// address.
+pub fn diff_state(left: Vec<StateEntry>, right: Vec<StateEntry>) -> Vec<StateDifference> {
+    let mut right = right
+        .into_iter()
+        .map(|entry| (entry.address.clone(), entry))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut differences = Vec::new();
+    for left_entry in left {
+        match right.remove(&left_entry.address) {
+            Some(right_entry) if right_entry.data == left_entry.data => (),
+            Some(right_entry) => differences.push(StateDifference::Changed {
+                left: left_entry,
+                right: right_entry,
+            }),
+            None => differences.push(StateDifference::Removed(left_entry)),
+        }
+    }
+    differences.extend(right.into_values().map(StateDifference::Added));
+
+    differences.sort_by(|a, b| a.address().cmp(b.address()));
+    differences
+}
+
+/// Returns a short description of the entry's data, decoding the entries stored by Sabre and
+/// Sawtooth settings.
+pub fn summarize(entry: &StateEntry) -> String {
+    let bytes = match base64::decode(&entry.data) {
+        Ok(bytes) => bytes,
+        Err(_) => return "undecodable data".into(),
+    };
+
+    let summary = match address_kind(&entry.address) {
+        Some("namespace registry") => NamespaceRegistryList::from_bytes(&bytes).ok().map(|list| {
+            list.registries()
+                .iter()
+                .map(|registry| {
+                    format!(
+                        "namespace {} ({} permissions)",
+                        registry.namespace(),
+                        registry.permissions().len()
+                    )
+                })
+                .collect::<Vec<_>>()
+        }),
+        Some("contract registry") => ContractRegistryList::from_bytes(&bytes).ok().map(|list| {
+            list.registries()
+                .iter()
+                .map(|registry| {
+                    format!(
+                        "contract registry {} ({} versions)",
+                        registry.name(),
+                        registry.versions().len()
+                    )
+                })
+                .collect::<Vec<_>>()
+        }),
+        Some("contract") => ContractList::from_bytes(&bytes).ok().map(|list| {
+            list.contracts()
+                .iter()
+                .map(|contract| format!("contract {}:{}", contract.name(), contract.version()))
+                .collect::<Vec<_>>()
+        }),
+        Some("setting") => Setting::from_bytes(&bytes).ok().map(|setting| {
+            setting
+                .entries()
+                .iter()
+                .map(|entry| format!("setting {}={}", entry.key(), entry.value()))
+                .collect::<Vec<_>>()
+        }),
+        _ => None,
+    };
+
+    match summary {
+        Some(summary) => summary.join("; "),
+        None => format!("{} bytes", bytes.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn entry(address: &str, data: &[u8]) -> StateEntry {
+        StateEntry {
+            address: address.to_string(),
+            data: base64::encode(data),
+        }
+    }
+
+    #[test]
+    // Asserts that entries only on the right are added, only on the left are removed, and with
+    // different data are changed, in address order
+    fn test_cli_diff_state() {
+        let left = vec![
+            entry("1cf1260c", b"c"),
+            entry("1cf1260a", b"a"),
+            entry("1cf1260b", b"b"),
+        ];
+        let right = vec![
+            entry("1cf1260d", b"d"),
+            entry("1cf1260b", b"x"),
+            entry("1cf1260c", b"c"),
+        ];
+
+        assert_eq!(
+            diff_state(left, right),
+            vec![
+                StateDifference::Removed(entry("1cf1260a", b"a")),
+                StateDifference::Changed {
+                    left: entry("1cf1260b", b"b"),
+                    right: entry("1cf1260b", b"x"),
+                },
+                StateDifference::Added(entry("1cf1260d", b"d")),
+            ]
+        );
+    }
+
+    #[test]
+    // Asserts that entries which are not Sabre or settings state are summarized by their size
+    fn test_cli_diff_summarize_unknown() {
+        assert_eq!(summarize(&entry("1cf12600", b"abc")), "3 bytes");
+    }
+}
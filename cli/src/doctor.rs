@@ -0,0 +1,324 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains the diagnostics run by `sabre doctor`
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use reqwest::header::{ACCEPT, DATE};
+use reqwest::Url;
+use sabre_sdk::protocol::ADMINISTRATORS_SETTING_KEY;
+use serde_json::Value;
+
+use crate::error::CliError;
+use crate::key::new_signer;
+use crate::rest_api::{authorize, client, parse_response, with_failover, JSON_MEDIA_TYPE};
+use crate::state;
+
+/// The largest difference between the local and REST API clocks which passes
+const MAX_CLOCK_SKEW_SECS: u64 = 30;
+
+/// The outcome of a diagnostic check
+#[derive(Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Pass(String),
+    Fail { problem: String, hint: &'static str },
+}
+
+/// A diagnostic check and its outcome
+#[derive(Debug, PartialEq, Eq)]
+pub struct Check {
+    pub name: &'static str,
+    pub outcome: Outcome,
+}
+
+/// Runs every check against the REST API and the signing key. The checks which read state are
+/// skipped if the REST API cannot be reached.
+pub fn run_checks(url: &str, key_name: Option<&str>, key_algo: Option<&str>) -> Vec<Check> {
+    let mut checks = vec![];
+
+    let date = match get_rest_api_date(url) {
+        Ok(date) => {
+            checks.push(Check {
+                name: "REST API",
+                outcome: Outcome::Pass(format!("{} is reachable", url)),
+            });
+            date
+        }
+        Err(err) => {
+            checks.push(Check {
+                name: "REST API",
+                outcome: Outcome::Fail {
+                    problem: err.to_string(),
+                    hint: "Check that --url names a running Sawtooth REST API",
+                },
+            });
+            checks.push(check_signing_key(key_name, key_algo, None));
+            return checks;
+        }
+    };
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    checks.push(Check {
+        name: "Clock skew",
+        outcome: check_clock_skew(date.as_deref(), now),
+    });
+
+    let administrators = state::get_administrators(url);
+    checks.push(Check {
+        name: "Administrators setting",
+        outcome: match &administrators {
+            Ok(administrators) => check_administrators(administrators.as_deref()),
+            Err(err) => Outcome::Fail {
+                problem: err.to_string(),
+                hint: "Check that the settings transaction processor is running",
+            },
+        },
+    });
+
+    checks.push(check_signing_key(
+        key_name,
+        key_algo,
+        administrators.ok().flatten().as_deref(),
+    ));
+
+    checks.push(Check {
+        name: "Sabre state",
+        outcome: match (
+            state::get_contract_registries(url),
+            state::get_namespace_registries(url),
+        ) {
+            (Ok(contract_registries), Ok(namespace_registries)) => Outcome::Pass(format!(
+                "{} contract registries and {} namespace registries",
+                contract_registries.len(),
+                namespace_registries.len()
+            )),
+            (Err(err), _) | (_, Err(err)) => Outcome::Fail {
+                problem: err.to_string(),
+                hint: "Check that the Sabre transaction processor is running and registered \
+                       with the validator",
+            },
+        },
+    });
+
+    checks
+}
+
+/// Requests a block from the REST API, returning the time it responded at, if it reported one.
+fn get_rest_api_date(url: &str) -> Result<Option<String>, CliError> {
+    with_failover(url, get_rest_api_date_from)
+}
+
+fn get_rest_api_date_from(url: &str) -> Result<Option<String>, CliError> {
+    let url = Url::parse(&format!("{}/blocks?limit=1", url))
+        .map_err(|e| CliError::User(format!("Invalid URL: {}: {}", e, url)))?;
+
+    let response = authorize(client(url.as_str())?.get(url))
+        .header(ACCEPT, JSON_MEDIA_TYPE)
+        .send()?;
+    let date = response
+        .headers()
+        .get(DATE)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from);
+    parse_response::<Value>(response)?;
+
+    Ok(date)
+}
+
+fn check_clock_skew(date: Option<&str>, now: u64) -> Outcome {
+    let date = match date {
+        Some(date) => date,
+        None => return Outcome::Pass("the REST API did not report its time".into()),
+    };
+
+    match parse_http_date(date) {
+        Some(time) if time.max(now) - time.min(now) <= MAX_CLOCK_SKEW_SECS => Outcome::Pass(
+            format!("within {}s of the REST API", time.max(now) - time.min(now)),
+        ),
+        Some(time) => Outcome::Fail {
+            problem: format!(
+                "local clock is {}s {} the REST API",
+                time.max(now) - time.min(now),
+                if now > time { "ahead of" } else { "behind" }
+            ),
+            hint: "Synchronize this host's clock, for example with NTP",
+        },
+        None => Outcome::Pass(format!("unable to parse the REST API's time: {}", date)),
+    }
+}
+
+fn check_administrators(administrators: Option<&str>) -> Outcome {
+    match administrators {
+        Some(administrators) if !administrators.trim().is_empty() => {
+            Outcome::Pass(format!("{} is set", ADMINISTRATORS_SETTING_KEY))
+        }
+        _ => Outcome::Fail {
+            problem: format!("{} is not set", ADMINISTRATORS_SETTING_KEY),
+            hint: "Set it with 'sawset proposal create sawtooth.swa.administrators=<public key>'; \
+                   only administrators may create namespace and contract registries",
+        },
+    }
+}
+
+fn check_signing_key(
+    key_name: Option<&str>,
+    key_algo: Option<&str>,
+    administrators: Option<&str>,
+) -> Check {
+    let public_key = new_signer(key_name, key_algo).and_then(|signer| {
+        signer
+            .public_key()
+            .map(|public_key| public_key.as_hex())
+            .map_err(|err| CliError::Signing(err.to_string()))
+    });
+
+    Check {
+        name: "Signing key",
+        outcome: match public_key {
+            Ok(public_key) => {
+                let is_administrator = administrators
+                    .map(|administrators| {
+                        administrators
+                            .split(',')
+                            .any(|administrator| administrator.trim() == public_key)
+                    })
+                    .unwrap_or(false);
+                Outcome::Pass(format!(
+                    "{} ({})",
+                    public_key,
+                    if is_administrator {
+                        "an administrator"
+                    } else {
+                        "not an administrator"
+                    }
+                ))
+            }
+            Err(err) => Outcome::Fail {
+                problem: err.to_string(),
+                hint: "Create a key with 'sawtooth keygen', or name one with --key",
+            },
+        },
+    }
+}
+
+/// Parses an HTTP date, such as "Sun, 06 Nov 1994 08:49:37 GMT", into seconds since the epoch.
+fn parse_http_date(date: &str) -> Option<u64> {
+    let parts: Vec<&str> = date.split_whitespace().collect();
+    if parts.len() != 6 || parts[5] != "GMT" {
+        return None;
+    }
+
+    let day: u64 = parts[1].parse().ok()?;
+    let month = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ]
+    .iter()
+    .position(|month| *month == parts[2])? as u64
+        + 1;
+    let year: u64 = parts[3].parse().ok()?;
+    let time = parts[4]
+        .split(':')
+        .map(|part| part.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    // Out of range fields are rejected before the arithmetic below, which would underflow or
+    // overflow on them
+    if !(1..=31).contains(&day)
+        || !(1970..=9999).contains(&year)
+        || time.len() != 3
+        || time[0] > 23
+        || time[1] > 59
+        || time[2] > 60
+    {
+        return None;
+    }
+
+    // Days since the epoch of the civil date, counting years from March so the leap day is last
+    let (year, month) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    Some(days * 86400 + time[0] * 3600 + time[1] * 60 + time[2])
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    // Asserts that HTTP dates are parsed into seconds since the epoch
+    fn test_cli_parse_http_date() {
+        assert_eq!(
+            parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(784_111_777)
+        );
+        assert_eq!(
+            parse_http_date("Thu, 29 Feb 2024 00:00:00 GMT"),
+            Some(1_709_164_800)
+        );
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 00 Nov 1994 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 32 Nov 1994 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 24:49:37 GMT"), None);
+        assert_eq!(
+            parse_http_date("Sun, 06 Nov 18446744073709551615 08:49:37 GMT"),
+            None
+        );
+    }
+
+    #[test]
+    // Asserts that clocks further apart than the limit fail, in either direction
+    fn test_cli_check_clock_skew() {
+        let date = "Sun, 06 Nov 1994 08:49:37 GMT";
+
+        assert!(matches!(
+            check_clock_skew(Some(date), 784_111_777 + MAX_CLOCK_SKEW_SECS),
+            Outcome::Pass(_)
+        ));
+        assert!(matches!(
+            check_clock_skew(Some(date), 784_111_777 + MAX_CLOCK_SKEW_SECS + 1),
+            Outcome::Fail { .. }
+        ));
+        assert!(matches!(
+            check_clock_skew(Some(date), 784_111_777 - MAX_CLOCK_SKEW_SECS - 1),
+            Outcome::Fail { .. }
+        ));
+        assert!(matches!(check_clock_skew(None, 0), Outcome::Pass(_)));
+    }
+
+    #[test]
+    // Asserts that a missing or empty administrators setting fails
+    fn test_cli_check_administrators() {
+        assert!(matches!(
+            check_administrators(Some("02abcdef")),
+            Outcome::Pass(_)
+        ));
+        assert!(matches!(
+            check_administrators(Some("")),
+            Outcome::Fail { .. }
+        ));
+        assert!(matches!(check_administrators(None), Outcome::Fail { .. }));
+    }
+}
@@ -15,6 +15,7 @@
 use std::borrow::Borrow;
 use std::error::Error as StdError;
 
+use rustls::AlertDescription;
 use sabre_sdk::protocol::payload::{ActionBuildError, SabrePayloadBuildError};
 use sabre_sdk::protos::ProtoConversionError;
 use sawtooth::{
@@ -73,8 +74,64 @@ impl From<std::io::Error> for CliError {
 
 impl From<reqwest::Error> for CliError {
     fn from(e: reqwest::Error) -> Self {
-        CliError::Request(e)
+        let host = e
+            .url()
+            .and_then(|url| url.host_str())
+            .unwrap_or("the REST API")
+            .to_string();
+        match certificate_error(&e) {
+            Some(CertificateError::Server(cause)) => CliError::User(format!(
+                "The TLS certificate of {} was rejected: {}; a certificate issued by a private CA \
+                 is trusted with --ca-file",
+                host, cause
+            )),
+            Some(CertificateError::Client(cause)) => CliError::User(format!(
+                "{} rejected the client certificate: {}; give a certificate it trusts with \
+                 --client-cert and --client-key",
+                host, cause
+            )),
+            None => CliError::Request(e),
+        }
+    }
+}
+
+/// A TLS handshake which failed on a certificate
+enum CertificateError {
+    /// The server's certificate failed validation
+    Server(rustls::Error),
+    /// The server refused the client's certificate, or the lack of one
+    Client(rustls::Error),
+}
+
+/// Returns the TLS error in the error's source chain if the handshake failed on a certificate.
+fn certificate_error(err: &reqwest::Error) -> Option<CertificateError> {
+    let mut source = err.source();
+    while let Some(cause) = source {
+        // The TLS error reaches reqwest wrapped in an I/O error, whose source skips it
+        let tls_error = cause.downcast_ref::<rustls::Error>().or_else(|| {
+            cause
+                .downcast_ref::<std::io::Error>()
+                .and_then(|err| err.get_ref())
+                .and_then(|err| err.downcast_ref::<rustls::Error>())
+        });
+        match tls_error {
+            Some(rustls::Error::InvalidCertificate(_)) => {
+                return tls_error.cloned().map(CertificateError::Server)
+            }
+            Some(rustls::Error::AlertReceived(
+                AlertDescription::BadCertificate
+                | AlertDescription::CertificateRequired
+                | AlertDescription::CertificateExpired
+                | AlertDescription::CertificateRevoked
+                | AlertDescription::CertificateUnknown
+                | AlertDescription::UnsupportedCertificate
+                | AlertDescription::UnknownCA,
+            )) => return tls_error.cloned().map(CertificateError::Client),
+            _ => (),
+        }
+        source = cause.source();
     }
+    None
 }
 
 impl From<ProtoConversionError> for CliError {
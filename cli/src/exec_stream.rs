@@ -0,0 +1,219 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains the execution of contracts read from an NDJSON stream
+//!
+//! Each line of the stream is a JSON object describing one execution:
+//!
+//! ```text
+//! {"id": "order-1", "contract": "intkey_multiply:1.0", "payload": "<base64>",
+//!  "inputs": ["1cf126"], "outputs": ["1cf126"]}
+//! ```
+//!
+//! The contract may be pinned to the sha512 of its wasm, as `intkey_multiply:1.0@<sha512>`.
+//!
+//! The id is optional, and defaults to the line number. It is reported with the transaction and
+//! batch the line was submitted in, so results can be correlated with their source.
+
+use std::io::BufRead;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use cylinder::Signer;
+use sabre_sdk::protocol::payload::ExecuteContractActionBuilder;
+use sawtooth::transact::protocol::transaction::Transaction;
+
+use crate::address_book;
+use crate::batching::{build_batches, BatchLimits};
+use crate::error::CliError;
+use crate::submit::{submit_batches, Submission};
+use crate::trace;
+use crate::{parse_name_version, split_contract_pin};
+
+/// One execution read from the stream
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+struct ExecuteSpec {
+    id: Option<String>,
+    contract: String,
+    /// The contract payload, base64 encoded
+    payload: String,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+}
+
+/// Reads executions from the stream, building at most `rate` transactions per second if it is
+/// given, and submits them in batches as they fill. Lines which cannot be built are reported and
+/// skipped; they make the command fail once the rest are submitted.
+///
+/// Returns the last submission, whose batches are the ones waited for, if any were submitted.
+pub fn do_exec_stream<R: BufRead>(
+    stream: R,
+    signer: &dyn Signer,
+    url: &str,
+    rate: Option<f64>,
+    limits: &BatchLimits,
+) -> Result<Option<Submission>, CliError> {
+    let started = Instant::now();
+    let mut pending: Vec<(String, Transaction)> = Vec::new();
+    let mut built = 0u64;
+    let mut failures = 0;
+    let mut last_submission = None;
+
+    for (number, line) in stream.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(rate) = rate {
+            let due = Duration::from_secs_f64(built as f64 / rate);
+            if let Some(delay) = due.checked_sub(started.elapsed()) {
+                thread::sleep(delay);
+            }
+        }
+
+        match build_transaction(&line, signer) {
+            Ok((id, transaction)) => {
+                built += 1;
+                pending.push((id.unwrap_or_else(|| (number + 1).to_string()), transaction));
+            }
+            Err(err) => {
+                failures += 1;
+                println!("{}: {}", number + 1, err);
+                continue;
+            }
+        }
+
+        if pending.len() >= limits.max_transactions {
+            last_submission = Some(submit_pending(&mut pending, signer, url, limits)?);
+        }
+    }
+
+    if !pending.is_empty() {
+        last_submission = Some(submit_pending(&mut pending, signer, url, limits)?);
+    }
+
+    if failures > 0 {
+        return Err(CliError::User(format!(
+            "{} lines could not be executed",
+            failures
+        )));
+    }
+
+    Ok(last_submission)
+}
+
+fn build_transaction(
+    line: &str,
+    signer: &dyn Signer,
+) -> Result<(Option<String>, Transaction), CliError> {
+    let spec: ExecuteSpec = serde_json::from_str(line)
+        .map_err(|err| CliError::User(format!("invalid execution: {}", err)))?;
+
+    let (contract, contract_sha512) = split_contract_pin(&spec.contract)?;
+    let (name, version) = parse_name_version(contract).ok_or_else(|| {
+        CliError::User(format!(
+            "contract must be of the form 'name:version': {}",
+            spec.contract
+        ))
+    })?;
+    let payload = base64::decode(&spec.payload)
+        .map_err(|err| CliError::User(format!("payload is not base64: {}", err)))?;
+    let inputs = spec
+        .inputs
+        .iter()
+        .map(|input| address_book::resolve(input))
+        .collect::<Result<Vec<_>, _>>()?;
+    let outputs = spec
+        .outputs
+        .iter()
+        .map(|output| address_book::resolve(output))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut action = ExecuteContractActionBuilder::new()
+        .with_name(name.into())
+        .with_version(version.into())
+        .with_inputs(inputs)
+        .with_outputs(outputs)
+        .with_payload(payload);
+    if let Some(contract_sha512) = contract_sha512 {
+        action = action.with_contract_sha512(contract_sha512);
+    }
+    let transaction = action
+        .into_payload_builder()?
+        .into_transaction_builder()?
+        .with_nonce(trace::transaction_nonce())
+        .build(signer)?;
+
+    Ok((spec.id, transaction))
+}
+
+/// Submits the pending transactions, reporting the transaction and batch of each id.
+fn submit_pending(
+    pending: &mut Vec<(String, Transaction)>,
+    signer: &dyn Signer,
+    url: &str,
+    limits: &BatchLimits,
+) -> Result<Submission, CliError> {
+    let (ids, transactions): (Vec<_>, Vec<_>) = pending.drain(..).unzip();
+    let batches = build_batches(transactions, limits, signer)?;
+
+    let mut ids = ids.into_iter();
+    for batch in &batches {
+        for transaction in batch.transactions() {
+            if let Some(id) = ids.next() {
+                println!(
+                    "{}: transaction {} in batch {}",
+                    id,
+                    transaction.header_signature(),
+                    batch.header_signature()
+                );
+            }
+        }
+    }
+
+    submit_batches(url, batches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cylinder::{secp256k1::Secp256k1Context, Context};
+
+    #[test]
+    // Asserts that a line is built into an execute contract transaction, and that malformed
+    // lines are rejected
+    fn test_cli_exec_stream_build_transaction() {
+        let context = Secp256k1Context::new();
+        let signer = context.new_signer(context.new_random_private_key());
+
+        let (id, transaction) = build_transaction(
+            "{\"id\": \"a\", \"contract\": \"intkey_multiply:1.0\", \"payload\": \"AQI=\", \
+             \"inputs\": [\"1cf126\"], \"outputs\": [\"1cf126\"]}",
+            &*signer,
+        )
+        .unwrap();
+        assert_eq!(id, Some("a".to_string()));
+        assert!(!transaction.payload().is_empty());
+
+        assert!(build_transaction("{\"contract\": \"intkey_multiply:1.0\"}", &*signer).is_err());
+        assert!(build_transaction(
+            "{\"contract\": \"intkey_multiply\", \"payload\": \"\", \"inputs\": [], \
+             \"outputs\": []}",
+            &*signer
+        )
+        .is_err());
+    }
+}
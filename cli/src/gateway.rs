@@ -0,0 +1,620 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains a JSON-RPC 2.0 gateway to Sabre, for clients which cannot build and sign Sabre
+//! transactions themselves
+//!
+//! Requests are POSTed as JSON to the gateway, which builds the transaction, signs it with the
+//! gateway's key and submits it to the REST API. Binary data, such as contracts, payloads and
+//! state, is base64 encoded. The methods are:
+//!
+//! * `contract.upload` - `name`, `version`, `inputs`, `outputs`, `wasm`
+//! * `contract.execute` - `name`, `version`, `inputs`, `outputs`, `payload`
+//! * `contract_registry.create` - `name`, `owners`; `contract_registry.delete` - `name`
+//! * `namespace.create` - `namespace`, `owners`; `namespace.delete` - `namespace`
+//! * `permission.create` - `namespace`, `contract`, `read`, `write`;
+//!   `permission.delete` - `namespace`, `contract`
+//! * `state.get` - `prefix`, returning the entries under it
+//! * `batch.status` - `batches`, the batch ids, and `wait`, returning the status of each batch
+//!
+//! Methods which submit a transaction return its status `link` and the ids of its `batches`.
+//! Requests are checked by an `Authenticator` before they are handled.
+
+use std::collections::HashSet;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use cylinder::Signer;
+use sabre_sdk::protocol::payload::{
+    Action, CreateContractActionBuilder, CreateContractRegistryActionBuilder,
+    CreateNamespaceRegistryActionBuilder, CreateNamespaceRegistryPermissionActionBuilder,
+    DeleteContractRegistryActionBuilder, DeleteNamespaceRegistryActionBuilder,
+    DeleteNamespaceRegistryPermissionActionBuilder, ExecuteContractActionBuilder,
+    SabrePayloadBuilder,
+};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use crate::error::CliError;
+use crate::rest_api::with_failover;
+use crate::state::get_state_with_prefix;
+use crate::submit::{batch_statuses_link, submit_batches, StatusPoller};
+use crate::trace;
+
+/// The largest request body the gateway reads, which bounds the size of uploaded contracts
+const MAX_REQUEST_SIZE: usize = 64 * 1024 * 1024;
+
+/// How long a client may take to send its request before its connection is dropped
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The most requests handled at once; further connections are dropped
+const MAX_CONCURRENT_REQUESTS: usize = 32;
+
+/// The longest a `batch.status` request may wait for its batches to be committed, in seconds
+const MAX_STATUS_WAIT: u64 = 60;
+
+// The error codes defined by JSON-RPC 2.0
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// The code of errors reported by the REST API, or in building the transaction
+const SERVER_ERROR: i64 = -32000;
+
+/// Decides whether a request may be handled, given its Authorization header
+pub trait Authenticator: Send + Sync {
+    /// Returns why the request is refused, if it is.
+    fn authenticate(&self, authorization: Option<&str>) -> Result<(), String>;
+}
+
+/// Handles every request, for a gateway reachable only by trusted clients
+pub struct AllowAll;
+
+impl Authenticator for AllowAll {
+    fn authenticate(&self, _authorization: Option<&str>) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Handles requests which carry one of the bearer tokens
+pub struct BearerTokens {
+    tokens: HashSet<String>,
+}
+
+impl BearerTokens {
+    pub fn new(tokens: HashSet<String>) -> Self {
+        BearerTokens { tokens }
+    }
+
+    /// Reads the tokens from a file, one per line. Blank lines and lines starting with # are
+    /// ignored.
+    pub fn from_file(path: &str) -> Result<Self, CliError> {
+        let contents = std::fs::read_to_string(path).map_err(|err| {
+            CliError::User(format!("Unable to read token file {}: {}", path, err))
+        })?;
+        let tokens = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(String::from)
+            .collect::<HashSet<_>>();
+        if tokens.is_empty() {
+            return Err(CliError::User(format!("Token file {} has no tokens", path)));
+        }
+
+        Ok(BearerTokens::new(tokens))
+    }
+}
+
+impl Authenticator for BearerTokens {
+    fn authenticate(&self, authorization: Option<&str>) -> Result<(), String> {
+        let token = authorization
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| "a bearer token is required".to_string())?
+            .trim();
+        // Every token is compared, in constant time, so the time taken does not show how much
+        // of a token was guessed
+        let accepted = self.tokens.iter().fold(false, |accepted, accepted_token| {
+            constant_time_eq(token.as_bytes(), accepted_token.as_bytes()) | accepted
+        });
+        if accepted {
+            Ok(())
+        } else {
+            Err("the bearer token is not accepted".into())
+        }
+    }
+}
+
+/// An error returned to the client in a JSON-RPC response
+#[derive(Debug, PartialEq, Eq)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<CliError> for RpcError {
+    fn from(err: CliError) -> Self {
+        RpcError::new(SERVER_ERROR, err.to_string())
+    }
+}
+
+#[derive(Deserialize)]
+struct UploadParams {
+    name: String,
+    version: String,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    wasm: String,
+}
+
+#[derive(Deserialize)]
+struct ExecuteParams {
+    name: String,
+    version: String,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    payload: String,
+}
+
+#[derive(Deserialize)]
+struct RegistryParams {
+    name: String,
+    #[serde(default)]
+    owners: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct NamespaceParams {
+    namespace: String,
+    #[serde(default)]
+    owners: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct PermissionParams {
+    namespace: String,
+    contract: String,
+    #[serde(default)]
+    read: bool,
+    #[serde(default)]
+    write: bool,
+}
+
+#[derive(Deserialize)]
+struct StateParams {
+    prefix: String,
+}
+
+#[derive(Deserialize)]
+struct StatusParams {
+    batches: Vec<String>,
+    #[serde(default)]
+    wait: u64,
+}
+
+/// Serves JSON-RPC requests, signing the transactions they submit with one key
+pub struct Gateway {
+    url: String,
+    /// The signer is held while a transaction is signed and submitted, so that transactions are
+    /// submitted in the order they are received
+    signer: Mutex<Box<dyn Signer>>,
+    authenticator: Box<dyn Authenticator>,
+}
+
+impl Gateway {
+    pub fn new(
+        url: String,
+        signer: Box<dyn Signer>,
+        authenticator: Box<dyn Authenticator>,
+    ) -> Self {
+        Gateway {
+            url,
+            signer: Mutex::new(signer),
+            authenticator,
+        }
+    }
+
+    /// Serves requests at the address until the process is stopped. Each connection is handled
+    /// on a thread of its own, but transactions are submitted one at a time.
+    pub fn serve(self, bind: &str) -> Result<(), CliError> {
+        let listener = TcpListener::bind(bind)
+            .map_err(|err| CliError::User(format!("Unable to listen on {}: {}", bind, err)))?;
+        println!("Serving the Sabre gateway at {}", bind);
+
+        let gateway = Arc::new(self);
+        let active = Arc::new(AtomicUsize::new(0));
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    eprintln!("Unable to accept gateway request: {}", err);
+                    continue;
+                }
+            };
+            if active.fetch_add(1, Ordering::SeqCst) >= MAX_CONCURRENT_REQUESTS {
+                active.fetch_sub(1, Ordering::SeqCst);
+                eprintln!("Dropping gateway request: too many requests in progress");
+                continue;
+            }
+
+            let connection_gateway = gateway.clone();
+            let connection_active = active.clone();
+            let spawned = thread::Builder::new()
+                .name("gateway-request".into())
+                .spawn(move || {
+                    if let Err(err) = connection_gateway.handle_connection(stream) {
+                        eprintln!("Unable to answer gateway request: {}", err);
+                    }
+                    connection_active.fetch_sub(1, Ordering::SeqCst);
+                });
+            if let Err(err) = spawned {
+                active.fetch_sub(1, Ordering::SeqCst);
+                eprintln!("Unable to answer gateway request: {}", err);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_connection(&self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+
+        let mut content_length = 0;
+        let mut authorization = None;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                match name.trim().to_ascii_lowercase().as_str() {
+                    "content-length" => content_length = value.trim().parse().unwrap_or(0),
+                    "authorization" => authorization = Some(value.trim().to_string()),
+                    _ => (),
+                }
+            }
+        }
+
+        let (status, body) = if !request_line.starts_with("POST ") {
+            (
+                "405 Method Not Allowed",
+                json!({"error": "requests must be POSTed"}),
+            )
+        } else if content_length > MAX_REQUEST_SIZE {
+            (
+                "413 Payload Too Large",
+                json!({"error": "the request is too large"}),
+            )
+        } else if let Err(reason) = self.authenticator.authenticate(authorization.as_deref()) {
+            ("401 Unauthorized", json!({ "error": reason }))
+        } else {
+            let mut request = vec![0; content_length];
+            reader.read_exact(&mut request)?;
+            ("200 OK", self.handle_request(&request))
+        };
+
+        let body = body.to_string();
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+             Connection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )?;
+        stream.flush()
+    }
+
+    /// Returns the JSON-RPC response to a request body.
+    fn handle_request(&self, request: &[u8]) -> Value {
+        let request: Value = match serde_json::from_slice(request) {
+            Ok(request) => request,
+            Err(err) => {
+                return error_response(Value::Null, RpcError::new(PARSE_ERROR, err.to_string()))
+            }
+        };
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+
+        let method = match request.get("method").and_then(Value::as_str) {
+            Some(method) if request.get("jsonrpc") == Some(&json!("2.0")) => method,
+            _ => {
+                return error_response(
+                    id,
+                    RpcError::new(INVALID_REQUEST, "not a JSON-RPC 2.0 request"),
+                )
+            }
+        };
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+
+        match self.call(method, params) {
+            Ok(result) => json!({"jsonrpc": "2.0", "result": result, "id": id}),
+            Err(err) => error_response(id, err),
+        }
+    }
+
+    fn call(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        let action = match method {
+            "contract.upload" => {
+                let params: UploadParams = parse_params(params)?;
+                Action::CreateContract(
+                    CreateContractActionBuilder::new()
+                        .with_name(params.name)
+                        .with_version(params.version)
+                        .with_inputs(params.inputs)
+                        .with_outputs(params.outputs)
+                        .with_contract(decode_param("wasm", &params.wasm)?)
+                        .build()
+                        .map_err(invalid_params)?,
+                )
+            }
+            "contract.execute" => {
+                let params: ExecuteParams = parse_params(params)?;
+                Action::ExecuteContract(
+                    ExecuteContractActionBuilder::new()
+                        .with_name(params.name)
+                        .with_version(params.version)
+                        .with_inputs(params.inputs)
+                        .with_outputs(params.outputs)
+                        .with_payload(decode_param("payload", &params.payload)?)
+                        .build()
+                        .map_err(invalid_params)?,
+                )
+            }
+            "contract_registry.create" => {
+                let params: RegistryParams = parse_params(params)?;
+                Action::CreateContractRegistry(
+                    CreateContractRegistryActionBuilder::new()
+                        .with_name(params.name)
+                        .with_owners(params.owners)
+                        .build()
+                        .map_err(invalid_params)?,
+                )
+            }
+            "contract_registry.delete" => {
+                let params: RegistryParams = parse_params(params)?;
+                Action::DeleteContractRegistry(
+                    DeleteContractRegistryActionBuilder::new()
+                        .with_name(params.name)
+                        .build()
+                        .map_err(invalid_params)?,
+                )
+            }
+            "namespace.create" => {
+                let params: NamespaceParams = parse_params(params)?;
+                Action::CreateNamespaceRegistry(
+                    CreateNamespaceRegistryActionBuilder::new()
+                        .with_namespace(params.namespace)
+                        .with_owners(params.owners)
+                        .build()
+                        .map_err(invalid_params)?,
+                )
+            }
+            "namespace.delete" => {
+                let params: NamespaceParams = parse_params(params)?;
+                Action::DeleteNamespaceRegistry(
+                    DeleteNamespaceRegistryActionBuilder::new()
+                        .with_namespace(params.namespace)
+                        .build()
+                        .map_err(invalid_params)?,
+                )
+            }
+            "permission.create" => {
+                let params: PermissionParams = parse_params(params)?;
+                if !(params.read || params.write) {
+                    return Err(RpcError::new(INVALID_PARAMS, "no permissions provided"));
+                }
+                Action::CreateNamespaceRegistryPermission(
+                    CreateNamespaceRegistryPermissionActionBuilder::new()
+                        .with_namespace(params.namespace)
+                        .with_contract_name(params.contract)
+                        .with_read(params.read)
+                        .with_write(params.write)
+                        .build()
+                        .map_err(invalid_params)?,
+                )
+            }
+            "permission.delete" => {
+                let params: PermissionParams = parse_params(params)?;
+                Action::DeleteNamespaceRegistryPermission(
+                    DeleteNamespaceRegistryPermissionActionBuilder::new()
+                        .with_namespace(params.namespace)
+                        .with_contract_name(params.contract)
+                        .build()
+                        .map_err(invalid_params)?,
+                )
+            }
+            "state.get" => {
+                let params: StateParams = parse_params(params)?;
+                let entries = get_state_with_prefix(&self.url, &params.prefix)?;
+                return Ok(json!(entries));
+            }
+            "batch.status" => {
+                let params: StatusParams = parse_params(params)?;
+                // The status link is built from the REST API URL, so clients cannot direct the
+                // gateway's requests elsewhere
+                if params.batches.is_empty()
+                    || !params
+                        .batches
+                        .iter()
+                        .all(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_hexdigit()))
+                {
+                    return Err(RpcError::new(
+                        INVALID_PARAMS,
+                        "batches must be a list of hex encoded batch ids",
+                    ));
+                }
+                let batch_ids = params
+                    .batches
+                    .iter()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>();
+                let wait = params.wait.min(MAX_STATUS_WAIT);
+                let mut poller = StatusPoller::new()?;
+                let response = with_failover(&self.url, |url| {
+                    poller.wait_for_batch(&batch_statuses_link(url, &batch_ids), wait)
+                })?;
+                return Ok(Value::Array(
+                    response
+                        .batch_statuses()
+                        .iter()
+                        .map(|status| json!({"id": status.id(), "status": status.status()}))
+                        .collect(),
+                ));
+            }
+            _ => {
+                return Err(RpcError::new(
+                    METHOD_NOT_FOUND,
+                    format!("unknown method '{}'", method),
+                ))
+            }
+        };
+
+        self.submit(action)
+    }
+
+    /// Signs and submits a transaction of the action, returning its link and batch ids.
+    fn submit(&self, action: Action) -> Result<Value, RpcError> {
+        let signer = self.signer.lock().unwrap_or_else(|err| err.into_inner());
+        let signer = &**signer;
+        let batch = SabrePayloadBuilder::new()
+            .with_action(action)
+            .into_transaction_builder()
+            .map_err(CliError::from)?
+            .with_nonce(trace::transaction_nonce())
+            .into_batch_builder(signer)
+            .map_err(CliError::from)?
+            .build(signer)
+            .map_err(CliError::from)?;
+
+        let submission = submit_batches(&self.url, vec![batch])?;
+        Ok(json!({"link": submission.link, "batches": submission.batches}))
+    }
+}
+
+/// Compares the bytes in time which depends only on their lengths.
+fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
+    left.len() == right.len()
+        && left
+            .iter()
+            .zip(right)
+            .fold(0u8, |difference, (l, r)| difference | (l ^ r))
+            == 0
+}
+
+fn error_response(id: Value, err: RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "error": {"code": err.code, "message": err.message},
+        "id": id,
+    })
+}
+
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(invalid_params)
+}
+
+fn decode_param(name: &str, value: &str) -> Result<Vec<u8>, RpcError> {
+    base64::decode(value)
+        .map_err(|err| RpcError::new(INVALID_PARAMS, format!("{} is not base64: {}", name, err)))
+}
+
+fn invalid_params<E: std::fmt::Display>(err: E) -> RpcError {
+    RpcError::new(INVALID_PARAMS, err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use cylinder::{secp256k1::Secp256k1Context, Context};
+
+    use super::*;
+
+    fn gateway() -> Gateway {
+        let context = Secp256k1Context::new();
+        let key = context.new_random_private_key();
+        Gateway::new(
+            "http://127.0.0.1:1".into(),
+            context.new_signer(key),
+            Box::new(AllowAll),
+        )
+    }
+
+    fn error_code(response: &Value) -> Option<i64> {
+        response["error"]["code"].as_i64()
+    }
+
+    #[test]
+    // Asserts that malformed requests, unknown methods and invalid params are answered with
+    // their JSON-RPC error codes and the request's id
+    fn test_cli_gateway_errors() {
+        let gateway = gateway();
+
+        assert_eq!(
+            error_code(&gateway.handle_request(b"not json")),
+            Some(PARSE_ERROR)
+        );
+        assert_eq!(
+            error_code(&gateway.handle_request(br#"{"method":"state.get","id":1}"#)),
+            Some(INVALID_REQUEST)
+        );
+
+        let response =
+            gateway.handle_request(br#"{"jsonrpc":"2.0","method":"contract.burn","id":7}"#);
+        assert_eq!(error_code(&response), Some(METHOD_NOT_FOUND));
+        assert_eq!(response["id"], json!(7));
+
+        let response = gateway.handle_request(
+            br#"{"jsonrpc":"2.0","method":"contract.execute","params":{"name":"a"},"id":8}"#,
+        );
+        assert_eq!(error_code(&response), Some(INVALID_PARAMS));
+
+        let response = gateway.handle_request(
+            br#"{"jsonrpc":"2.0","method":"permission.create","params":{"namespace":"abcdef","contract":"a"},"id":9}"#,
+        );
+        assert_eq!(error_code(&response), Some(INVALID_PARAMS));
+
+        let response = gateway.handle_request(
+            br#"{"jsonrpc":"2.0","method":"batch.status","params":{"batches":["ab@evil"]},"id":10}"#,
+        );
+        assert_eq!(error_code(&response), Some(INVALID_PARAMS));
+    }
+
+    #[test]
+    // Asserts that only requests carrying an accepted bearer token are authenticated
+    fn test_cli_gateway_bearer_tokens() {
+        let tokens = BearerTokens::new(vec!["secret".to_string()].into_iter().collect());
+
+        assert!(tokens.authenticate(Some("Bearer secret")).is_ok());
+        assert!(tokens.authenticate(Some("Bearer other")).is_err());
+        assert!(tokens.authenticate(Some("Basic secret")).is_err());
+        assert!(tokens.authenticate(None).is_err());
+    }
+}
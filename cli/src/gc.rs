@@ -0,0 +1,275 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains the search for Sabre state left behind by deleted contracts and registries
+
+use sabre_sdk::protocol::namespaces::SabreNamespace;
+use sabre_sdk::protocol::state::{ContractList, ContractRegistryList, NamespaceRegistryList};
+use sabre_sdk::protos::FromBytes;
+
+use crate::error::CliError;
+use crate::state::StateEntry;
+
+/// Sabre state which no longer serves a purpose
+#[derive(Debug, PartialEq, Eq)]
+pub enum Residue {
+    /// A state entry whose list holds nothing
+    EmptyEntry { address: String, kind: &'static str },
+    /// A contract whose version is not in its contract registry
+    OrphanedContract { name: String, version: String },
+    /// A permission granted to a contract name with no contract registry
+    ExpiredPermission {
+        namespace: String,
+        contract_name: String,
+    },
+}
+
+impl std::fmt::Display for Residue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Residue::EmptyEntry { address, kind } => {
+                write!(f, "{} entry {} is empty", kind, address)
+            }
+            Residue::OrphanedContract { name, version } => write!(
+                f,
+                "contract {}:{} is not in its contract registry",
+                name, version
+            ),
+            Residue::ExpiredPermission {
+                namespace,
+                contract_name,
+            } => write!(
+                f,
+                "namespace {} grants permissions to unregistered contract {}",
+                namespace, contract_name
+            ),
+        }
+    }
+}
+
+/// Returns the residue among the state entries of the namespace registry, contract registry and
+/// contract namespaces. Registries without versions or permissions are not residue, as they are
+/// how a registry starts out.
+pub fn find_residue(entries: &[StateEntry]) -> Result<Vec<Residue>, CliError> {
+    let mut residue = Vec::new();
+    let mut contract_registries = Vec::new();
+    let mut contracts = Vec::new();
+    let mut namespace_registries = Vec::new();
+
+    for entry in entries {
+        let namespace = match SabreNamespace::of_address(&entry.address) {
+            Some(namespace) => namespace,
+            None => continue,
+        };
+        let bytes = base64::decode(&entry.data)
+            .map_err(|_| CliError::User(format!("Unable to decode state at {}", entry.address)))?;
+
+        let len = match namespace {
+            SabreNamespace::ContractRegistry => {
+                let list = ContractRegistryList::from_bytes(&bytes)?;
+                contract_registries.extend(list.registries().iter().cloned());
+                list.registries().len()
+            }
+            SabreNamespace::Contract => {
+                let list = ContractList::from_bytes(&bytes)?;
+                contracts.extend(list.contracts().iter().cloned());
+                list.contracts().len()
+            }
+            SabreNamespace::NamespaceRegistry => {
+                let list = NamespaceRegistryList::from_bytes(&bytes)?;
+                namespace_registries.extend(list.registries().iter().cloned());
+                list.registries().len()
+            }
+            _ => continue,
+        };
+        if len == 0 {
+            residue.push(Residue::EmptyEntry {
+                address: entry.address.clone(),
+                kind: namespace.description(),
+            });
+        }
+    }
+
+    for contract in &contracts {
+        let registered = contract_registries
+            .iter()
+            .filter(|registry| registry.name() == contract.name())
+            .flat_map(|registry| registry.versions())
+            .any(|version| version.version() == contract.version());
+        if !registered {
+            residue.push(Residue::OrphanedContract {
+                name: contract.name().to_string(),
+                version: contract.version().to_string(),
+            });
+        }
+    }
+
+    for registry in &namespace_registries {
+        residue.extend(
+            registry
+                .permissions()
+                .iter()
+                .filter(|permission| {
+                    !contract_registries.iter().any(|contract_registry| {
+                        contract_registry.name() == permission.contract_name()
+                    })
+                })
+                .map(|permission| Residue::ExpiredPermission {
+                    namespace: registry.namespace().to_string(),
+                    contract_name: permission.contract_name().to_string(),
+                }),
+        );
+    }
+
+    Ok(residue)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use sabre_sdk::protocol::state::{
+        ContractBuilder, ContractListBuilder, ContractRegistryBuilder, ContractRegistryListBuilder,
+        NamespaceRegistryBuilder, NamespaceRegistryListBuilder, PermissionBuilder, VersionBuilder,
+    };
+    use sabre_sdk::protos::IntoBytes;
+
+    use super::*;
+
+    fn entry(address: &str, data: Vec<u8>) -> StateEntry {
+        StateEntry {
+            address: address.to_string(),
+            data: base64::encode(data),
+        }
+    }
+
+    #[test]
+    // Asserts that orphaned contracts, permissions for unregistered contracts and empty entries
+    // are found, and that state in use, including registries yet to be filled, is not
+    fn test_cli_find_residue() {
+        let contract_registries = vec![
+            ContractRegistryBuilder::new()
+                .with_name("intkey_multiply".to_string())
+                .with_owners(vec!["owner".to_string()])
+                .with_versions(vec![VersionBuilder::new()
+                    .with_version("1.0".to_string())
+                    .with_contract_sha512("sha512".to_string())
+                    .with_creator("owner".to_string())
+                    .build()
+                    .unwrap()])
+                .build()
+                .unwrap(),
+            ContractRegistryBuilder::new()
+                .with_name("new".to_string())
+                .with_owners(vec!["owner".to_string()])
+                .build()
+                .unwrap(),
+        ];
+        let contracts = ["1.0", "0.9"]
+            .iter()
+            .map(|version| {
+                ContractBuilder::new()
+                    .with_name("intkey_multiply".to_string())
+                    .with_version(version.to_string())
+                    .with_inputs(vec!["1cf126".to_string()])
+                    .with_outputs(vec!["1cf126".to_string()])
+                    .with_creator("owner".to_string())
+                    .with_contract(b"\0asm".to_vec())
+                    .build()
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let namespace_registries = vec![
+            NamespaceRegistryBuilder::new()
+                .with_namespace("1cf126".to_string())
+                .with_owners(vec!["owner".to_string()])
+                .with_permissions(
+                    ["intkey_multiply", "deleted"]
+                        .iter()
+                        .map(|name| {
+                            PermissionBuilder::new()
+                                .with_contract_name(name.to_string())
+                                .with_read(true)
+                                .with_write(true)
+                                .build()
+                                .unwrap()
+                        })
+                        .collect(),
+                )
+                .build()
+                .unwrap(),
+            NamespaceRegistryBuilder::new()
+                .with_namespace("cad11d".to_string())
+                .with_owners(vec!["owner".to_string()])
+                .build()
+                .unwrap(),
+        ];
+
+        let entries = vec![
+            entry(
+                "00ec01aa",
+                ContractRegistryListBuilder::new()
+                    .with_registries(contract_registries)
+                    .build()
+                    .unwrap()
+                    .into_bytes()
+                    .unwrap(),
+            ),
+            entry(
+                "00ec02aa",
+                ContractListBuilder::new()
+                    .with_contracts(contracts)
+                    .build()
+                    .unwrap()
+                    .into_bytes()
+                    .unwrap(),
+            ),
+            entry(
+                "00ec02bb",
+                ContractListBuilder::new()
+                    .with_contracts(vec![])
+                    .build()
+                    .unwrap()
+                    .into_bytes()
+                    .unwrap(),
+            ),
+            entry(
+                "00ec00aa",
+                NamespaceRegistryListBuilder::new()
+                    .with_registries(namespace_registries)
+                    .build()
+                    .unwrap()
+                    .into_bytes()
+                    .unwrap(),
+            ),
+        ];
+
+        assert_eq!(
+            find_residue(&entries).unwrap(),
+            vec![
+                Residue::EmptyEntry {
+                    address: "00ec02bb".to_string(),
+                    kind: "contract",
+                },
+                Residue::OrphanedContract {
+                    name: "intkey_multiply".to_string(),
+                    version: "0.9".to_string(),
+                },
+                Residue::ExpiredPermission {
+                    namespace: "1cf126".to_string(),
+                    contract_name: "deleted".to_string(),
+                },
+            ]
+        );
+    }
+}
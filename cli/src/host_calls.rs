@@ -0,0 +1,89 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains reading the host call trace written by the transaction processor's
+//! `--host-call-trace` option
+
+use std::io::BufRead;
+
+use crate::error::CliError;
+
+/// A state read, write or delete, event or receipt made by a transaction
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct HostCall {
+    pub transaction_id: String,
+    pub function: String,
+    #[serde(default)]
+    pub address: Option<String>,
+    pub bytes: u64,
+    pub result: String,
+}
+
+/// Reads the host calls in a trace, keeping those of the given transaction if one is given.
+pub fn read_host_calls<R: BufRead>(
+    trace: R,
+    transaction_id: Option<&str>,
+) -> Result<Vec<HostCall>, CliError> {
+    let mut calls = Vec::new();
+
+    for (i, line) in trace.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let call: HostCall = serde_json::from_str(&line).map_err(|err| {
+            CliError::User(format!("Invalid host call on line {}: {}", i + 1, err))
+        })?;
+        if transaction_id.map_or(true, |id| id == call.transaction_id) {
+            calls.push(call);
+        }
+    }
+
+    Ok(calls)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    // Asserts that host calls are read in order, filtered by transaction, and that invalid
+    // lines are reported by number
+    fn test_cli_read_host_calls() {
+        let trace = "{\"transaction_id\":\"t1\",\"function\":\"get_state\",\"address\":\"00ec00\",\"bytes\":12,\"result\":\"ok\"}\n\
+                     {\"transaction_id\":\"t2\",\"function\":\"add_event\",\"bytes\":3,\"result\":\"ok\"}\n\
+                     \n\
+                     {\"transaction_id\":\"t1\",\"function\":\"set_state\",\"address\":\"00ec01\",\"bytes\":4,\"result\":\"ok\"}\n";
+
+        let calls = read_host_calls(trace.as_bytes(), None).unwrap();
+        assert_eq!(calls.len(), 3);
+        assert_eq!(calls[1].address, None);
+
+        let calls = read_host_calls(trace.as_bytes(), Some("t1")).unwrap();
+        assert_eq!(
+            calls
+                .iter()
+                .map(|call| (call.function.as_str(), call.bytes))
+                .collect::<Vec<_>>(),
+            vec![("get_state", 12), ("set_state", 4)]
+        );
+
+        match read_host_calls("{}\nnot json\n".as_bytes(), None) {
+            Err(CliError::User(msg)) => assert!(msg.contains("line 1"), "{}", msg),
+            other => panic!("expected an error, got {:?}", other),
+        }
+    }
+}
@@ -27,11 +27,91 @@ use users::get_current_username;
 
 use crate::error::CliError;
 
+/// The algorithms a signing key may use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAlgorithm {
+    Secp256k1,
+    Ed25519,
+}
+
+impl KeyAlgorithm {
+    /// Parses the name given to `--key-algo`
+    pub fn from_name(name: &str) -> Result<KeyAlgorithm, CliError> {
+        match name {
+            "secp256k1" => Ok(KeyAlgorithm::Secp256k1),
+            "ed25519" => Ok(KeyAlgorithm::Ed25519),
+            _ => Err(CliError::User(format!(
+                "Unsupported key algorithm: {}",
+                name
+            ))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeyAlgorithm::Secp256k1 => "secp256k1",
+            KeyAlgorithm::Ed25519 => "ed25519",
+        }
+    }
+}
+
 /// Return a `TransactSigner`, loading the signing key from the user's environment.
-pub fn new_signer(key_name: Option<&str>) -> Result<Box<dyn Signer>, CliError> {
-    let context = Secp256k1Context::new();
-    let private_key = load_signing_key(key_name)?;
-    Ok(context.new_signer(private_key))
+///
+/// The key's algorithm is detected from the key file, and must match `key_algo` if it is given.
+pub fn new_signer(
+    key_name: Option<&str>,
+    key_algo: Option<&str>,
+) -> Result<Box<dyn Signer>, CliError> {
+    let (private_key_filename, key_str) = load_signing_key(key_name)?;
+    let (detected, key_hex) = detect_key_algorithm(&key_str);
+
+    if let Some(key_algo) = key_algo.map(KeyAlgorithm::from_name).transpose()? {
+        if key_algo != detected {
+            return Err(CliError::Signing(format!(
+                "Key file {} contains a {} key, but --key-algo is {}",
+                private_key_filename.display(),
+                detected.name(),
+                key_algo.name()
+            )));
+        }
+    }
+
+    match detected {
+        KeyAlgorithm::Secp256k1 => {
+            let private_key = PrivateKey::new_from_hex(key_hex).map_err(|err| {
+                CliError::Signing(format!(
+                    "Unable to parse private key file {}: {} ",
+                    private_key_filename.display(),
+                    err
+                ))
+            })?;
+            Ok(Secp256k1Context::new().new_signer(private_key))
+        }
+        // Sawtooth validators only verify secp256k1 signatures
+        algorithm => Err(CliError::Signing(format!(
+            "Unsupported key algorithm {} in key file {}: only secp256k1 keys may sign \
+             Sawtooth transactions",
+            algorithm.name(),
+            private_key_filename.display()
+        ))),
+    }
+}
+
+/// Returns the algorithm of a key read from a key file, along with its hex encoding.
+///
+/// A key may be prefixed with its algorithm, as in "ed25519:<hex>". Otherwise, a 32 byte key is
+/// secp256k1 and a 64 byte key, an ed25519 seed followed by its public key, is ed25519.
+fn detect_key_algorithm(key_str: &str) -> (KeyAlgorithm, &str) {
+    let key_str = key_str.trim();
+    if let Some(key_hex) = key_str.strip_prefix("secp256k1:") {
+        (KeyAlgorithm::Secp256k1, key_hex)
+    } else if let Some(key_hex) = key_str.strip_prefix("ed25519:") {
+        (KeyAlgorithm::Ed25519, key_hex)
+    } else if key_str.len() == 128 {
+        (KeyAlgorithm::Ed25519, key_str)
+    } else {
+        (KeyAlgorithm::Secp256k1, key_str)
+    }
 }
 
 /// Return a signing key loaded from the user's environment
@@ -67,7 +147,9 @@ pub fn new_signer(key_name: Option<&str>) -> Result<Box<dyn Signer>, CliError> {
 ///
 /// If a HOME or USER environment variable is required but cannot be
 /// retrieved from the environment, a CliError::VarError is returned.
-fn load_signing_key(key_param: Option<&str>) -> Result<PrivateKey, CliError> {
+///
+/// Returns the path of the key file and the key it contains.
+fn load_signing_key(key_param: Option<&str>) -> Result<(PathBuf, String), CliError> {
     let derived_keyfile: String = key_param
         .map(String::from)
         .ok_or_else(|| env::var("USER"))
@@ -128,7 +210,7 @@ fn load_signing_key(key_param: Option<&str>) -> Result<PrivateKey, CliError> {
     f.read_to_string(&mut contents)?;
 
     let key_str = match contents.lines().next() {
-        Some(k) => k,
+        Some(k) => k.to_string(),
         None => {
             return Err(CliError::User(format!(
                 "Empty key file: {}",
@@ -137,11 +219,32 @@ fn load_signing_key(key_param: Option<&str>) -> Result<PrivateKey, CliError> {
         }
     };
 
-    PrivateKey::new_from_hex(key_str).map_err(|err| {
-        CliError::Signing(format!(
-            "Unable to parse private key file {}: {} ",
-            private_key_filename.display(),
-            err
-        ))
-    })
+    Ok((private_key_filename, key_str))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    // Asserts that a key's algorithm is detected from its prefix or its length
+    fn test_cli_detect_key_algorithm() {
+        let secp256k1_key = "2f1e7b7a130d7ba9da0068b3bb0ba1d79e7e77110302c9f746c3c2a63fe40088";
+        let ed25519_key = secp256k1_key.repeat(2);
+
+        assert_eq!(
+            detect_key_algorithm(secp256k1_key),
+            (KeyAlgorithm::Secp256k1, secp256k1_key)
+        );
+        assert_eq!(
+            detect_key_algorithm(&ed25519_key),
+            (KeyAlgorithm::Ed25519, ed25519_key.as_str())
+        );
+        assert_eq!(
+            detect_key_algorithm(&format!("ed25519:{}", secp256k1_key)),
+            (KeyAlgorithm::Ed25519, secp256k1_key)
+        );
+        assert!(KeyAlgorithm::from_name("rsa").is_err());
+    }
 }
@@ -0,0 +1,595 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains a static check of a compiled contract against the inputs and outputs it declares,
+//! and for floating point instructions.
+//!
+//! The check is heuristic: state accesses are inferred from the Sabre host functions the
+//! contract imports, and the prefixes it uses from hex strings in its data segments. Contracts
+//! which compute their prefixes, such as by hashing a family name, are not covered.
+//!
+//! Floating point results, such as the bits of a NaN, may differ between executors, so a
+//! contract which uses floats may not be applied the same way on every node.
+
+use std::fmt;
+
+const IMPORT_SECTION: u8 = 2;
+const MEMORY_SECTION: u8 = 5;
+const EXPORT_SECTION: u8 = 7;
+const CODE_SECTION: u8 = 10;
+const DATA_SECTION: u8 = 11;
+
+/// The length of a namespace prefix, in hex characters
+const PREFIX_LENGTH: usize = 6;
+
+/// The length of a state address, in hex characters
+const ADDRESS_LENGTH: usize = 70;
+
+/// A likely mismatch between a contract and its declared inputs and outputs
+#[derive(Debug, PartialEq, Eq)]
+pub enum Finding {
+    /// The contract reads state but declares no inputs
+    NoInputs,
+    /// The contract writes or deletes state but declares no outputs
+    NoOutputs,
+    /// A prefix found in the contract is not covered by any declared input or output
+    UndeclaredPrefix(String),
+    /// The contract contains the given number of floating point instructions
+    FloatInstructions(usize),
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Finding::NoInputs => f.write_str("contract imports get_state but declares no inputs"),
+            Finding::NoOutputs => {
+                f.write_str("contract imports set_state or delete_state but declares no outputs")
+            }
+            Finding::UndeclaredPrefix(prefix) => write!(
+                f,
+                "contract contains prefix {} which no input or output covers",
+                prefix
+            ),
+            Finding::FloatInstructions(count) => write!(
+                f,
+                "contract contains {} floating point instructions, whose results may differ \
+                 between executors",
+                count
+            ),
+        }
+    }
+}
+
+/// Checks the compiled contract against its declared inputs and outputs.
+pub fn lint(inputs: &[String], outputs: &[String], wasm: &[u8]) -> Result<Vec<Finding>, String> {
+    let module = Module::parse(wasm)?;
+    let mut findings = Vec::new();
+
+    if module.imports("get_state") && inputs.is_empty() {
+        findings.push(Finding::NoInputs);
+    }
+    if (module.imports("set_state") || module.imports("delete_state")) && outputs.is_empty() {
+        findings.push(Finding::NoOutputs);
+    }
+
+    let mut prefixes = module
+        .data
+        .iter()
+        .flat_map(|segment| hex_prefixes(segment))
+        .collect::<Vec<_>>();
+    prefixes.sort();
+    prefixes.dedup();
+    for prefix in prefixes {
+        let covered = inputs
+            .iter()
+            .chain(outputs.iter())
+            .any(|declared| prefix.starts_with(declared) || declared.starts_with(&prefix));
+        if !covered {
+            findings.push(Finding::UndeclaredPrefix(prefix));
+        }
+    }
+
+    match module.float_instructions()? {
+        0 => (),
+        count => findings.push(Finding::FloatInstructions(count)),
+    }
+
+    Ok(findings)
+}
+
+/// Returns the number of floating point instructions in the compiled contract's functions.
+pub fn float_instructions(wasm: &[u8]) -> Result<usize, String> {
+    Module::parse(wasm)?.float_instructions()
+}
+
+/// The imports, exports and memory of a compiled contract
+#[derive(Debug, PartialEq, Eq)]
+pub struct Interface {
+    /// The imported functions, as module and name
+    pub imports: Vec<(String, String)>,
+    /// The names of the exported functions
+    pub exports: Vec<String>,
+    /// The initial and maximum size of the memory, in 64 KiB pages, if the module has one
+    pub memory: Option<(u64, Option<u64>)>,
+}
+
+/// Returns the imports, exports and memory of the compiled contract.
+#[cfg_attr(not(feature = "contract-test"), allow(dead_code))]
+pub fn interface(wasm: &[u8]) -> Result<Interface, String> {
+    let module = Module::parse(wasm)?;
+    Ok(Interface {
+        imports: module.imports,
+        exports: module.exports,
+        memory: module.memory,
+    })
+}
+
+/// The parts of a WebAssembly module the check uses
+struct Module<'a> {
+    /// The imported functions, as module and name
+    imports: Vec<(String, String)>,
+    /// The names of the exported functions
+    exports: Vec<String>,
+    /// The limits of the memory, whether it is defined or imported
+    memory: Option<(u64, Option<u64>)>,
+    /// The contents of the data segments
+    data: Vec<Vec<u8>>,
+    /// The code section, which is only decoded when floating point instructions are counted
+    code: &'a [u8],
+}
+
+impl<'a> Module<'a> {
+    fn parse(wasm: &'a [u8]) -> Result<Module<'a>, String> {
+        if wasm.get(..8) != Some(&[0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00][..]) {
+            return Err("not a WebAssembly module".into());
+        }
+
+        let mut module = Module {
+            imports: vec![],
+            exports: vec![],
+            memory: None,
+            data: vec![],
+            code: &[],
+        };
+        let mut reader = Reader {
+            bytes: wasm,
+            pos: 8,
+        };
+        while !reader.is_empty() {
+            let id = reader.byte()?;
+            let size = reader.leb128()? as usize;
+            let mut section = Reader {
+                bytes: reader.take(size)?,
+                pos: 0,
+            };
+            match id {
+                IMPORT_SECTION => parse_imports(&mut section, &mut module)?,
+                MEMORY_SECTION => {
+                    if section.leb128()? > 0 {
+                        module.memory = Some(section.limits()?);
+                    }
+                }
+                EXPORT_SECTION => module.exports = parse_exports(&mut section)?,
+                CODE_SECTION => module.code = section.bytes,
+                DATA_SECTION => module.data = parse_data(&mut section)?,
+                _ => (),
+            }
+        }
+
+        Ok(module)
+    }
+
+    fn imports(&self, name: &str) -> bool {
+        self.imports.iter().any(|(_, import)| import == name)
+    }
+
+    fn float_instructions(&self) -> Result<usize, String> {
+        if self.code.is_empty() {
+            return Ok(0);
+        }
+
+        let mut section = Reader {
+            bytes: self.code,
+            pos: 0,
+        };
+        let count = section.leb128()?;
+        let mut floats = 0;
+        for _ in 0..count {
+            let size = section.leb128()? as usize;
+            let mut body = Reader {
+                bytes: section.take(size)?,
+                pos: 0,
+            };
+            // local declarations: a count and a value type each
+            for _ in 0..body.leb128()? {
+                body.leb128()?;
+                body.byte()?;
+            }
+            while !body.is_empty() {
+                if body.float_instruction()? {
+                    floats += 1;
+                }
+            }
+        }
+
+        Ok(floats)
+    }
+}
+
+fn parse_imports(section: &mut Reader, module: &mut Module) -> Result<(), String> {
+    let count = section.leb128()?;
+    for _ in 0..count {
+        let import_module = section.name()?;
+        let name = section.name()?;
+        match section.byte()? {
+            // function: type index
+            0x00 => {
+                section.leb128()?;
+                module.imports.push((import_module, name));
+            }
+            // table: element type and limits
+            0x01 => {
+                section.byte()?;
+                section.limits()?;
+            }
+            // memory: limits
+            0x02 => module.memory = Some(section.limits()?),
+            // global: value type and mutability
+            0x03 => {
+                section.take(2)?;
+            }
+            kind => return Err(format!("unknown import kind {}", kind)),
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_exports(section: &mut Reader) -> Result<Vec<String>, String> {
+    let count = section.leb128()?;
+    let mut names = Vec::new();
+    for _ in 0..count {
+        let name = section.name()?;
+        // the kind of export, then its index; 0x00 is a function
+        let kind = section.byte()?;
+        section.leb128()?;
+        if kind == 0x00 {
+            names.push(name);
+        }
+    }
+
+    Ok(names)
+}
+
+fn parse_data(section: &mut Reader) -> Result<Vec<Vec<u8>>, String> {
+    let count = section.leb128()?;
+    let mut segments = Vec::new();
+    for _ in 0..count {
+        match section.leb128()? {
+            0 => section.skip_expr()?,
+            1 => (),
+            2 => {
+                section.leb128()?;
+                section.skip_expr()?;
+            }
+            flags => return Err(format!("unknown data segment flags {}", flags)),
+        }
+        let len = section.leb128()? as usize;
+        segments.push(section.take(len)?.to_vec());
+    }
+
+    Ok(segments)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(len).ok_or("length too large")?;
+        let bytes = self
+            .bytes
+            .get(self.pos..end)
+            .ok_or("unexpected end of module")?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn leb128(&mut self) -> Result<u64, String> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            if shift >= 64 {
+                return Err("integer too large".into());
+            }
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+            shift += 7;
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let len = self.leb128()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| "invalid name".to_string())
+    }
+
+    /// Reads limits, returning the minimum and the maximum, if there is one
+    fn limits(&mut self) -> Result<(u64, Option<u64>), String> {
+        let has_maximum = self.byte()? & 0x01 == 0x01;
+        let minimum = self.leb128()?;
+        let maximum = if has_maximum {
+            Some(self.leb128()?)
+        } else {
+            None
+        };
+        Ok((minimum, maximum))
+    }
+
+    /// Reads an instruction and its immediates, returning whether it operates on floating point
+    /// values
+    fn float_instruction(&mut self) -> Result<bool, String> {
+        let opcode = self.byte()?;
+        match opcode {
+            // unreachable, nop, else, end, return, drop, select, integer comparison, arithmetic
+            // and conversion, ref.is_null and sign extension: no immediates
+            0x00
+            | 0x01
+            | 0x05
+            | 0x0b
+            | 0x0f
+            | 0x1a
+            | 0x1b
+            | 0x45..=0x5a
+            | 0x67..=0x8a
+            | 0xa7
+            | 0xac
+            | 0xad
+            | 0xc0..=0xc4
+            | 0xd1 => (),
+            // block, loop, if: a block type; br, br_if, call, local and global access, table.get,
+            // table.set, integer constants and ref.func: an integer
+            0x02..=0x04 | 0x0c | 0x0d | 0x10 | 0x20..=0x26 | 0x41 | 0x42 | 0xd2 => {
+                self.leb128()?;
+            }
+            // br_table: a vector of labels and a default label
+            0x0e => {
+                for _ in 0..=self.leb128()? {
+                    self.leb128()?;
+                }
+            }
+            // call_indirect: a type and a table
+            0x11 => {
+                self.leb128()?;
+                self.leb128()?;
+            }
+            // typed select: a vector of value types
+            0x1c => {
+                let len = self.leb128()? as usize;
+                self.take(len)?;
+            }
+            // loads and stores: alignment and offset
+            0x28..=0x3e => {
+                self.leb128()?;
+                self.leb128()?;
+                return Ok(matches!(opcode, 0x2a | 0x2b | 0x38 | 0x39));
+            }
+            // memory.size, memory.grow and ref.null: a byte
+            0x3f | 0x40 | 0xd0 => {
+                self.byte()?;
+            }
+            // f32.const and f64.const
+            0x43 => {
+                self.take(4)?;
+                return Ok(true);
+            }
+            0x44 => {
+                self.take(8)?;
+                return Ok(true);
+            }
+            // float comparison, arithmetic and conversion
+            0x5b..=0x66 | 0x8b..=0xa6 | 0xa8..=0xab | 0xae..=0xbf => return Ok(true),
+            0xfc => match self.leb128()? {
+                // saturating float to integer truncation
+                0..=7 => return Ok(true),
+                // memory.init: a data segment and a memory
+                8 => {
+                    self.leb128()?;
+                    self.byte()?;
+                }
+                // data.drop, elem.drop, table.grow, table.size, table.fill: an index
+                9 | 13 | 15..=17 => {
+                    self.leb128()?;
+                }
+                // memory.copy: two memories
+                10 => {
+                    self.take(2)?;
+                }
+                // memory.fill: a memory
+                11 => {
+                    self.byte()?;
+                }
+                // table.init and table.copy: two indexes
+                12 | 14 => {
+                    self.leb128()?;
+                    self.leb128()?;
+                }
+                op => return Err(format!("unknown opcode 0xfc {}", op)),
+            },
+            opcode => return Err(format!("unsupported opcode {:#x}", opcode)),
+        }
+
+        Ok(false)
+    }
+
+    /// Skips a constant expression, which ends with the end opcode
+    fn skip_expr(&mut self) -> Result<(), String> {
+        match self.byte()? {
+            // i32.const, i64.const or global.get, followed by an integer
+            0x41 | 0x42 | 0x23 => {
+                self.leb128()?;
+            }
+            opcode => return Err(format!("unsupported opcode {:#x} in data offset", opcode)),
+        }
+        match self.byte()? {
+            0x0b => Ok(()),
+            _ => Err("unterminated data offset".into()),
+        }
+    }
+}
+
+/// Returns the prefixes of the lowercase hex strings in the data, which are between a prefix and
+/// an address long. The hex digits themselves, as used for formatting, are ignored.
+fn hex_prefixes(data: &[u8]) -> Vec<String> {
+    data.split(|b| !(b.is_ascii_digit() || (b'a'..=b'f').contains(b)))
+        .filter(|run| run.len() >= PREFIX_LENGTH && run.len() <= ADDRESS_LENGTH)
+        .filter(|run| run.len() % 2 == 0)
+        .filter(|run| !run.windows(6).any(|window| window == b"abcdef"))
+        .filter(|run| !run.windows(6).any(|window| window == b"012345"))
+        .map(|run| String::from_utf8_lossy(&run[..PREFIX_LENGTH]).into_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a module importing the given functions from "env", with one data segment.
+    fn module(imports: &[&str], data: &[u8]) -> Vec<u8> {
+        let mut wasm = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+
+        let mut section = vec![imports.len() as u8];
+        for import in imports {
+            section.extend(&[3, b'e', b'n', b'v', import.len() as u8]);
+            section.extend(import.as_bytes());
+            section.extend(&[0x00, 0x00]);
+        }
+        wasm.push(IMPORT_SECTION);
+        wasm.push(section.len() as u8);
+        wasm.extend(section);
+
+        let mut section = vec![1, 0, 0x41, 0x08, 0x0b, data.len() as u8];
+        section.extend(data);
+        wasm.push(DATA_SECTION);
+        wasm.push(section.len() as u8);
+        wasm.extend(section);
+
+        wasm
+    }
+
+    #[test]
+    // check that state access without declared namespaces, and prefixes no namespace covers,
+    // are reported
+    fn test_cli_lint() {
+        let wasm = module(
+            &["get_state", "set_state"],
+            b"1cf126\x00cad11dXYZ0123456789abcdef",
+        );
+
+        let findings = lint(&["1cf126".into()], &[], &wasm).unwrap();
+        assert_eq!(
+            findings,
+            vec![
+                Finding::NoOutputs,
+                Finding::UndeclaredPrefix("cad11d".into())
+            ]
+        );
+
+        let findings = lint(&["1cf126".into()], &["cad11d".into()], &wasm).unwrap();
+        assert!(findings.is_empty());
+
+        assert!(lint(&[], &[], b"not wasm").is_err());
+
+        // a custom section claiming to be u64::MAX bytes long
+        let mut wasm = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0];
+        wasm.extend(&[0xff; 9]);
+        wasm.push(0x01);
+        assert!(lint(&[], &[], &wasm).is_err());
+    }
+
+    /// Builds a module with one function, whose body has no locals and the given instructions.
+    fn module_with_code(instructions: &[u8]) -> Vec<u8> {
+        let mut wasm = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+
+        let mut body = vec![0];
+        body.extend(instructions);
+        let mut section = vec![1, body.len() as u8];
+        section.extend(body);
+        wasm.push(CODE_SECTION);
+        wasm.push(section.len() as u8);
+        wasm.extend(section);
+
+        wasm
+    }
+
+    #[test]
+    // check that floating point instructions are counted, skipping the immediates of other
+    // instructions, and reported by lint
+    fn test_cli_float_instructions() {
+        // i32.const 0x44, drop, i32.load offset 4, drop, end
+        let wasm = module_with_code(&[0x41, 0x44, 0x1a, 0x28, 0x02, 0x04, 0x1a, 0x0b]);
+        assert_eq!(float_instructions(&wasm).unwrap(), 0);
+        assert!(lint(&[], &[], &wasm).unwrap().is_empty());
+
+        // f64.const 1.0, f64.sqrt, i64.trunc_f64_s, drop, end
+        let wasm = module_with_code(&[0x44, 0, 0, 0, 0, 0, 0, 0xf0, 0x3f, 0x9f, 0xb0, 0x1a, 0x0b]);
+        assert_eq!(float_instructions(&wasm).unwrap(), 3);
+        assert_eq!(
+            lint(&[], &[], &wasm).unwrap(),
+            vec![Finding::FloatInstructions(3)]
+        );
+
+        // a SIMD instruction
+        assert!(float_instructions(&module_with_code(&[0xfd, 0x0c, 0x0b])).is_err());
+    }
+
+    #[test]
+    // check that the imported functions, exported functions and memory limits are read
+    fn test_cli_interface() {
+        let mut wasm = module(&["get_state"], b"");
+
+        // one memory of 17 pages, growing to at most 32
+        wasm.extend(&[MEMORY_SECTION, 4, 1, 0x01, 17, 32]);
+        // the memory, as "memory", and function 1, as "entrypoint"
+        let mut section = vec![2, 6];
+        section.extend(b"memory");
+        section.extend(&[0x02, 0, 10]);
+        section.extend(b"entrypoint");
+        section.extend(&[0x00, 1]);
+        wasm.push(EXPORT_SECTION);
+        wasm.push(section.len() as u8);
+        wasm.extend(section);
+
+        assert_eq!(
+            interface(&wasm).unwrap(),
+            Interface {
+                imports: vec![("env".into(), "get_state".into())],
+                exports: vec!["entrypoint".into()],
+                memory: Some((17, Some(32))),
+            }
+        );
+    }
+}
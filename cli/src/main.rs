@@ -17,42 +17,82 @@ extern crate clap;
 #[macro_use]
 extern crate serde_derive;
 
+mod address_book;
+mod audit;
+mod batching;
+mod blocks;
+mod config;
+#[cfg(feature = "contract-test")]
+mod contract_test;
+mod dependencies;
+mod dev;
+mod diff;
+mod doctor;
 mod error;
+mod exec_stream;
+#[cfg(feature = "gateway")]
+mod gateway;
+mod gc;
+mod host_calls;
 mod key;
+mod lint;
+mod owners;
+mod paths;
+mod permission_file;
+mod policy;
+mod preflight;
+mod progress;
+mod prune;
+mod rest_api;
+mod rotate;
+mod show;
 mod state;
 mod submit;
+mod trace;
+mod upgrade;
 mod upload;
+#[cfg(feature = "zmq")]
+mod validator;
+mod watch;
 
+use std::ffi::OsString;
 use std::fs::File;
-use std::io::{prelude::*, BufReader};
+use std::io::{self, prelude::*, BufReader, BufWriter};
 use std::path::Path;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use clap::{AppSettings, Arg, SubCommand};
+use sabre_sdk::protocol::namespaces::SabreNamespace;
 use sabre_sdk::protocol::payload::{
-    CreateContractRegistryActionBuilder, CreateNamespaceRegistryActionBuilder,
+    Action, CreateContractRegistryActionBuilder, CreateNamespaceRegistryActionBuilder,
     CreateNamespaceRegistryPermissionActionBuilder, DeleteContractRegistryActionBuilder,
     DeleteNamespaceRegistryActionBuilder, DeleteNamespaceRegistryPermissionActionBuilder,
-    ExecuteContractActionBuilder, UpdateContractRegistryOwnersActionBuilder,
+    ExecuteContractActionBuilder, SabrePayloadBuilder, UpdateContractRegistryOwnersActionBuilder,
     UpdateNamespaceRegistryOwnersActionBuilder,
 };
 use sabre_sdk::protocol::{
-    compute_contract_address,
+    compute_contract_address, permissions,
     state::{ContractList, ContractRegistryList},
-    CONTRACT_REGISTRY_ADDRESS_PREFIX,
+    PROTOCOL_VERSIONS_SETTING_KEY, SABRE_FAMILY_NAME, SABRE_PROTOCOL_VERSION,
 };
 use sabre_sdk::protos::FromBytes;
+use sabre_sdk::schemas;
 
 use error::CliError;
 use key::new_signer;
-use submit::submit_batches;
+use policy::Policy;
+use progress::ProgressEvent;
+use rest_api::{set_authorization, Authorization};
+use submit::{submit_batches, Submission};
 
 const APP_NAME: &str = env!("CARGO_PKG_NAME");
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-const DEFAULT_REST_API_ENDPOINT: &str = "http://localhost:8008/";
-
-fn run() -> Result<(), CliError> {
+fn run<I, T>(args: I) -> Result<(), CliError>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
     // Below, unwrap() is used on required arguments, since they will always
     // contain a value (and lack of value is should cause a panic). unwrap()
     // is also used on get_matches() because SubcommandRequiredElseHelp will
@@ -63,22 +103,76 @@ fn run() -> Result<(), CliError> {
         (version: VERSION)
         (about: "Sawtooth Sabre CLI")
         (@setting SubcommandRequiredElseHelp)
+        (@arg progress: --progress +takes_value +global possible_value[none json]
+            "Emit progress events on stderr in the given format")
+        (@arg policy: --policy +takes_value +global
+            "Path to a policy file restricting the commands each signing key may run")
+        (@arg key_algo: --("key-algo") +takes_value +global possible_value[secp256k1 ed25519]
+            "Algorithm of the signing key; detected from the key file if not given")
+        (@arg auth: --auth +takes_value +global conflicts_with[token]
+            "Basic credentials, as USERNAME:PASSWORD, sent to the REST API; prefer $SABRE_AUTH")
+        (@arg token: --token +takes_value +global
+            "Bearer token sent to the REST API; prefer $SABRE_TOKEN")
+        (@arg target: --target +takes_value +global possible_value[sawtooth splinter]
+            "Kind of REST API --url names; requests to splinter carry a Cylinder JWT made with the signing key")
+        (@arg service_id: --("service-id") +takes_value +global
+            "Scabbard service, as CIRCUIT::SERVICE, of the Splinter node at --url to submit batches to")
+        (@arg estimate: --estimate +global
+            "Report the sizes of the transactions and batches before submitting them, and refuse batches beyond the size limits")
+        (@arg max_batch_bytes: --("max-batch-bytes") +takes_value +global
+            "Largest batch list, in bytes, the REST API accepts; $SABRE_MAX_BATCH_BYTES")
+        (@arg max_batch_transactions: --("max-batch-transactions") +takes_value +global
+            "Most transactions the CLI puts in a batch, 100 by default; $SABRE_MAX_BATCH_TRANSACTIONS")
+        (@arg proxy: --proxy +takes_value +global
+            "Proxy URL for REST API requests; $HTTP_PROXY and $HTTPS_PROXY are used if not given")
+        (@arg proxy_auth: --("proxy-auth") +takes_value +global
+            "Basic credentials, as USERNAME:PASSWORD, sent to the proxy; prefer $SABRE_PROXY_AUTH")
+        (@arg ca_file: --("ca-file") +takes_value +global
+            "PEM file of CA certificates trusted, in addition to the built-in roots, for https REST APIs; $SABRE_CA_FILE")
+        (@arg client_cert: --("client-cert") +takes_value +global
+            "PEM file of the client certificate presented to https REST APIs for mutual TLS; $SABRE_CLIENT_CERT")
+        (@arg client_key: --("client-key") +takes_value +global
+            "PEM file of the client certificate's private key, if not in --client-cert; $SABRE_CLIENT_KEY")
+        (@arg quorum_reads: --("quorum-reads") +global
+            "Read registries, contracts and settings from every REST API in --url, and fail unless they agree")
         (@subcommand upload =>
             (about: "upload a Sabre contract")
+            (@arg filename: -f --filename +takes_value required_unless[archive] "Path to Sabre contract definition (*.yaml)")
+            (@arg archive: -a --archive +takes_value conflicts_with[filename wasm]
+                "Path to an archive (*.tar.gz) of contract definitions and their compiled contracts")
+            (@arg key: -k --key +takes_value "Signing key name")
+            (@arg url: --url +takes_value "URL to the Sawtooth REST API, or a comma separated list to fail over between")
+            (@arg wait: --wait +takes_value "A time in seconds to wait for batches to be committed")
+            (@arg wasm: -w --wasm +takes_value "Path to compiled smart contract (*.wasm)")
+            (@arg if_not_exists: --("if-not-exists") "Succeed without submitting if the same contract version already exists")
+            (@arg deny_floats: --("deny-floats") "Refuse contracts with floating point instructions, instead of warning of them")
+        )
+        (@subcommand upgrade =>
+            (about: "upload a new version of a Sabre contract and copy its namespace permissions")
             (@arg filename: -f --filename +required +takes_value "Path to Sabre contract definition (*.yaml)")
+            (@arg from: --from +takes_value "Name:Version of the contract being upgraded; defaults to the latest version")
             (@arg key: -k --key +takes_value "Signing key name")
-            (@arg url: --url +takes_value "URL to the Sawtooth REST API")
+            (@arg url: --url +takes_value "URL to the Sawtooth REST API, or a comma separated list to fail over between")
             (@arg wait: --wait +takes_value "A time in seconds to wait for batches to be committed")
             (@arg wasm: -w --wasm +takes_value "Path to compiled smart contract (*.wasm)")
         )
         (@subcommand exec =>
             (about: "execute a Sabre contract")
-            (@arg contract: -C --contract +required +takes_value "Name:Version of a Sabre contract")
-            (@arg payload: -p --payload +required +takes_value "Path to Sabre contract payload")
+            (@arg contract: -C --contract required_unless[stream] +takes_value "Name:Version of a Sabre contract, optionally pinned to the sha512 of its wasm with Name:Version@sha512")
+            (@arg payload: -p --payload required_unless[stream] +takes_value "Path to Sabre contract payload")
+            (@arg stream: --stream +takes_value conflicts_with[contract payload inputs outputs simulate]
+                "Path to an NDJSON file of executions, one per line, or - to read them from stdin")
+            (@arg rate: --rate +takes_value requires[stream]
+                "The most transactions to build per second when executing a stream")
             (@arg key: -k --key +takes_value "Signing key name")
-            (@arg url: --url +takes_value "URL to the Sawtooth REST API")
+            (@arg url: --url +takes_value "URL to the Sawtooth REST API, or a comma separated list to fail over between")
             (@arg inputs: --inputs +takes_value +multiple "Input addresses used by the contract")
             (@arg outputs: --outputs +takes_value +multiple "Output addresses used by the contract")
+            (@arg compress_threshold: --("compress-threshold") +takes_value conflicts_with[stream]
+                "Compress payloads of at least this many bytes; requires a transaction processor which decompresses them")
+            (@arg no_preflight: --("no-preflight") "Skip checking the contract and its permissions before submitting")
+            (@arg simulate: --simulate conflicts_with[no_preflight]
+                "Check and build the transaction without submitting it, simulating it if the backend allows")
             (@arg wait: --wait +takes_value "A time in seconds to wait for batches to be committed")
         )
         (@subcommand ns =>
@@ -90,20 +184,26 @@ fn run() -> Result<(), CliError> {
             )
             (@arg namespace: +required "A global state address prefix (namespace)")
             (@arg key: -k --key +takes_value "Signing key name")
-            (@arg url: -U --url +takes_value "URL to the Sawtooth REST API")
+            (@arg url: -U --url +takes_value "URL to the Sawtooth REST API, or a comma separated list to fail over between")
             (@arg owner: -O --owner +takes_value +multiple "Owner of this namespace")
+            (@arg if_not_exists: --("if-not-exists") conflicts_with[update delete]
+                "Succeed without submitting if the namespace already exists with the same owners")
             (@arg wait: --wait +takes_value "A time in seconds to wait for batches to be committed")
         )
         (@subcommand perm =>
             (about: "set or delete a Sabre namespace permission")
-            (@arg namespace: +required "A global state address prefix (namespace)")
-            (@arg contract: +required "Name of the contract")
+            (@arg namespace: required_unless[file] "A global state address prefix (namespace)")
+            (@arg contract: required_unless[file] "Name of the contract")
+            (@arg file: -f --file +takes_value conflicts_with[namespace contract delete read write]
+                "Path to a YAML list of permissions to set together in one batch")
             (@arg key: -k --key +takes_value "Signing key name")
-            (@arg url: -U --url +takes_value "URL to the Sawtooth REST API")
+            (@arg url: -U --url +takes_value "URL to the Sawtooth REST API, or a comma separated list to fail over between")
             (@arg delete: -d --delete "Remove all permissions")
             (@arg read: -r --read conflicts_with[delete] "Set read permission")
             (@arg write: -w --write conflicts_with[delete] "Set write permission")
             (@arg wait: --wait +takes_value "A time in seconds to wait for batches to be committed")
+            (@arg simulate: --simulate conflicts_with[wait]
+                "Report the contracts which would gain or lose access to namespaces, without submitting")
         )
         (@subcommand cr =>
             (about: "create, update, or delete a Sabre contract registry")
@@ -114,22 +214,61 @@ fn run() -> Result<(), CliError> {
             )
             (@arg name: +required "Name of the contracts in the registry")
             (@arg key: -k --key +takes_value "Signing key name")
-            (@arg url: -U --url +takes_value "URL to the Sawtooth REST API")
+            (@arg url: -U --url +takes_value "URL to the Sawtooth REST API, or a comma separated list to fail over between")
             (@arg owner: -O --owner +takes_value +multiple "Owner of this contract registry")
+            (@arg if_not_exists: --("if-not-exists") conflicts_with[update delete]
+                "Succeed without submitting if the contract registry already exists with the same owners")
+            (@arg wait: --wait +takes_value "A time in seconds to wait for batches to be committed")
+        )
+        (@subcommand prune =>
+            (about: "delete all but the most recently registered versions of a Sabre contract")
+            (@arg name: +required "Name of the contract")
+            (@arg keep: --keep +required +takes_value "Number of the highest versions to keep, at least 1")
+            (@arg dry_run: --("dry-run") "Report the versions which would be deleted without deleting them")
+            (@arg key: -k --key +takes_value "Signing key name")
+            (@arg url: -U --url +takes_value "URL to the Sawtooth REST API, or a comma separated list to fail over between")
             (@arg wait: --wait +takes_value "A time in seconds to wait for batches to be committed")
         )
+        (@subcommand owner =>
+            (about: "manage the owners of Sabre registries")
+            (@setting SubcommandRequiredElseHelp)
+            (@subcommand rotate =>
+                (about: "replace an owner's public key in every contract and namespace registry it owns, in one batch")
+                (@arg old: --old +required +takes_value "Public key of the owner being replaced")
+                (@arg new: --new +required +takes_value "Public key replacing it")
+                (@arg dry_run: --("dry-run") "Report the registries which would be updated without updating them")
+                (@arg key: -k --key +takes_value "Signing key name; the old key or an administrator's")
+                (@arg url: -U --url +takes_value "URL to the Sawtooth REST API, or a comma separated list to fail over between")
+                (@arg wait: --wait +takes_value "A time in seconds to wait for batches to be committed")
+            )
+            (@subcommand add =>
+                (about: "add owners to a contract registry, keeping its current owners")
+                (@arg name: +required "Name of the contract registry")
+                (@arg owner: -O --owner +required +takes_value +multiple "Public key of an owner to add")
+                (@arg key: -k --key +takes_value "Signing key name")
+                (@arg url: -U --url +takes_value "URL to the Sawtooth REST API, or a comma separated list to fail over between")
+                (@arg wait: --wait +takes_value "A time in seconds to wait for batches to be committed")
+            )
+            (@subcommand remove =>
+                (about: "remove owners from a contract registry, keeping its other owners")
+                (@arg name: +required "Name of the contract registry")
+                (@arg owner: -O --owner +required +takes_value +multiple "Public key of an owner to remove")
+                (@arg key: -k --key +takes_value "Signing key name")
+                (@arg url: -U --url +takes_value "URL to the Sawtooth REST API, or a comma separated list to fail over between")
+                (@arg wait: --wait +takes_value "A time in seconds to wait for batches to be committed")
+            )
+        )
     );
 
-    let app = app.subcommand(
-        SubCommand::with_name("contract")
-            .about("List or show a Sabre smart contract")
+    let contract_subcommand = SubCommand::with_name("contract")
+            .about("List, show or lint a Sabre smart contract")
             .setting(AppSettings::SubcommandRequiredElseHelp)
             .subcommand(
                 SubCommand::with_name("list")
                     .about("List all registered Sabre smart contracts")
                     .args(&[
                         Arg::with_name("url")
-                            .help("URL to the Sawtooth REST API")
+                            .help("URL to the Sawtooth REST API, or a comma separated list to fail over between")
                             .short("U")
                             .long("url")
                             .takes_value(true),
@@ -147,7 +286,7 @@ fn run() -> Result<(), CliError> {
                     .about("Show details about a registered Sabre smart contract")
                     .args(&[
                         Arg::with_name("url")
-                            .help("URL to the Sawtooth REST API")
+                            .help("URL to the Sawtooth REST API, or a comma separated list to fail over between")
                             .short("U")
                             .long("url")
                             .takes_value(true),
@@ -159,34 +298,696 @@ fn run() -> Result<(), CliError> {
                             .takes_value(true)
                             .required(true),
                     ]),
+            )
+            .subcommand(
+                SubCommand::with_name("lint")
+                    .about(
+                        "Check a compiled contract for state access its inputs and outputs do \
+                         not declare, and for floating point instructions",
+                    )
+                    .args(&[
+                        Arg::with_name("filename")
+                            .help("Path to Sabre contract definition (*.yaml)")
+                            .short("f")
+                            .long("filename")
+                            .takes_value(true)
+                            .required(true),
+                        Arg::with_name("wasm")
+                            .help("Path to compiled smart contract (*.wasm)")
+                            .short("w")
+                            .long("wasm")
+                            .takes_value(true),
+                    ]),
+            );
+
+    #[cfg(feature = "contract-test")]
+    let contract_subcommand = contract_subcommand.subcommand(
+        SubCommand::with_name("test")
+            .about(
+                "Run conformance probes against a compiled contract, executing it locally, and \
+                 report whether each passed",
+            )
+            .args(&[
+                Arg::with_name("filename")
+                    .help("Path to Sabre contract definition (*.yaml)")
+                    .short("f")
+                    .long("filename")
+                    .takes_value(true)
+                    .required(true),
+                Arg::with_name("wasm")
+                    .help("Path to compiled smart contract (*.wasm)")
+                    .short("w")
+                    .long("wasm")
+                    .takes_value(true),
+                Arg::with_name("payload")
+                    .help("Path to a contract payload the contract should apply")
+                    .short("p")
+                    .long("payload")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1),
+                Arg::with_name("max_memory_pages")
+                    .help("Most 64 KiB pages of memory the contract may declare")
+                    .long("max-memory-pages")
+                    .takes_value(true)
+                    .default_value("256"),
+                Arg::with_name("format")
+                    .help("Format to display the probe results in")
+                    .long("format")
+                    .takes_value(true)
+                    .possible_values(&["human", "json"])
+                    .default_value("human"),
+            ]),
+    );
+
+    let app = app.subcommand(contract_subcommand);
+
+    let app = app.subcommand(
+        SubCommand::with_name("state")
+            .about("List Sabre and contract state")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                SubCommand::with_name("list")
+                    .about(
+                        "List state addresses, labeled with the registry or namespace owning each",
+                    )
+                    .args(&[
+                        Arg::with_name("url")
+                            .help("URL to the Sawtooth REST API, or a comma separated list to fail over between")
+                            .short("U")
+                            .long("url")
+                            .takes_value(true),
+                        Arg::with_name("format")
+                            .help("Format to display list of state addresses in")
+                            .short("f")
+                            .long("format")
+                            .takes_value(true)
+                            .possible_values(&["human", "csv"])
+                            .default_value("human"),
+                        Arg::with_name("prefix")
+                            .help("Address prefix of the state to list")
+                            .takes_value(true)
+                            .required(true),
+                    ]),
+            )
+            .subcommand(
+                SubCommand::with_name("show")
+                    .about("Print the data at an address, as hex unless --raw is given")
+                    .args(&[
+                        Arg::with_name("url")
+                            .help("URL to the Sawtooth REST API, or a comma separated list to fail over between")
+                            .short("U")
+                            .long("url")
+                            .takes_value(true),
+                        Arg::with_name("raw")
+                            .help("Write the data as raw bytes instead of hex")
+                            .long("raw"),
+                        Arg::with_name("output")
+                            .help("File to write the data to instead of stdout")
+                            .short("o")
+                            .long("output")
+                            .takes_value(true),
+                        Arg::with_name("offset")
+                            .help("Byte offset in the data to start from")
+                            .long("offset")
+                            .takes_value(true)
+                            .default_value("0"),
+                        Arg::with_name("length")
+                            .help("Number of bytes to write, instead of to the end of the data")
+                            .long("length")
+                            .takes_value(true),
+                        Arg::with_name("address")
+                            .help("Address of the state entry to show")
+                            .takes_value(true)
+                            .required(true),
+                    ]),
+            )
+            .subcommand(
+                SubCommand::with_name("watch")
+                    .about("Wait for the data at an address to change, and print the new data")
+                    .args(&[
+                        Arg::with_name("url")
+                            .help("URL to the Sawtooth REST API, or a comma separated list to fail over between")
+                            .short("U")
+                            .long("url")
+                            .takes_value(true),
+                        Arg::with_name("timeout")
+                            .help("Seconds to wait for the data to change")
+                            .long("timeout")
+                            .takes_value(true)
+                            .default_value("60"),
+                        Arg::with_name("interval")
+                            .help("Milliseconds between checks for a new block")
+                            .long("interval")
+                            .takes_value(true)
+                            .default_value("1000"),
+                        Arg::with_name("address")
+                            .help("Address of the state entry to watch")
+                            .takes_value(true)
+                            .required(true),
+                    ]),
+            ),
+    );
+
+    let app = app.subcommand(
+        SubCommand::with_name("trace")
+            .about("Read the host calls traced by the transaction processor")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                SubCommand::with_name("show")
+                    .about(
+                        "Show the state reads, writes and deletes, events and receipts of \
+                         transactions, from a file written by the transaction processor's \
+                         --host-call-trace option",
+                    )
+                    .args(&[
+                        Arg::with_name("txn")
+                            .help("Show only the host calls of the transaction with this id")
+                            .long("txn")
+                            .takes_value(true),
+                        Arg::with_name("file")
+                            .help("Host call trace file")
+                            .takes_value(true)
+                            .required(true),
+                    ]),
+            ),
+    );
+
+    let app = app.subcommand(
+        SubCommand::with_name("addr")
+            .about("Manage the address book, whose entries may be given as @name")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                SubCommand::with_name("add")
+                    .about("Add or replace an address book entry")
+                    .args(&[
+                        Arg::with_name("name")
+                            .help("Name of the entry")
+                            .takes_value(true)
+                            .required(true),
+                        Arg::with_name("address")
+                            .help("Address or address prefix the name refers to")
+                            .takes_value(true)
+                            .required(true),
+                    ]),
+            )
+            .subcommand(
+                SubCommand::with_name("list")
+                    .about("List the address book entries")
+                    .args(&[Arg::with_name("format")
+                        .help("Format to display the address book in")
+                        .short("f")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["human", "csv"])
+                        .default_value("human")]),
+            )
+            .subcommand(
+                SubCommand::with_name("sync")
+                    .about(
+                        "Add an entry for each namespace, named after the contracts which may \
+                         write to it",
+                    )
+                    .args(&[Arg::with_name("url")
+                        .help("URL to the Sawtooth REST API, or a comma separated list to fail over between")
+                        .short("U")
+                        .long("url")
+                        .takes_value(true)]),
+            ),
+    );
+
+    let app = app.subcommand(
+        SubCommand::with_name("diff")
+            .about("Compare the state of two networks")
+            .args(&[
+                Arg::with_name("left")
+                    .help("URL to the Sawtooth REST API of the left network")
+                    .long("left")
+                    .takes_value(true)
+                    .required(true),
+                Arg::with_name("right")
+                    .help("URL to the Sawtooth REST API of the right network")
+                    .long("right")
+                    .takes_value(true)
+                    .required(true),
+                Arg::with_name("prefix")
+                    .help("Address prefix of the state to compare")
+                    .long("prefix")
+                    .takes_value(true)
+                    .default_value("00ec"),
+                Arg::with_name("format")
+                    .help("Format to display the differences in")
+                    .short("f")
+                    .long("format")
+                    .takes_value(true)
+                    .possible_values(&["human", "csv"])
+                    .default_value("human"),
+            ]),
+    );
+
+    let app = app.subcommand(
+        SubCommand::with_name("doctor")
+            .about("Diagnose problems with the REST API, Sabre state and signing key")
+            .args(&[
+                Arg::with_name("url")
+                    .help("URL to the Sawtooth REST API, or a comma separated list to fail over between")
+                    .short("U")
+                    .long("url")
+                    .takes_value(true),
+                Arg::with_name("key")
+                    .help("Signing key name")
+                    .short("k")
+                    .long("key")
+                    .takes_value(true),
+            ]),
+    );
+
+    let app = app.subcommand(
+        SubCommand::with_name("audit")
+            .about("Report the contracts which may write to the given namespaces")
+            .args(&[
+                Arg::with_name("url")
+                    .help(
+                        "URL to the Sawtooth REST API, or a comma separated list to fail over \
+                         between",
+                    )
+                    .short("U")
+                    .long("url")
+                    .takes_value(true),
+                Arg::with_name("namespace")
+                    .help("Namespace prefix to report write access to")
+                    .long("namespace")
+                    .takes_value(true)
+                    .multiple(true)
+                    .required(true),
+                Arg::with_name("format")
+                    .help("Format to display the report in")
+                    .short("f")
+                    .long("format")
+                    .takes_value(true)
+                    .possible_values(&["human", "csv", "json"])
+                    .default_value("human"),
+            ])
+            .setting(AppSettings::SubcommandsNegateReqs)
+            .subcommand(
+                SubCommand::with_name("export")
+                    .about(
+                        "Export the Sabre transactions committed since a block as a \
+                         chronological NDJSON audit log",
+                    )
+                    .args(&[
+                        Arg::with_name("url")
+                            .help(
+                                "URL to the Sawtooth REST API, or a comma separated list to fail \
+                                 over between",
+                            )
+                            .short("U")
+                            .long("url")
+                            .takes_value(true),
+                        Arg::with_name("from_block")
+                            .help("Number of the first block to export")
+                            .long("from-block")
+                            .takes_value(true)
+                            .default_value("0"),
+                    ]),
+            ),
+    );
+
+    let app = app.subcommand(
+        SubCommand::with_name("gc")
+            .about("Find Sabre state left behind by deleted contracts and registries")
+            .args(&[
+                Arg::with_name("url")
+                    .help("URL to the Sawtooth REST API, or a comma separated list to fail over between")
+                    .short("U")
+                    .long("url")
+                    .takes_value(true),
+                Arg::with_name("dry_run")
+                    .help("Report the state which would be removed without removing it")
+                    .long("dry-run"),
+            ]),
+    );
+
+    let app = app.subcommand(
+        SubCommand::with_name("version")
+            .about("Show the versions of the CLI and of the Sabre protocol it supports")
+            .args(&[
+                Arg::with_name("remote")
+                    .help(
+                        "Compare the protocol versions with those the network declares in the \
+                         sabre.protocol.versions setting",
+                    )
+                    .long("remote"),
+                Arg::with_name("url")
+                    .help(
+                        "URL to the Sawtooth REST API, or a comma separated list to fail over \
+                         between",
+                    )
+                    .short("U")
+                    .long("url")
+                    .takes_value(true),
+            ]),
+    );
+
+    let app = app.subcommand(
+        SubCommand::with_name("protos")
+            .about("Export the Sabre protobuf schemas")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                SubCommand::with_name("export")
+                    .about("Write the Sabre .proto files to a directory")
+                    .args(&[Arg::with_name("dir")
+                        .help("Directory to write the .proto files to")
+                        .takes_value(true)
+                        .required(true)]),
+            ),
+    );
+
+    let app = app.subcommand(
+        SubCommand::with_name("config")
+            .about("Inspect the CLI's settings and where they come from")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                SubCommand::with_name("show")
+                    .about(
+                        "Show the settings given by flags and environment variables; a flag \
+                         takes precedence over an environment variable, which takes precedence \
+                         over the default",
+                    )
+                    .args(&[
+                        Arg::with_name("resolved")
+                            .help("Show the effective value of every setting, including defaults")
+                            .long("resolved"),
+                        Arg::with_name("url")
+                            .help("URL to the Sawtooth REST API, or a comma separated list to fail over between")
+                            .short("U")
+                            .long("url")
+                            .takes_value(true),
+                        Arg::with_name("key")
+                            .help("Signing key name")
+                            .short("k")
+                            .long("key")
+                            .takes_value(true),
+                    ]),
+            )
+            .subcommand(
+                SubCommand::with_name("doctor")
+                    .about("Report settings given by more than one source")
+                    .args(&[
+                        Arg::with_name("url")
+                            .help("URL to the Sawtooth REST API, or a comma separated list to fail over between")
+                            .short("U")
+                            .long("url")
+                            .takes_value(true),
+                        Arg::with_name("key")
+                            .help("Signing key name")
+                            .short("k")
+                            .long("key")
+                            .takes_value(true),
+                    ]),
+            ),
+    );
+
+    let app = app.subcommand(
+        SubCommand::with_name("dev")
+            .about("Start and stop local single node networks for development")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                SubCommand::with_name("up")
+                    .about(
+                        "Start a network running Sabre, with the signing key as its \
+                         administrator, and wait until it is ready",
+                    )
+                    .args(&[
+                        Arg::with_name("name")
+                            .help("Name of the network")
+                            .long("name")
+                            .takes_value(true)
+                            .default_value("default"),
+                        Arg::with_name("port")
+                            .help("Port on localhost to expose the REST API on")
+                            .long("port")
+                            .takes_value(true)
+                            .default_value("9708"),
+                        Arg::with_name("tp_image")
+                            .help("Image to run the Sabre transaction processor from")
+                            .long("tp-image")
+                            .takes_value(true)
+                            .default_value(dev::DEFAULT_TP_IMAGE),
+                        Arg::with_name("timeout")
+                            .help("Seconds to wait for the network to be ready")
+                            .long("timeout")
+                            .takes_value(true)
+                            .default_value("120"),
+                        Arg::with_name("key")
+                            .help("Signing key name")
+                            .short("k")
+                            .long("key")
+                            .takes_value(true),
+                    ]),
+            )
+            .subcommand(
+                SubCommand::with_name("down")
+                    .about("Stop a network and remove its state")
+                    .args(&[Arg::with_name("name")
+                        .help("Name of the network")
+                        .long("name")
+                        .takes_value(true)
+                        .default_value("default")]),
             ),
     );
 
-    let matches = app.get_matches();
+    #[cfg(feature = "gateway")]
+    let app = app.subcommand(
+        SubCommand::with_name("gateway")
+            .about(
+                "Serve a JSON-RPC gateway which signs and submits Sabre transactions for its \
+                 clients",
+            )
+            .args(&[
+                Arg::with_name("bind")
+                    .help("Address to serve the gateway at")
+                    .long("bind")
+                    .takes_value(true)
+                    .default_value("127.0.0.1:8090"),
+                Arg::with_name("url")
+                    .help("URL to the Sawtooth REST API, or a comma separated list to fail over between")
+                    .short("U")
+                    .long("url")
+                    .takes_value(true),
+                Arg::with_name("key")
+                    .help("Signing key name, which signs every transaction submitted")
+                    .short("k")
+                    .long("key")
+                    .takes_value(true),
+                Arg::with_name("token_file")
+                    .help("File of bearer tokens, one per line, which clients must present")
+                    .long("token-file")
+                    .takes_value(true)
+                    .required_unless("allow_all"),
+                Arg::with_name("allow_all")
+                    .help("Serve every client, without a bearer token")
+                    .long("allow-all")
+                    .conflicts_with("token_file"),
+            ]),
+    );
+
+    #[cfg(feature = "zmq")]
+    let app = app.arg(
+        Arg::with_name("connect")
+            .help(
+                "ZMQ endpoint of a validator, such as tcp://validator:4004, to submit batches to \
+                 in place of the REST API",
+            )
+            .long("connect")
+            .takes_value(true)
+            .global(true),
+    );
+
+    let matches = app.get_matches_from(args);
+
+    // The REST API options may be given before or after the subcommand, and its action
+    let mut subcommand_matches = None;
+    while let (_, Some(sub_matches)) = subcommand_matches.unwrap_or(&matches).subcommand() {
+        subcommand_matches = Some(sub_matches);
+    }
+    let authorization = config::authorization(subcommand_matches, &matches)?;
+    let target = subcommand_matches
+        .and_then(|sub_matches| sub_matches.value_of("target"))
+        .or_else(|| matches.value_of("target"));
+    if target == Some("splinter") {
+        if authorization.is_some() {
+            return Err(CliError::User(
+                "--target splinter authorizes requests with the signing key, so --auth and \
+                 --token may not be set"
+                    .into(),
+            ));
+        }
+        let key_matches = subcommand_matches.unwrap_or(&matches);
+        let signer = new_signer(
+            config::signing_key(key_matches).as_deref(),
+            key_matches.value_of("key_algo"),
+        )?;
+        set_authorization(Authorization::cylinder(&*signer)?);
+    } else if let Some(authorization) = authorization {
+        set_authorization(authorization);
+    }
+    let service_id = subcommand_matches
+        .and_then(|sub_matches| sub_matches.value_of("service_id"))
+        .or_else(|| matches.value_of("service_id"));
+    if let Some(service_id) = service_id {
+        submit::set_backend(submit::SubmissionBackend::scabbard(service_id)?);
+        state::set_unreadable(format!(
+            "batches are submitted to scabbard service {}, whose state is not read from the \
+             Sawtooth REST API",
+            service_id
+        ));
+    }
+    #[cfg(feature = "zmq")]
+    {
+        let connect = subcommand_matches
+            .and_then(|sub_matches| sub_matches.value_of("connect"))
+            .or_else(|| matches.value_of("connect"));
+        if let Some(endpoint) = connect {
+            if service_id.is_some() {
+                return Err(CliError::User(
+                    "--connect and --service-id may not both be set".into(),
+                ));
+            }
+            submit::set_backend(submit::SubmissionBackend::Validator {
+                endpoint: endpoint.into(),
+            });
+            let url_given = subcommand_matches.map_or(false, config::rest_api_url_given)
+                || config::rest_api_url_given(&matches);
+            if !url_given {
+                state::set_unreadable(format!(
+                    "batches are submitted to validator {} and no --url names a REST API to \
+                     read state from",
+                    endpoint
+                ));
+            }
+        }
+    }
+    if matches.is_present("estimate")
+        || subcommand_matches.map_or(false, |sub_matches| sub_matches.is_present("estimate"))
+    {
+        submit::set_size_limits(config::size_limits(subcommand_matches, &matches)?);
+    }
+    rest_api::set_proxy(config::proxy_settings(subcommand_matches, &matches)?)?;
+    rest_api::set_tls(config::tls_settings(subcommand_matches, &matches))?;
+    state::set_quorum_reads(
+        matches.is_present("quorum_reads")
+            || subcommand_matches
+                .map_or(false, |sub_matches| sub_matches.is_present("quorum_reads")),
+    );
+
+    #[cfg(feature = "gateway")]
+    if let Some(gateway_matches) = matches.subcommand_matches("gateway") {
+        return gateway(gateway_matches);
+    }
 
     if let Some(contract_matches) = matches.subcommand_matches("contract") {
         contract(contract_matches)?
+    } else if let Some(state_matches) = matches.subcommand_matches("state") {
+        state(state_matches)?
+    } else if let Some(trace_matches) = matches.subcommand_matches("trace") {
+        trace(trace_matches)?
+    } else if let Some(addr_matches) = matches.subcommand_matches("addr") {
+        addr(addr_matches)?
+    } else if let Some(diff_matches) = matches.subcommand_matches("diff") {
+        diff(diff_matches)?
+    } else if let Some(doctor_matches) = matches.subcommand_matches("doctor") {
+        doctor(doctor_matches)?
+    } else if let Some(audit_matches) = matches.subcommand_matches("audit") {
+        audit(audit_matches)?
+    } else if let Some(gc_matches) = matches.subcommand_matches("gc") {
+        gc(gc_matches)?
+    } else if let Some(version_matches) = matches.subcommand_matches("version") {
+        version(version_matches)?
+    } else if let Some(protos_matches) = matches.subcommand_matches("protos") {
+        protos(protos_matches)?
+    } else if let Some(config_matches) = matches.subcommand_matches("config") {
+        config(config_matches)?
+    } else if let Some(dev_matches) = matches.subcommand_matches("dev") {
+        dev(dev_matches)?
     } else {
-        let (batch_link, mut wait) =
-            if let Some(upload_matches) = matches.subcommand_matches("upload") {
-                upload(upload_matches)?
-            } else if let Some(exec_matches) = matches.subcommand_matches("exec") {
-                execute(exec_matches)?
-            } else if let Some(ns_matches) = matches.subcommand_matches("ns") {
-                namespace_registry(ns_matches)?
-            } else if let Some(perm_matches) = matches.subcommand_matches("perm") {
-                namespace_permission(perm_matches)?
-            } else if let Some(cr_matches) = matches.subcommand_matches("cr") {
-                contract_registry(cr_matches)?
-            } else {
-                return Err(CliError::User("Subcommand required".into()));
-            };
+        // --progress may be given before or after the subcommand
+        let (step, step_matches) = matches.subcommand();
+        let step_action = step_matches.and_then(|step_matches| step_matches.subcommand_name());
+        // The arguments of a step with actions, such as owner rotate, belong to the action
+        let step_matches =
+            step_matches.map(|step_matches| step_matches.subcommand().1.unwrap_or(step_matches));
+        let progress = progress::new_reporter(
+            step_matches
+                .and_then(|step_matches| step_matches.value_of("progress"))
+                .or_else(|| matches.value_of("progress")),
+        )?;
+
+        let policy_file = config::policy_file(step_matches, &matches);
+        if let (Some(policy_file), Some(step_matches)) = (policy_file, step_matches) {
+            let public_key = new_signer(
+                config::signing_key(step_matches).as_deref(),
+                step_matches.value_of("key_algo"),
+            )?
+            .public_key()
+            .map_err(|err| CliError::Signing(err.to_string()))?
+            .as_hex();
+            Policy::load(&policy_file)?
+                .check(&public_key, &command_name(step, step_action, step_matches))?;
+        }
+
+        progress.report(ProgressEvent::StepStarted { step });
+
+        let submitted = if let Some(upload_matches) = matches.subcommand_matches("upload") {
+            upload(upload_matches)?
+        } else if let Some(upgrade_matches) = matches.subcommand_matches("upgrade") {
+            Some(upgrade(upgrade_matches)?)
+        } else if let Some(exec_matches) = matches.subcommand_matches("exec") {
+            execute(exec_matches)?
+        } else if let Some(ns_matches) = matches.subcommand_matches("ns") {
+            namespace_registry(ns_matches)?
+        } else if let Some(perm_matches) = matches.subcommand_matches("perm") {
+            namespace_permission(perm_matches)?
+        } else if let Some(cr_matches) = matches.subcommand_matches("cr") {
+            contract_registry(cr_matches)?
+        } else if let Some(prune_matches) = matches.subcommand_matches("prune") {
+            prune(prune_matches)?
+        } else if let Some(owner_matches) = matches.subcommand_matches("owner") {
+            owner(owner_matches)?
+        } else {
+            return Err(CliError::User("Subcommand required".into()));
+        };
+
+        // Nothing is submitted when --if-not-exists finds the entity already exists, when exec
+        // or perm is only simulated, when prune or owner rotate has nothing to do or is a dry run,
+        // or when owner add or remove would not change the owners
+        let (submission, mut wait) = match submitted {
+            Some(submitted) => submitted,
+            None => {
+                progress.report(ProgressEvent::StepCompleted { step });
+                return Ok(());
+            }
+        };
+        progress.report(ProgressEvent::BatchesSubmitted {
+            link: &submission.link,
+            batches: &submission.batches,
+        });
 
         if wait > 0 {
+            let mut poller = submit::StatusPoller::new()?;
             let response_body = loop {
                 let time = Instant::now();
-                let status_response = submit::wait_for_batch(&batch_link, wait)?;
+                let status_response = poller.wait_for_batch(&submission.link, wait)?;
+                for batch_status in status_response.batch_statuses() {
+                    progress.report(ProgressEvent::BatchStatus {
+                        id: batch_status.id(),
+                        status: batch_status.status(),
+                    });
+                }
 
+                if !status_response.is_finished() {
+                    poller.pause(Duration::from_secs(wait).saturating_sub(time.elapsed()));
+                }
                 wait = wait.saturating_sub(time.elapsed().as_secs());
 
                 if wait == 0 || status_response.is_finished() {
@@ -196,17 +997,17 @@ fn run() -> Result<(), CliError> {
 
             println!("Response Body:\n{}", response_body);
         }
+
+        progress.report(ProgressEvent::StepCompleted { step });
     }
 
     Ok(())
 }
 
-fn upload(upload_matches: &clap::ArgMatches) -> Result<(String, u64), CliError> {
-    let filename = upload_matches.value_of("filename").unwrap();
-    let key_name = upload_matches.value_of("key");
-    let url = upload_matches
-        .value_of("url")
-        .unwrap_or(DEFAULT_REST_API_ENDPOINT);
+fn upload(upload_matches: &clap::ArgMatches) -> Result<Option<(Submission, u64)>, CliError> {
+    let key_name = config::signing_key(upload_matches);
+    let key_algo = upload_matches.value_of("key_algo");
+    let url = &config::rest_api_url(upload_matches);
     let wasm_name = upload_matches.value_of("wasm");
 
     let wait = match value_t!(upload_matches, "wait", u64) {
@@ -217,17 +1018,78 @@ fn upload(upload_matches: &clap::ArgMatches) -> Result<(String, u64), CliError>
         },
     };
 
-    let batch_link = upload::do_upload(filename, key_name, url, wasm_name)?;
-    Ok((batch_link, wait))
+    let if_not_exists = upload_matches.is_present("if_not_exists") && can_check_existence();
+    let deny_floats = upload_matches.is_present("deny_floats");
+
+    let submission = match upload_matches.value_of("archive") {
+        Some(archive) => upload::do_upload_archive(
+            archive,
+            key_name.as_deref(),
+            key_algo,
+            url,
+            if_not_exists,
+            deny_floats,
+        )?,
+        None => {
+            // --filename is required unless --archive is given
+            let filename = upload_matches.value_of("filename").unwrap();
+            upload::do_upload(
+                filename,
+                key_name.as_deref(),
+                key_algo,
+                url,
+                wasm_name,
+                if_not_exists,
+                deny_floats,
+            )?
+        }
+    };
+    Ok(submission.map(|submission| (submission, wait)))
+}
+
+/// Returns whether --if-not-exists can look for what would be created, warning that the check
+/// is skipped if state cannot be read.
+fn can_check_existence() -> bool {
+    match state::unreadable() {
+        Some(reason) => {
+            eprintln!("Skipping the --if-not-exists check: {}", reason);
+            false
+        }
+        None => true,
+    }
 }
 
-fn execute(exec_matches: &clap::ArgMatches) -> Result<(String, u64), CliError> {
-    let contract = exec_matches.value_of("contract").unwrap();
-    let payload = exec_matches.value_of("payload").unwrap();
-    let key_name = exec_matches.value_of("key");
-    let url = exec_matches
-        .value_of("url")
-        .unwrap_or(DEFAULT_REST_API_ENDPOINT);
+fn upgrade(upgrade_matches: &clap::ArgMatches) -> Result<(Submission, u64), CliError> {
+    let filename = upgrade_matches.value_of("filename").unwrap();
+    let from = upgrade_matches.value_of("from");
+    let key_name = config::signing_key(upgrade_matches);
+    let key_algo = upgrade_matches.value_of("key_algo");
+    let url = &config::rest_api_url(upgrade_matches);
+    let wasm_name = upgrade_matches.value_of("wasm");
+
+    let wait = match value_t!(upgrade_matches, "wait", u64) {
+        Ok(wait) => wait,
+        Err(err) => match err.kind {
+            clap::ErrorKind::ArgumentNotFound => 0,
+            _ => return Err(CliError::User("Wait must be an integer".into())),
+        },
+    };
+
+    let submission = upgrade::do_upgrade(
+        filename,
+        key_name.as_deref(),
+        key_algo,
+        url,
+        wasm_name,
+        from,
+    )?;
+    Ok((submission, wait))
+}
+
+fn execute(exec_matches: &clap::ArgMatches) -> Result<Option<(Submission, u64)>, CliError> {
+    let key_name = config::signing_key(exec_matches);
+    let key_algo = exec_matches.value_of("key_algo");
+    let url = &config::rest_api_url(exec_matches);
 
     let wait = match value_t!(exec_matches, "wait", u64) {
         Ok(wait) => wait,
@@ -237,19 +1099,50 @@ fn execute(exec_matches: &clap::ArgMatches) -> Result<(String, u64), CliError> {
         },
     };
 
-    let inputs = exec_matches
+    if let Some(stream) = exec_matches.value_of("stream") {
+        let rate = match value_t!(exec_matches, "rate", f64) {
+            Ok(rate) if rate > 0.0 => Some(rate),
+            Ok(_) => return Err(CliError::User("Rate must be greater than 0".into())),
+            Err(err) => match err.kind {
+                clap::ErrorKind::ArgumentNotFound => None,
+                _ => return Err(CliError::User("Rate must be a number".into())),
+            },
+        };
+
+        let signer = new_signer(key_name.as_deref(), key_algo)?;
+        let limits = batching::BatchLimits::default();
+        // Only the last batch submitted is waited for
+        let submission = if stream == "-" {
+            exec_stream::do_exec_stream(std::io::stdin().lock(), &*signer, url, rate, &limits)?
+        } else {
+            let file = File::open(stream).map_err(|e| {
+                CliError::User(format!("Could not open stream \"{}\": {}", stream, e))
+            })?;
+            exec_stream::do_exec_stream(BufReader::new(file), &*signer, url, rate, &limits)?
+        };
+
+        return Ok(submission.map(|submission| (submission, wait)));
+    }
+
+    let (contract, contract_sha512) =
+        split_contract_pin(exec_matches.value_of("contract").unwrap())?;
+    let payload = exec_matches.value_of("payload").unwrap();
+
+    let inputs: Vec<String> = exec_matches
         .values_of("inputs")
-        .map(|values| values.map(|v| v.into()).collect())
         .ok_or_else(|| {
             CliError::User("exec action requires one or more --inputs arguments".into())
-        })?;
+        })?
+        .map(address_book::resolve)
+        .collect::<Result<_, _>>()?;
 
-    let outputs = exec_matches
+    let outputs: Vec<String> = exec_matches
         .values_of("outputs")
-        .map(|values| values.map(|v| v.into()).collect())
         .ok_or_else(|| {
             CliError::User("exec action requires one or more --outputs arguments".into())
-        })?;
+        })?
+        .map(address_book::resolve)
+        .collect::<Result<_, _>>()?;
     let (name, version) = match contract.split(':').collect::<Vec<_>>() {
         ref v if (v.len() == 1 || v.len() == 2) && v[0].is_empty() => {
             Err(CliError::User("contract name must be specified".into()))
@@ -260,33 +1153,89 @@ fn execute(exec_matches: &clap::ArgMatches) -> Result<(String, u64), CliError> {
             "malformed contract argument, may contain at most one ':'".into(),
         )),
     }?;
+    if contract_sha512.is_some() && version == "latest" {
+        return Err(CliError::User(
+            "a contract pinned to a sha512 must name its version".into(),
+        ));
+    }
+
+    let compress_threshold = match value_t!(exec_matches, "compress_threshold", usize) {
+        Ok(threshold) => Some(threshold),
+        Err(err) => match err.kind {
+            clap::ErrorKind::ArgumentNotFound => None,
+            _ => {
+                return Err(CliError::User(
+                    "Compress threshold must be an integer".into(),
+                ))
+            }
+        },
+    };
 
     let contract_payload = load_bytes_from_file(payload)?;
-    let signer = new_signer(key_name)?;
-    let batch = ExecuteContractActionBuilder::new()
+
+    let mut action = ExecuteContractActionBuilder::new()
         .with_name(name.into())
         .with_version(version.into())
-        .with_inputs(inputs)
-        .with_outputs(outputs)
-        .with_payload(contract_payload)
-        .into_payload_builder()?
+        .with_inputs(inputs.clone())
+        .with_outputs(outputs.clone())
+        .with_payload(contract_payload);
+    if let Some(contract_sha512) = contract_sha512 {
+        action = action.with_contract_sha512(contract_sha512);
+    }
+    if let Some(threshold) = compress_threshold {
+        action = action.with_compression_threshold(threshold);
+    }
+    let action = action.build()?;
+
+    // The payload is checked as it will be submitted, after any compression
+    if !exec_matches.is_present("no_preflight") {
+        match state::unreadable() {
+            Some(reason) => eprintln!("Skipping the pre-flight checks: {}", reason),
+            None => preflight::check_execute(
+                url,
+                name,
+                version,
+                &inputs,
+                &outputs,
+                action.payload().len(),
+            )?,
+        }
+    }
+
+    let signer = new_signer(key_name.as_deref(), key_algo)?;
+    let batch = SabrePayloadBuilder::new()
+        .with_action(Action::from(action))
         .into_transaction_builder()?
+        .with_nonce(trace::transaction_nonce())
         .into_batch_builder(&*signer)?
         .build(&*signer)?;
 
-    let batch_link = submit_batches(url, vec![batch])?;
+    // The Sawtooth REST API has no simulation endpoint, so the pre-flight checks and the built
+    // batch are all that can be reported
+    if exec_matches.is_present("simulate") {
+        println!(
+            "The REST API at {} does not support simulation; batch {} was checked but not \
+             submitted",
+            url,
+            batch.header_signature()
+        );
+        return Ok(None);
+    }
+
+    let submission = submit_batches(url, vec![batch])?;
 
-    Ok((batch_link, wait))
+    Ok(Some((submission, wait)))
 }
 
-fn namespace_registry(ns_matches: &clap::ArgMatches) -> Result<(String, u64), CliError> {
-    let namespace = ns_matches.value_of("namespace").unwrap();
+fn namespace_registry(
+    ns_matches: &clap::ArgMatches,
+) -> Result<Option<(Submission, u64)>, CliError> {
+    let namespace = &address_book::resolve(ns_matches.value_of("namespace").unwrap())?;
 
-    let key_name = ns_matches.value_of("key");
+    let key_name = config::signing_key(ns_matches);
+    let key_algo = ns_matches.value_of("key_algo");
 
-    let url = ns_matches
-        .value_of("url")
-        .unwrap_or(DEFAULT_REST_API_ENDPOINT);
+    let url = &config::rest_api_url(ns_matches);
 
     let wait = match value_t!(ns_matches, "wait", u64) {
         Ok(wait) => wait,
@@ -296,13 +1245,13 @@ fn namespace_registry(ns_matches: &clap::ArgMatches) -> Result<(String, u64), Cl
         },
     };
 
-    let signer = new_signer(key_name)?;
+    let signer = new_signer(key_name.as_deref(), key_algo)?;
 
     let owners = ns_matches
         .values_of("owner")
         .map(|values| values.map(|v| v.into()).collect());
 
-    let batch_link = if ns_matches.is_present("update") {
+    let submission = if ns_matches.is_present("update") {
         let owners = owners.ok_or_else(|| {
             CliError::User("update action requires one or more --owner arguments".into())
         })?;
@@ -312,6 +1261,7 @@ fn namespace_registry(ns_matches: &clap::ArgMatches) -> Result<(String, u64), Cl
             .with_owners(owners)
             .into_payload_builder()?
             .into_transaction_builder()?
+            .with_nonce(trace::transaction_nonce())
             .into_batch_builder(&*signer)?
             .build(&*signer)?;
 
@@ -327,36 +1277,51 @@ fn namespace_registry(ns_matches: &clap::ArgMatches) -> Result<(String, u64), Cl
             .with_namespace(namespace.into())
             .into_payload_builder()?
             .into_transaction_builder()?
+            .with_nonce(trace::transaction_nonce())
             .into_batch_builder(&*signer)?
             .build(&*signer)?;
 
         submit_batches(url, vec![batch])?
     } else {
-        let owners = owners.ok_or_else(|| {
+        let owners: Vec<String> = owners.ok_or_else(|| {
             CliError::User("create action requires one or more --owner arguments".into())
         })?;
 
+        if ns_matches.is_present("if_not_exists") && can_check_existence() {
+            if let Some(existing) = state::get_namespace_registry(url, namespace)? {
+                if !same_owners(existing.owners(), &owners) {
+                    return Err(CliError::User(format!(
+                        "namespace {} already exists with different owners",
+                        namespace
+                    )));
+                }
+
+                println!("Namespace {} already exists", namespace);
+                return Ok(None);
+            }
+        }
+
         let batch = CreateNamespaceRegistryActionBuilder::new()
             .with_namespace(namespace.into())
             .with_owners(owners)
             .into_payload_builder()?
             .into_transaction_builder()?
+            .with_nonce(trace::transaction_nonce())
             .into_batch_builder(&*signer)?
             .build(&*signer)?;
 
         submit_batches(url, vec![batch])?
     };
 
-    Ok((batch_link, wait))
+    Ok(Some((submission, wait)))
 }
 
-fn namespace_permission(perm_matches: &clap::ArgMatches) -> Result<(String, u64), CliError> {
-    let namespace = perm_matches.value_of("namespace").unwrap();
-    let contract = perm_matches.value_of("contract").unwrap();
-    let key_name = perm_matches.value_of("key");
-    let url = perm_matches
-        .value_of("url")
-        .unwrap_or(DEFAULT_REST_API_ENDPOINT);
+fn namespace_permission(
+    perm_matches: &clap::ArgMatches,
+) -> Result<Option<(Submission, u64)>, CliError> {
+    let key_name = config::signing_key(perm_matches);
+    let key_algo = perm_matches.value_of("key_algo");
+    let url = &config::rest_api_url(perm_matches);
 
     let wait = match value_t!(perm_matches, "wait", u64) {
         Ok(wait) => wait,
@@ -366,59 +1331,120 @@ fn namespace_permission(perm_matches: &clap::ArgMatches) -> Result<(String, u64)
         },
     };
 
-    let signer = new_signer(key_name)?;
-
-    let batch_link = if perm_matches.is_present("delete") {
-        let batch = DeleteNamespaceRegistryPermissionActionBuilder::new()
-            .with_namespace(namespace.into())
-            .into_payload_builder()?
-            .into_transaction_builder()?
-            .into_batch_builder(&*signer)?
-            .build(&*signer)?;
-
-        submit_batches(url, vec![batch])?
+    let actions = if let Some(file) = perm_matches.value_of("file") {
+        permission_file::load_permissions(file)?
+            .into_iter()
+            .map(|permission| {
+                Ok(Action::from(
+                    CreateNamespaceRegistryPermissionActionBuilder::new()
+                        .with_namespace(permission.namespace)
+                        .with_contract_name(permission.contract)
+                        .with_read(permission.read)
+                        .with_write(permission.write)
+                        .build()?,
+                ))
+            })
+            .collect::<Result<Vec<_>, CliError>>()?
     } else {
-        let read = perm_matches.is_present("read");
-        let write = perm_matches.is_present("write");
+        let namespace = address_book::resolve(perm_matches.value_of("namespace").unwrap())?;
+        let contract = perm_matches.value_of("contract").unwrap();
+
+        if perm_matches.is_present("delete") {
+            vec![Action::from(
+                DeleteNamespaceRegistryPermissionActionBuilder::new()
+                    .with_namespace(namespace)
+                    .with_contract_name(contract.into())
+                    .build()?,
+            )]
+        } else {
+            let read = perm_matches.is_present("read");
+            let write = perm_matches.is_present("write");
+
+            if !(read || write) {
+                return Err(CliError::User("no permissions provided".into()));
+            }
 
-        if !(read || write) {
-            return Err(CliError::User("no permissions provided".into()));
+            vec![Action::from(
+                CreateNamespaceRegistryPermissionActionBuilder::new()
+                    .with_namespace(namespace)
+                    .with_contract_name(contract.into())
+                    .with_read(read)
+                    .with_write(write)
+                    .build()?,
+            )]
         }
+    };
 
-        let batch = CreateNamespaceRegistryPermissionActionBuilder::new()
-            .with_namespace(namespace.into())
-            .with_contract_name(contract.into())
-            .with_read(read)
-            .with_write(write)
-            .into_payload_builder()?
-            .into_transaction_builder()?
-            .into_batch_builder(&*signer)?
-            .build(&*signer)?;
+    if perm_matches.is_present("simulate") {
+        let registries = state::get_namespace_registries(url)?;
+        let changes = permissions::simulate(&registries, &actions)
+            .map_err(|err| CliError::User(format!("The permissions would be rejected: {}", err)))?;
+        if changes.is_empty() {
+            println!("No contract would gain or lose access");
+            return Ok(None);
+        }
 
-        submit_batches(url, vec![batch])?
-    };
+        let mut table = vec![vec![
+            "NAMESPACE".to_string(),
+            "CONTRACT".to_string(),
+            "BEFORE".to_string(),
+            "AFTER".to_string(),
+        ]];
+        table.extend(changes.into_iter().map(|change| {
+            vec![
+                change.namespace,
+                change.contract_name,
+                change.before.to_string(),
+                change.after.to_string(),
+            ]
+        }));
+        print_table(table);
+        return Ok(None);
+    }
+
+    let signer = new_signer(key_name.as_deref(), key_algo)?;
+    let transactions = actions
+        .into_iter()
+        .map(|action| {
+            Ok(SabrePayloadBuilder::new()
+                .with_action(action)
+                .into_transaction_builder()?
+                .with_nonce(trace::transaction_nonce())
+                .build(&*signer)?)
+        })
+        .collect::<Result<Vec<_>, CliError>>()?;
+
+    // The permissions in a file are set in a single batch, so they are set together or not at
+    // all
+    let batches =
+        batching::build_batches(transactions, &batching::BatchLimits::default(), &*signer)?;
+    if batches.len() > 1 {
+        return Err(CliError::User(format!(
+            "\"{}\" lists more permissions than fit in one batch",
+            perm_matches.value_of("file").unwrap_or_default()
+        )));
+    }
 
-    Ok((batch_link, wait))
+    Ok(Some((submit_batches(url, batches)?, wait)))
 }
 
-fn contract_registry(cr_matches: &clap::ArgMatches) -> Result<(String, u64), CliError> {
+fn contract_registry(cr_matches: &clap::ArgMatches) -> Result<Option<(Submission, u64)>, CliError> {
     let name = cr_matches.value_of("name").unwrap();
 
-    let key_name = cr_matches.value_of("key");
+    let key_name = config::signing_key(cr_matches);
+    let key_algo = cr_matches.value_of("key_algo");
 
-    let url = cr_matches
-        .value_of("url")
-        .unwrap_or(DEFAULT_REST_API_ENDPOINT);
+    let url = &config::rest_api_url(cr_matches);
 
     let wait = value_t!(cr_matches, "wait", u64).unwrap_or(0);
 
-    let signer = new_signer(key_name)?;
+    let signer = new_signer(key_name.as_deref(), key_algo)?;
 
     let owners = cr_matches
         .values_of("owner")
         .map(|values| values.map(|v| v.into()).collect());
 
-    let batch_link = if cr_matches.is_present("update") {
+    let submission = if cr_matches.is_present("update") {
         let owners = owners.ok_or_else(|| {
             CliError::User("update action requires one or more --owner arguments".into())
         })?;
@@ -428,6 +1454,7 @@ fn contract_registry(cr_matches: &clap::ArgMatches) -> Result<(String, u64), Cli
             .with_owners(owners)
             .into_payload_builder()?
             .into_transaction_builder()?
+            .with_nonce(trace::transaction_nonce())
             .into_batch_builder(&*signer)?
             .build(&*signer)?;
 
@@ -443,48 +1470,152 @@ fn contract_registry(cr_matches: &clap::ArgMatches) -> Result<(String, u64), Cli
             .with_name(name.into())
             .into_payload_builder()?
             .into_transaction_builder()?
+            .with_nonce(trace::transaction_nonce())
             .into_batch_builder(&*signer)?
             .build(&*signer)?;
 
         submit_batches(url, vec![batch])?
     } else {
-        let owners = owners.ok_or_else(|| {
+        let owners: Vec<String> = owners.ok_or_else(|| {
             CliError::User("create action requires one or more --owner arguments".into())
         })?;
 
+        if cr_matches.is_present("if_not_exists") && can_check_existence() {
+            if let Some(existing) = state::get_contract_registry(url, name)? {
+                if !same_owners(existing.owners(), &owners) {
+                    return Err(CliError::User(format!(
+                        "contract registry '{}' already exists with different owners",
+                        name
+                    )));
+                }
+
+                println!("Contract registry {} already exists", name);
+                return Ok(None);
+            }
+        }
+
         let batch = CreateContractRegistryActionBuilder::new()
             .with_name(name.into())
             .with_owners(owners)
             .into_payload_builder()?
             .into_transaction_builder()?
+            .with_nonce(trace::transaction_nonce())
             .into_batch_builder(&*signer)?
             .build(&*signer)?;
 
         submit_batches(url, vec![batch])?
     };
-    Ok((batch_link, wait))
+    Ok(Some((submission, wait)))
+}
+
+/// Returns whether the two owner lists contain the same keys, in any order
+fn same_owners(existing: &[String], owners: &[String]) -> bool {
+    let mut existing = existing.to_vec();
+    let mut owners = owners.to_vec();
+    existing.sort();
+    owners.sort();
+    existing == owners
+}
+
+fn prune(prune_matches: &clap::ArgMatches) -> Result<Option<(Submission, u64)>, CliError> {
+    let name = prune_matches.value_of("name").unwrap();
+    let keep = value_t!(prune_matches, "keep", usize)
+        .map_err(|_| CliError::User("Keep must be an integer".into()))?;
+    // Pruning every version would leave the contract registry with none to execute
+    if keep == 0 {
+        return Err(CliError::User("Keep must be at least 1".into()));
+    }
+
+    let key_name = config::signing_key(prune_matches);
+    let key_algo = prune_matches.value_of("key_algo");
+
+    let url = &config::rest_api_url(prune_matches);
+
+    let wait = value_t!(prune_matches, "wait", u64).unwrap_or(0);
+
+    let submission = prune::do_prune(
+        name,
+        keep,
+        key_name.as_deref(),
+        key_algo,
+        url,
+        prune_matches.is_present("dry_run"),
+    )?;
+
+    Ok(submission.map(|submission| (submission, wait)))
+}
+
+fn owner(owner_matches: &clap::ArgMatches) -> Result<Option<(Submission, u64)>, CliError> {
+    match owner_matches.subcommand() {
+        (action @ "add", Some(matches)) | (action @ "remove", Some(matches)) => {
+            let key_name = config::signing_key(matches);
+            let key_algo = matches.value_of("key_algo");
+            let url = &config::rest_api_url(matches);
+            let wait = value_t!(matches, "wait", u64).unwrap_or(0);
+
+            let keys = matches
+                .values_of("owner")
+                .unwrap()
+                .map(String::from)
+                .collect::<Vec<_>>();
+            let change = if action == "add" {
+                owners::OwnerChange::Add(keys)
+            } else {
+                owners::OwnerChange::Remove(keys)
+            };
+
+            let submission = owners::do_change_owners(
+                matches.value_of("name").unwrap(),
+                &change,
+                key_name.as_deref(),
+                key_algo,
+                url,
+            )?;
+
+            Ok(submission.map(|submission| (submission, wait)))
+        }
+        ("rotate", Some(matches)) => {
+            let key_name = config::signing_key(matches);
+            let key_algo = matches.value_of("key_algo");
+            let url = &config::rest_api_url(matches);
+            let wait = value_t!(matches, "wait", u64).unwrap_or(0);
+
+            let submission = rotate::do_rotate(
+                matches.value_of("old").unwrap(),
+                matches.value_of("new").unwrap(),
+                key_name.as_deref(),
+                key_algo,
+                url,
+                matches.is_present("dry_run"),
+            )?;
+
+            Ok(submission.map(|submission| (submission, wait)))
+        }
+        _ => Err(CliError::User("Invalid Subcommand".into())),
+    }
 }
 
 fn contract(contract_matches: &clap::ArgMatches) -> Result<(), CliError> {
     match contract_matches.subcommand() {
         ("list", Some(matches)) => {
-            let url = matches.value_of("url").unwrap_or(DEFAULT_REST_API_ENDPOINT);
+            let url = &config::rest_api_url(matches);
 
             let format = matches
                 .value_of("format")
                 .expect("default not set for --format");
 
-            let registries = state::get_state_with_prefix(url, CONTRACT_REGISTRY_ADDRESS_PREFIX)?
-                .into_iter()
-                .map(|entry| {
-                    base64::decode(entry.data)
-                        .map_err(|_| CliError::User("Unable to decode state".into()))
-                        .and_then(|bytes| {
-                            ContractRegistryList::from_bytes(&bytes)
-                                .map_err(CliError::ProtoConversion)
-                        })
-                })
-                .collect::<Result<Vec<_>, _>>()?;
+            let registries =
+                state::get_state_with_prefix(url, SabreNamespace::ContractRegistry.prefix())?
+                    .into_iter()
+                    .map(|entry| {
+                        base64::decode(entry.data)
+                            .map_err(|_| CliError::User("Unable to decode state".into()))
+                            .and_then(|bytes| {
+                                ContractRegistryList::from_bytes(&bytes)
+                                    .map_err(CliError::ProtoConversion)
+                            })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
 
             let mut data = vec![
                 // Headers
@@ -520,7 +1651,7 @@ fn contract(contract_matches: &clap::ArgMatches) -> Result<(), CliError> {
             Ok(())
         }
         ("show", Some(matches)) => {
-            let url = matches.value_of("url").unwrap_or(DEFAULT_REST_API_ENDPOINT);
+            let url = &config::rest_api_url(matches);
 
             let contract = matches
                 .value_of("contract")
@@ -561,6 +1692,771 @@ fn contract(contract_matches: &clap::ArgMatches) -> Result<(), CliError> {
 
             Ok(())
         }
+        ("lint", Some(matches)) => {
+            let filename = matches.value_of("filename").unwrap();
+            let (inputs, outputs, wasm) =
+                upload::load_contract_namespaces(filename, matches.value_of("wasm"))?;
+
+            let findings = lint::lint(&inputs, &outputs, &wasm)
+                .map_err(|msg| CliError::User(format!("Unable to lint contract: {}", msg)))?;
+            if findings.is_empty() {
+                println!("No problems found");
+                return Ok(());
+            }
+
+            for finding in &findings {
+                println!("warning: {}", finding);
+            }
+
+            Err(CliError::User(format!(
+                "{} likely problems with the contract",
+                findings.len()
+            )))
+        }
+        #[cfg(feature = "contract-test")]
+        ("test", Some(matches)) => contract_test(matches),
+        _ => Err(CliError::User("Invalid Subcommand".into())),
+    }
+}
+
+#[cfg(feature = "contract-test")]
+fn contract_test(test_matches: &clap::ArgMatches) -> Result<(), CliError> {
+    let filename = test_matches.value_of("filename").unwrap();
+    let (definition, wasm) = upload::load_contract(filename, test_matches.value_of("wasm"))?;
+    let payloads = test_matches
+        .values_of("payload")
+        .map(|paths| paths.collect::<Vec<_>>())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|path| {
+            Ok(contract_test::Payload {
+                name: path.into(),
+                bytes: load_bytes_from_file(path)?,
+            })
+        })
+        .collect::<Result<Vec<_>, CliError>>()?;
+    let max_memory_pages = value_t!(test_matches, "max_memory_pages", u64)
+        .map_err(|_| CliError::User("--max-memory-pages must be a number".into()))?;
+
+    let probes = contract_test::run_probes(&definition, &wasm, &payloads, max_memory_pages);
+    let failures = probes
+        .iter()
+        .filter(|probe| matches!(probe.outcome, contract_test::Outcome::Fail(_)))
+        .count();
+
+    match test_matches
+        .value_of("format")
+        .expect("default not set for --format")
+    {
+        "json" => {
+            let report = serde_json::json!({
+                "contract": format!("{}:{}", definition.name, definition.version),
+                "passed": failures == 0,
+                "probes": probes
+                    .iter()
+                    .map(|probe| {
+                        let (status, detail) = match &probe.outcome {
+                            contract_test::Outcome::Pass(detail) => ("pass", detail),
+                            contract_test::Outcome::Fail(detail) => ("fail", detail),
+                        };
+                        serde_json::json!({
+                            "name": probe.name,
+                            "status": status,
+                            "detail": detail,
+                        })
+                    })
+                    .collect::<Vec<_>>(),
+            });
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&report).map_err(|err| CliError::User(format!(
+                    "Unable to serialize report: {}",
+                    err
+                )))?
+            );
+        }
+        _ => {
+            for probe in &probes {
+                match &probe.outcome {
+                    contract_test::Outcome::Pass(detail) => {
+                        println!("[PASS] {}: {}", probe.name, detail)
+                    }
+                    contract_test::Outcome::Fail(detail) => {
+                        println!("[FAIL] {}: {}", probe.name, detail)
+                    }
+                }
+            }
+        }
+    }
+
+    if failures > 0 {
+        return Err(CliError::User(format!(
+            "{} of {} probes failed",
+            failures,
+            probes.len()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Returns the name of the command being run, as used by policy files: the subcommand followed by
+/// its action, if it has one, e.g. "ns delete".
+fn command_name(subcommand: &str, step_action: Option<&str>, matches: &clap::ArgMatches) -> String {
+    let action = match subcommand {
+        "ns" | "cr" => ["create", "update", "delete"]
+            .iter()
+            .find(|action| matches.is_present(action))
+            .copied()
+            .or(Some("create")),
+        "perm" if matches.is_present("delete") => Some("delete"),
+        "perm" => Some("set"),
+        "owner" => step_action,
+        _ => None,
+    };
+
+    match action {
+        Some(action) => format!("{} {}", subcommand, action),
+        None => subcommand.to_string(),
+    }
+}
+
+fn state(state_matches: &clap::ArgMatches) -> Result<(), CliError> {
+    match state_matches.subcommand() {
+        ("list", Some(matches)) => {
+            let url = &config::rest_api_url(matches);
+
+            let format = matches
+                .value_of("format")
+                .expect("default not set for --format");
+
+            let prefix = &address_book::resolve(matches.value_of("prefix").unwrap())?;
+
+            let namespace_registries = state::get_namespace_registries(url)?;
+
+            let mut data = vec![
+                // Headers
+                vec![
+                    "ADDRESS".to_string(),
+                    "OWNER".to_string(),
+                    "CONTRACTS".to_string(),
+                ],
+            ];
+            for entry in state::get_state_with_prefix(url, prefix)? {
+                let (owner, contracts) = match (
+                    state::address_kind(&entry.address),
+                    state::owning_namespace(&namespace_registries, &entry.address),
+                ) {
+                    (Some(kind), _) => (kind.to_string(), String::new()),
+                    (None, Some(registry)) => (
+                        format!("namespace {}", registry.namespace()),
+                        registry
+                            .permissions()
+                            .iter()
+                            .map(|permission| {
+                                format!(
+                                    "{} ({}{})",
+                                    permission.contract_name(),
+                                    if permission.read() { "r" } else { "-" },
+                                    if permission.write() { "w" } else { "-" }
+                                )
+                            })
+                            .collect::<Vec<_>>()
+                            .join(" "),
+                    ),
+                    (None, None) => ("unknown".to_string(), String::new()),
+                };
+
+                data.push(vec![entry.address, owner, contracts]);
+            }
+
+            if format == "csv" {
+                for row in data {
+                    println!("{}", row.join(","))
+                }
+            } else {
+                print_table(data);
+            }
+
+            Ok(())
+        }
+        ("show", Some(matches)) => {
+            let url = &config::rest_api_url(matches);
+            let address = &address_book::resolve(matches.value_of("address").unwrap())?;
+            let raw = matches.is_present("raw");
+            let offset = value_t!(matches, "offset", u64)
+                .map_err(|_| CliError::User("--offset must be an integer".into()))?;
+            let length = match matches.value_of("length") {
+                Some(length) => Some(
+                    length
+                        .parse::<u64>()
+                        .map_err(|_| CliError::User("--length must be an integer".into()))?,
+                ),
+                None => None,
+            };
+
+            let entry = state::get_state_with_prefix(url, address)?
+                .into_iter()
+                .find(|entry| &entry.address == address)
+                .ok_or_else(|| CliError::User(format!("{} is not set", address)))?;
+
+            match matches.value_of("output") {
+                Some(path) => {
+                    let mut file = BufWriter::new(File::create(path).map_err(|e| {
+                        CliError::User(format!("Could not create file \"{}\": {}", path, e))
+                    })?);
+                    let written =
+                        show::write_decoded(&entry.data, offset, length, !raw, &mut file)?;
+                    println!("Wrote {} bytes of {} to {}", written, address, path);
+                }
+                None => {
+                    let stdout = io::stdout();
+                    let mut stdout = stdout.lock();
+                    show::write_decoded(&entry.data, offset, length, !raw, &mut stdout)?;
+                    if !raw {
+                        writeln!(stdout)?;
+                    }
+                }
+            }
+
+            Ok(())
+        }
+        ("watch", Some(matches)) => {
+            let url = &config::rest_api_url(matches);
+            let address = &address_book::resolve(matches.value_of("address").unwrap())?;
+            let timeout = value_t!(matches, "timeout", u64)
+                .map_err(|_| CliError::User("--timeout must be an integer".into()))?;
+            let interval = value_t!(matches, "interval", u64)
+                .map_err(|_| CliError::User("--interval must be an integer".into()))?;
+
+            let current = watch::get_state_entry(url, address)?;
+            match watch::wait_for_state_change(
+                url,
+                address,
+                |data| data != current.as_deref(),
+                Duration::from_secs(timeout),
+                Duration::from_millis(interval),
+            )? {
+                Some(data) => println!("{}", to_hex(&data)),
+                None => println!("{} was deleted", address),
+            }
+
+            Ok(())
+        }
+        _ => Err(CliError::User("Invalid Subcommand".into())),
+    }
+}
+
+fn trace(trace_matches: &clap::ArgMatches) -> Result<(), CliError> {
+    match trace_matches.subcommand() {
+        ("show", Some(matches)) => {
+            let path = matches.value_of("file").unwrap();
+            let file = File::open(path).map_err(|e| {
+                CliError::User(format!(
+                    "Could not open host call trace \"{}\": {}",
+                    path, e
+                ))
+            })?;
+            let calls = host_calls::read_host_calls(BufReader::new(file), matches.value_of("txn"))?;
+
+            // Calls are grouped by transaction, as the ids are too long for a column
+            let mut transactions: Vec<(&str, Vec<&host_calls::HostCall>)> = Vec::new();
+            for call in &calls {
+                match transactions.last_mut() {
+                    Some((transaction_id, calls)) if *transaction_id == call.transaction_id => {
+                        calls.push(call)
+                    }
+                    _ => transactions.push((call.transaction_id.as_str(), vec![call])),
+                }
+            }
+
+            if transactions.is_empty() {
+                println!("No host calls found");
+            }
+            for (i, (transaction_id, calls)) in transactions.into_iter().enumerate() {
+                if i > 0 {
+                    println!();
+                }
+                println!("Transaction {}", transaction_id);
+
+                let mut data = vec![
+                    // Headers
+                    vec![
+                        "FUNCTION".to_string(),
+                        "ADDRESS".to_string(),
+                        "BYTES".to_string(),
+                        "RESULT".to_string(),
+                    ],
+                ];
+                for call in calls {
+                    data.push(vec![
+                        call.function.clone(),
+                        call.address.clone().unwrap_or_else(|| "-".to_string()),
+                        call.bytes.to_string(),
+                        call.result.clone(),
+                    ]);
+                }
+                print_table(data);
+            }
+
+            Ok(())
+        }
+        _ => Err(CliError::User("Invalid Subcommand".into())),
+    }
+}
+
+fn addr(addr_matches: &clap::ArgMatches) -> Result<(), CliError> {
+    let mut address_book = address_book::AddressBook::load()?;
+
+    match addr_matches.subcommand() {
+        ("add", Some(matches)) => {
+            address_book.add(
+                matches.value_of("name").unwrap(),
+                matches.value_of("address").unwrap(),
+            )?;
+            address_book.save()
+        }
+        ("list", Some(matches)) => {
+            let format = matches
+                .value_of("format")
+                .expect("default not set for --format");
+
+            let mut data = vec![
+                // Headers
+                vec!["NAME".to_string(), "ADDRESS".to_string()],
+            ];
+            for (name, address) in address_book.entries() {
+                data.push(vec![name.clone(), address.clone()]);
+            }
+
+            if format == "csv" {
+                for row in data {
+                    println!("{}", row.join(","))
+                }
+            } else {
+                print_table(data);
+            }
+
+            Ok(())
+        }
+        ("sync", Some(matches)) => {
+            let url = &config::rest_api_url(matches);
+
+            for name in address_book.populate(&state::get_namespace_registries(url)?) {
+                println!("Added {}", name);
+            }
+            address_book.save()
+        }
+        _ => Err(CliError::User("Invalid Subcommand".into())),
+    }
+}
+
+fn diff(diff_matches: &clap::ArgMatches) -> Result<(), CliError> {
+    let left_url = diff_matches.value_of("left").unwrap();
+    let right_url = diff_matches.value_of("right").unwrap();
+    let prefix = &address_book::resolve(
+        diff_matches
+            .value_of("prefix")
+            .expect("default not set for --prefix"),
+    )?;
+    let format = diff_matches
+        .value_of("format")
+        .expect("default not set for --format");
+
+    let differences = diff::diff_state(
+        state::get_state_with_prefix(left_url, prefix)?,
+        state::get_state_with_prefix(right_url, prefix)?,
+    );
+
+    let mut data = vec![
+        // Headers
+        vec![
+            "CHANGE".to_string(),
+            "ADDRESS".to_string(),
+            "LEFT".to_string(),
+            "RIGHT".to_string(),
+        ],
+    ];
+    for difference in differences {
+        let row = match difference {
+            diff::StateDifference::Added(right) => vec![
+                "added".to_string(),
+                right.address.clone(),
+                String::new(),
+                diff::summarize(&right),
+            ],
+            diff::StateDifference::Removed(left) => vec![
+                "removed".to_string(),
+                left.address.clone(),
+                diff::summarize(&left),
+                String::new(),
+            ],
+            diff::StateDifference::Changed { left, right } => vec![
+                "changed".to_string(),
+                left.address.clone(),
+                diff::summarize(&left),
+                diff::summarize(&right),
+            ],
+        };
+        data.push(row);
+    }
+
+    if format == "csv" {
+        for row in data {
+            println!("{}", row.join(","))
+        }
+    } else {
+        print_table(data);
+    }
+
+    Ok(())
+}
+
+fn doctor(doctor_matches: &clap::ArgMatches) -> Result<(), CliError> {
+    let url = &config::rest_api_url(doctor_matches);
+
+    let checks = doctor::run_checks(
+        url,
+        config::signing_key(doctor_matches).as_deref(),
+        doctor_matches.value_of("key_algo"),
+    );
+
+    let mut failures = 0;
+    for check in &checks {
+        match &check.outcome {
+            doctor::Outcome::Pass(detail) => println!("[PASS] {}: {}", check.name, detail),
+            doctor::Outcome::Fail { problem, hint } => {
+                failures += 1;
+                println!("[FAIL] {}: {}", check.name, problem);
+                println!("       {}", hint);
+            }
+        }
+    }
+
+    if failures > 0 {
+        return Err(CliError::User(format!(
+            "{} of {} checks failed",
+            failures,
+            checks.len()
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "gateway")]
+fn gateway(gateway_matches: &clap::ArgMatches) -> Result<(), CliError> {
+    let signer = new_signer(
+        config::signing_key(gateway_matches).as_deref(),
+        gateway_matches.value_of("key_algo"),
+    )?;
+    let token_file = gateway_matches.value_of("token_file");
+    let authenticator: Box<dyn gateway::Authenticator> = match token_file {
+        Some(path) => Box::new(gateway::BearerTokens::from_file(path)?),
+        None if gateway_matches.is_present("allow_all") => {
+            eprintln!("Warning: serving every client, without a bearer token");
+            Box::new(gateway::AllowAll)
+        }
+        None => {
+            return Err(CliError::User(
+                "the gateway requires --token-file, or --allow-all to serve every client".into(),
+            ))
+        }
+    };
+
+    gateway::Gateway::new(config::rest_api_url(gateway_matches), signer, authenticator)
+        .serve(gateway_matches.value_of("bind").unwrap())
+}
+
+fn audit(audit_matches: &clap::ArgMatches) -> Result<(), CliError> {
+    if let Some(export_matches) = audit_matches.subcommand_matches("export") {
+        return audit_export(export_matches);
+    }
+
+    let url = &config::rest_api_url(audit_matches);
+    let format = audit_matches
+        .value_of("format")
+        .expect("default not set for --format");
+    let prefixes = audit_matches
+        .values_of("namespace")
+        .unwrap()
+        .map(address_book::resolve)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let administrators = state::get_administrators(url)?
+        .map(|administrators| {
+            administrators
+                .split(',')
+                .map(|key| key.trim().to_string())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let report = audit::find_write_access(
+        &prefixes,
+        &state::get_namespace_registries(url)?,
+        &state::get_contract_registries(url)?,
+        &administrators,
+    );
+
+    match format {
+        "json" => println!(
+            "{}",
+            serde_json::to_string_pretty(&report)
+                .map_err(|err| CliError::User(format!("Unable to serialize report: {}", err)))?
+        ),
+        _ => {
+            let mut data = vec![vec![
+                "NAMESPACE".to_string(),
+                "CONTRACT".to_string(),
+                "VERSIONS".to_string(),
+                "OWNERS".to_string(),
+                "NON-ADMIN OWNERS".to_string(),
+            ]];
+            for access in report {
+                data.push(vec![
+                    access.namespace,
+                    access.contract,
+                    access.versions.join(" "),
+                    access.owners.join(" "),
+                    access.non_admin_owners.to_string(),
+                ]);
+            }
+
+            if format == "csv" {
+                for row in data {
+                    println!("{}", row.join(","))
+                }
+            } else {
+                print_table(data);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn version(version_matches: &clap::ArgMatches) -> Result<(), CliError> {
+    println!("sabre CLI {}", VERSION);
+    println!("Sabre protocol version: {}", SABRE_PROTOCOL_VERSION);
+
+    if !version_matches.is_present("remote") {
+        return Ok(());
+    }
+
+    let url = &config::rest_api_url(version_matches);
+    match state::get_protocol_versions(url)? {
+        Some(versions) => {
+            println!("Network protocol versions: {}", versions.join(", "));
+            if !versions
+                .iter()
+                .any(|version| version == SABRE_PROTOCOL_VERSION)
+            {
+                eprintln!(
+                    "Warning: the network does not declare support for protocol version {}, \
+                     which this CLI submits",
+                    SABRE_PROTOCOL_VERSION
+                );
+            }
+        }
+        None => println!(
+            "Network protocol versions: unknown ({} is not set)",
+            PROTOCOL_VERSIONS_SETTING_KEY
+        ),
+    }
+
+    Ok(())
+}
+
+fn audit_export(export_matches: &clap::ArgMatches) -> Result<(), CliError> {
+    let url = &config::rest_api_url(export_matches);
+    let from_block = value_t!(export_matches, "from_block", u64)
+        .map_err(|_| CliError::User("From block must be an integer".into()))?;
+
+    for transaction in blocks::get_transactions_since(url, SABRE_FAMILY_NAME, from_block)? {
+        println!(
+            "{}",
+            serde_json::to_string(&audit::audit_record(&transaction)?)
+                .map_err(|err| CliError::User(format!("Unable to serialize record: {}", err)))?
+        );
+    }
+
+    Ok(())
+}
+
+fn gc(gc_matches: &clap::ArgMatches) -> Result<(), CliError> {
+    // The Sabre transaction processor has no action which removes residue, so it can only be
+    // reported
+    if !gc_matches.is_present("dry_run") {
+        return Err(CliError::User(
+            "the Sabre transaction processor cannot remove residue; run with --dry-run to \
+             report it"
+                .into(),
+        ));
+    }
+
+    let url = &config::rest_api_url(gc_matches);
+
+    let mut entries = Vec::new();
+    for namespace in &[
+        SabreNamespace::NamespaceRegistry,
+        SabreNamespace::ContractRegistry,
+        SabreNamespace::Contract,
+    ] {
+        entries.extend(state::get_state_with_prefix(url, namespace.prefix())?);
+    }
+    let residue = gc::find_residue(&entries)?;
+
+    if residue.is_empty() {
+        println!("No residue found");
+    }
+    for residue in residue {
+        println!("{}", residue);
+    }
+
+    Ok(())
+}
+
+fn protos(protos_matches: &clap::ArgMatches) -> Result<(), CliError> {
+    match protos_matches.subcommand() {
+        ("export", Some(matches)) => {
+            let dir = Path::new(matches.value_of("dir").unwrap());
+
+            std::fs::create_dir_all(dir).map_err(|e| {
+                CliError::User(format!(
+                    "Failed to create directory \"{}\": {}",
+                    dir.display(),
+                    e
+                ))
+            })?;
+
+            for (name, contents) in schemas::PROTO_FILES {
+                let path = dir.join(name);
+                std::fs::write(&path, contents).map_err(|e| {
+                    CliError::User(format!(
+                        "Failed to write file \"{}\": {}",
+                        path.display(),
+                        e
+                    ))
+                })?;
+                println!("Wrote {}", path.display());
+            }
+
+            Ok(())
+        }
+        _ => Err(CliError::User("Invalid Subcommand".into())),
+    }
+}
+
+fn config(config_matches: &clap::ArgMatches) -> Result<(), CliError> {
+    match config_matches.subcommand() {
+        ("show", Some(matches)) => {
+            let settings = config::resolve_all(matches);
+
+            let mut data = vec![vec![
+                "SETTING".to_string(),
+                "VALUE".to_string(),
+                "SOURCE".to_string(),
+            ]];
+            for setting in settings.iter().filter(|setting| {
+                matches.is_present("resolved") || setting.source != config::Source::Default
+            }) {
+                data.push(vec![
+                    setting.name.to_string(),
+                    setting.display_value().to_string(),
+                    setting.source.to_string(),
+                ]);
+            }
+            print_table(data);
+
+            for setting in &settings {
+                for (source, value) in &setting.overridden {
+                    println!(
+                        "Warning: {} from {} ({}) is overridden by {}",
+                        setting.name,
+                        source,
+                        setting.display(value),
+                        setting.source
+                    );
+                }
+            }
+
+            Ok(())
+        }
+        ("doctor", Some(matches)) => {
+            let conflicts = config::resolve_all(matches)
+                .into_iter()
+                .filter(|setting| !setting.overridden.is_empty())
+                .collect::<Vec<_>>();
+
+            if conflicts.is_empty() {
+                println!("No setting is given by more than one source");
+                return Ok(());
+            }
+
+            for setting in &conflicts {
+                println!(
+                    "{}: using {} from {}",
+                    setting.name,
+                    setting.display_value(),
+                    setting.source
+                );
+                for (source, value) in &setting.overridden {
+                    println!("    ignoring {} from {}", setting.display(value), source);
+                }
+            }
+
+            Err(CliError::User(format!(
+                "{} settings are given by more than one source",
+                conflicts.len()
+            )))
+        }
+        _ => Err(CliError::User("Invalid Subcommand".into())),
+    }
+}
+
+fn dev(dev_matches: &clap::ArgMatches) -> Result<(), CliError> {
+    match dev_matches.subcommand() {
+        ("up", Some(matches)) => {
+            let name = matches.value_of("name").unwrap_or("default");
+            let port = matches
+                .value_of("port")
+                .unwrap_or("9708")
+                .parse::<u16>()
+                .map_err(|_| CliError::User("Port must be a number from 0 to 65535".into()))?;
+            let timeout = matches
+                .value_of("timeout")
+                .unwrap_or("120")
+                .parse::<u64>()
+                .map_err(|_| CliError::User("Timeout must be a number of seconds".into()))?;
+            let admin_key = new_signer(
+                config::signing_key(matches).as_deref(),
+                matches.value_of("key_algo"),
+            )?
+            .public_key()
+            .map_err(|err| CliError::Signing(err.to_string()))?
+            .as_hex();
+
+            let url = dev::DevNetwork::new(name)?.up(
+                &admin_key,
+                port,
+                matches
+                    .value_of("tp_image")
+                    .unwrap_or(dev::DEFAULT_TP_IMAGE),
+                Duration::from_secs(timeout),
+            )?;
+            println!("Network '{}' is ready at {}", name, url);
+            println!("Administrator: {}", admin_key);
+
+            Ok(())
+        }
+        ("down", Some(matches)) => {
+            let name = matches.value_of("name").unwrap_or("default");
+            dev::DevNetwork::new(name)?.down()?;
+            println!("Network '{}' stopped", name);
+
+            Ok(())
+        }
         _ => Err(CliError::User("Invalid Subcommand".into())),
     }
 }
@@ -607,6 +2503,23 @@ fn parse_name_version(name_version_string: &str) -> Option<(&str, &str)> {
     }
 }
 
+/// Splits a contract argument of the form `name:version@sha512` into the contract and the sha512
+/// it is pinned to, if it is pinned.
+fn split_contract_pin(contract: &str) -> Result<(&str, Option<String>), CliError> {
+    match contract.split_once('@') {
+        None => Ok((contract, None)),
+        Some((contract, sha512))
+            if sha512.len() == 128 && sha512.chars().all(|c| c.is_ascii_hexdigit()) =>
+        {
+            Ok((contract, Some(sha512.to_ascii_lowercase())))
+        }
+        Some((_, sha512)) => Err(CliError::User(format!(
+            "a pinned sha512 must be 128 hex characters: {}",
+            sha512
+        ))),
+    }
+}
+
 fn to_hex(bytes: &[u8]) -> String {
     bytes
         .iter()
@@ -637,8 +2550,537 @@ fn load_bytes_from_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, CliError> {
 }
 
 fn main() {
-    if let Err(e) = run() {
+    if let Err(e) = run(std::env::args_os()) {
         println!("{}", e);
         std::process::exit(1);
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    use std::io::BufRead;
+    use std::net::TcpListener;
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+
+    use cylinder::{secp256k1::Secp256k1Context, Context};
+    use sabre_sdk::protocol::payload::{Action, CreateContractActionBuilder, SabrePayload};
+    use sabre_sdk::protocol::{
+        compute_contract_registry_address, compute_namespace_registry_address,
+        ADMINISTRATORS_SETTING_ADDRESS,
+    };
+    use sawtooth::protos::FromBytes as _;
+    use sawtooth::transact::protocol::batch::Batch;
+
+    use super::*;
+
+    /// A REST API which records the body of every batch submission
+    struct MockRestApi {
+        url: String,
+        bodies: Arc<Mutex<Vec<Vec<u8>>>>,
+    }
+
+    impl MockRestApi {
+        fn start() -> MockRestApi {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let bodies = Arc::new(Mutex::new(Vec::new()));
+
+            let thread_bodies = bodies.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let mut stream = stream.unwrap();
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                    let mut content_length = 0;
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        if line.trim().is_empty() {
+                            break;
+                        }
+                        if let Some(value) =
+                            line.to_ascii_lowercase().strip_prefix("content-length:")
+                        {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                    let mut body = vec![0; content_length];
+                    reader.read_exact(&mut body).unwrap();
+                    thread_bodies.lock().unwrap().push(body);
+
+                    let response = "{\"link\":\"http://localhost/batch_statuses?id=test\"}";
+                    write!(
+                        stream,
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        response.len(),
+                        response
+                    )
+                    .unwrap();
+                }
+            });
+
+            MockRestApi { url, bodies }
+        }
+
+        /// Returns the action, input addresses and output addresses of every transaction
+        /// submitted so far, in order
+        fn submitted(&self) -> Vec<(Action, Vec<String>, Vec<String>)> {
+            self.bodies
+                .lock()
+                .unwrap()
+                .iter()
+                .flat_map(|body| Vec::<Batch>::from_bytes(body).unwrap())
+                .flat_map(|batch| batch.transactions().to_vec())
+                .map(|transaction| {
+                    let pair = transaction.into_pair().unwrap();
+                    let payload = SabrePayload::from_bytes(pair.transaction().payload()).unwrap();
+                    (
+                        payload.action().clone(),
+                        pair.header().inputs().iter().map(|a| to_hex(a)).collect(),
+                        pair.header().outputs().iter().map(|a| to_hex(a)).collect(),
+                    )
+                })
+                .collect()
+        }
+    }
+
+    /// Writes a new signing key to a file named for the test, returning its path
+    fn key_file(test_name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("sabre_{}.priv", test_name));
+        let key = Secp256k1Context::new().new_random_private_key();
+        std::fs::write(&path, key.as_hex()).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    fn namespace_addresses(namespace: &str) -> Vec<String> {
+        vec![
+            to_hex(&compute_namespace_registry_address(namespace).unwrap()),
+            ADMINISTRATORS_SETTING_ADDRESS.to_string(),
+        ]
+    }
+
+    fn contract_registry_addresses(name: &str) -> Vec<String> {
+        vec![
+            to_hex(&compute_contract_registry_address(name).unwrap()),
+            ADMINISTRATORS_SETTING_ADDRESS.to_string(),
+        ]
+    }
+
+    fn contract_addresses(name: &str, version: &str) -> Vec<String> {
+        vec![
+            to_hex(&compute_contract_registry_address(name).unwrap()),
+            to_hex(&compute_contract_address(name, version).unwrap()),
+        ]
+    }
+
+    #[test]
+    // Asserts that ns create, update and delete submit the matching namespace registry actions
+    fn test_cli_ns() {
+        let api = MockRestApi::start();
+        let key = key_file("test_cli_ns");
+
+        run(&[
+            "sabre", "ns", "--create", "1cf126", "-O", "a", "-O", "b", "-k", &key, "-U", &api.url,
+        ])
+        .unwrap();
+        run(&[
+            "sabre", "ns", "--update", "1cf126", "-O", "c", "-k", &key, "-U", &api.url,
+        ])
+        .unwrap();
+        run(&[
+            "sabre", "ns", "--delete", "1cf126", "-k", &key, "-U", &api.url,
+        ])
+        .unwrap();
+
+        let expected = vec![
+            CreateNamespaceRegistryActionBuilder::new()
+                .with_namespace("1cf126".into())
+                .with_owners(vec!["a".into(), "b".into()])
+                .into_payload_builder()
+                .unwrap(),
+            UpdateNamespaceRegistryOwnersActionBuilder::new()
+                .with_namespace("1cf126".into())
+                .with_owners(vec!["c".into()])
+                .into_payload_builder()
+                .unwrap(),
+            DeleteNamespaceRegistryActionBuilder::new()
+                .with_namespace("1cf126".into())
+                .into_payload_builder()
+                .unwrap(),
+        ]
+        .into_iter()
+        .map(|payload| {
+            (
+                payload.build().unwrap().action().clone(),
+                namespace_addresses("1cf126"),
+                namespace_addresses("1cf126"),
+            )
+        })
+        .collect::<Vec<_>>();
+
+        assert_eq!(api.submitted(), expected);
+        std::fs::remove_file(key).unwrap();
+    }
+
+    #[test]
+    // Asserts that perm sets and deletes the contract's permission on the namespace
+    fn test_cli_perm() {
+        let api = MockRestApi::start();
+        let key = key_file("test_cli_perm");
+
+        run(&[
+            "sabre",
+            "perm",
+            "1cf126",
+            "intkey_multiply",
+            "-r",
+            "-w",
+            "-k",
+            &key,
+            "-U",
+            &api.url,
+        ])
+        .unwrap();
+        run(&[
+            "sabre",
+            "perm",
+            "-d",
+            "1cf126",
+            "intkey_multiply",
+            "-k",
+            &key,
+            "-U",
+            &api.url,
+        ])
+        .unwrap();
+        assert!(run(&[
+            "sabre",
+            "perm",
+            "1cf126",
+            "intkey_multiply",
+            "-k",
+            &key,
+            "-U",
+            &api.url
+        ])
+        .is_err());
+
+        let expected = vec![
+            CreateNamespaceRegistryPermissionActionBuilder::new()
+                .with_namespace("1cf126".into())
+                .with_contract_name("intkey_multiply".into())
+                .with_read(true)
+                .with_write(true)
+                .into_payload_builder()
+                .unwrap(),
+            DeleteNamespaceRegistryPermissionActionBuilder::new()
+                .with_namespace("1cf126".into())
+                .with_contract_name("intkey_multiply".into())
+                .into_payload_builder()
+                .unwrap(),
+        ]
+        .into_iter()
+        .map(|payload| {
+            (
+                payload.build().unwrap().action().clone(),
+                namespace_addresses("1cf126"),
+                namespace_addresses("1cf126"),
+            )
+        })
+        .collect::<Vec<_>>();
+
+        assert_eq!(api.submitted(), expected);
+        std::fs::remove_file(key).unwrap();
+    }
+
+    #[test]
+    // Asserts that cr create, update and delete submit the matching contract registry actions
+    fn test_cli_cr() {
+        let api = MockRestApi::start();
+        let key = key_file("test_cli_cr");
+
+        run(&[
+            "sabre",
+            "cr",
+            "--create",
+            "intkey_multiply",
+            "-O",
+            "a",
+            "-k",
+            &key,
+            "-U",
+            &api.url,
+        ])
+        .unwrap();
+        run(&[
+            "sabre",
+            "cr",
+            "--update",
+            "intkey_multiply",
+            "-O",
+            "b",
+            "-k",
+            &key,
+            "-U",
+            &api.url,
+        ])
+        .unwrap();
+        run(&[
+            "sabre",
+            "cr",
+            "--delete",
+            "intkey_multiply",
+            "-k",
+            &key,
+            "-U",
+            &api.url,
+        ])
+        .unwrap();
+
+        let expected = vec![
+            CreateContractRegistryActionBuilder::new()
+                .with_name("intkey_multiply".into())
+                .with_owners(vec!["a".into()])
+                .into_payload_builder()
+                .unwrap(),
+            UpdateContractRegistryOwnersActionBuilder::new()
+                .with_name("intkey_multiply".into())
+                .with_owners(vec!["b".into()])
+                .into_payload_builder()
+                .unwrap(),
+            DeleteContractRegistryActionBuilder::new()
+                .with_name("intkey_multiply".into())
+                .into_payload_builder()
+                .unwrap(),
+        ]
+        .into_iter()
+        .map(|payload| {
+            (
+                payload.build().unwrap().action().clone(),
+                contract_registry_addresses("intkey_multiply"),
+                contract_registry_addresses("intkey_multiply"),
+            )
+        })
+        .collect::<Vec<_>>();
+
+        assert_eq!(api.submitted(), expected);
+        std::fs::remove_file(key).unwrap();
+    }
+
+    #[test]
+    // Asserts that upload submits the contract named by the definition file, loaded relative to it
+    fn test_cli_upload() {
+        let api = MockRestApi::start();
+        let key = key_file("test_cli_upload");
+
+        let dir = std::env::temp_dir().join("sabre_test_cli_upload");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("intkey_multiply.wasm"), b"\0asm").unwrap();
+        let definition: PathBuf = dir.join("intkey_multiply.yaml");
+        std::fs::write(
+            &definition,
+            "name: intkey_multiply\nversion: '1.0'\nwasm: intkey_multiply.wasm\n\
+             inputs:\n  - '1cf126'\noutputs:\n  - '1cf126'\n",
+        )
+        .unwrap();
+
+        run(&[
+            "sabre",
+            "upload",
+            "-f",
+            definition.to_str().unwrap(),
+            "-k",
+            &key,
+            "--url",
+            &api.url,
+        ])
+        .unwrap();
+
+        let expected = CreateContractActionBuilder::new()
+            .with_name("intkey_multiply".into())
+            .with_version("1.0".into())
+            .with_inputs(vec!["1cf126".into()])
+            .with_outputs(vec!["1cf126".into()])
+            .with_contract(b"\0asm".to_vec())
+            .into_payload_builder()
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            api.submitted(),
+            vec![(
+                expected.action().clone(),
+                contract_addresses("intkey_multiply", "1.0"),
+                contract_addresses("intkey_multiply", "1.0"),
+            )]
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+        std::fs::remove_file(key).unwrap();
+    }
+
+    #[test]
+    // Asserts that upload --archive submits every contract in the archive, and that an archive
+    // containing an invalid contract submits nothing
+    fn test_cli_upload_archive() {
+        let api = MockRestApi::start();
+        let key = key_file("test_cli_upload_archive");
+
+        let write_archive = |path: &Path, contracts: &[(&str, &[u8])]| {
+            let file = File::create(path).unwrap();
+            let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+                file,
+                flate2::Compression::default(),
+            ));
+            for (name, wasm) in contracts {
+                let definition = format!(
+                    "name: {}\nversion: '1.0'\nwasm: {}.wasm\ninputs:\n  - '1cf126'\n\
+                     outputs:\n  - '1cf126'\n",
+                    name, name
+                );
+                for (file_name, contents) in [
+                    (format!("{}/{}.yaml", name, name), definition.as_bytes()),
+                    (format!("{}/{}.wasm", name, name), *wasm),
+                ]
+                .iter()
+                {
+                    let mut header = tar::Header::new_gnu();
+                    header.set_size(contents.len() as u64);
+                    header.set_mode(0o644);
+                    header.set_cksum();
+                    builder
+                        .append_data(&mut header, file_name, *contents)
+                        .unwrap();
+                }
+            }
+            builder.into_inner().unwrap().finish().unwrap();
+        };
+
+        let wasm: &[u8] = b"\0asm\x01\0\0\0";
+        let archive = std::env::temp_dir().join("sabre_test_cli_upload_archive.tar.gz");
+        write_archive(&archive, &[("intkey_multiply", wasm), ("intkey_add", wasm)]);
+        run(&[
+            "sabre",
+            "upload",
+            "-a",
+            archive.to_str().unwrap(),
+            "-k",
+            &key,
+            "--url",
+            &api.url,
+        ])
+        .unwrap();
+
+        let names = api
+            .submitted()
+            .into_iter()
+            .map(|(action, inputs, _)| match action {
+                Action::CreateContract(action) => {
+                    assert_eq!(inputs, contract_addresses(action.name(), "1.0"));
+                    action.name().to_string()
+                }
+                action => panic!("unexpected action: {:?}", action),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["intkey_add", "intkey_multiply"]);
+
+        write_archive(
+            &archive,
+            &[("intkey_multiply", wasm), ("intkey_add", &b"not wasm"[..])],
+        );
+        assert!(run(&[
+            "sabre",
+            "upload",
+            "-a",
+            archive.to_str().unwrap(),
+            "-k",
+            &key,
+            "--url",
+            &api.url
+        ])
+        .is_err());
+        assert_eq!(api.submitted().len(), 2);
+
+        std::fs::remove_file(archive).unwrap();
+        std::fs::remove_file(key).unwrap();
+    }
+
+    #[test]
+    // Asserts that exec submits the payload file along with the namespace registries of its
+    // inputs and outputs
+    fn test_cli_exec() {
+        let api = MockRestApi::start();
+        let key = key_file("test_cli_exec");
+
+        let payload = std::env::temp_dir().join("sabre_test_cli_exec.payload");
+        std::fs::write(&payload, b"inc a 1").unwrap();
+
+        run(&[
+            "sabre",
+            "exec",
+            "-C",
+            "intkey_multiply:1.0",
+            "-p",
+            payload.to_str().unwrap(),
+            "--inputs",
+            "1cf12600",
+            "--outputs",
+            "1cf12601",
+            "--no-preflight",
+            "-k",
+            &key,
+            "--url",
+            &api.url,
+        ])
+        .unwrap();
+
+        let expected = ExecuteContractActionBuilder::new()
+            .with_name("intkey_multiply".into())
+            .with_version("1.0".into())
+            .with_inputs(vec!["1cf12600".into()])
+            .with_outputs(vec!["1cf12601".into()])
+            .with_payload(b"inc a 1".to_vec())
+            .into_payload_builder()
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let mut inputs = contract_addresses("intkey_multiply", "1.0");
+        inputs.push(to_hex(
+            &compute_namespace_registry_address("1cf126").unwrap(),
+        ));
+        inputs.push("1cf12600".into());
+        let mut outputs = contract_addresses("intkey_multiply", "1.0");
+        outputs.push(to_hex(
+            &compute_namespace_registry_address("1cf126").unwrap(),
+        ));
+        outputs.push("1cf12601".into());
+
+        assert_eq!(
+            api.submitted(),
+            vec![(expected.action().clone(), inputs, outputs)]
+        );
+        std::fs::remove_file(payload).unwrap();
+        std::fs::remove_file(key).unwrap();
+    }
+
+    #[test]
+    // Asserts that a contract pinned to a sha512 is split from its pin, and that a malformed
+    // pin is rejected
+    fn test_cli_split_contract_pin() {
+        let sha512 = "AB".repeat(64);
+        assert_eq!(
+            split_contract_pin("intkey_multiply:1.0").unwrap(),
+            ("intkey_multiply:1.0", None)
+        );
+        assert_eq!(
+            split_contract_pin(&format!("intkey_multiply:1.0@{}", sha512)).unwrap(),
+            ("intkey_multiply:1.0", Some("ab".repeat(64)))
+        );
+        assert!(split_contract_pin("intkey_multiply:1.0@abcd").is_err());
+        assert!(split_contract_pin(&format!("intkey_multiply:1.0@{}", "xy".repeat(64))).is_err());
+    }
+}
@@ -0,0 +1,133 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains functions which add or remove owners of a contract registry
+//!
+//! Sabre only has an action replacing all of a registry's owners, so the current owners are
+//! read, the change is applied to them, and the resulting list is submitted. An owner change
+//! submitted by someone else between the read and the commit is overwritten.
+
+use sabre_sdk::protocol::payload::UpdateContractRegistryOwnersActionBuilder;
+
+use crate::error::CliError;
+use crate::key::new_signer;
+use crate::state::get_contract_registry;
+use crate::submit::{submit_batches, Submission};
+use crate::trace;
+
+/// Owners to add to or remove from a registry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OwnerChange {
+    Add(Vec<String>),
+    Remove(Vec<String>),
+}
+
+/// Applies the change to the owners of the contract registry `name` and prints the resulting
+/// owners. Returns the submission, or None if the change leaves the owners as they are.
+pub fn do_change_owners(
+    name: &str,
+    change: &OwnerChange,
+    key_name: Option<&str>,
+    key_algo: Option<&str>,
+    url: &str,
+) -> Result<Option<Submission>, CliError> {
+    let registry = get_contract_registry(url, name)?
+        .ok_or_else(|| CliError::User(format!("contract registry '{}' does not exist", name)))?;
+
+    let owners = change_owners(registry.owners(), change)
+        .map_err(|err| CliError::User(format!("cannot change owners of '{}': {}", name, err)))?;
+    if owners == registry.owners() {
+        println!(
+            "Owners of contract registry {} are unchanged: {}",
+            name,
+            owners.join(", ")
+        );
+        return Ok(None);
+    }
+
+    let signer = new_signer(key_name, key_algo)?;
+    let batch = UpdateContractRegistryOwnersActionBuilder::new()
+        .with_name(name.into())
+        .with_owners(owners.clone())
+        .into_payload_builder()?
+        .into_transaction_builder()?
+        .with_nonce(trace::transaction_nonce())
+        .into_batch_builder(&*signer)?
+        .build(&*signer)?;
+
+    let submission = submit_batches(url, vec![batch])?;
+    println!(
+        "Owners of contract registry {}: {}",
+        name,
+        owners.join(", ")
+    );
+
+    Ok(Some(submission))
+}
+
+/// Returns the owners with the change applied, keeping their order. Adding an existing owner
+/// or removing one which is not an owner leaves the owners as they are, but removing every
+/// owner is an error, as the registry could then only be managed by an administrator.
+pub fn change_owners(owners: &[String], change: &OwnerChange) -> Result<Vec<String>, String> {
+    let mut changed = owners.to_vec();
+
+    match change {
+        OwnerChange::Add(keys) => {
+            for key in keys {
+                if !changed.contains(key) {
+                    changed.push(key.clone());
+                }
+            }
+        }
+        OwnerChange::Remove(keys) => {
+            changed.retain(|owner| !keys.contains(owner));
+            if changed.is_empty() {
+                return Err("a registry must keep at least one owner".into());
+            }
+        }
+    }
+
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn keys(keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|key| key.to_string()).collect()
+    }
+
+    #[test]
+    // Asserts that owners are added and removed in order without duplicates, and that the last
+    // owner cannot be removed
+    fn test_cli_change_owners() {
+        let owners = keys(&["a", "b"]);
+
+        assert_eq!(
+            change_owners(&owners, &OwnerChange::Add(keys(&["c", "a", "c"]))).unwrap(),
+            keys(&["a", "b", "c"])
+        );
+        assert_eq!(
+            change_owners(&owners, &OwnerChange::Remove(keys(&["a", "z"]))).unwrap(),
+            keys(&["b"])
+        );
+        assert_eq!(
+            change_owners(&owners, &OwnerChange::Remove(keys(&["z"]))).unwrap(),
+            owners
+        );
+        assert!(change_owners(&owners, &OwnerChange::Remove(keys(&["a", "b"]))).is_err());
+    }
+}
@@ -0,0 +1,99 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains the locations of the files the CLI keeps for the user
+//!
+//! Files are kept in the platform's configuration directory: $XDG_CONFIG_HOME/sabre on Linux,
+//! ~/Library/Application Support/sabre on macOS and %APPDATA%\sabre on Windows. The directory
+//! may be overridden with $SABRE_CONFIG_DIR.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::CliError;
+
+/// The environment variable which overrides the configuration directory
+pub const CONFIG_DIR_ENV_VAR: &str = "SABRE_CONFIG_DIR";
+
+/// Returns the directory the CLI keeps the user's files in.
+pub fn config_dir() -> Result<PathBuf, CliError> {
+    if let Some(dir) = std::env::var_os(CONFIG_DIR_ENV_VAR).filter(|dir| !dir.is_empty()) {
+        return Ok(PathBuf::from(dir));
+    }
+
+    dirs::config_dir()
+        .map(|dir| dir.join("sabre"))
+        .ok_or_else(|| CliError::User("unable to determine the configuration directory".into()))
+}
+
+/// Returns the path of a file in the configuration directory, moving it there from
+/// ~/.sabre, where earlier releases kept it, if it is only found there.
+pub fn config_file(name: &str) -> Result<PathBuf, CliError> {
+    let path = config_dir()?.join(name);
+    if let Some(legacy) = dirs::home_dir().map(|home| home.join(".sabre").join(name)) {
+        migrate(&legacy, &path)?;
+    }
+
+    Ok(path)
+}
+
+/// Moves the file at `legacy` to `path`, unless there is already a file at `path`.
+fn migrate(legacy: &Path, path: &Path) -> Result<(), CliError> {
+    if path.exists() || !legacy.is_file() {
+        return Ok(());
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // A rename fails across file systems, so the file is copied instead
+    fs::copy(legacy, path)
+        .and_then(|_| fs::remove_file(legacy))
+        .map_err(|e| {
+            CliError::User(format!(
+                "Could not move \"{}\" to \"{}\": {}",
+                legacy.display(),
+                path.display(),
+                e
+            ))
+        })?;
+    eprintln!("Moved {} to {}", legacy.display(), path.display());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // Asserts that a legacy file is moved, but never replaces an existing file
+    fn test_cli_migrate() {
+        let dir = std::env::temp_dir().join(format!("sabre-paths-{}", std::process::id()));
+        let legacy = dir.join("legacy").join("address_book.yaml");
+        let path = dir.join("config").join("address_book.yaml");
+        fs::create_dir_all(legacy.parent().unwrap()).unwrap();
+
+        fs::write(&legacy, "old").unwrap();
+        migrate(&legacy, &path).unwrap();
+        assert!(!legacy.exists());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+
+        fs::write(&legacy, "older").unwrap();
+        migrate(&legacy, &path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,142 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains the loading of files listing many namespace permissions to set at once
+//!
+//! A permission file is a YAML list:
+//!
+//! ```yaml
+//! - namespace: 1cf126
+//!   contract: intkey_multiply
+//!   read: true
+//!   write: true
+//! ```
+
+use std::fs;
+
+use yaml_rust::{Yaml, YamlLoader};
+
+use crate::address_book;
+use crate::error::CliError;
+
+/// A namespace permission to set for a contract
+#[derive(Debug, PartialEq, Eq)]
+pub struct PermissionEntry {
+    pub namespace: String,
+    pub contract: String,
+    pub read: bool,
+    pub write: bool,
+}
+
+/// Loads the permissions listed in the file. Namespaces may be given as `@name`.
+pub fn load_permissions(path: &str) -> Result<Vec<PermissionEntry>, CliError> {
+    let contents = fs::read_to_string(path).map_err(|e| {
+        CliError::User(format!(
+            "Could not load permission file \"{}\": {}",
+            path, e
+        ))
+    })?;
+
+    parse_permissions(&contents)
+        .map_err(|msg| CliError::User(format!("Malformed permission file \"{}\": {}", path, msg)))?
+        .into_iter()
+        .map(|entry| {
+            Ok(PermissionEntry {
+                namespace: address_book::resolve(&entry.namespace)?,
+                ..entry
+            })
+        })
+        .collect()
+}
+
+fn parse_permissions(contents: &str) -> Result<Vec<PermissionEntry>, String> {
+    let docs = YamlLoader::load_from_str(contents).map_err(|e| e.to_string())?;
+    let entries = match docs.get(0) {
+        Some(Yaml::Array(entries)) => entries,
+        _ => return Err("expected a list of permissions".into()),
+    };
+
+    entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let field = |name: &str| {
+                entry[name].as_str().map(String::from).ok_or_else(|| {
+                    format!("permission {} is missing string field \"{}\"", i + 1, name)
+                })
+            };
+            let flag = |name: &str| match &entry[name] {
+                Yaml::Boolean(value) => Ok(*value),
+                Yaml::BadValue => Ok(false),
+                _ => Err(format!(
+                    "\"{}\" of permission {} is not a boolean",
+                    name,
+                    i + 1
+                )),
+            };
+
+            let permission = PermissionEntry {
+                namespace: field("namespace")?,
+                contract: field("contract")?,
+                read: flag("read")?,
+                write: flag("write")?,
+            };
+            if !(permission.read || permission.write) {
+                return Err(format!(
+                    "permission {} grants neither read nor write",
+                    i + 1
+                ));
+            }
+
+            Ok(permission)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // Asserts that permissions are parsed from a list, and that entries granting nothing or
+    // missing fields are rejected
+    fn test_cli_parse_permissions() {
+        let permissions = parse_permissions(
+            "- namespace: 1cf126\n  contract: intkey_multiply\n  read: true\n  write: true\n\
+             - namespace: cad11d\n  contract: intkey_multiply\n  read: true\n",
+        )
+        .unwrap();
+        assert_eq!(
+            permissions,
+            vec![
+                PermissionEntry {
+                    namespace: "1cf126".into(),
+                    contract: "intkey_multiply".into(),
+                    read: true,
+                    write: true,
+                },
+                PermissionEntry {
+                    namespace: "cad11d".into(),
+                    contract: "intkey_multiply".into(),
+                    read: true,
+                    write: false,
+                },
+            ]
+        );
+
+        assert!(parse_permissions("- namespace: 1cf126\n  contract: intkey_multiply\n").is_err());
+        assert!(parse_permissions("- namespace: 1cf126\n  read: true\n").is_err());
+        assert!(parse_permissions("namespace: 1cf126\n").is_err());
+    }
+}
@@ -0,0 +1,243 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains the access policy which restricts the commands a signing key may run
+//!
+//! A policy file is YAML of the form:
+//!
+//! ```yaml
+//! roles:
+//!   deployer: [upload, upgrade, "perm set"]
+//!   operator: [exec]
+//! keys:
+//!   <public key>: [deployer, operator]
+//! admins:
+//!   - <public key>
+//! audit_log: /var/log/sabre/refused.log
+//! ```
+//!
+//! A role's commands are subcommands, such as "ns", which allow every action of the subcommand,
+//! or a subcommand and action, such as "ns delete". Admin keys may run every command. Refused
+//! commands are appended to the audit log, or printed to stderr if there is none.
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use yaml_rust::{Yaml, YamlLoader};
+
+use crate::error::CliError;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Policy {
+    roles: HashMap<String, Vec<String>>,
+    key_roles: HashMap<String, Vec<String>>,
+    admins: Vec<String>,
+    audit_log: Option<PathBuf>,
+}
+
+impl Policy {
+    pub fn load(filename: &str) -> Result<Policy, CliError> {
+        let file = File::open(filename).map_err(|e| {
+            CliError::User(format!(
+                "Could not load policy file \"{}\": {}",
+                filename, e
+            ))
+        })?;
+        let mut buf_reader = BufReader::new(file);
+        let mut contents = String::new();
+        buf_reader.read_to_string(&mut contents).map_err(|e| {
+            CliError::User(format!(
+                "IoError while reading policy file \"{}\": {}",
+                filename, e
+            ))
+        })?;
+
+        Policy::from_yaml(&contents).map_err(|msg| {
+            CliError::User(format!("Malformed policy file \"{}\": {}", filename, msg))
+        })
+    }
+
+    fn from_yaml(contents: &str) -> Result<Policy, String> {
+        let docs = YamlLoader::load_from_str(contents).map_err(|err| err.to_string())?;
+        let doc = docs.get(0).ok_or_else(|| "no content".to_string())?;
+
+        let roles = string_list_map(&doc["roles"], "roles")?;
+        let key_roles = string_list_map(&doc["keys"], "keys")?;
+        let admins = match &doc["admins"] {
+            Yaml::BadValue => Vec::new(),
+            admins => string_list(admins, "admins")?,
+        };
+        let audit_log = match &doc["audit_log"] {
+            Yaml::BadValue => None,
+            audit_log => {
+                Some(PathBuf::from(audit_log.as_str().ok_or_else(|| {
+                    "field \"audit_log\" must be a string".to_string()
+                })?))
+            }
+        };
+
+        Ok(Policy {
+            roles,
+            key_roles,
+            admins,
+            audit_log,
+        })
+    }
+
+    /// Returns whether the key may run the command, given as a subcommand optionally followed by
+    /// its action, e.g. "ns delete".
+    pub fn is_allowed(&self, public_key: &str, command: &str) -> bool {
+        if self.admins.iter().any(|admin| admin == public_key) {
+            return true;
+        }
+
+        let subcommand = command.split(' ').next().unwrap_or_default();
+        self.key_roles
+            .get(public_key)
+            .into_iter()
+            .flatten()
+            .filter_map(|role| self.roles.get(role))
+            .flatten()
+            .any(|allowed| allowed == command || allowed == subcommand)
+    }
+
+    /// Checks that the key may run the command, recording the attempt in the audit log if not.
+    pub fn check(&self, public_key: &str, command: &str) -> Result<(), CliError> {
+        if self.is_allowed(public_key, command) {
+            return Ok(());
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        let record = format!(
+            "{} refused \"{}\" for key {}",
+            timestamp, command, public_key
+        );
+        match &self.audit_log {
+            Some(path) => {
+                let mut audit_log = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| {
+                        CliError::User(format!(
+                            "Could not open audit log \"{}\": {}",
+                            path.display(),
+                            e
+                        ))
+                    })?;
+                writeln!(audit_log, "{}", record)?;
+            }
+            None => eprintln!("{}", record),
+        }
+
+        Err(CliError::User(format!(
+            "key {} is not permitted to run \"{}\"",
+            public_key, command
+        )))
+    }
+}
+
+fn string_list(yaml: &Yaml, field: &str) -> Result<Vec<String>, String> {
+    yaml.as_vec()
+        .ok_or_else(|| format!("field \"{}\" must be a list", field))?
+        .iter()
+        .map(|value| {
+            value
+                .as_str()
+                .map(String::from)
+                .ok_or_else(|| format!("field \"{}\" contains non-string values", field))
+        })
+        .collect()
+}
+
+fn string_list_map(yaml: &Yaml, field: &str) -> Result<HashMap<String, Vec<String>>, String> {
+    let hash = match yaml {
+        Yaml::BadValue => return Ok(HashMap::new()),
+        yaml => yaml
+            .as_hash()
+            .ok_or_else(|| format!("field \"{}\" must be a mapping", field))?,
+    };
+
+    hash.iter()
+        .map(|(key, value)| {
+            let key = key
+                .as_str()
+                .ok_or_else(|| format!("field \"{}\" contains non-string keys", field))?;
+            Ok((key.to_string(), string_list(value, field)?))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const POLICY: &str = "
+roles:
+  deployer: [upload, \"ns create\"]
+  operator: [exec]
+keys:
+  deployer_key: [deployer]
+  operator_key: [operator, unknown]
+admins:
+  - admin_key
+";
+
+    #[test]
+    // Asserts that keys may only run the commands of their roles, and that admins may run any
+    fn test_cli_policy_is_allowed() {
+        let policy = Policy::from_yaml(POLICY).unwrap();
+
+        assert!(policy.is_allowed("deployer_key", "upload"));
+        assert!(policy.is_allowed("deployer_key", "ns create"));
+        assert!(!policy.is_allowed("deployer_key", "ns delete"));
+        assert!(!policy.is_allowed("deployer_key", "exec"));
+        assert!(policy.is_allowed("operator_key", "exec"));
+        assert!(!policy.is_allowed("other_key", "exec"));
+        assert!(policy.is_allowed("admin_key", "cr delete"));
+    }
+
+    #[test]
+    // Asserts that a refused command is an error and is appended to the audit log
+    fn test_cli_policy_check_audit_log() {
+        let audit_log = std::env::temp_dir().join("sabre_test_cli_policy_audit.log");
+        let _ = std::fs::remove_file(&audit_log);
+
+        let policy = Policy {
+            audit_log: Some(audit_log.clone()),
+            ..Policy::from_yaml(POLICY).unwrap()
+        };
+
+        assert!(policy.check("operator_key", "exec").is_ok());
+        assert!(policy.check("operator_key", "ns delete").is_err());
+
+        let contents = std::fs::read_to_string(&audit_log).unwrap();
+        assert!(contents.ends_with("refused \"ns delete\" for key operator_key\n"));
+        std::fs::remove_file(&audit_log).unwrap();
+    }
+
+    #[test]
+    // Asserts that a policy with a malformed role list is rejected
+    fn test_cli_policy_malformed() {
+        assert!(Policy::from_yaml("roles:\n  deployer: upload\n").is_err());
+    }
+}
@@ -0,0 +1,184 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains checks run before a contract execution is submitted, so that failures the
+//! transaction processor would report are caught before submission
+
+use sabre_sdk::protocol::state::NamespaceRegistry;
+
+use crate::error::CliError;
+use crate::state::{get_contract_registry, get_namespace_registries};
+
+/// The largest request body accepted by the Sawtooth REST API by default (10 MiB)
+const MAX_PAYLOAD_SIZE: usize = 10 * 1024 * 1024;
+
+/// Checks that the contract version is registered, that the contract has permission to read
+/// each input and write each output, and that the payload is small enough to be submitted.
+pub fn check_execute(
+    url: &str,
+    name: &str,
+    version: &str,
+    inputs: &[String],
+    outputs: &[String],
+    payload_size: usize,
+) -> Result<(), CliError> {
+    if payload_size > MAX_PAYLOAD_SIZE {
+        return Err(CliError::User(format!(
+            "payload is {} bytes, which exceeds the limit of {} bytes",
+            payload_size, MAX_PAYLOAD_SIZE
+        )));
+    }
+
+    let registry = get_contract_registry(url, name)?
+        .ok_or_else(|| CliError::User(format!("contract '{}' is not registered", name)))?;
+    if version == "latest" {
+        if registry.versions().is_empty() {
+            return Err(CliError::User(format!(
+                "contract '{}' has no registered versions",
+                name
+            )));
+        }
+    } else if !registry
+        .versions()
+        .iter()
+        .any(|registered| registered.version() == version)
+    {
+        return Err(CliError::User(format!(
+            "contract '{}:{}' is not registered",
+            name, version
+        )));
+    }
+
+    let namespace_registries = get_namespace_registries(url)?;
+    for input in inputs {
+        check_permission(&namespace_registries, name, input, false)?;
+    }
+    for output in outputs {
+        check_permission(&namespace_registries, name, output, true)?;
+    }
+
+    Ok(())
+}
+
+/// Checks that a namespace containing `address` grants the contract read permission, or write
+/// permission if `write` is set. Namespaces may nest, so, as in the transaction processor, any
+/// of the namespaces containing the address may grant the permission.
+fn check_permission(
+    namespace_registries: &[NamespaceRegistry],
+    name: &str,
+    address: &str,
+    write: bool,
+) -> Result<(), CliError> {
+    let mut registries = namespace_registries
+        .iter()
+        .filter(|registry| address.starts_with(registry.namespace().as_str()))
+        .peekable();
+    if registries.peek().is_none() {
+        return Err(CliError::User(format!(
+            "address {} is not in a namespace",
+            address
+        )));
+    }
+
+    let permitted = registries.any(|registry| {
+        registry
+            .permissions()
+            .iter()
+            .filter(|permission| permission.contract_name() == name)
+            .any(|permission| {
+                if write {
+                    permission.write()
+                } else {
+                    permission.read()
+                }
+            })
+    });
+
+    if permitted {
+        Ok(())
+    } else {
+        Err(CliError::User(format!(
+            "contract '{}' does not have {} permission for any namespace containing address {}",
+            name,
+            if write { "write" } else { "read" },
+            address
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use sabre_sdk::protocol::state::{NamespaceRegistryBuilder, PermissionBuilder};
+
+    use super::*;
+
+    #[test]
+    // Asserts that an address is permitted only by its namespace's permissions for the contract
+    fn test_cli_preflight_check_permission() {
+        let registries = vec![NamespaceRegistryBuilder::new()
+            .with_namespace("1cf126".to_string())
+            .with_owners(vec!["owner".to_string()])
+            .with_permissions(vec![PermissionBuilder::new()
+                .with_contract_name("intkey_multiply".to_string())
+                .with_read(true)
+                .with_write(false)
+                .build()
+                .unwrap()])
+            .build()
+            .unwrap()];
+
+        assert!(check_permission(&registries, "intkey_multiply", "1cf12600", false).is_ok());
+        assert!(check_permission(&registries, "intkey_multiply", "1cf12600", true).is_err());
+        assert!(check_permission(&registries, "other", "1cf12600", false).is_err());
+        assert!(check_permission(&registries, "intkey_multiply", "cad11d00", false).is_err());
+    }
+
+    #[test]
+    // Asserts that an address in nested namespaces is permitted by any of them, not only the
+    // first
+    fn test_cli_preflight_check_permission_nested() {
+        let registry = |namespace: &str, write: bool| {
+            NamespaceRegistryBuilder::new()
+                .with_namespace(namespace.to_string())
+                .with_owners(vec!["owner".to_string()])
+                .with_permissions(vec![PermissionBuilder::new()
+                    .with_contract_name("intkey_multiply".to_string())
+                    .with_read(true)
+                    .with_write(write)
+                    .build()
+                    .unwrap()])
+                .build()
+                .unwrap()
+        };
+        let registries = vec![registry("1cf126", false), registry("1cf12600", true)];
+
+        assert!(check_permission(&registries, "intkey_multiply", "1cf1260000", true).is_ok());
+        assert!(check_permission(&registries, "intkey_multiply", "1cf1260100", true).is_err());
+    }
+
+    #[test]
+    // Asserts that an oversized payload fails before state is read
+    fn test_cli_preflight_payload_size() {
+        assert!(check_execute(
+            "http://localhost",
+            "intkey_multiply",
+            "1.0",
+            &[],
+            &[],
+            MAX_PAYLOAD_SIZE + 1
+        )
+        .is_err());
+    }
+}
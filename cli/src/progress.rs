@@ -0,0 +1,118 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains the progress reporting used by subcommands which submit batches
+
+use crate::error::CliError;
+use crate::submit::SubmittedBatch;
+
+/// A step in the progress of a subcommand
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProgressEvent<'a> {
+    StepStarted {
+        step: &'a str,
+    },
+    StepCompleted {
+        step: &'a str,
+    },
+    BatchesSubmitted {
+        link: &'a str,
+        batches: &'a [SubmittedBatch],
+    },
+    BatchStatus {
+        id: &'a str,
+        status: &'a str,
+    },
+}
+
+/// Reports the progress of a subcommand to tools which wrap the CLI
+pub trait ProgressReporter {
+    fn report(&self, event: ProgressEvent);
+}
+
+/// Discards progress events; the subcommands' human readable output is all that is printed
+pub struct NoProgress;
+
+impl ProgressReporter for NoProgress {
+    fn report(&self, _event: ProgressEvent) {}
+}
+
+/// Writes each progress event to stderr as a line of JSON, keeping stdout for human readable
+/// output
+pub struct JsonProgress;
+
+impl ProgressReporter for JsonProgress {
+    fn report(&self, event: ProgressEvent) {
+        match serde_json::to_string(&event) {
+            Ok(line) => eprintln!("{}", line),
+            Err(err) => eprintln!("Unable to serialize progress event {:?}: {}", event, err),
+        }
+    }
+}
+
+/// Returns the reporter for the given `--progress` mode
+pub fn new_reporter(mode: Option<&str>) -> Result<Box<dyn ProgressReporter>, CliError> {
+    match mode {
+        None | Some("none") => Ok(Box::new(NoProgress)),
+        Some("json") => Ok(Box::new(JsonProgress)),
+        Some(mode) => Err(CliError::User(format!(
+            "Unsupported progress mode: {}",
+            mode
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    // Asserts that progress events serialize to JSON objects tagged with the event name
+    fn test_cli_progress_event_json() {
+        assert_eq!(
+            serde_json::to_string(&ProgressEvent::StepStarted { step: "upload" }).unwrap(),
+            "{\"event\":\"step_started\",\"step\":\"upload\"}"
+        );
+        assert_eq!(
+            serde_json::to_string(&ProgressEvent::BatchStatus {
+                id: "abc",
+                status: "COMMITTED"
+            })
+            .unwrap(),
+            "{\"event\":\"batch_status\",\"id\":\"abc\",\"status\":\"COMMITTED\"}"
+        );
+        assert_eq!(
+            serde_json::to_string(&ProgressEvent::BatchesSubmitted {
+                link: "/batch_statuses?id=abc",
+                batches: &[SubmittedBatch {
+                    id: "abc".into(),
+                    transaction_ids: vec!["def".into()],
+                }],
+            })
+            .unwrap(),
+            "{\"event\":\"batches_submitted\",\"link\":\"/batch_statuses?id=abc\",\
+             \"batches\":[{\"id\":\"abc\",\"transaction_ids\":[\"def\"]}]}"
+        );
+    }
+
+    #[test]
+    // Asserts that only the supported progress modes create a reporter
+    fn test_cli_progress_new_reporter() {
+        assert!(new_reporter(None).is_ok());
+        assert!(new_reporter(Some("json")).is_ok());
+        assert!(new_reporter(Some("xml")).is_err());
+    }
+}
@@ -0,0 +1,137 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains functions which delete the older versions of a contract
+
+use sabre_sdk::protocol::payload::DeleteContractActionBuilder;
+use sabre_sdk::protocol::state::ContractRegistry;
+
+use crate::batching::{build_batches, BatchLimits};
+use crate::dependencies::compare_versions;
+use crate::error::CliError;
+use crate::key::new_signer;
+use crate::state::get_contract_registry;
+use crate::submit::{submit_batches, Submission};
+use crate::trace;
+
+/// Deletes all but the `keep` highest versions of a contract, or only reports
+/// them if `dry_run` is set. Returns the submission, or None if nothing was submitted.
+pub fn do_prune(
+    name: &str,
+    keep: usize,
+    key_name: Option<&str>,
+    key_algo: Option<&str>,
+    url: &str,
+    dry_run: bool,
+) -> Result<Option<Submission>, CliError> {
+    let registry = get_contract_registry(url, name)?
+        .ok_or_else(|| CliError::User(format!("contract registry '{}' not found", name)))?;
+
+    let versions = versions_to_prune(&registry, keep);
+    if versions.is_empty() {
+        println!(
+            "{} has {} versions; nothing to prune",
+            name,
+            registry.versions().len()
+        );
+        return Ok(None);
+    }
+
+    if dry_run {
+        for version in &versions {
+            println!("Would delete {}:{}", name, version);
+        }
+        return Ok(None);
+    }
+
+    let signer = new_signer(key_name, key_algo)?;
+    let transactions = versions
+        .iter()
+        .map(|version| {
+            Ok(DeleteContractActionBuilder::new()
+                .with_name(name.to_string())
+                .with_version(version.to_string())
+                .into_payload_builder()?
+                .into_transaction_builder()?
+                .with_nonce(trace::transaction_nonce())
+                .build(&*signer)?)
+        })
+        .collect::<Result<Vec<_>, CliError>>()?;
+
+    let submission = submit_batches(
+        url,
+        build_batches(transactions, &BatchLimits::default(), &*signer)?,
+    )?;
+    for version in &versions {
+        println!("Deleting {}:{}", name, version);
+    }
+
+    Ok(Some(submission))
+}
+
+/// Returns the versions in the registry lower than the `keep` highest, lowest first.
+///
+/// Versions are compared by their dot separated components rather than by registration order,
+/// since a version which is deleted and uploaded again is registered after higher versions.
+fn versions_to_prune(registry: &ContractRegistry, keep: usize) -> Vec<String> {
+    let mut versions = registry
+        .versions()
+        .iter()
+        .map(|version| version.version().to_string())
+        .collect::<Vec<_>>();
+    versions.sort_by(|left, right| compare_versions(left, right));
+    versions.truncate(versions.len().saturating_sub(keep));
+    versions
+}
+
+#[cfg(test)]
+mod tests {
+
+    use sabre_sdk::protocol::state::{ContractRegistryBuilder, VersionBuilder};
+
+    use super::*;
+
+    fn registry_with_versions(versions: &[&str]) -> ContractRegistry {
+        ContractRegistryBuilder::new()
+            .with_name("intkey_multiply".to_string())
+            .with_versions(
+                versions
+                    .iter()
+                    .map(|version| {
+                        VersionBuilder::new()
+                            .with_version(version.to_string())
+                            .with_contract_sha512("sha512".to_string())
+                            .with_creator("creator".to_string())
+                            .build()
+                            .unwrap()
+                    })
+                    .collect(),
+            )
+            .with_owners(vec!["owner".to_string()])
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    // Asserts that all but the highest versions are pruned, whatever order they were registered in
+    fn test_cli_versions_to_prune() {
+        let registry = registry_with_versions(&["1.0", "1.1", "2.0"]);
+        assert_eq!(versions_to_prune(&registry, 1), vec!["1.0", "1.1"]);
+        assert!(versions_to_prune(&registry, 3).is_empty());
+        assert!(versions_to_prune(&registry, 5).is_empty());
+
+        let registry = registry_with_versions(&["1.10", "2.0", "1.9"]);
+        assert_eq!(versions_to_prune(&registry, 1), vec!["1.9", "1.10"]);
+    }
+}
@@ -0,0 +1,621 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains the parsing of responses from the Sawtooth REST API, failover between REST API
+//! endpoints, and the credentials sent to them
+//!
+//! A REST API listening on a Unix socket is given as unix:///path/to/rest.sock, on Unix
+//! platforms. Requests to one are sent to a placeholder host, and the clients built for URLs on
+//! that host connect to the socket in place of resolving it.
+//!
+//! Requests are sent through the proxies given by --proxy, or by $HTTP_PROXY and $HTTPS_PROXY,
+//! except to the hosts in $NO_PROXY, to loopback addresses and to Unix sockets.
+//! REST APIs with certificates issued by a private CA are trusted with --ca-file, in addition to
+//! the built-in roots, and --client-cert and --client-key give the certificate for mutual TLS.
+//!
+//! The REST API does not report its version, so a response is checked for the shape the CLI
+//! expects before it is parsed. Responses which are not JSON, or which lack the expected fields,
+//! are reported as coming from an unsupported REST API version.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use cylinder::{jwt::JsonWebTokenBuilder, Signer};
+use reqwest::blocking::{Client, ClientBuilder, RequestBuilder, Response};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Certificate, Identity, Proxy, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::error::CliError;
+
+/// The media type requested from, and expected of, the REST API
+pub const JSON_MEDIA_TYPE: &str = "application/json";
+
+/// The index of the endpoint requests are sent to first. Once an endpoint cannot be reached,
+/// the endpoint which answered in its place is used for the rest of the run.
+static CURRENT_ENDPOINT: AtomicUsize = AtomicUsize::new(0);
+
+static AUTHORIZATION: OnceLock<Authorization> = OnceLock::new();
+
+static PROXY: OnceLock<Proxy> = OnceLock::new();
+
+static TLS: OnceLock<Tls> = OnceLock::new();
+
+/// The certificates loaded from the files given by TlsSettings
+struct Tls {
+    ca: Option<Certificate>,
+    identity: Option<Identity>,
+}
+
+/// The socket of each Unix socket REST API, by the placeholder host requests to it are sent to
+static UNIX_SOCKETS: OnceLock<Mutex<HashMap<String, PathBuf>>> = OnceLock::new();
+
+/// The scheme of a REST API listening on a Unix socket
+const UNIX_SCHEME: &str = "unix://";
+
+/// The credentials sent in the Authorization header of every request, for a REST API behind an
+/// authenticating proxy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Authorization {
+    Basic { username: String, password: String },
+    Bearer(String),
+}
+
+impl Authorization {
+    /// Parses Basic credentials given as USERNAME:PASSWORD.
+    pub fn basic(credentials: &str) -> Result<Self, CliError> {
+        match credentials.split_once(':') {
+            Some((username, password)) if !username.is_empty() => Ok(Authorization::Basic {
+                username: username.into(),
+                password: password.into(),
+            }),
+            _ => Err(CliError::User(
+                "Basic credentials must be given as USERNAME:PASSWORD".into(),
+            )),
+        }
+    }
+
+    /// Creates the bearer credentials a Splinter node accepts, which are a JSON web token
+    /// signed by the signer.
+    pub fn cylinder(signer: &dyn Signer) -> Result<Self, CliError> {
+        let token = JsonWebTokenBuilder::new().build(signer).map_err(|err| {
+            CliError::Signing(format!("Unable to create authorization token: {}", err))
+        })?;
+
+        Ok(Authorization::Bearer(format!("Cylinder:{}", token)))
+    }
+}
+
+/// Sets the credentials sent with every request made by this invocation. Only the first
+/// credentials set are used.
+pub fn set_authorization(authorization: Authorization) {
+    let _ = AUTHORIZATION.set(authorization);
+}
+
+/// Adds the Authorization header to the request, if credentials are set.
+pub fn authorize(request: RequestBuilder) -> RequestBuilder {
+    match AUTHORIZATION.get() {
+        Some(Authorization::Basic { username, password }) => {
+            request.basic_auth(username, Some(password))
+        }
+        Some(Authorization::Bearer(token)) => request.bearer_auth(token),
+        None => request,
+    }
+}
+
+/// The proxies requests are sent through
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProxySettings {
+    /// The proxy URL for http requests
+    pub http: Option<String>,
+    /// The proxy URL for https requests
+    pub https: Option<String>,
+    /// The hosts, and the domains of hosts, requested directly; "*" is every host
+    pub no_proxy: Vec<String>,
+    /// Basic credentials for the proxies
+    pub auth: Option<(String, String)>,
+}
+
+impl ProxySettings {
+    /// Returns the URL of the proxy a request to the URL is sent through, if any.
+    pub fn proxy_for(&self, url: &Url) -> Option<&str> {
+        let host = url
+            .host_str()?
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+        if host == "localhost"
+            || host
+                .parse::<std::net::IpAddr>()
+                .map_or(false, |ip| ip.is_loopback())
+            || self.no_proxy.iter().any(|entry| {
+                let domain = entry.trim_start_matches('.');
+                entry == "*" || host == domain || host.ends_with(&format!(".{}", domain))
+            })
+        {
+            return None;
+        }
+
+        match url.scheme() {
+            "http" => self.http.as_deref(),
+            "https" => self.https.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// Sets the proxies requests made by this invocation are sent through. Only the first settings
+/// set are used.
+pub fn set_proxy(settings: ProxySettings) -> Result<(), CliError> {
+    for proxy in settings.http.iter().chain(settings.https.iter()) {
+        let url = Url::parse(proxy)
+            .map_err(|e| CliError::User(format!("Invalid proxy URL: {}: {}", e, proxy)))?;
+        check_scheme(&url)?;
+    }
+
+    let auth = settings.auth.clone();
+    let mut proxy = Proxy::custom(move |url| settings.proxy_for(url).map(String::from));
+    if let Some((username, password)) = auth {
+        proxy = proxy.basic_auth(&username, &password);
+    }
+    let _ = PROXY.set(proxy);
+
+    Ok(())
+}
+
+/// The PEM files which configure TLS connections to the REST API
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsSettings {
+    /// The CA certificates trusted in addition to the built-in roots
+    pub ca_file: Option<String>,
+    /// The client certificate chain presented for mutual TLS
+    pub client_cert: Option<String>,
+    /// The client certificate's private key, if it is not in the client certificate file
+    pub client_key: Option<String>,
+}
+
+/// Loads the certificates TLS connections made by this invocation use. Only the first settings
+/// set are used.
+pub fn set_tls(settings: TlsSettings) -> Result<(), CliError> {
+    let read = |path: &str, kind: &str| {
+        fs::read(path)
+            .map_err(|e| CliError::User(format!("Could not load {} \"{}\": {}", kind, path, e)))
+    };
+    let invalid = |path: &str, kind: &str, e: reqwest::Error| {
+        CliError::User(format!("Malformed {} \"{}\": {}", kind, path, e))
+    };
+
+    // A PEM file without certificates is not rejected by the TLS backend, but ignored
+    let ca = match &settings.ca_file {
+        Some(path) => {
+            let pem = read(path, "CA file")?;
+            if !String::from_utf8_lossy(&pem).contains("-----BEGIN CERTIFICATE-----") {
+                return Err(CliError::User(format!(
+                    "Malformed CA file \"{}\": no PEM certificates found",
+                    path
+                )));
+            }
+            Some(Certificate::from_pem(&pem).map_err(|e| invalid(path, "CA file", e))?)
+        }
+        None => None,
+    };
+
+    let identity = match (&settings.client_cert, &settings.client_key) {
+        (Some(cert), key) => {
+            let mut pem = read(cert, "client certificate")?;
+            if let Some(key) = key {
+                pem.push(b'\n');
+                pem.extend(read(key, "client key")?);
+            }
+            Some(Identity::from_pem(&pem).map_err(|e| invalid(cert, "client certificate", e))?)
+        }
+        (None, Some(_)) => {
+            return Err(CliError::User("--client-key requires --client-cert".into()))
+        }
+        (None, None) => None,
+    };
+
+    let _ = TLS.set(Tls { ca, identity });
+
+    Ok(())
+}
+
+/// Returns a builder of clients for requests to the URL, which send them through the proxies
+/// set, using the TLS certificates loaded. The proxies are chosen here, rather than by reqwest
+/// from the environment, so that loopback URLs are never proxied. Requests to a Unix socket
+/// REST API are sent over its socket.
+pub fn client_builder(url: &str) -> ClientBuilder {
+    let mut builder = Client::builder().no_proxy();
+    match unix_socket(url) {
+        #[cfg(unix)]
+        Some(socket) => builder = builder.unix_socket(socket),
+        _ => {
+            if let Some(proxy) = PROXY.get() {
+                builder = builder.proxy(proxy.clone());
+            }
+        }
+    }
+    if let Some(tls) = TLS.get() {
+        if let Some(ca) = &tls.ca {
+            builder = builder.add_root_certificate(ca.clone());
+        }
+        if let Some(identity) = &tls.identity {
+            builder = builder.identity(identity.clone());
+        }
+    }
+    builder
+}
+
+/// Returns a client for requests to the URL, which sends them through the proxies set, using
+/// the TLS certificates loaded.
+pub fn client(url: &str) -> Result<Client, CliError> {
+    Ok(client_builder(url).build()?)
+}
+
+/// Splits a comma separated list of REST API URLs.
+pub fn endpoints(url: &str) -> Vec<&str> {
+    url.split(',')
+        .map(str::trim)
+        .filter(|endpoint| !endpoint.is_empty())
+        .collect()
+}
+
+/// Returns a URL requests to the endpoint can be sent to with reqwest. An endpoint on a Unix
+/// socket is replaced by a URL on a placeholder host, for which client_builder connects to the
+/// socket.
+pub fn connectable(endpoint: &str) -> Result<String, CliError> {
+    let path = match endpoint.strip_prefix(UNIX_SCHEME) {
+        Some(path) => path.trim_end_matches('/'),
+        None => return Ok(endpoint.to_string()),
+    };
+    if cfg!(not(unix)) {
+        return Err(CliError::User(format!(
+            "REST APIs on Unix sockets are not supported on this platform: {}",
+            endpoint
+        )));
+    }
+
+    let mut sockets = UNIX_SOCKETS
+        .get_or_init(Default::default)
+        .lock()
+        .expect("the Unix sockets lock is not poisoned");
+    let host = match sockets
+        .iter()
+        .find(|(_, socket)| socket.as_path() == Path::new(path))
+    {
+        Some((host, _)) => host.clone(),
+        None => {
+            let host = format!("unix-socket-{}.localhost", sockets.len());
+            sockets.insert(host.clone(), path.into());
+            host
+        }
+    };
+    Ok(format!("http://{}", host))
+}
+
+/// Returns the socket of the Unix socket REST API the URL is on, if it is on one.
+fn unix_socket(url: &str) -> Option<PathBuf> {
+    let url = Url::parse(url).ok()?;
+    UNIX_SOCKETS
+        .get()?
+        .lock()
+        .expect("the Unix sockets lock is not poisoned")
+        .get(url.host_str()?)
+        .cloned()
+}
+
+/// Checks that the URL is one the CLI can send requests to, over HTTP or HTTPS.
+pub fn check_scheme(url: &Url) -> Result<(), CliError> {
+    match url.scheme() {
+        "http" | "https" => Ok(()),
+        "" => Err(CliError::User(format!("No scheme in URL: {}", url))),
+        s => Err(CliError::User(format!(
+            "Unsupported scheme ({}) in URL: {}",
+            s, url
+        ))),
+    }
+}
+
+/// Sends a request to the REST API endpoints in `url`, a comma separated list, starting with the
+/// endpoint in use. If the endpoint cannot be reached, the request is retried on the next one.
+///
+/// Requests must be safe to repeat. Batch submission is, as the validator ignores a batch it
+/// has already received.
+pub fn with_failover<T, F>(url: &str, mut request: F) -> Result<T, CliError>
+where
+    F: FnMut(&str) -> Result<T, CliError>,
+{
+    let endpoints = endpoints(url);
+    if endpoints.is_empty() {
+        return Err(CliError::User("No REST API URL given".into()));
+    }
+
+    let start = CURRENT_ENDPOINT.load(Ordering::Relaxed) % endpoints.len();
+    let mut last_err = None;
+    for offset in 0..endpoints.len() {
+        let i = (start + offset) % endpoints.len();
+        let endpoint = match connectable(endpoints[i]) {
+            Ok(endpoint) => endpoint,
+            Err(err) => {
+                if endpoints.len() > 1 {
+                    eprintln!(
+                        "REST API {} is unreachable: {}; trying {}",
+                        endpoints[i],
+                        err,
+                        endpoints[(i + 1) % endpoints.len()]
+                    );
+                }
+                last_err = Some(err);
+                continue;
+            }
+        };
+        match request(&endpoint) {
+            Err(CliError::Request(err)) if err.is_connect() || err.is_timeout() => {
+                if endpoints.len() > 1 {
+                    eprintln!(
+                        "REST API {} is unreachable: {}; trying {}",
+                        endpoints[i],
+                        err,
+                        endpoints[(i + 1) % endpoints.len()]
+                    );
+                }
+                last_err = Some(CliError::Request(err));
+            }
+            result => {
+                if i != start {
+                    eprintln!("Switched to REST API {}", endpoints[i]);
+                    CURRENT_ENDPOINT.store(i, Ordering::Relaxed);
+                }
+                return result;
+            }
+        }
+    }
+
+    Err(last_err.expect("every endpoint was tried"))
+}
+
+/// Parses a response from the REST API, reporting the REST API's errors and responses of an
+/// unsupported shape instead of failing to deserialize them.
+pub fn parse_response<T: DeserializeOwned>(response: Response) -> Result<T, CliError> {
+    let url = response.url().to_string();
+    let status = response.status();
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let body = response.text()?;
+
+    parse_body(&url, status, &content_type, &body)
+}
+
+fn parse_body<T: DeserializeOwned>(
+    url: &str,
+    status: StatusCode,
+    content_type: &str,
+    body: &str,
+) -> Result<T, CliError> {
+    // A proxy or server in front of the REST API may answer with its own error page
+    if !content_type.starts_with(JSON_MEDIA_TYPE) {
+        return Err(CliError::User(format!(
+            "{} responded with HTTP {} and content type '{}' instead of {}",
+            url, status, content_type, JSON_MEDIA_TYPE
+        )));
+    }
+
+    let value: Value = serde_json::from_str(body).map_err(|err| {
+        CliError::User(format!(
+            "{} responded with HTTP {} and invalid JSON: {}",
+            url, status, err
+        ))
+    })?;
+
+    // Errors are reported as {"error": {"code": .., "title": .., "message": ..}}
+    if let Some(error) = value.get("error") {
+        return Err(CliError::User(format!(
+            "REST API error: {}: {}",
+            error
+                .get("title")
+                .and_then(Value::as_str)
+                .unwrap_or("Unknown Error"),
+            error
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+        )));
+    }
+
+    serde_json::from_value(value).map_err(|err| {
+        CliError::User(format!(
+            "Unsupported REST API version: unexpected response from {}: {}",
+            url, err
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[derive(Deserialize, Debug, PartialEq, Eq)]
+    struct Link {
+        link: String,
+    }
+
+    #[test]
+    #[cfg(unix)]
+    // Asserts that requests to a Unix socket URL reach the REST API listening on the socket
+    fn test_cli_unix_socket_endpoint() {
+        use std::io::{BufRead, BufReader, Write};
+        use std::os::unix::net::UnixListener;
+
+        let path = std::env::temp_dir().join("sabre_test_cli_unix_socket_endpoint.sock");
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let mut request_line = String::new();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+                    continue;
+                }
+                let body = format!("{{\"link\": \"{}\"}}", request_line.trim());
+                let _ = write!(
+                    &stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+            }
+        });
+
+        let url = format!("unix://{}", path.display());
+        let link = with_failover(&url, |url| {
+            let response = client(url)?.get(format!("{}/batches", url)).send()?;
+            parse_response::<Link>(response)
+        })
+        .unwrap();
+        assert_eq!(link.link, "GET /batches HTTP/1.1");
+        assert_eq!(connectable(&url).unwrap(), connectable(&url).unwrap());
+
+        let _ = std::fs::remove_file(&path);
+        let result = with_failover("unix:///nonexistent/sabre.sock", |url| {
+            Ok(client(url)?.get(format!("{}/batches", url)).send()?)
+        });
+        assert!(matches!(result, Err(CliError::Request(err)) if err.is_connect()));
+    }
+
+    #[test]
+    // Asserts that requests are proxied by scheme, except to loopback addresses and the hosts
+    // excluded from proxying
+    fn test_cli_proxy_for() {
+        let settings = ProxySettings {
+            http: Some("http://proxy:3128".into()),
+            https: None,
+            no_proxy: vec!["internal.example.com".into(), ".corp".into()],
+            auth: None,
+        };
+        let proxy_for = |url: &str| settings.proxy_for(&Url::parse(url).unwrap());
+
+        assert_eq!(proxy_for("http://rest-api:8008"), Some("http://proxy:3128"));
+        assert_eq!(proxy_for("https://rest-api:8008"), None);
+        assert_eq!(proxy_for("http://127.0.0.1:8008"), None);
+        assert_eq!(proxy_for("http://[::1]:8008"), None);
+        assert_eq!(proxy_for("http://localhost:8008"), None);
+        assert_eq!(proxy_for("http://internal.example.com"), None);
+        assert_eq!(proxy_for("http://api.internal.example.com"), None);
+        assert_eq!(proxy_for("http://rest.corp"), None);
+        assert_eq!(
+            proxy_for("http://notinternal.example.com"),
+            Some("http://proxy:3128")
+        );
+
+        assert!(set_proxy(ProxySettings {
+            http: Some("proxy:3128".into()),
+            ..ProxySettings::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    // Asserts that missing and malformed certificate files, and a client key without a client
+    // certificate, are reported
+    fn test_cli_set_tls_errors() {
+        let dir = std::env::temp_dir().join(format!("sabre-tls-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let malformed = dir.join("malformed.pem");
+        fs::write(&malformed, "not a certificate").unwrap();
+        let malformed = malformed.to_str().unwrap().to_string();
+
+        let missing = dir.join("missing.pem").to_str().unwrap().to_string();
+        assert!(set_tls(TlsSettings {
+            ca_file: Some(missing),
+            ..TlsSettings::default()
+        })
+        .is_err());
+        assert!(set_tls(TlsSettings {
+            ca_file: Some(malformed.clone()),
+            ..TlsSettings::default()
+        })
+        .is_err());
+        assert!(set_tls(TlsSettings {
+            client_cert: Some(malformed.clone()),
+            ..TlsSettings::default()
+        })
+        .is_err());
+        assert!(set_tls(TlsSettings {
+            client_key: Some(malformed),
+            ..TlsSettings::default()
+        })
+        .is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    // Asserts that a response of the expected shape is parsed
+    fn test_cli_parse_body() {
+        assert_eq!(
+            parse_body::<Link>(
+                "url",
+                StatusCode::OK,
+                "application/json",
+                "{\"link\": \"test\"}"
+            )
+            .unwrap(),
+            Link {
+                link: "test".into()
+            }
+        );
+    }
+
+    #[test]
+    // Asserts that REST API errors, non-JSON responses, with their status and content type, and
+    // responses of another shape are reported explicitly
+    fn test_cli_parse_body_errors() {
+        let error = parse_body::<Link>(
+            "url",
+            StatusCode::BAD_REQUEST,
+            "application/json",
+            "{\"error\": {\"code\": 30, \"title\": \"Submitted Batches Invalid\", \
+             \"message\": \"bad batch\"}}",
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Error: REST API error: Submitted Batches Invalid: bad batch"
+        );
+
+        let error =
+            parse_body::<Link>("url", StatusCode::BAD_GATEWAY, "text/html", "<html></html>")
+                .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Error: url responded with HTTP 502 Bad Gateway and content type 'text/html' \
+             instead of application/json"
+        );
+
+        let error = parse_body::<Link>("url", StatusCode::OK, "application/json", "{\"data\": []}")
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Error: Unsupported REST API version"));
+    }
+}
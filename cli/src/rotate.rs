@@ -0,0 +1,215 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains functions which replace an owner's public key in every registry it owns
+
+use sabre_sdk::protocol::payload::{
+    UpdateContractRegistryOwnersActionBuilder, UpdateNamespaceRegistryOwnersActionBuilder,
+};
+use sabre_sdk::protocol::state::{ContractRegistry, NamespaceRegistry};
+
+use crate::batching::{build_batches, BatchLimits};
+use crate::error::CliError;
+use crate::key::new_signer;
+use crate::state::{get_contract_registries, get_namespace_registries};
+use crate::submit::{submit_batches, Submission};
+use crate::trace;
+
+/// A registry owned by the key being rotated, and its owners once it is replaced
+#[derive(Debug, PartialEq, Eq)]
+pub enum Rotation {
+    ContractRegistry {
+        name: String,
+        owners: Vec<String>,
+    },
+    NamespaceRegistry {
+        namespace: String,
+        owners: Vec<String>,
+    },
+}
+
+/// Replaces the `old` owner key with `new` in every contract and namespace registry `old` owns,
+/// or only reports the registries if `dry_run` is set. The registries are updated in a single
+/// batch, so either all or none of them are. Returns the submission, or None if nothing was
+/// submitted.
+///
+/// The signer must be an owner of every registry, which the old key is, or an administrator.
+pub fn do_rotate(
+    old: &str,
+    new: &str,
+    key_name: Option<&str>,
+    key_algo: Option<&str>,
+    url: &str,
+    dry_run: bool,
+) -> Result<Option<Submission>, CliError> {
+    if old == new {
+        return Err(CliError::User("--old and --new are the same key".into()));
+    }
+
+    let rotations = find_rotations(
+        old,
+        new,
+        &get_contract_registries(url)?,
+        &get_namespace_registries(url)?,
+    );
+    if rotations.is_empty() {
+        println!("{} owns no registries; nothing to rotate", old);
+        return Ok(None);
+    }
+
+    if dry_run {
+        for rotation in &rotations {
+            println!("Would rotate {}", rotation);
+        }
+        return Ok(None);
+    }
+
+    let signer = new_signer(key_name, key_algo)?;
+    let transactions = rotations
+        .iter()
+        .map(|rotation| {
+            let payload_builder = match rotation {
+                Rotation::ContractRegistry { name, owners } => {
+                    UpdateContractRegistryOwnersActionBuilder::new()
+                        .with_name(name.clone())
+                        .with_owners(owners.clone())
+                        .into_payload_builder()?
+                }
+                Rotation::NamespaceRegistry { namespace, owners } => {
+                    UpdateNamespaceRegistryOwnersActionBuilder::new()
+                        .with_namespace(namespace.clone())
+                        .with_owners(owners.clone())
+                        .into_payload_builder()?
+                }
+            };
+
+            Ok(payload_builder
+                .into_transaction_builder()?
+                .with_nonce(trace::transaction_nonce())
+                .build(&*signer)?)
+        })
+        .collect::<Result<Vec<_>, CliError>>()?;
+
+    let batches = build_batches(transactions, &BatchLimits::default(), &*signer)?;
+    if batches.len() > 1 {
+        return Err(CliError::User(format!(
+            "{} owns more registries than can be updated in one batch",
+            old
+        )));
+    }
+
+    let submission = submit_batches(url, batches)?;
+    for rotation in &rotations {
+        println!("Rotating {}", rotation);
+    }
+
+    Ok(Some(submission))
+}
+
+/// Returns the registries owned by `old`, with their owners once `old` is replaced by `new`.
+/// If `new` already owns a registry, `old` is only removed from it.
+pub fn find_rotations(
+    old: &str,
+    new: &str,
+    contract_registries: &[ContractRegistry],
+    namespace_registries: &[NamespaceRegistry],
+) -> Vec<Rotation> {
+    let contract_rotations = contract_registries.iter().filter_map(|registry| {
+        replace_owner(registry.owners(), old, new).map(|owners| Rotation::ContractRegistry {
+            name: registry.name().to_string(),
+            owners,
+        })
+    });
+    let namespace_rotations = namespace_registries.iter().filter_map(|registry| {
+        replace_owner(registry.owners(), old, new).map(|owners| Rotation::NamespaceRegistry {
+            namespace: registry.namespace().to_string(),
+            owners,
+        })
+    });
+
+    contract_rotations.chain(namespace_rotations).collect()
+}
+
+/// Returns the owners with `old` replaced by `new`, or None if `old` is not an owner.
+fn replace_owner(owners: &[String], old: &str, new: &str) -> Option<Vec<String>> {
+    if !owners.iter().any(|owner| owner == old) {
+        return None;
+    }
+
+    let mut replaced = Vec::with_capacity(owners.len());
+    for owner in owners {
+        let owner = if owner == old { new } else { owner.as_str() };
+        if !replaced.iter().any(|existing| existing == owner) {
+            replaced.push(owner.to_string());
+        }
+    }
+
+    Some(replaced)
+}
+
+impl std::fmt::Display for Rotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Rotation::ContractRegistry { name, .. } => write!(f, "contract registry {}", name),
+            Rotation::NamespaceRegistry { namespace, .. } => {
+                write!(f, "namespace registry {}", namespace)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use sabre_sdk::protocol::state::{ContractRegistryBuilder, NamespaceRegistryBuilder};
+
+    use super::*;
+
+    #[test]
+    // Asserts that the old key is replaced in the registries it owns only, without duplicating
+    // the new key where it is already an owner
+    fn test_cli_find_rotations() {
+        let contract_registries = vec![
+            ContractRegistryBuilder::new()
+                .with_name("intkey_multiply".to_string())
+                .with_owners(vec!["old".to_string(), "other".to_string()])
+                .build()
+                .unwrap(),
+            ContractRegistryBuilder::new()
+                .with_name("other".to_string())
+                .with_owners(vec!["other".to_string()])
+                .build()
+                .unwrap(),
+        ];
+        let namespace_registries = vec![NamespaceRegistryBuilder::new()
+            .with_namespace("1cf126".to_string())
+            .with_owners(vec!["new".to_string(), "old".to_string()])
+            .build()
+            .unwrap()];
+
+        assert_eq!(
+            find_rotations("old", "new", &contract_registries, &namespace_registries),
+            vec![
+                Rotation::ContractRegistry {
+                    name: "intkey_multiply".to_string(),
+                    owners: vec!["new".to_string(), "other".to_string()],
+                },
+                Rotation::NamespaceRegistry {
+                    namespace: "1cf126".to_string(),
+                    owners: vec!["new".to_string()],
+                },
+            ]
+        );
+    }
+}
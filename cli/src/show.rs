@@ -0,0 +1,157 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains writing the data of a state entry
+//!
+//! State entries, such as contracts, may be several megabytes. The REST API returns the data
+//! base64 encoded, and it is decoded and written a chunk at a time rather than all at once.
+//! Every 4 base64 characters decode to 3 bytes on their own, so a byte range is written by
+//! decoding only the chunks which hold it.
+
+use std::io::Write;
+
+use crate::error::CliError;
+use crate::to_hex;
+
+/// The number of base64 characters decoded at a time, a multiple of 4
+const CHUNK_LEN: usize = 64 * 1024;
+
+/// Returns the number of bytes the base64 data decodes to.
+pub fn decoded_len(encoded: &str) -> Result<u64, CliError> {
+    if encoded.len() % 4 != 0 {
+        return Err(CliError::User("Unable to decode state".into()));
+    }
+    let padding = encoded.bytes().rev().take_while(|b| *b == b'=').count();
+
+    Ok((encoded.len() / 4 * 3).saturating_sub(padding) as u64)
+}
+
+/// Decodes `length` bytes of the base64 data from `offset`, or to the end if no length is
+/// given, and writes them to `out` as raw bytes or hex. Returns the number of bytes written.
+pub fn write_decoded<W: Write>(
+    encoded: &str,
+    offset: u64,
+    length: Option<u64>,
+    hex: bool,
+    out: &mut W,
+) -> Result<u64, CliError> {
+    write_decoded_chunks(encoded, offset, length, hex, out, CHUNK_LEN)
+}
+
+fn write_decoded_chunks<W: Write>(
+    encoded: &str,
+    offset: u64,
+    length: Option<u64>,
+    hex: bool,
+    out: &mut W,
+    chunk_len: usize,
+) -> Result<u64, CliError> {
+    let total = decoded_len(encoded)?;
+    if offset > total {
+        return Err(CliError::User(format!(
+            "--offset {} is past the end of the {} bytes of data",
+            offset, total
+        )));
+    }
+    let mut remaining = length.unwrap_or(total - offset).min(total - offset);
+    let written = remaining;
+
+    // Start at the group of 4 characters holding the byte at the offset
+    let start = (offset / 3 * 4) as usize;
+    let mut skip = (offset % 3) as usize;
+
+    for chunk in encoded.as_bytes()[start..].chunks(chunk_len) {
+        if remaining == 0 {
+            break;
+        }
+
+        let decoded =
+            base64::decode(chunk).map_err(|_| CliError::User("Unable to decode state".into()))?;
+        let end = decoded.len().min(skip + remaining as usize);
+        let bytes = &decoded[skip.min(end)..end];
+
+        if hex {
+            out.write_all(to_hex(bytes).as_bytes())?;
+        } else {
+            out.write_all(bytes)?;
+        }
+
+        remaining -= bytes.len() as u64;
+        skip = 0;
+    }
+    out.flush()?;
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    // Asserts that any byte range is decoded across chunk boundaries, in raw bytes and hex
+    fn test_cli_write_decoded_range() {
+        let data = (0..=255u8).collect::<Vec<_>>();
+        for len in &[0, 1, 2, 3, 100, 256] {
+            let data = &data[..*len];
+            let encoded = base64::encode(data);
+            assert_eq!(decoded_len(&encoded).unwrap(), data.len() as u64);
+
+            for offset in 0..=data.len() {
+                for length in &[None, Some(0), Some(1), Some(5), Some(1000)] {
+                    let expected = match length {
+                        Some(length) => &data[offset..(offset + length).min(data.len())],
+                        None => &data[offset..],
+                    };
+
+                    let mut out = Vec::new();
+                    let written = write_decoded_chunks(
+                        &encoded,
+                        offset as u64,
+                        length.map(|length| length as u64),
+                        false,
+                        &mut out,
+                        8,
+                    )
+                    .unwrap();
+                    assert_eq!(out, expected);
+                    assert_eq!(written, expected.len() as u64);
+
+                    let mut out = Vec::new();
+                    write_decoded_chunks(
+                        &encoded,
+                        offset as u64,
+                        length.map(|length| length as u64),
+                        true,
+                        &mut out,
+                        8,
+                    )
+                    .unwrap();
+                    assert_eq!(String::from_utf8(out).unwrap(), to_hex(expected));
+                }
+            }
+        }
+    }
+
+    #[test]
+    // Asserts that an offset past the end of the data and malformed data are errors
+    fn test_cli_write_decoded_errors() {
+        let encoded = base64::encode(b"abcd");
+
+        assert!(write_decoded(&encoded, 5, None, false, &mut Vec::new()).is_err());
+        assert!(write_decoded("YWJjZA", 0, None, false, &mut Vec::new()).is_err());
+        assert!(write_decoded("YW!jZA==", 0, None, false, &mut Vec::new()).is_err());
+    }
+}
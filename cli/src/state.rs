@@ -14,37 +14,348 @@
 
 //! Contains functions which assist with fetching state
 
-use reqwest::Url;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+use reqwest::{header::ACCEPT, Url};
+use sabre_sdk::protocol::namespaces::SabreNamespace;
+use sabre_sdk::protocol::settings::Setting;
+use sabre_sdk::protocol::state::{
+    Contract, ContractList, ContractRegistry, ContractRegistryList, NamespaceRegistry,
+    NamespaceRegistryList,
+};
+use sabre_sdk::protocol::{
+    compute_contract_address, compute_contract_registry_address,
+    compute_namespace_registry_address, compute_setting_address, ADMINISTRATORS_SETTING_KEY,
+    PROTOCOL_VERSIONS_SETTING_KEY,
+};
+use sabre_sdk::protos::FromBytes;
+use sabre_sdk::trace::TRACEPARENT;
 
 use crate::error::CliError;
+use crate::rest_api::{
+    authorize, check_scheme, client, connectable, endpoints, parse_response, with_failover,
+    JSON_MEDIA_TYPE,
+};
+use crate::to_hex;
+use crate::trace;
+
+/// Whether registry and contract lookups are read from every REST API and compared
+static QUORUM_READS: AtomicBool = AtomicBool::new(false);
+
+/// Why the state of the network batches are submitted to cannot be read, if it cannot
+static UNREADABLE: OnceLock<String> = OnceLock::new();
+
+/// Records that the state of the network batches are submitted to cannot be read, so checks
+/// which read it are skipped. Only the first reason set is kept.
+pub fn set_unreadable(reason: String) {
+    let _ = UNREADABLE.set(reason);
+}
+
+/// Returns why state cannot be read, if it cannot.
+pub fn unreadable() -> Option<&'static str> {
+    UNREADABLE.get().map(String::as_str)
+}
+
+/// Makes registry and contract lookups quorum reads, which read state from every REST API in
+/// the URL list and fail unless they all return the same entries.
+pub fn set_quorum_reads(enabled: bool) {
+    QUORUM_READS.store(enabled, Ordering::Relaxed);
+}
 
 pub fn get_state_with_prefix(url: &str, prefix: &str) -> Result<Vec<StateEntry>, CliError> {
+    with_failover(url, |url| Ok(get_state_from(url, prefix, None)?.data))
+}
+
+/// Returns the registry or contract state under the prefix, as a quorum read if quorum reads
+/// are enabled.
+fn get_registry_state(url: &str, prefix: &str) -> Result<Vec<StateEntry>, CliError> {
+    if QUORUM_READS.load(Ordering::Relaxed) {
+        get_state_with_quorum(url, prefix)
+    } else {
+        get_state_with_prefix(url, prefix)
+    }
+}
+
+/// Reads the state under the prefix from every REST API, returning it only if they all hold the
+/// same entries. The first REST API's head block anchors the read, and the others are read at
+/// the same block, so REST APIs which are behind are not reported as diverging.
+pub fn get_state_with_quorum(url: &str, prefix: &str) -> Result<Vec<StateEntry>, CliError> {
+    let endpoints = endpoints(url);
+    if endpoints.len() < 2 {
+        return Err(CliError::User(
+            "Quorum reads need a comma separated list of at least two REST API URLs".into(),
+        ));
+    }
+
+    let first = get_state_from(&connectable(endpoints[0])?, prefix, None)?;
+    let head = first.head.ok_or_else(|| {
+        CliError::User(format!(
+            "REST API {} did not report the block its state was read at",
+            endpoints[0]
+        ))
+    })?;
+    let mut entries = first.data;
+    entries.sort_by(|a, b| a.address.cmp(&b.address));
+
+    for endpoint in &endpoints[1..] {
+        let mut other = get_state_from(&connectable(endpoint)?, prefix, Some(&head))
+            .map_err(|err| {
+                CliError::User(format!(
+                    "Quorum read of {} failed: REST API {} could not read state at block {}: {}",
+                    prefix, endpoint, head, err
+                ))
+            })?
+            .data;
+        other.sort_by(|a, b| a.address.cmp(&b.address));
+
+        if other != entries {
+            return Err(CliError::User(format!(
+                "Quorum read of {} failed: REST APIs {} and {} hold different state at block {}",
+                prefix, endpoints[0], endpoint, head
+            )));
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Reads the state under the prefix, at the given block if one is given, or at the head. Every
+/// page of the listing is read, at the block the first page was read at.
+fn get_state_from(url: &str, prefix: &str, head: Option<&str>) -> Result<JsonStateEntry, CliError> {
+    let mut state = get_state_page(url, prefix, head, None)?;
+    let head = state.head.clone().or_else(|| head.map(String::from));
+
+    let mut paging = state.paging.take();
+    // The next page is requested from the same REST API, rather than the URL it links to
+    while let Some(JsonPaging {
+        next: Some(_),
+        next_position: Some(start),
+    }) = paging
+    {
+        let mut page = get_state_page(url, prefix, head.as_deref(), Some(&start))?;
+        state.data.append(&mut page.data);
+        paging = page.paging;
+    }
+
+    Ok(state)
+}
+
+/// Reads a page of the state under the prefix, from the start of the listing if no start is
+/// given.
+fn get_state_page(
+    url: &str,
+    prefix: &str,
+    head: Option<&str>,
+    start: Option<&str>,
+) -> Result<JsonStateEntry, CliError> {
     let url = Url::parse(&format!(
-        "{url}/state?address={prefix}",
+        "{url}/state?address={prefix}{head}{start}",
         url = url,
-        prefix = prefix
+        prefix = prefix,
+        head = head
+            .map(|head| format!("&head={}", head))
+            .unwrap_or_default(),
+        start = start
+            .map(|start| format!("&start={}", start))
+            .unwrap_or_default()
     ))
     .map_err(|e| CliError::User(format!("Invalid URL: {}: {}", e, url)))?;
 
-    match url.scheme() {
-        "http" => (),
-        "" => return Err(CliError::User(format!("No scheme in URL: {}", url))),
-        s => {
-            return Err(CliError::User(format!(
-                "Unsupported scheme ({}) in URL: {}",
-                s, url
-            )))
-        }
-    }
+    check_scheme(&url)?;
+
+    let response = authorize(client(url.as_str())?.get(url))
+        .header(ACCEPT, JSON_MEDIA_TYPE)
+        .header(TRACEPARENT, trace::new_span().to_string())
+        .send()?;
+    parse_response::<JsonStateEntry>(response)
+}
+
+/// Returns the contract registry with the given name, if it exists
+pub fn get_contract_registry(url: &str, name: &str) -> Result<Option<ContractRegistry>, CliError> {
+    let address = to_hex(&compute_contract_registry_address(name).map_err(|err| {
+        CliError::User(format!("Unable to get contract registry address: {}", err))
+    })?);
+
+    let entry = match get_registry_state(url, &address)?.into_iter().next() {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
+
+    let registry_list = ContractRegistryList::from_bytes(
+        &base64::decode(entry.data).map_err(|_| CliError::User("Unable to decode state".into()))?,
+    )?;
+
+    Ok(registry_list
+        .registries()
+        .iter()
+        .find(|registry| registry.name() == name)
+        .cloned())
+}
+
+/// Returns the contract with the given name and version, if it exists
+pub fn get_contract(url: &str, name: &str, version: &str) -> Result<Option<Contract>, CliError> {
+    let address = to_hex(
+        &compute_contract_address(name, version)
+            .map_err(|err| CliError::User(format!("Unable to get contract address: {}", err)))?,
+    );
+
+    let entry = match get_registry_state(url, &address)?.into_iter().next() {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
+
+    let contract_list = ContractList::from_bytes(
+        &base64::decode(entry.data).map_err(|_| CliError::User("Unable to decode state".into()))?,
+    )?;
+
+    Ok(contract_list
+        .contracts()
+        .iter()
+        .find(|contract| contract.name() == name && contract.version() == version)
+        .cloned())
+}
+
+/// Returns the namespace registry for the given namespace, if it exists
+pub fn get_namespace_registry(
+    url: &str,
+    namespace: &str,
+) -> Result<Option<NamespaceRegistry>, CliError> {
+    let address = to_hex(
+        &compute_namespace_registry_address(namespace).map_err(|err| {
+            CliError::User(format!("Unable to get namespace registry address: {}", err))
+        })?,
+    );
+
+    let entry = match get_registry_state(url, &address)?.into_iter().next() {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
+
+    let registry_list = NamespaceRegistryList::from_bytes(
+        &base64::decode(entry.data).map_err(|_| CliError::User("Unable to decode state".into()))?,
+    )?;
+
+    Ok(registry_list
+        .registries()
+        .iter()
+        .find(|registry| registry.namespace() == namespace)
+        .cloned())
+}
+
+/// Returns every namespace registry in state
+pub fn get_namespace_registries(url: &str) -> Result<Vec<NamespaceRegistry>, CliError> {
+    let registry_lists = get_registry_state(url, SabreNamespace::NamespaceRegistry.prefix())?
+        .into_iter()
+        .map(|entry| {
+            base64::decode(entry.data)
+                .map_err(|_| CliError::User("Unable to decode state".into()))
+                .and_then(|bytes| {
+                    NamespaceRegistryList::from_bytes(&bytes).map_err(CliError::ProtoConversion)
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(registry_lists
+        .iter()
+        .flat_map(|registry_list| registry_list.registries())
+        .cloned()
+        .collect())
+}
+
+/// Returns every contract registry in state
+pub fn get_contract_registries(url: &str) -> Result<Vec<ContractRegistry>, CliError> {
+    let registry_lists = get_registry_state(url, SabreNamespace::ContractRegistry.prefix())?
+        .into_iter()
+        .map(|entry| {
+            base64::decode(entry.data)
+                .map_err(|_| CliError::User("Unable to decode state".into()))
+                .and_then(|bytes| {
+                    ContractRegistryList::from_bytes(&bytes).map_err(CliError::ProtoConversion)
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(registry_lists
+        .iter()
+        .flat_map(|registry_list| registry_list.registries())
+        .cloned()
+        .collect())
+}
+
+/// Returns the value of the setting with the given key, if it is set.
+pub fn get_setting(url: &str, key: &str) -> Result<Option<String>, CliError> {
+    let address = to_hex(
+        &compute_setting_address(key)
+            .map_err(|err| CliError::User(format!("Unable to get setting address: {}", err)))?,
+    );
+    let entry = match get_registry_state(url, &address)?.into_iter().next() {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
 
-    let response = reqwest::blocking::get(url)?.json::<JsonStateEntry>()?;
+    let bytes =
+        base64::decode(entry.data).map_err(|_| CliError::User("Unable to decode state".into()))?;
+    let setting = Setting::from_bytes(&bytes)?;
 
-    Ok(response.data)
+    Ok(setting.get_value(key).map(String::from))
+}
+
+/// Returns the value of the administrators setting, if it is set.
+pub fn get_administrators(url: &str) -> Result<Option<String>, CliError> {
+    get_setting(url, ADMINISTRATORS_SETTING_KEY)
+}
+
+/// Returns the Sabre protocol versions the network declares its transaction processors support,
+/// if it declares them.
+pub fn get_protocol_versions(url: &str) -> Result<Option<Vec<String>>, CliError> {
+    Ok(
+        get_setting(url, PROTOCOL_VERSIONS_SETTING_KEY)?.map(|versions| {
+            versions
+                .split(',')
+                .map(|version| version.trim().to_string())
+                .filter(|version| !version.is_empty())
+                .collect()
+        }),
+    )
+}
+
+/// Returns the kind of entry stored at an address owned by Sabre or by Sawtooth settings, if it
+/// is one.
+pub fn address_kind(address: &str) -> Option<&'static str> {
+    SabreNamespace::of_address(address).map(|namespace| namespace.description())
+}
+
+/// Returns the namespace registry whose namespace contains the address, if there is one.
+pub fn owning_namespace<'a>(
+    namespace_registries: &'a [NamespaceRegistry],
+    address: &str,
+) -> Option<&'a NamespaceRegistry> {
+    namespace_registries
+        .iter()
+        .filter(|registry| address.starts_with(registry.namespace().as_str()))
+        // The most specific namespace owns the address
+        .max_by_key(|registry| registry.namespace().len())
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 struct JsonStateEntry {
     data: Vec<StateEntry>,
+    /// The block the state was read at
+    #[serde(default)]
+    head: Option<String>,
+    #[serde(default)]
+    paging: Option<JsonPaging>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct JsonPaging {
+    /// The URL of the next page, absent on the last page
+    #[serde(default)]
+    next: Option<String>,
+    /// The position the next page starts at
+    #[serde(default)]
+    next_position: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -57,14 +368,30 @@ pub struct StateEntry {
 mod tests {
 
     use mockito;
+    use sabre_sdk::protocol::settings::SettingEntry;
+    use sabre_sdk::protocol::state::{
+        ContractRegistryBuilder, ContractRegistryListBuilder, NamespaceRegistryBuilder,
+    };
+    use sabre_sdk::protos::IntoBytes;
 
     use super::*;
 
     #[test]
-    // Asserts that URLs with a scheme other that http return an error
+    // Asserts that URLs with a scheme other than http or https return an error, and that https
+    // URLs are requested
     fn test_cli_get_state_with_prefix_scheme() {
-        assert!(get_state_with_prefix("https://test.com", "test").is_err());
-        assert!(get_state_with_prefix("file://test", "test").is_err());
+        let unreachable = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            format!("https://{}", listener.local_addr().unwrap())
+        };
+        assert!(matches!(
+            get_state_with_prefix(&unreachable, "test"),
+            Err(CliError::Request(_))
+        ));
+        assert!(matches!(
+            get_state_with_prefix("file://test", "test"),
+            Err(CliError::User(_))
+        ));
     }
 
     #[test]
@@ -72,6 +399,7 @@ mod tests {
     fn test_cli_get_state_with_prefix() {
         let url = mockito::server_url();
         let _m1 = mockito::mock("GET", "/state?address=test")
+            .with_header("content-type", "application/json")
             .with_body("{\"data\":[{\"address\": \"abc\", \"data\": \"def\"}]}")
             .create();
         let expected = vec![StateEntry {
@@ -82,4 +410,192 @@ mod tests {
 
         assert_eq!(result.unwrap(), expected);
     }
+
+    #[test]
+    // Asserts that every page of state is read, each at the block the first page was read at
+    fn test_cli_get_state_with_prefix_paging() {
+        let url = mockito::server_url();
+        let _m1 = mockito::mock("GET", "/state?address=paged")
+            .with_header("content-type", "application/json")
+            .with_body(format!(
+                "{{\"data\":[{{\"address\":\"a\",\"data\":\"YQ==\"}}],\"head\":\"b1\",\
+                 \"paging\":{{\"next\":\"{}/state?address=paged&head=b1&start=p2\",\
+                 \"next_position\":\"p2\"}}}}",
+                url
+            ))
+            .create();
+        let _m2 = mockito::mock("GET", "/state?address=paged&head=b1&start=p2")
+            .with_header("content-type", "application/json")
+            .with_body(format!(
+                "{{\"data\":[{{\"address\":\"b\",\"data\":\"Yg==\"}}],\"head\":\"b1\",\
+                 \"paging\":{{\"next\":\"{}/state?address=paged&head=b1&start=p3\",\
+                 \"next_position\":\"p3\"}}}}",
+                url
+            ))
+            .create();
+        let _m3 = mockito::mock("GET", "/state?address=paged&head=b1&start=p3")
+            .with_header("content-type", "application/json")
+            .with_body(
+                "{\"data\":[{\"address\":\"c\",\"data\":\"Yw==\"}],\"head\":\"b1\",\
+                 \"paging\":{\"start\":\"p3\"}}",
+            )
+            .create();
+
+        let entries = get_state_with_prefix(&url, "paged").unwrap();
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry.address.as_str())
+                .collect::<Vec<_>>(),
+            vec!["a", "b", "c"]
+        );
+    }
+
+    #[test]
+    // Asserts that a read is retried on the next REST API when one cannot be reached
+    fn test_cli_get_state_with_prefix_failover() {
+        let unreachable = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            format!("http://{}", listener.local_addr().unwrap())
+        };
+        let _m1 = mockito::mock("GET", "/state?address=failover")
+            .with_header("content-type", "application/json")
+            .with_body("{\"data\":[]}")
+            .create();
+
+        let url = format!("{},{}", unreachable, mockito::server_url());
+        assert_eq!(get_state_with_prefix(&url, "failover").unwrap(), vec![]);
+        assert!(get_state_with_prefix(&unreachable, "failover").is_err());
+    }
+
+    #[test]
+    // Asserts that addresses are classified by their prefix, and are owned by the most specific
+    // namespace containing them
+    fn test_cli_address_labels() {
+        assert_eq!(address_kind("00ec01abcdef"), Some("contract registry"));
+        assert_eq!(address_kind("000000abcdef"), Some("setting"));
+        assert_eq!(address_kind("1cf126abcdef"), None);
+
+        let registries = ["1cf126", "1cf12600"]
+            .iter()
+            .map(|namespace| {
+                NamespaceRegistryBuilder::new()
+                    .with_namespace(namespace.to_string())
+                    .with_owners(vec!["owner".to_string()])
+                    .build()
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            owning_namespace(&registries, "1cf12600ab")
+                .map(|registry| registry.namespace().as_str()),
+            Some("1cf12600")
+        );
+        assert_eq!(
+            owning_namespace(&registries, "1cf126ab").map(|registry| registry.namespace().as_str()),
+            Some("1cf126")
+        );
+        assert!(owning_namespace(&registries, "cad11d00").is_none());
+    }
+
+    #[test]
+    // Asserts that a quorum read reads every REST API at the first one's head block, and fails
+    // if they disagree or only one REST API is given
+    fn test_cli_get_state_with_quorum() {
+        let url = mockito::server_url();
+        let _m1 = mockito::mock("GET", "/state?address=quorum")
+            .with_header("content-type", "application/json")
+            .with_body("{\"data\":[{\"address\":\"b\",\"data\":\"YQ==\"},{\"address\":\"a\",\"data\":\"Yg==\"}],\"head\":\"b1\"}")
+            .create();
+        let _m2 = mockito::mock("GET", "/state?address=quorum&head=b1")
+            .with_header("content-type", "application/json")
+            .with_body("{\"data\":[{\"address\":\"a\",\"data\":\"Yg==\"},{\"address\":\"b\",\"data\":\"YQ==\"}],\"head\":\"b1\"}")
+            .create();
+        let _m3 = mockito::mock("GET", "/state?address=diverge")
+            .with_header("content-type", "application/json")
+            .with_body("{\"data\":[{\"address\":\"a\",\"data\":\"YQ==\"}],\"head\":\"b1\"}")
+            .create();
+        let _m4 = mockito::mock("GET", "/state?address=diverge&head=b1")
+            .with_header("content-type", "application/json")
+            .with_body("{\"data\":[{\"address\":\"a\",\"data\":\"Yg==\"}],\"head\":\"b1\"}")
+            .create();
+
+        let entries = get_state_with_quorum(&format!("{},{}", url, url), "quorum").unwrap();
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry.address.as_str())
+                .collect::<Vec<_>>(),
+            vec!["a", "b"]
+        );
+
+        match get_state_with_quorum(&format!("{},{}", url, url), "diverge") {
+            Err(CliError::User(msg)) => assert!(msg.contains("different state"), "{}", msg),
+            other => panic!("expected a divergence error, got {:?}", other),
+        }
+        assert!(get_state_with_quorum(&url, "quorum").is_err());
+    }
+
+    #[test]
+    // Asserts that get_contract_registry() decodes the registry stored at the registry address
+    fn test_cli_get_contract_registry() {
+        let registry = ContractRegistryBuilder::new()
+            .with_name("intkey_multiply".to_string())
+            .with_owners(vec!["owner".to_string()])
+            .build()
+            .unwrap();
+        let registry_list = ContractRegistryListBuilder::new()
+            .with_registries(vec![registry.clone()])
+            .build()
+            .unwrap();
+        let address = to_hex(&compute_contract_registry_address("intkey_multiply").unwrap());
+
+        let url = mockito::server_url();
+        let _m1 = mockito::mock("GET", format!("/state?address={}", address).as_str())
+            .with_header("content-type", "application/json")
+            .with_body(format!(
+                "{{\"data\":[{{\"address\": \"{}\", \"data\": \"{}\"}}]}}",
+                address,
+                base64::encode(registry_list.into_bytes().unwrap())
+            ))
+            .create();
+
+        assert_eq!(
+            get_contract_registry(&url, "intkey_multiply").unwrap(),
+            Some(registry)
+        );
+    }
+
+    #[test]
+    // Asserts that the protocol versions setting is split into its versions, and that an unset
+    // setting reads as None
+    fn test_cli_get_protocol_versions() {
+        let setting = Setting::new(vec![SettingEntry::new(
+            PROTOCOL_VERSIONS_SETTING_KEY.to_string(),
+            "1, 2".to_string(),
+        )]);
+        let address = to_hex(&compute_setting_address(PROTOCOL_VERSIONS_SETTING_KEY).unwrap());
+
+        let url = mockito::server_url();
+        let _m1 = mockito::mock("GET", format!("/state?address={}", address).as_str())
+            .with_header("content-type", "application/json")
+            .with_body(format!(
+                "{{\"data\":[{{\"address\": \"{}\", \"data\": \"{}\"}}]}}",
+                address,
+                base64::encode(setting.into_bytes().unwrap())
+            ))
+            .create();
+
+        assert_eq!(
+            get_protocol_versions(&url).unwrap(),
+            Some(vec!["1".to_string(), "2".to_string()])
+        );
+        drop(_m1);
+
+        let _m2 = mockito::mock("GET", format!("/state?address={}", address).as_str())
+            .with_header("content-type", "application/json")
+            .with_body("{\"data\":[]}")
+            .create();
+        assert_eq!(get_protocol_versions(&url).unwrap(), None);
+    }
 }
@@ -13,66 +13,408 @@
 // limitations under the License.
 
 //! Contains functions which assist with batch submission to a REST API
+//!
+//! Batches are submitted to the Sawtooth REST API, or to a Splinter scabbard service when a
+//! service id is set. The two differ in the paths batches are posted to and in the shape of
+//! the batch statuses they return, which are converted to the Sawtooth shape. With the zmq
+//! feature, batches may also be sent straight to a validator.
 
 use reqwest::{
-    header::{CONTENT_LENGTH, CONTENT_TYPE},
+    blocking::Response,
+    header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE},
     Url,
 };
+use serde_json::Value;
+use std::collections::hash_map::{Entry, RandomState};
+use std::collections::HashMap;
 use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::OnceLock;
+use std::time::Duration;
 
+use sabre_sdk::protocol::size::{estimate_batch_list, SizeLimits};
+use sabre_sdk::trace::TRACEPARENT;
 use sawtooth::protos::IntoBytes;
 use sawtooth::transact::protocol::batch::Batch;
+#[cfg(feature = "zmq")]
+use sawtooth_sdk::messages::client_batch_submit::{ClientBatchStatus, ClientBatchStatus_Status};
 
 use crate::error::CliError;
+use crate::rest_api::{
+    authorize, check_scheme, client, client_builder, parse_response, with_failover, JSON_MEDIA_TYPE,
+};
+use crate::trace;
+
+static BACKEND: OnceLock<SubmissionBackend> = OnceLock::new();
+
+/// The limits batches are estimated against before they are submitted, if estimation is enabled
+static SIZE_LIMITS: OnceLock<SizeLimits> = OnceLock::new();
+
+/// Where batches are submitted, and their statuses polled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubmissionBackend {
+    /// The Sawtooth REST API, at `/batches` and `/batch_statuses`
+    Sawtooth,
+    /// A scabbard service of a Splinter node, at `/scabbard/{circuit}/{service}/batches` and
+    /// `/scabbard/{circuit}/{service}/batch_statuses`
+    Scabbard { circuit: String, service: String },
+    /// A validator's ZMQ client interface, such as tcp://validator:4004, bypassing the REST API
+    #[cfg(feature = "zmq")]
+    Validator { endpoint: String },
+}
 
-pub fn submit_batches(url: &str, batch_list: Vec<Batch>) -> Result<String, CliError> {
-    let url = Url::parse(&format!("{}/batches", url))
-        .map_err(|e| CliError::User(format!("Invalid URL: {}: {}", e, url)))?;
+impl SubmissionBackend {
+    /// Parses a scabbard service id given as CIRCUIT::SERVICE.
+    pub fn scabbard(service_id: &str) -> Result<Self, CliError> {
+        match service_id.split_once("::") {
+            Some((circuit, service))
+                if !circuit.is_empty()
+                    && !service.is_empty()
+                    && !service_id.contains('/')
+                    && !service.contains("::") =>
+            {
+                Ok(SubmissionBackend::Scabbard {
+                    circuit: circuit.into(),
+                    service: service.into(),
+                })
+            }
+            _ => Err(CliError::User(format!(
+                "Invalid service id '{}': expected CIRCUIT::SERVICE",
+                service_id
+            ))),
+        }
+    }
+
+    fn batches_url(&self, url: &str) -> String {
+        match self {
+            SubmissionBackend::Scabbard { circuit, service } => {
+                format!("{}/scabbard/{}/{}/batches", url, circuit, service)
+            }
+            _ => format!("{}/batches", url),
+        }
+    }
 
-    match url.scheme() {
-        "http" => (),
-        "" => return Err(CliError::User(format!("No scheme in URL: {}", url))),
-        s => {
-            return Err(CliError::User(format!(
-                "Unsupported scheme ({}) in URL: {}",
-                s, url
-            )))
+    /// Returns the link to the statuses of the batches, in the form of the link returned when
+    /// they are submitted to the URL.
+    fn batch_statuses_link(&self, url: &str, batch_ids: &[&str]) -> String {
+        match self {
+            SubmissionBackend::Sawtooth => {
+                format!("{}/batch_statuses?id={}", url, batch_ids.join(","))
+            }
+            SubmissionBackend::Scabbard { circuit, service } => format!(
+                "{}/scabbard/{}/{}/batch_statuses?ids={}",
+                url,
+                circuit,
+                service,
+                batch_ids.join(",")
+            ),
+            #[cfg(feature = "zmq")]
+            SubmissionBackend::Validator { endpoint } => {
+                format!("{}/batch_statuses?id={}", endpoint, batch_ids.join(","))
+            }
         }
     }
 
+    /// Returns the batch status link in a submission response as an absolute URL. Scabbard
+    /// links are paths on the node the batches were submitted to.
+    fn status_link(&self, batches_url: &Url, link: String) -> Result<String, CliError> {
+        match self {
+            SubmissionBackend::Scabbard { .. } => batches_url
+                .join(&link)
+                .map(String::from)
+                .map_err(|e| CliError::User(format!("Invalid batch status link: {}: {}", e, link))),
+            _ => Ok(link),
+        }
+    }
+
+    fn parse_statuses(&self, link: &str, response: Response) -> Result<StatusResponse, CliError> {
+        match self {
+            SubmissionBackend::Scabbard { .. } => {
+                let batches = parse_response::<Vec<ScabbardBatchInfo>>(response)?;
+                Ok(StatusResponse {
+                    data: batches.into_iter().map(BatchStatus::from).collect(),
+                    link: link.to_string(),
+                })
+            }
+            _ => parse_response::<StatusResponse>(response),
+        }
+    }
+}
+
+/// Returns the link to the statuses of the batches submitted to the URL.
+pub fn batch_statuses_link(url: &str, batch_ids: &[&str]) -> String {
+    backend().batch_statuses_link(url, batch_ids)
+}
+
+/// Sets where the batches of this invocation are submitted. Only the first backend set is used;
+/// without one, batches are submitted to the Sawtooth REST API.
+pub fn set_backend(backend: SubmissionBackend) {
+    let _ = BACKEND.set(backend);
+}
+
+fn backend() -> &'static SubmissionBackend {
+    BACKEND.get_or_init(|| SubmissionBackend::Sawtooth)
+}
+
+/// Enables the estimation of batch sizes before submission, against the limits. Only the first
+/// limits set are used.
+pub fn set_size_limits(limits: SizeLimits) {
+    let _ = SIZE_LIMITS.set(limits);
+}
+
+/// The fraction of a limit beyond which a batch list is reported as close to it
+const SIZE_WARNING_FRACTION: f64 = 0.9;
+
+/// Prints the sizes of the batches and their transactions, warning of sizes close to the limits,
+/// and returns an error if any limit is exceeded.
+fn estimate_sizes(batch_list: &[Batch], limits: &SizeLimits) -> Result<(), CliError> {
+    let size = estimate_batch_list(batch_list)?;
+
+    println!("Estimated sizes:");
+    for batch in &size.batches {
+        println!(
+            "    Batch {}: {} bytes, {} transactions",
+            batch.id,
+            batch.bytes,
+            batch.transactions.len()
+        );
+        for transaction in &batch.transactions {
+            println!(
+                "        Transaction {}: {} bytes",
+                transaction.id, transaction.bytes
+            );
+        }
+    }
+    println!(
+        "    Batch list: {} of at most {} bytes",
+        size.bytes, limits.max_batch_list_bytes
+    );
+
+    let violations = size.check(limits);
+    if !violations.is_empty() {
+        return Err(CliError::User(format!(
+            "Not submitting: {}",
+            violations
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("; ")
+        )));
+    }
+    if size.bytes as f64 > limits.max_batch_list_bytes as f64 * SIZE_WARNING_FRACTION {
+        eprintln!(
+            "Warning: batch list is {} bytes, close to the limit of {} bytes",
+            size.bytes, limits.max_batch_list_bytes
+        );
+    }
+
+    Ok(())
+}
+
+/// The ids of a submitted batch and of its transactions, by which their statuses and receipts
+/// are queried
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SubmittedBatch {
+    pub id: String,
+    pub transaction_ids: Vec<String>,
+}
+
+impl SubmittedBatch {
+    fn new(batch: &Batch) -> Self {
+        SubmittedBatch {
+            id: batch.header_signature().to_string(),
+            transaction_ids: batch
+                .transactions()
+                .iter()
+                .map(|transaction| transaction.header_signature().to_string())
+                .collect(),
+        }
+    }
+}
+
+/// The batches submitted together, and the link to their statuses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Submission {
+    pub link: String,
+    pub batches: Vec<SubmittedBatch>,
+}
+
+/// Submits the batches, printing the ids of each batch and its transactions.
+pub fn submit_batches(url: &str, batch_list: Vec<Batch>) -> Result<Submission, CliError> {
+    submit_batches_to(backend(), url, batch_list)
+}
+
+fn submit_batches_to(
+    backend: &SubmissionBackend,
+    url: &str,
+    batch_list: Vec<Batch>,
+) -> Result<Submission, CliError> {
+    if let Some(limits) = SIZE_LIMITS.get() {
+        estimate_sizes(&batch_list, limits)?;
+    }
+
+    let batches = batch_list
+        .iter()
+        .map(SubmittedBatch::new)
+        .collect::<Vec<_>>();
     let bytes = batch_list.into_bytes()?;
-    let client = reqwest::blocking::Client::new();
-    let response = client
-        .post(url)
+
+    let link = send_batch_list(backend, url, &bytes, &batches)?;
+
+    for batch in &batches {
+        println!("Batch ID: {}", batch.id);
+        for transaction_id in &batch.transaction_ids {
+            println!("    Transaction ID: {}", transaction_id);
+        }
+    }
+
+    Ok(Submission { link, batches })
+}
+
+/// Sends the serialized batch list, returning the link to the statuses of its batches.
+fn send_batch_list(
+    backend: &SubmissionBackend,
+    url: &str,
+    bytes: &[u8],
+    #[cfg_attr(not(feature = "zmq"), allow(unused_variables))] batches: &[SubmittedBatch],
+) -> Result<String, CliError> {
+    // The validator returns no link, so one is made in the form of the REST API's
+    #[cfg(feature = "zmq")]
+    {
+        if let SubmissionBackend::Validator { endpoint } = backend {
+            crate::validator::submit_batches(endpoint, bytes)?;
+            let ids = batches
+                .iter()
+                .map(|batch| batch.id.as_str())
+                .collect::<Vec<_>>();
+            return Ok(backend.batch_statuses_link(url, &ids));
+        }
+    }
+
+    with_failover(url, |url| submit_bytes(backend, url, bytes))
+}
+
+fn submit_bytes(backend: &SubmissionBackend, url: &str, bytes: &[u8]) -> Result<String, CliError> {
+    let url = Url::parse(&backend.batches_url(url))
+        .map_err(|e| CliError::User(format!("Invalid URL: {}: {}", e, url)))?;
+
+    check_scheme(&url)?;
+
+    let client = client(url.as_str())?;
+    let response = authorize(client.post(url.clone()))
         .header(CONTENT_TYPE, "application/octet-stream")
         .header(CONTENT_LENGTH, bytes.len())
-        .body(bytes)
-        .send()?
-        .json::<Link>()?;
+        .header(ACCEPT, JSON_MEDIA_TYPE)
+        .header(TRACEPARENT, trace::new_span().to_string())
+        .body(bytes.to_vec())
+        .send()?;
+    let response = parse_response::<Link>(response)?;
 
     println!("Response Body:\n{:?}", response);
+    println!("Trace ID: {}", trace::trace().trace_id());
 
-    Ok(response.link)
+    backend.status_link(&url, response.link)
 }
 
-pub fn wait_for_batch(url: &str, wait: u64) -> Result<StatusResponse, CliError> {
-    let url = Url::parse(&format!("{url}&wait={wait}", url = url, wait = wait))
-        .map_err(|e| CliError::User(format!("Invalid URL: {}: {}", e, url)))?;
+/// Polls the status of submitted batches, reusing one keep-alive connection to each REST API
+/// across polls, and pausing for a jittered, growing interval between them so many clients
+/// polling at once do not poll in step.
+pub struct StatusPoller {
+    clients: HashMap<String, reqwest::blocking::Client>,
+    backend: SubmissionBackend,
+    polls: u32,
+}
+
+impl StatusPoller {
+    pub fn new() -> Result<Self, CliError> {
+        Self::with_backend(backend().clone())
+    }
 
-    match url.scheme() {
-        "http" => (),
-        "" => return Err(CliError::User(format!("No scheme in URL: {}", url))),
-        s => {
-            return Err(CliError::User(format!(
-                "Unsupported scheme ({}) in URL: {}",
-                s, url
-            )))
+    fn with_backend(backend: SubmissionBackend) -> Result<Self, CliError> {
+        Ok(StatusPoller {
+            clients: HashMap::new(),
+            backend,
+            polls: 0,
+        })
+    }
+
+    /// Returns the client polling the REST API the URL is on.
+    fn client(&mut self, url: &Url) -> Result<&reqwest::blocking::Client, CliError> {
+        match self.clients.entry(url.origin().ascii_serialization()) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => Ok(entry.insert(
+                client_builder(url.as_str())
+                    .tcp_keepalive(Some(POLL_KEEPALIVE))
+                    .pool_max_idle_per_host(1)
+                    .build()?,
+            )),
+        }
+    }
+
+    /// Returns the statuses at the batch status link, which the REST API waits up to `wait`
+    /// seconds to return until the batches are committed or invalid.
+    pub fn wait_for_batch(&mut self, link: &str, wait: u64) -> Result<StatusResponse, CliError> {
+        #[cfg(feature = "zmq")]
+        {
+            if let SubmissionBackend::Validator { endpoint } = &self.backend {
+                let batch_ids = link
+                    .split_once("?id=")
+                    .map(|(_, ids)| ids.split(',').map(String::from).collect::<Vec<_>>())
+                    .unwrap_or_default();
+                let statuses = crate::validator::batch_statuses(endpoint, &batch_ids, wait)?;
+                self.polls += 1;
+
+                return Ok(StatusResponse {
+                    data: statuses.into_iter().map(BatchStatus::from).collect(),
+                    link: link.to_string(),
+                });
+            }
         }
+
+        let url = Url::parse(&format!("{link}&wait={wait}", link = link, wait = wait))
+            .map_err(|e| CliError::User(format!("Invalid URL: {}: {}", e, link)))?;
+
+        check_scheme(&url)?;
+
+        let response = authorize(self.client(&url)?.get(url))
+            .header(ACCEPT, JSON_MEDIA_TYPE)
+            .header(TRACEPARENT, trace::new_span().to_string())
+            .send()?;
+        let response = self.backend.parse_statuses(link, response)?;
+        self.polls += 1;
+
+        Ok(response)
+    }
+
+    /// Sleeps before the next poll, for no longer than `remaining`.
+    pub fn pause(&self, remaining: Duration) {
+        std::thread::sleep(poll_delay(self.polls, random_fraction()).min(remaining));
     }
+}
 
-    let response = reqwest::blocking::get(url)?.json::<StatusResponse>()?;
+/// The interval between the TCP keep-alive probes of the polling connection
+const POLL_KEEPALIVE: Duration = Duration::from_secs(30);
+/// The pause after the first poll, which doubles after each poll up to POLL_DELAY_MAX
+const POLL_DELAY_BASE: Duration = Duration::from_millis(500);
+const POLL_DELAY_MAX: Duration = Duration::from_secs(8);
+
+/// Returns the pause after the given number of polls, which is between half and all of the
+/// backed off interval, as chosen by `jitter` in [0, 1).
+fn poll_delay(polls: u32, jitter: f64) -> Duration {
+    let interval = POLL_DELAY_BASE
+        .checked_mul(1 << polls.saturating_sub(1).min(16))
+        .unwrap_or(POLL_DELAY_MAX)
+        .min(POLL_DELAY_MAX);
+
+    interval.div_f64(2.0) + interval.mul_f64(jitter.clamp(0.0, 1.0) / 2.0)
+}
 
-    Ok(response)
+/// Returns a random fraction in [0, 1).
+fn random_fraction() -> f64 {
+    // Each RandomState is seeded differently, which makes its hashes random
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(0);
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
 }
 
 #[derive(Deserialize, Debug, PartialEq, Eq)]
@@ -99,7 +441,96 @@ pub struct StatusResponse {
     link: String,
 }
 
+/// The status of a batch as a scabbard service reports it
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+struct ScabbardBatchInfo {
+    id: String,
+    status: ScabbardBatchStatus,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(tag = "statusType", content = "message")]
+enum ScabbardBatchStatus {
+    Unknown,
+    Pending,
+    Invalid(Vec<ScabbardInvalidTransaction>),
+    Valid(Vec<Value>),
+    Committed(Vec<Value>),
+}
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+struct ScabbardInvalidTransaction {
+    transaction_id: String,
+    error_message: String,
+}
+
+impl From<ScabbardBatchInfo> for BatchStatus {
+    fn from(info: ScabbardBatchInfo) -> Self {
+        // A valid batch has been executed, but is not yet committed
+        let (status, invalid_transactions) = match info.status {
+            ScabbardBatchStatus::Unknown => ("UNKNOWN", vec![]),
+            ScabbardBatchStatus::Pending | ScabbardBatchStatus::Valid(_) => ("PENDING", vec![]),
+            ScabbardBatchStatus::Committed(_) => ("COMMITTED", vec![]),
+            ScabbardBatchStatus::Invalid(transactions) => (
+                "INVALID",
+                transactions
+                    .into_iter()
+                    .map(|transaction| InvalidTransaction {
+                        id: transaction.transaction_id,
+                        message: transaction.error_message,
+                    })
+                    .collect(),
+            ),
+        };
+
+        BatchStatus {
+            id: info.id,
+            status: status.into(),
+            invalid_transactions,
+        }
+    }
+}
+
+#[cfg(feature = "zmq")]
+impl From<ClientBatchStatus> for BatchStatus {
+    fn from(mut status: ClientBatchStatus) -> Self {
+        let status_name = match status.get_status() {
+            ClientBatchStatus_Status::COMMITTED => "COMMITTED",
+            ClientBatchStatus_Status::INVALID => "INVALID",
+            ClientBatchStatus_Status::PENDING => "PENDING",
+            _ => "UNKNOWN",
+        };
+
+        BatchStatus {
+            id: status.take_batch_id(),
+            status: status_name.into(),
+            invalid_transactions: status
+                .take_invalid_transactions()
+                .into_iter()
+                .map(|mut transaction| InvalidTransaction {
+                    id: transaction.take_transaction_id(),
+                    message: transaction.take_message(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl BatchStatus {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+}
+
 impl StatusResponse {
+    pub fn batch_statuses(&self) -> &[BatchStatus] {
+        &self.data
+    }
+
     pub fn is_finished(&self) -> bool {
         self.data.iter().all(|x| x.status == "COMMITTED")
             || self.data.iter().any(|x| x.status == "INVALID")
@@ -206,31 +637,135 @@ mod tests {
         }
     }
 
+    /// Returns an https URL at which nothing is listening
+    fn unreachable_https_url() -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        format!("https://{}", listener.local_addr().unwrap())
+    }
+
     #[test]
-    // Asserts that URLs with a scheme other that http return an error
+    // Asserts that URLs with a scheme other than http or https return an error, and that https
+    // URLs are requested
     fn test_cli_submit_batches_scheme() {
-        assert!(submit_batches("https://test.com", vec![MockBatch::new()]).is_err());
-        assert!(submit_batches("file://test", vec![MockBatch::new()]).is_err());
+        assert!(matches!(
+            submit_batches(&unreachable_https_url(), vec![MockBatch::new()]),
+            Err(CliError::Request(_))
+        ));
+        assert!(matches!(
+            submit_batches("file://test", vec![MockBatch::new()]),
+            Err(CliError::User(_))
+        ));
     }
 
     #[test]
-    // Asserts that submit_batches() returns data as expected
+    // Asserts that submit_batches() returns the link, and the ids of the batches and their
+    // transactions
     fn test_cli_submit_batches() {
         let url = mockito::server_url();
         let _m1 = mockito::mock("POST", "/batches")
+            .with_header("content-type", "application/json")
             .with_body("{\"link\":\"test.com/success\"}")
             .create();
-        let expected = "test.com/success".to_string();
-        let result = submit_batches(&url, vec![MockBatch::new()]);
+        let batch = MockBatch::new();
+        let expected = Submission {
+            link: "test.com/success".to_string(),
+            batches: vec![SubmittedBatch {
+                id: batch.header_signature().to_string(),
+                transaction_ids: vec![batch.transactions()[0].header_signature().to_string()],
+            }],
+        };
+        let result = submit_batches(&url, vec![batch]);
 
         assert_eq!(result.unwrap(), expected);
     }
 
     #[test]
-    // Asserts that URLs with a scheme other that http return an error
+    // Asserts that batches are posted to the scabbard service, whose batch status link is made
+    // absolute, and that its batch statuses are converted to those of the Sawtooth REST API
+    fn test_cli_submit_batches_scabbard() {
+        let url = mockito::server_url();
+        let _m1 = mockito::mock("POST", "/scabbard/abcde-01234/a000/batches")
+            .with_header("content-type", "application/json")
+            .with_body("{\"link\":\"/scabbard/abcde-01234/a000/batch_statuses?ids=1,2\"}")
+            .create();
+        let _m2 = mockito::mock(
+            "GET",
+            "/scabbard/abcde-01234/a000/batch_statuses?ids=1,2&wait=30",
+        )
+        .with_header("content-type", "application/json")
+        .with_body(
+            "[{\"id\":\"1\",\"status\":{\"statusType\":\"Committed\",\"message\":[]}},\
+                  {\"id\":\"2\",\"status\":{\"statusType\":\"Invalid\",\"message\":[\
+                  {\"transaction_id\":\"3\",\"error_message\":\"bad\",\"error_data\":[]}]}}]",
+        )
+        .create();
+
+        let backend = SubmissionBackend::scabbard("abcde-01234::a000").unwrap();
+        let submission = submit_batches_to(&backend, &url, vec![MockBatch::new()]).unwrap();
+        let link = format!("{}/scabbard/abcde-01234/a000/batch_statuses?ids=1,2", url);
+        assert_eq!(submission.link, link);
+
+        let mut poller = StatusPoller::with_backend(backend).unwrap();
+        let response = poller.wait_for_batch(&submission.link, 30).unwrap();
+        assert_eq!(
+            response,
+            StatusResponse {
+                data: vec![
+                    BatchStatus {
+                        id: "1".into(),
+                        status: "COMMITTED".into(),
+                        invalid_transactions: vec![],
+                    },
+                    BatchStatus {
+                        id: "2".into(),
+                        status: "INVALID".into(),
+                        invalid_transactions: vec![InvalidTransaction {
+                            id: "3".into(),
+                            message: "bad".into(),
+                        }],
+                    },
+                ],
+                link,
+            }
+        );
+        assert!(response.is_finished());
+
+        assert!(SubmissionBackend::scabbard("abcde-01234").is_err());
+        assert!(SubmissionBackend::scabbard("::a000").is_err());
+        assert!(SubmissionBackend::scabbard("abcde/01234::a000").is_err());
+    }
+
+    #[test]
+    // Asserts that batches within the size limits may be submitted, and that those beyond them
+    // are refused
+    fn test_cli_estimate_sizes() {
+        let batches = vec![MockBatch::new()];
+        assert!(estimate_sizes(&batches, &SizeLimits::default()).is_ok());
+
+        let limits = SizeLimits {
+            max_batch_list_bytes: 64,
+            ..SizeLimits::default()
+        };
+        assert!(matches!(
+            estimate_sizes(&batches, &limits),
+            Err(CliError::User(_))
+        ));
+    }
+
+    #[test]
+    // Asserts that URLs with a scheme other than http or https return an error, and that https
+    // URLs are requested
     fn test_cli_wait_for_batches_scheme() {
-        assert!(submit_batches("https://test.com", vec![MockBatch::new()]).is_err());
-        assert!(submit_batches("file://test", vec![MockBatch::new()]).is_err());
+        let mut poller = StatusPoller::new().unwrap();
+        let unreachable = format!("{}/batch_statuses?id=1", unreachable_https_url());
+        assert!(matches!(
+            poller.wait_for_batch(&unreachable, 1),
+            Err(CliError::Request(_))
+        ));
+        assert!(matches!(
+            poller.wait_for_batch("file://test/batch_statuses?id=1", 1),
+            Err(CliError::User(_))
+        ));
     }
 
     #[test]
@@ -238,14 +773,33 @@ mod tests {
     fn test_cli_wait_for_batch() {
         let url = mockito::server_url();
         let _m1 = mockito::mock("GET", "/test?foo=bar&wait=30")
+            .with_header("content-type", "application/json")
             .with_body("{\"data\":[], \"link\":\"test.com/success\"}")
+            .expect(2)
             .create();
         let expected = StatusResponse {
             data: Vec::new(),
             link: "test.com/success".to_string(),
         };
-        let result = wait_for_batch(&format!("{}/test?foo=bar", &url), 30);
+        let mut poller = StatusPoller::new().unwrap();
+        let result = poller.wait_for_batch(&format!("{}/test?foo=bar", &url), 30);
+        assert_eq!(result.unwrap(), expected);
 
+        let result = poller.wait_for_batch(&format!("{}/test?foo=bar", &url), 30);
         assert_eq!(result.unwrap(), expected);
+        _m1.assert();
+    }
+
+    #[test]
+    // Asserts that the pause between polls backs off up to its maximum, and is jittered
+    // between half and all of the interval
+    fn test_cli_poll_delay() {
+        assert_eq!(poll_delay(1, 0.0), Duration::from_millis(250));
+        assert_eq!(poll_delay(1, 1.0), Duration::from_millis(500));
+        assert_eq!(poll_delay(3, 0.5), Duration::from_millis(1500));
+        assert_eq!(poll_delay(100, 1.0), POLL_DELAY_MAX);
+
+        let fraction = random_fraction();
+        assert!((0.0..1.0).contains(&fraction));
     }
 }
@@ -0,0 +1,99 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains the trace each invocation of the CLI makes its requests and transactions in
+//!
+//! Each transaction's nonce is a traceparent naming a new span in the trace, which the
+//! transaction processor logs, and each request to the REST API carries one in its
+//! traceparent header.
+
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sabre_sdk::trace::TraceParent;
+
+/// The environment variable holding a traceparent whose trace the CLI should join
+pub const TRACEPARENT_ENV_VAR: &str = "SABRE_TRACEPARENT";
+
+static TRACE: OnceLock<TraceParent> = OnceLock::new();
+
+/// Returns the trace of this invocation, which is the one in $SABRE_TRACEPARENT if it holds a
+/// valid traceparent, or a new one.
+pub fn trace() -> &'static TraceParent {
+    TRACE.get_or_init(|| {
+        env::var(TRACEPARENT_ENV_VAR)
+            .ok()
+            .and_then(|value| TraceParent::parse(&value).ok())
+            .unwrap_or_else(|| {
+                TraceParent::new(&random_hex(2), &random_hex(1)).expect("random ids are valid hex")
+            })
+    })
+}
+
+/// Returns a traceparent for a new span in this invocation's trace.
+pub fn new_span() -> TraceParent {
+    trace()
+        .with_parent_id(&random_hex(1))
+        .expect("random ids are valid hex")
+}
+
+/// Returns a nonce for a transaction, which is a traceparent for a new span. The span id makes
+/// the nonce unique.
+pub fn transaction_nonce() -> Vec<u8> {
+    new_span().to_string().into_bytes()
+}
+
+/// Returns the given number of random 64 bit words as hex. The words are never zero, so the
+/// result is a valid trace or span id.
+fn random_hex(words: usize) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or(0);
+
+    (0..words)
+        .map(|i| {
+            // Each RandomState is seeded differently, which makes its hashes random
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_usize(i);
+            hasher.write_u128(nanos);
+            format!("{:016x}", hasher.finish().max(1))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // Asserts that the spans of an invocation share its trace id and have distinct span ids
+    fn test_cli_new_span() {
+        let first = new_span();
+        let second = new_span();
+
+        assert_eq!(first.trace_id(), trace().trace_id());
+        assert_eq!(second.trace_id(), trace().trace_id());
+        assert_ne!(first.parent_id(), second.parent_id());
+        assert_eq!(
+            TraceParent::parse(&String::from_utf8(transaction_nonce()).unwrap())
+                .unwrap()
+                .trace_id(),
+            trace().trace_id()
+        );
+    }
+}
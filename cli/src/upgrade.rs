@@ -0,0 +1,186 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains functions which assist with upgrading a contract to a new version
+
+use sabre_sdk::protocol::payload::CreateNamespaceRegistryPermissionActionBuilder;
+use sabre_sdk::protocol::state::ContractRegistry;
+
+use crate::batching::{build_batches, BatchLimits};
+use crate::error::CliError;
+use crate::key::new_signer;
+use crate::parse_name_version;
+use crate::state::{get_contract_registry, get_namespace_registries};
+use crate::submit::{submit_batches, Submission};
+use crate::trace;
+use crate::upload::build_upload_batch;
+
+/// Uploads a new version of a contract and copies the namespace permissions granted to the
+/// previous contract's name, printing the commands which undo the upgrade.
+///
+/// The previous contract is given by `from` in the form 'name:version'. If it is not given, the
+/// latest registered version of the contract being uploaded is used.
+pub fn do_upgrade(
+    filename: &str,
+    key_name: Option<&str>,
+    key_algo: Option<&str>,
+    url: &str,
+    wasm_name: Option<&str>,
+    from: Option<&str>,
+) -> Result<Submission, CliError> {
+    let signer = new_signer(key_name, key_algo)?;
+    let (name, version, upload_batch) = build_upload_batch(filename, wasm_name, &*signer)?;
+
+    let (from_name, from_version) = match from {
+        Some(from) => parse_name_version(from)
+            .ok_or_else(|| CliError::User("--from must be of the form 'name:version'".into()))?,
+        None => (name.as_str(), ""),
+    };
+
+    let registry = get_contract_registry(url, from_name)?
+        .ok_or_else(|| CliError::User(format!("contract registry '{}' not found", from_name)))?;
+    let from_version = if from_version.is_empty() {
+        previous_version(&registry)?
+    } else if registry
+        .versions()
+        .iter()
+        .any(|registered| registered.version() == from_version)
+    {
+        from_version.to_string()
+    } else {
+        return Err(CliError::User(format!(
+            "contract '{}:{}' not found",
+            from_name, from_version
+        )));
+    };
+
+    if from_name == name
+        && registry
+            .versions()
+            .iter()
+            .any(|registered| registered.version() == &version)
+    {
+        return Err(CliError::User(format!(
+            "contract '{}:{}' is already registered",
+            name, version
+        )));
+    }
+
+    // Namespace permissions are granted to a contract name, so they only need to be copied when
+    // the new version is uploaded under a different name
+    let mut permission_transactions = Vec::new();
+    let mut copied_namespaces = Vec::new();
+    if from_name != name {
+        for registry in get_namespace_registries(url)? {
+            for permission in registry
+                .permissions()
+                .iter()
+                .filter(|permission| permission.contract_name() == from_name)
+            {
+                let transaction = CreateNamespaceRegistryPermissionActionBuilder::new()
+                    .with_namespace(registry.namespace().to_string())
+                    .with_contract_name(name.clone())
+                    .with_read(permission.read())
+                    .with_write(permission.write())
+                    .into_payload_builder()?
+                    .into_transaction_builder()?
+                    .with_nonce(trace::transaction_nonce())
+                    .build(&*signer)?;
+
+                permission_transactions.push(transaction);
+                copied_namespaces.push(registry.namespace().to_string());
+            }
+        }
+    }
+
+    let mut batches = vec![upload_batch];
+    batches.extend(build_batches(
+        permission_transactions,
+        &BatchLimits::default(),
+        &*signer,
+    )?);
+
+    let submission = submit_batches(url, batches)?;
+
+    println!(
+        "Upgraded {}:{} to {}:{}",
+        from_name, from_version, name, version
+    );
+    for namespace in &copied_namespaces {
+        println!("Copied permissions on namespace {} to {}", namespace, name);
+    }
+    println!(
+        "To roll back, execute {}:{} in place of {}:{}",
+        from_name, from_version, name, version
+    );
+    for namespace in &copied_namespaces {
+        println!("  sabre perm --url {} --delete {} {}", url, namespace, name);
+    }
+
+    Ok(submission)
+}
+
+/// Returns the most recently registered version in the contract registry.
+fn previous_version(registry: &ContractRegistry) -> Result<String, CliError> {
+    registry
+        .versions()
+        .last()
+        .map(|version| version.version().to_string())
+        .ok_or_else(|| {
+            CliError::User(format!(
+                "contract registry '{}' has no versions to upgrade from",
+                registry.name()
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use sabre_sdk::protocol::state::{ContractRegistryBuilder, VersionBuilder};
+
+    use super::*;
+
+    fn registry_with_versions(versions: &[&str]) -> ContractRegistry {
+        ContractRegistryBuilder::new()
+            .with_name("intkey_multiply".to_string())
+            .with_versions(
+                versions
+                    .iter()
+                    .map(|version| {
+                        VersionBuilder::new()
+                            .with_version(version.to_string())
+                            .with_contract_sha512("sha512".to_string())
+                            .with_creator("creator".to_string())
+                            .build()
+                            .unwrap()
+                    })
+                    .collect(),
+            )
+            .with_owners(vec!["owner".to_string()])
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    // Asserts that the latest registered version is the one upgraded from, and that a registry
+    // without versions is an error
+    fn test_cli_upgrade_previous_version() {
+        let registry = registry_with_versions(&["1.0", "1.1"]);
+        assert_eq!(previous_version(&registry).unwrap(), "1.1");
+
+        let registry = registry_with_versions(&[]);
+        assert!(previous_version(&registry).is_err());
+    }
+}
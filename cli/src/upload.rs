@@ -18,19 +18,358 @@ use std::io::BufReader;
 use std::path::Path;
 use std::path::PathBuf;
 
+use cylinder::Signer;
+use flate2::read::GzDecoder;
 use sabre_sdk::protocol::payload::CreateContractActionBuilder;
+use sawtooth::transact::protocol::{
+    batch::{Batch, BatchBuilder},
+    transaction::Transaction,
+};
 use yaml_rust::YamlLoader;
 
+use crate::batching::{build_batches, BatchLimits};
+use crate::dependencies::{install_order, parse_dependencies, Dependency};
 use crate::error::CliError;
 use crate::key::new_signer;
-use crate::submit::submit_batches;
+use crate::lint;
+use crate::state::{get_contract, get_contract_registry};
+use crate::submit::{submit_batches, Submission};
+use crate::trace;
 
 pub fn do_upload(
     filename: &str,
     key_name: Option<&str>,
+    key_algo: Option<&str>,
     url: &str,
     wasm_name: Option<&str>,
-) -> Result<String, CliError> {
+    if_not_exists: bool,
+    deny_floats: bool,
+) -> Result<Option<Submission>, CliError> {
+    let (definition, contract) = load_contract(filename, wasm_name)?;
+    check_floats(&definition, &contract, deny_floats)?;
+
+    if if_not_exists && contract_exists(url, &definition, &contract)? {
+        println!(
+            "Contract {}:{} already exists",
+            definition.name, definition.version
+        );
+        return Ok(None);
+    }
+
+    check_dependencies(url, &definition, &[])?;
+
+    let signer = new_signer(key_name, key_algo)?;
+    let transaction = build_transaction(definition, contract, &*signer)?;
+
+    submit_batches(
+        url,
+        build_batches(vec![transaction], &BatchLimits::default(), &*signer)?,
+    )
+    .map(Some)
+}
+
+/// Uploads every contract in a .tar.gz archive, packing the contracts into as few batches as
+/// the batch limits allow.
+///
+/// Each contract definition file (*.yaml) in the archive is the manifest of a contract, whose
+/// wasm file is found relative to it.
+pub fn do_upload_archive(
+    archive: &str,
+    key_name: Option<&str>,
+    key_algo: Option<&str>,
+    url: &str,
+    if_not_exists: bool,
+    deny_floats: bool,
+) -> Result<Option<Submission>, CliError> {
+    // The archive is unpacked into a private directory, which is removed when dropped; the
+    // unpacked files are only needed while the batches are built
+    let dir = tempfile::Builder::new()
+        .prefix("sabre-upload-")
+        .tempdir()
+        .map_err(|e| {
+            CliError::User(format!(
+                "Could not create a directory to unpack \"{}\" into: {}",
+                archive, e
+            ))
+        })?;
+    unpack_archive(archive, dir.path())?;
+
+    upload_dir(
+        dir.path(),
+        key_name,
+        key_algo,
+        url,
+        if_not_exists,
+        deny_floats,
+    )
+}
+
+fn unpack_archive(archive: &str, dir: &Path) -> Result<(), CliError> {
+    let file = File::open(archive)
+        .map_err(|e| CliError::User(format!("Could not load archive \"{}\": {}", archive, e)))?;
+
+    let unpack_error = |e: std::io::Error| {
+        CliError::User(format!("Could not unpack archive \"{}\": {}", archive, e))
+    };
+
+    // Links could point the search for contract definitions anywhere on the filesystem, so an
+    // archive containing one is rejected; the entries unpacked before it are removed with the
+    // directory
+    let mut unpacked = tar::Archive::new(GzDecoder::new(file));
+    for entry in unpacked.entries().map_err(unpack_error)? {
+        let mut entry = entry.map_err(unpack_error)?;
+        let entry_type = entry.header().entry_type();
+        if entry_type.is_symlink() || entry_type.is_hard_link() {
+            return Err(CliError::User(format!(
+                "Archive \"{}\" contains a link, {}, which contract archives may not contain",
+                archive,
+                entry.path().map_err(unpack_error)?.display()
+            )));
+        }
+        entry.unpack_in(dir).map_err(unpack_error)?;
+    }
+
+    Ok(())
+}
+
+fn upload_dir(
+    dir: &Path,
+    key_name: Option<&str>,
+    key_algo: Option<&str>,
+    url: &str,
+    if_not_exists: bool,
+    deny_floats: bool,
+) -> Result<Option<Submission>, CliError> {
+    let mut definition_files = Vec::new();
+    find_definition_files(dir, &mut definition_files)?;
+    definition_files.sort();
+    if definition_files.is_empty() {
+        return Err(CliError::User(
+            "archive contains no contract definition files (*.yaml)".into(),
+        ));
+    }
+
+    // Every contract is loaded and validated before any is submitted
+    let mut contracts = Vec::new();
+    for definition_file in &definition_files {
+        let filename = definition_file.to_str().ok_or_else(|| {
+            CliError::User(format!(
+                "Invalid file name in archive: {}",
+                definition_file.display()
+            ))
+        })?;
+        let (definition, contract) = load_contract(filename, None)?;
+        validate_wasm(&contract).map_err(|msg| {
+            CliError::User(format!(
+                "Invalid contract {}:{}: {}",
+                definition.name, definition.version, msg
+            ))
+        })?;
+        check_floats(&definition, &contract, deny_floats)?;
+        contracts.push((definition, contract));
+    }
+
+    // Contracts are uploaded after the contracts in the archive they depend on
+    let order = install_order(
+        &contracts
+            .iter()
+            .map(|(definition, _)| {
+                (
+                    definition.name.as_str(),
+                    definition.version.as_str(),
+                    &definition.dependencies[..],
+                )
+            })
+            .collect::<Vec<_>>(),
+    )
+    .map_err(CliError::User)?;
+    let mut contracts = contracts.into_iter().map(Some).collect::<Vec<_>>();
+    let contracts = order
+        .into_iter()
+        .filter_map(|i| contracts[i].take())
+        .collect::<Vec<_>>();
+
+    let signer = new_signer(key_name, key_algo)?;
+    let mut transactions = Vec::new();
+    let mut uploaded = Vec::new();
+    for (definition, contract) in contracts {
+        let name_version = format!("{}:{}", definition.name, definition.version);
+        if if_not_exists && contract_exists(url, &definition, &contract)? {
+            println!("{}: already exists", name_version);
+            uploaded.push((definition.name, definition.version));
+            continue;
+        }
+
+        check_dependencies(url, &definition, &uploaded)?;
+        uploaded.push((definition.name.clone(), definition.version.clone()));
+
+        let transaction = build_transaction(definition, contract, &*signer)?;
+        println!(
+            "{}: transaction {}",
+            name_version,
+            transaction.header_signature()
+        );
+        transactions.push(transaction);
+    }
+
+    if transactions.is_empty() {
+        return Ok(None);
+    }
+
+    // The transactions are packed in install order, so no contract is created before those it
+    // depends on
+    submit_batches(
+        url,
+        build_batches(transactions, &BatchLimits::default(), &*signer)?,
+    )
+    .map(Some)
+}
+
+/// Collects the paths of the contract definition files in the directory and its subdirectories.
+fn find_definition_files(dir: &Path, definition_files: &mut Vec<PathBuf>) -> Result<(), CliError> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        // The file type is that of the entry itself, so links are not followed
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+        if file_type.is_symlink() {
+            continue;
+        } else if file_type.is_dir() {
+            find_definition_files(&path, definition_files)?;
+        } else if path.extension().map_or(false, |ext| ext == "yaml") {
+            definition_files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that the contract is a WebAssembly binary module.
+fn validate_wasm(contract: &[u8]) -> Result<(), String> {
+    match contract.get(..8) {
+        Some([0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00]) => Ok(()),
+        Some([0x00, 0x61, 0x73, 0x6d, ..]) => Err("unsupported WebAssembly version".into()),
+        _ => Err("not a WebAssembly module".into()),
+    }
+}
+
+/// Warns of the floating point instructions in the contract, whose results may differ between
+/// executors, or refuses the contract if `deny` is set.
+fn check_floats(
+    definition: &ContractDefinition,
+    contract: &[u8],
+    deny: bool,
+) -> Result<(), CliError> {
+    let message = match lint::float_instructions(contract) {
+        Ok(0) => return Ok(()),
+        Ok(count) => format!(
+            "contract {}:{} contains {} floating point instructions, whose results may differ \
+             between executors",
+            definition.name, definition.version, count
+        ),
+        Err(msg) => format!(
+            "unable to check contract {}:{} for floating point instructions: {}",
+            definition.name, definition.version, msg
+        ),
+    };
+
+    if deny {
+        return Err(CliError::User(message));
+    }
+    eprintln!("Warning: {}", message);
+    Ok(())
+}
+
+/// Returns whether the contract version already exists with the same definition, or an error if
+/// it exists with a different one.
+fn contract_exists(
+    url: &str,
+    definition: &ContractDefinition,
+    contract: &[u8],
+) -> Result<bool, CliError> {
+    match get_contract(url, &definition.name, &definition.version)? {
+        Some(existing)
+            if existing.inputs() == &definition.inputs[..]
+                && existing.outputs() == &definition.outputs[..]
+                && existing.contract() == contract =>
+        {
+            Ok(true)
+        }
+        Some(_) => Err(CliError::User(format!(
+            "contract '{}:{}' already exists with a different definition",
+            definition.name, definition.version
+        ))),
+        None => Ok(false),
+    }
+}
+
+/// Checks that every dependency of the contract is satisfied, either by a contract uploaded
+/// before it in the same submission or by a contract version already in state.
+fn check_dependencies(
+    url: &str,
+    definition: &ContractDefinition,
+    uploaded: &[(String, String)],
+) -> Result<(), CliError> {
+    for dependency in &definition.dependencies {
+        if uploaded
+            .iter()
+            .any(|(name, version)| dependency.is_satisfied_by(name, version))
+        {
+            continue;
+        }
+
+        let satisfied = get_contract_registry(url, &dependency.name)?.map_or(false, |registry| {
+            registry
+                .versions()
+                .iter()
+                .any(|version| dependency.is_satisfied_by(registry.name(), version.version()))
+        });
+        if !satisfied {
+            return Err(CliError::User(format!(
+                "contract '{}:{}' depends on {}, which has not been uploaded",
+                definition.name, definition.version, dependency
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Builds the batch which uploads the contract described by the definition file, returning the
+/// name and version of the contract along with the batch.
+pub fn build_upload_batch(
+    filename: &str,
+    wasm_name: Option<&str>,
+    signer: &dyn Signer,
+) -> Result<(String, String, Batch), CliError> {
+    let (definition, contract) = load_contract(filename, wasm_name)?;
+    let name = definition.name.clone();
+    let version = definition.version.clone();
+
+    let batch = BatchBuilder::new()
+        .with_transactions(vec![build_transaction(definition, contract, signer)?])
+        .build(signer)?;
+
+    Ok((name, version, batch))
+}
+
+/// Loads the inputs and outputs the contract definition file declares, and the compiled contract
+/// it refers to, or that `wasm_name` names.
+pub fn load_contract_namespaces(
+    filename: &str,
+    wasm_name: Option<&str>,
+) -> Result<(Vec<String>, Vec<String>, Vec<u8>), CliError> {
+    let (definition, contract) = load_contract(filename, wasm_name)?;
+
+    Ok((definition.inputs, definition.outputs, contract))
+}
+
+/// Loads the contract definition and the compiled contract it refers to, or that `wasm_name`
+/// names.
+pub fn load_contract(
+    filename: &str,
+    wasm_name: Option<&str>,
+) -> Result<(ContractDefinition, Vec<u8>), CliError> {
     let definition = ContractDefinition::load(filename)?;
 
     // Load the contract file relative to the directory containing the
@@ -38,7 +377,7 @@ pub fn do_upload(
     let mut contract_path_buf = PathBuf::new();
     if let Some(path) = wasm_name {
         contract_path_buf.push(path);
-    } else if let Some(wasm) = definition.wasm {
+    } else if let Some(wasm) = &definition.wasm {
         contract_path_buf.push(filename);
         contract_path_buf.pop();
         contract_path_buf.push(wasm);
@@ -51,8 +390,15 @@ pub fn do_upload(
 
     let contract = load_contract_file(contract_path_buf.as_path())?;
 
-    let signer = new_signer(key_name)?;
-    let batch = CreateContractActionBuilder::new()
+    Ok((definition, contract))
+}
+
+fn build_transaction(
+    definition: ContractDefinition,
+    contract: Vec<u8>,
+    signer: &dyn Signer,
+) -> Result<Transaction, CliError> {
+    Ok(CreateContractActionBuilder::new()
         .with_name(definition.name)
         .with_version(definition.version)
         .with_inputs(definition.inputs)
@@ -60,10 +406,8 @@ pub fn do_upload(
         .with_contract(contract)
         .into_payload_builder()?
         .into_transaction_builder()?
-        .into_batch_builder(&*signer)?
-        .build(&*signer)?;
-
-    submit_batches(url, vec![batch])
+        .with_nonce(trace::transaction_nonce())
+        .build(signer)?)
 }
 
 fn load_contract_file(path: &Path) -> Result<Vec<u8>, CliError> {
@@ -87,12 +431,13 @@ fn load_contract_file(path: &Path) -> Result<Vec<u8>, CliError> {
     Ok(contents)
 }
 
-struct ContractDefinition {
-    name: String,
-    version: String,
-    inputs: Vec<String>,
-    outputs: Vec<String>,
+pub struct ContractDefinition {
+    pub name: String,
+    pub version: String,
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
     wasm: Option<String>,
+    dependencies: Vec<Dependency>,
 }
 
 impl ContractDefinition {
@@ -169,12 +514,70 @@ impl ContractDefinition {
             })
             .collect::<Result<Vec<_>, CliError>>()?;
 
+        let dependencies = parse_dependencies(doc).map_err(|msg| {
+            CliError::User(format!(
+                "Malformed contract definition file \"{}\": {}",
+                filename, msg
+            ))
+        })?;
+
         Ok(ContractDefinition {
             name: String::from(name),
             version: String::from(version),
             inputs,
             outputs,
             wasm,
+            dependencies,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use flate2::{write::GzEncoder, Compression};
+
+    /// Writes a .tar.gz archive holding a contract definition and a link to the root directory
+    fn write_archive_with_link(path: &Path, link_type: tar::EntryType) {
+        let mut builder = tar::Builder::new(GzEncoder::new(
+            File::create(path).unwrap(),
+            Compression::default(),
+        ));
+
+        let definition = b"name: intkey_multiply\nversion: '1.0'\nwasm: intkey.wasm\n";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(definition.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "intkey.yaml", &definition[..])
+            .unwrap();
+
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(link_type);
+        header.set_size(0);
+        header.set_mode(0o777);
+        builder.append_link(&mut header, "root", "/").unwrap();
+
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[test]
+    // Asserts that an archive containing a symbolic or hard link is rejected rather than
+    // unpacked
+    fn test_cli_unpack_archive_with_link() {
+        let dir = tempfile::tempdir().unwrap();
+        for link_type in [tar::EntryType::Symlink, tar::EntryType::Link] {
+            let archive = dir.path().join("contracts.tar.gz");
+            write_archive_with_link(&archive, link_type);
+
+            let unpacked = tempfile::tempdir().unwrap();
+            match unpack_archive(archive.to_str().unwrap(), unpacked.path()) {
+                Err(CliError::User(msg)) => assert!(msg.contains("contains a link"), "{}", msg),
+                _ => panic!("an archive containing a link was unpacked"),
+            }
+            assert!(!unpacked.path().join("root").exists());
+        }
+    }
+}
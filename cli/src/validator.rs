@@ -0,0 +1,129 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Submits batches, and queries their statuses, over the validator's ZMQ client interface,
+//! for deployments which do not run the REST API
+
+use std::time::Duration;
+
+use protobuf::{Message as _, ProtobufError};
+use sawtooth_sdk::messages::batch::BatchList;
+use sawtooth_sdk::messages::client_batch_submit::{
+    ClientBatchStatus, ClientBatchStatusRequest, ClientBatchStatusResponse,
+    ClientBatchStatusResponse_Status, ClientBatchSubmitRequest, ClientBatchSubmitResponse,
+    ClientBatchSubmitResponse_Status,
+};
+use sawtooth_sdk::messages::validator::Message_MessageType;
+use sawtooth_sdk::messaging::stream::{MessageConnection, MessageSender};
+use sawtooth_sdk::messaging::zmq_stream::ZmqMessageConnection;
+
+use crate::error::CliError;
+
+/// How long the validator is given to answer a submission
+const SUBMIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Submits the serialized batch list to the validator at the endpoint, such as
+/// tcp://validator:4004.
+pub fn submit_batches(endpoint: &str, batch_list: &[u8]) -> Result<(), CliError> {
+    let mut batch_list = BatchList::parse_from_bytes(batch_list).map_err(message_error)?;
+    let mut request = ClientBatchSubmitRequest::new();
+    request.set_batches(batch_list.take_batches());
+
+    let response = send(
+        endpoint,
+        Message_MessageType::CLIENT_BATCH_SUBMIT_REQUEST,
+        &request.write_to_bytes().map_err(message_error)?,
+        SUBMIT_TIMEOUT,
+    )?;
+    let response = ClientBatchSubmitResponse::parse_from_bytes(&response).map_err(message_error)?;
+
+    match response.get_status() {
+        ClientBatchSubmitResponse_Status::OK => Ok(()),
+        ClientBatchSubmitResponse_Status::INVALID_BATCH => Err(CliError::User(format!(
+            "Validator {} rejected a batch as invalid",
+            endpoint
+        ))),
+        ClientBatchSubmitResponse_Status::QUEUE_FULL => Err(CliError::User(format!(
+            "Validator {} is not accepting batches: its queue is full",
+            endpoint
+        ))),
+        status => Err(CliError::User(format!(
+            "Validator {} did not accept the batches: {:?}",
+            endpoint, status
+        ))),
+    }
+}
+
+/// Returns the statuses of the batches, which the validator waits up to `wait` seconds to
+/// return until they are committed or invalid.
+pub fn batch_statuses(
+    endpoint: &str,
+    batch_ids: &[String],
+    wait: u64,
+) -> Result<Vec<ClientBatchStatus>, CliError> {
+    let mut request = ClientBatchStatusRequest::new();
+    request.set_batch_ids(batch_ids.to_vec().into());
+    request.set_wait(wait > 0);
+    request.set_timeout(wait.min(u64::from(u32::MAX)) as u32);
+
+    let response = send(
+        endpoint,
+        Message_MessageType::CLIENT_BATCH_STATUS_REQUEST,
+        &request.write_to_bytes().map_err(message_error)?,
+        Duration::from_secs(wait) + SUBMIT_TIMEOUT,
+    )?;
+    let mut response =
+        ClientBatchStatusResponse::parse_from_bytes(&response).map_err(message_error)?;
+
+    match response.get_status() {
+        ClientBatchStatusResponse_Status::OK => Ok(response.take_batch_statuses().into_vec()),
+        status => Err(CliError::User(format!(
+            "Validator {} did not return the batch statuses: {:?}",
+            endpoint, status
+        ))),
+    }
+}
+
+/// Sends the message to the validator, returning the content of its reply.
+fn send(
+    endpoint: &str,
+    message_type: Message_MessageType,
+    content: &[u8],
+    timeout: Duration,
+) -> Result<Vec<u8>, CliError> {
+    let connection = ZmqMessageConnection::new(endpoint);
+    let (mut sender, _receiver) = connection.create();
+
+    let correlation_id = crate::trace::new_span().to_string();
+    let reply = sender
+        .send(message_type, &correlation_id, content)
+        .map_err(|err| {
+            CliError::User(format!(
+                "Unable to send to validator {}: {:?}",
+                endpoint, err
+            ))
+        })
+        .and_then(|mut future| {
+            future.get_timeout(timeout).map_err(|err| {
+                CliError::User(format!("No reply from validator {}: {:?}", endpoint, err))
+            })
+        });
+    sender.close();
+
+    Ok(reply?.take_content())
+}
+
+fn message_error(err: ProtobufError) -> CliError {
+    CliError::User(format!("Invalid validator message: {}", err))
+}
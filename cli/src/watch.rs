@@ -0,0 +1,154 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains waiting for a state entry to meet a condition
+//!
+//! State only changes when a block is committed, so the head block is polled, which is a small
+//! response, and the entry is only read again once the head has moved.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use reqwest::{header::ACCEPT, Url};
+use sabre_sdk::trace::TRACEPARENT;
+
+use crate::error::CliError;
+use crate::rest_api::{
+    authorize, check_scheme, client, parse_response, with_failover, JSON_MEDIA_TYPE,
+};
+use crate::state::get_state_with_prefix;
+use crate::trace;
+
+/// Waits until the data at the address meets the condition, and returns it. `None` is given to
+/// the condition, and returned, while the address is unset.
+///
+/// The condition is checked against the current data first, then each time the head block
+/// changes, checking the head at most once per interval. An error is returned if the condition
+/// is not met within the timeout.
+pub fn wait_for_state_change<P>(
+    url: &str,
+    address: &str,
+    condition: P,
+    timeout: Duration,
+    interval: Duration,
+) -> Result<Option<Vec<u8>>, CliError>
+where
+    P: Fn(Option<&[u8]>) -> bool,
+{
+    let deadline = Instant::now() + timeout;
+    let mut checked_head = None;
+
+    loop {
+        let head = get_head(url)?;
+        if checked_head.as_ref() != Some(&head) {
+            let data = get_state_entry(url, address)?;
+            if condition(data.as_deref()) {
+                return Ok(data);
+            }
+            checked_head = Some(head);
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(CliError::User(format!(
+                "{} did not change within {}s",
+                address,
+                timeout.as_secs()
+            )));
+        }
+        thread::sleep(interval.min(deadline - now));
+    }
+}
+
+/// Returns the data at the address, if it is set.
+pub fn get_state_entry(url: &str, address: &str) -> Result<Option<Vec<u8>>, CliError> {
+    get_state_with_prefix(url, address)?
+        .into_iter()
+        .find(|entry| entry.address == address)
+        .map(|entry| {
+            base64::decode(entry.data).map_err(|_| CliError::User("Unable to decode state".into()))
+        })
+        .transpose()
+}
+
+/// Returns the id of the head block.
+pub fn get_head(url: &str) -> Result<String, CliError> {
+    with_failover(url, get_head_from)
+}
+
+fn get_head_from(url: &str) -> Result<String, CliError> {
+    let url = Url::parse(&format!("{}/blocks?limit=1", url))
+        .map_err(|e| CliError::User(format!("Invalid URL: {}: {}", e, url)))?;
+
+    check_scheme(&url)?;
+
+    let response = authorize(client(url.as_str())?.get(url))
+        .header(ACCEPT, JSON_MEDIA_TYPE)
+        .header(TRACEPARENT, trace::new_span().to_string())
+        .send()?;
+
+    Ok(parse_response::<HeadResponse>(response)?.head)
+}
+
+#[derive(Deserialize, Debug)]
+struct HeadResponse {
+    head: String,
+}
+
+#[cfg(test)]
+mod tests {
+
+    use mockito;
+
+    use super::*;
+
+    #[test]
+    // Asserts that the current data is returned when it already meets the condition, and that
+    // waiting stops with an error once the timeout passes
+    fn test_cli_wait_for_state_change() {
+        let url = mockito::server_url();
+        let _m1 = mockito::mock("GET", "/blocks?limit=1")
+            .with_header("content-type", "application/json")
+            .with_body("{\"head\": \"abc\", \"data\": []}")
+            .create();
+        let _m2 = mockito::mock("GET", "/state?address=1cf126aa")
+            .with_header("content-type", "application/json")
+            .with_body(format!(
+                "{{\"data\":[{{\"address\": \"1cf126aa\", \"data\": \"{}\"}}]}}",
+                base64::encode(b"value")
+            ))
+            .create();
+
+        assert_eq!(
+            wait_for_state_change(
+                &url,
+                "1cf126aa",
+                |data| data.is_some(),
+                Duration::from_secs(1),
+                Duration::from_millis(10),
+            )
+            .unwrap(),
+            Some(b"value".to_vec())
+        );
+
+        assert!(wait_for_state_change(
+            &url,
+            "1cf126aa",
+            |data| data.is_none(),
+            Duration::from_millis(50),
+            Duration::from_millis(10),
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,358 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Keeps a stock count for each SKU.
+//!
+//! The payload holds one operation per line, `add,<sku>,<quantity>` or
+//! `remove,<sku>,<quantity>`. The key which first adds a SKU owns it, and only the owner may
+//! remove its stock.
+//!
+//! Each operation is applied on its own, through a `KeyValueTransactionContext`: an operation
+//! which is refused, such as removing more stock than there is, is rolled back and reported
+//! with an `inventory/rejected` event, and the other operations are committed, each reported
+//! with an `inventory/updated` event.
+
+use sha2::{Digest, Sha512};
+
+use sabre_sdk::events::AttributesBuilder;
+use sabre_sdk::middleware::KeyValueTransactionContext;
+use sabre_sdk::{ApplyError, TpProcessRequest, TransactionContext, WasmSdkError};
+
+/// The longest SKU accepted
+const MAX_SKU_LENGTH: usize = 64;
+
+/// Returns the namespace the inventory is stored under, 8336ff.
+pub fn inventory_prefix() -> String {
+    to_hex(&Sha512::digest(b"inventory")[..3])
+}
+
+/// Returns the address of the stock of a SKU.
+pub fn compute_address(sku: &str) -> String {
+    inventory_prefix() + &to_hex(&Sha512::digest(sku.as_bytes())[..32])
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Operation {
+    Add { sku: String, quantity: u64 },
+    Remove { sku: String, quantity: u64 },
+}
+
+impl Operation {
+    fn sku(&self) -> &str {
+        match self {
+            Operation::Add { sku, .. } | Operation::Remove { sku, .. } => sku,
+        }
+    }
+}
+
+/// The stock of a SKU, and the key which owns it
+#[derive(Debug, PartialEq, Eq)]
+struct Stock {
+    owner: String,
+    quantity: u64,
+}
+
+impl Stock {
+    /// Stock is stored as `<owner>,<quantity>`
+    fn from_bytes(bytes: &[u8]) -> Result<Stock, ApplyError> {
+        let invalid = || ApplyError::InvalidTransaction("stored stock is malformed".into());
+        let text = std::str::from_utf8(bytes).map_err(|_| invalid())?;
+        let (owner, quantity) = text.split_once(',').ok_or_else(invalid)?;
+
+        Ok(Stock {
+            owner: owner.to_string(),
+            quantity: quantity.parse().map_err(|_| invalid())?,
+        })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        format!("{},{}", self.owner, self.quantity).into_bytes()
+    }
+}
+
+/// Parses the operations in the payload.
+pub fn parse_payload(payload: &[u8]) -> Result<Vec<Operation>, ApplyError> {
+    let payload = std::str::from_utf8(payload)
+        .map_err(|_| ApplyError::InvalidTransaction("payload is not UTF-8".into()))?;
+
+    let operations = payload
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(parse_operation)
+        .collect::<Result<Vec<_>, _>>()?;
+    if operations.is_empty() {
+        return Err(ApplyError::InvalidTransaction(
+            "payload holds no operations".into(),
+        ));
+    }
+
+    Ok(operations)
+}
+
+fn parse_operation(line: &str) -> Result<Operation, ApplyError> {
+    let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    let (action, sku, quantity) = match fields.as_slice() {
+        [action, sku, quantity] => (*action, *sku, *quantity),
+        _ => {
+            return Err(ApplyError::InvalidTransaction(format!(
+                "'{}' is not of the form <action>,<sku>,<quantity>",
+                line
+            )))
+        }
+    };
+
+    if sku.is_empty() || sku.len() > MAX_SKU_LENGTH {
+        return Err(ApplyError::InvalidTransaction(format!(
+            "SKU '{}' must be 1 to {} characters",
+            sku, MAX_SKU_LENGTH
+        )));
+    }
+    let quantity = quantity.parse().map_err(|_| {
+        ApplyError::InvalidTransaction(format!("quantity '{}' is not an integer", quantity))
+    })?;
+
+    let sku = sku.to_string();
+    match action {
+        "add" => Ok(Operation::Add { sku, quantity }),
+        "remove" => Ok(Operation::Remove { sku, quantity }),
+        _ => Err(ApplyError::InvalidTransaction(format!(
+            "unknown action '{}'",
+            action
+        ))),
+    }
+}
+
+/// Applies the operation for the signer, returning the new quantity.
+fn apply_operation(
+    context: &dyn TransactionContext,
+    signer: &str,
+    operation: &Operation,
+) -> Result<u64, ApplyError> {
+    let address = compute_address(operation.sku());
+    let stock = context
+        .get_state_entry(&address)?
+        .map(|bytes| Stock::from_bytes(&bytes))
+        .transpose()?;
+
+    let stock = match (operation, stock) {
+        (Operation::Add { quantity, .. }, Some(stock)) => Stock {
+            quantity: stock
+                .quantity
+                .checked_add(*quantity)
+                .ok_or_else(|| ApplyError::InvalidTransaction("quantity would overflow".into()))?,
+            ..stock
+        },
+        (Operation::Add { quantity, .. }, None) => Stock {
+            owner: signer.to_string(),
+            quantity: *quantity,
+        },
+        (Operation::Remove { sku, .. }, None) => {
+            return Err(ApplyError::InvalidTransaction(format!(
+                "{} is not stocked",
+                sku
+            )))
+        }
+        (Operation::Remove { sku, quantity }, Some(stock)) => {
+            if stock.owner != signer {
+                return Err(ApplyError::InvalidTransaction(format!(
+                    "only the owner of {} may remove its stock",
+                    sku
+                )));
+            }
+            Stock {
+                quantity: stock.quantity.checked_sub(*quantity).ok_or_else(|| {
+                    ApplyError::InvalidTransaction(format!(
+                        "{} has only {} in stock",
+                        sku, stock.quantity
+                    ))
+                })?,
+                ..stock
+            }
+        }
+    };
+
+    context.set_state_entry(address, stock.to_bytes())?;
+    Ok(stock.quantity)
+}
+
+/// Applies the operations in the payload, committing those which succeed.
+pub fn apply(
+    request: &TpProcessRequest,
+    context: &mut dyn TransactionContext,
+) -> Result<bool, ApplyError> {
+    let operations = parse_payload(request.get_payload())?;
+    let signer = request.get_header().get_signer_public_key();
+
+    let staging = KeyValueTransactionContext::new(&*context);
+    for operation in &operations {
+        staging.checkpoint();
+        match apply_operation(&staging, signer, operation) {
+            Ok(quantity) => {
+                let attributes = AttributesBuilder::new()
+                    .with_str("sku", operation.sku())
+                    .with_u64("quantity", quantity)
+                    .build()
+                    .map_err(WasmSdkError::from)?;
+                staging.add_event("inventory/updated".into(), attributes, &[])?;
+            }
+            Err(ApplyError::InvalidTransaction(reason)) => {
+                staging.rollback();
+                let attributes = AttributesBuilder::new()
+                    .with_str("sku", operation.sku())
+                    .build()
+                    .map_err(WasmSdkError::from)?;
+                staging.add_event("inventory/rejected".into(), attributes, reason.as_bytes())?;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    staging.commit()?;
+
+    Ok(true)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    use sabre_sdk::testing::TpProcessRequestBuilder;
+    /// A context backed by a map, which records the events added
+    #[derive(Default)]
+    struct MockContext {
+        state: RefCell<HashMap<String, Vec<u8>>>,
+        events: RefCell<Vec<(String, Vec<(String, String)>)>>,
+    }
+
+    impl TransactionContext for MockContext {
+        fn get_state_entries(
+            &self,
+            addresses: &[String],
+        ) -> Result<Vec<(String, Vec<u8>)>, WasmSdkError> {
+            let state = self.state.borrow();
+            Ok(addresses
+                .iter()
+                .filter_map(|address| {
+                    state
+                        .get(address)
+                        .map(|data| (address.clone(), data.clone()))
+                })
+                .collect())
+        }
+
+        fn set_state_entries(&self, entries: Vec<(String, Vec<u8>)>) -> Result<(), WasmSdkError> {
+            self.state.borrow_mut().extend(entries);
+            Ok(())
+        }
+
+        fn delete_state_entries(&self, addresses: &[String]) -> Result<Vec<String>, WasmSdkError> {
+            let mut state = self.state.borrow_mut();
+            Ok(addresses
+                .iter()
+                .filter(|address| state.remove(*address).is_some())
+                .cloned()
+                .collect())
+        }
+
+        fn add_event(
+            &self,
+            event_type: String,
+            attributes: Vec<(String, String)>,
+            _data: &[u8],
+        ) -> Result<(), WasmSdkError> {
+            self.events.borrow_mut().push((event_type, attributes));
+            Ok(())
+        }
+    }
+
+    fn apply_payload(context: &mut MockContext, signer: &str, payload: &str) {
+        let mut request = TpProcessRequestBuilder::new()
+            .with_payload(payload.as_bytes().to_vec())
+            .with_signer_public_key(signer.to_string())
+            .build();
+        assert!(apply(&request.as_request(), context).unwrap());
+    }
+
+    fn quantity(context: &MockContext, sku: &str) -> Option<u64> {
+        context
+            .state
+            .borrow()
+            .get(&compute_address(sku))
+            .map(|bytes| Stock::from_bytes(bytes).unwrap().quantity)
+    }
+
+    #[test]
+    // Asserts that the payload is parsed into operations, and malformed lines are rejected
+    fn test_parse_payload() {
+        assert_eq!(
+            parse_payload(b"add,widget,5\nremove, widget, 2\n").unwrap(),
+            vec![
+                Operation::Add {
+                    sku: "widget".into(),
+                    quantity: 5
+                },
+                Operation::Remove {
+                    sku: "widget".into(),
+                    quantity: 2
+                },
+            ]
+        );
+        assert!(parse_payload(b"").is_err());
+        assert!(parse_payload(b"add,widget").is_err());
+        assert!(parse_payload(b"sell,widget,1").is_err());
+        assert!(parse_payload(b"add,widget,-1").is_err());
+    }
+
+    #[test]
+    // Asserts that refused operations are rolled back and reported while the others are
+    // committed, and that only the owner of a SKU may remove its stock
+    fn test_apply() {
+        let mut context = MockContext::default();
+
+        apply_payload(
+            &mut context,
+            "owner",
+            "add,widget,5\nremove,widget,7\nadd,gadget,1",
+        );
+        assert_eq!(quantity(&context, "widget"), Some(5));
+        assert_eq!(quantity(&context, "gadget"), Some(1));
+        let event_types: Vec<String> = context
+            .events
+            .borrow()
+            .iter()
+            .map(|(event_type, _)| event_type.clone())
+            .collect();
+        assert_eq!(
+            event_types,
+            vec![
+                "inventory/updated",
+                "inventory/rejected",
+                "inventory/updated"
+            ]
+        );
+
+        apply_payload(&mut context, "other", "remove,widget,1\nadd,widget,2");
+        assert_eq!(quantity(&context, "widget"), Some(7));
+
+        apply_payload(&mut context, "owner", "remove,widget,7");
+        assert_eq!(quantity(&context, "widget"), Some(0));
+    }
+}
@@ -28,11 +28,14 @@ use std::io::Read;
 use std::io::{BufRead, BufReader};
 
 const INTKEY_MULTIPLY_DEF: &str = "/project/example/intkey_multiply/intkey_multiply.yaml";
+const INVENTORY_DEF: &str = "/project/example/inventory/inventory.yaml";
 
 // Path to a payload to multiply intkey value B and C and store in A.
 const GOOD_PAYLOAD: &str = "/project/integration/payloads/A_B_C_payload";
 // Path to a payload to multiply intkey value C and nonexisties and store in A.
 const BAD_PAYLOAD: &str = "/project/integration/payloads/A_Bad_C_payload";
+// Path to a payload that adds stock, removes more than was added and adds other stock.
+const INVENTORY_PAYLOAD: &str = "/project/integration/payloads/inventory_payload";
 const SIGNER: &str = "/root/.sawtooth/keys/root.pub";
 
 #[derive(Debug)]
@@ -322,4 +325,46 @@ fn test_sabre() {
     let message: String = response["data"][0]["invalid_transactions"][0]["message"].to_string();
     println!("{}", message);
     assert!(message.contains("Wasm contract returned invalid transaction"));
+
+    // Test that the inventory example contract can be registered, given its namespace and
+    // executed.
+    //
+    // Send CreateContractRegistryAction, CreateContractAction, CreateNamespaceRegistryAction
+    // and CreateNamespaceRegistryPermissionAction for the inventory contract and its 8336ff
+    // namespace.
+    //
+    // Result: Committed.
+    for command in &[
+        "cr --create inventory --owner ".to_string() + &signer,
+        "upload -f ".to_string() + &INVENTORY_DEF,
+        "ns --create 8336ff --owner ".to_string() + &signer,
+        "perm 8336ff inventory --read --write".to_string(),
+    ] {
+        let response = match sabre_cli(command.to_string()) {
+            Ok(x) => x,
+            Err(err) => panic!("No Response {}", err),
+        };
+        assert!(response["data"][0]["status"] == "COMMITTED");
+    }
+
+    // Test that the inventory contract commits the operations which succeed when another in
+    // the same payload is refused.
+    //
+    // Send ExecuteContractAction with the following:
+    //      Name: inventory
+    //      Version: 1.0
+    //      Inputs: 8336ff
+    //      Outputs: 8336ff
+    //      Payload: A payload that adds 5 widgets, removes 7 widgets and adds 1 gadget.
+    //
+    // Result: Committed. The removal is rolled back and reported with an event.
+    let response = match sabre_cli(
+        "exec --contract inventory:1.0 --payload ".to_string()
+            + &INVENTORY_PAYLOAD
+            + " --inputs 8336ff --outputs 8336ff",
+    ) {
+        Ok(x) => x,
+        Err(err) => panic!("No Response {}", err),
+    };
+    assert!(response["data"][0]["status"] == "COMMITTED");
 }
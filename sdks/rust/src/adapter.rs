@@ -0,0 +1,166 @@
+// Copyright 2019 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Runs transaction handlers written for the Sawtooth SDK as Sabre contracts.
+//!
+//! The SDK's `TransactionHandler`, `TransactionContext`, `TpProcessRequest` and `ApplyError`
+//! mirror those of the Sawtooth SDK, so a handler compiles against either once its imports are
+//! switched on the target. `sabre_entrypoint!` then generates the contract's entrypoint:
+//!
+//! ```ignore
+//! cfg_if! {
+//!     if #[cfg(target_arch = "wasm32")] {
+//!         use sabre_sdk::{ApplyError, TpProcessRequest, TransactionContext, TransactionHandler};
+//!     } else {
+//!         use sawtooth_sdk::messages::processor::TpProcessRequest;
+//!         use sawtooth_sdk::processor::handler::{
+//!             ApplyError, TransactionContext, TransactionHandler,
+//!         };
+//!     }
+//! }
+//!
+//! #[cfg(target_arch = "wasm32")]
+//! sabre_sdk::sabre_entrypoint!(IntkeyMultiplyTransactionHandler::new());
+//! ```
+//!
+//! A handler's namespaces become the contract's inputs and outputs, and each needs a Sabre
+//! namespace registry granting the contract read and write permission. `NamespaceContext`
+//! refuses state access outside the namespaces, as Sabre does, so that a ported handler can be
+//! checked with `testing::PortingHarness` before it is uploaded.
+
+use crate::protocol::namespaces::ADDRESS_LENGTH;
+use crate::{ApplyError, TpProcessRequest, TransactionContext, TransactionHandler, WasmSdkError};
+
+/// Applies a transaction with the handler, returning the result a Sabre contract's `apply`
+/// returns.
+pub fn apply_handler<H: TransactionHandler + ?Sized>(
+    handler: &H,
+    request: &TpProcessRequest,
+    context: &mut dyn TransactionContext,
+) -> Result<bool, ApplyError> {
+    handler.apply(request, context).map(|_| true)
+}
+
+/// Generates the `entrypoint` of a Sabre contract which applies transactions with the handler
+/// the expression creates. The handler is created for each transaction.
+#[cfg(feature = "contract-wasm")]
+#[macro_export]
+macro_rules! sabre_entrypoint {
+    ($handler:expr) => {
+        #[no_mangle]
+        pub unsafe fn entrypoint(
+            payload: $crate::WasmPtr,
+            signer: $crate::WasmPtr,
+            signature: $crate::WasmPtr,
+        ) -> i32 {
+            $crate::execute_entrypoint(payload, signer, signature, |request, context| {
+                $crate::adapter::apply_handler(&$handler, request, context)
+            })
+        }
+    };
+}
+
+/// Returns the handler's namespaces, checked to be hex prefixes of an address, for use as the
+/// contract's inputs and outputs and as the namespaces to register.
+pub fn contract_namespaces<H: TransactionHandler + ?Sized>(
+    handler: &H,
+) -> Result<Vec<String>, ApplyError> {
+    let namespaces = handler.namespaces();
+    for namespace in &namespaces {
+        if namespace.len() < 6
+            || namespace.len() > ADDRESS_LENGTH
+            || namespace.len() % 2 != 0
+            || !namespace.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            return Err(ApplyError::InternalError(format!(
+                "namespace '{}' of {} is not an even number of hex characters from 6 to {}",
+                namespace,
+                handler.family_name(),
+                ADDRESS_LENGTH
+            )));
+        }
+    }
+
+    Ok(namespaces)
+}
+
+/// Returns whether the address is within one of the namespaces.
+pub fn in_namespaces(namespaces: &[String], address: &str) -> bool {
+    namespaces
+        .iter()
+        .any(|namespace| address.starts_with(namespace.as_str()))
+}
+
+/// A `TransactionContext` which refuses to read, write or delete addresses outside the
+/// namespaces, as a Sabre contract is refused access beyond its inputs and outputs.
+pub struct NamespaceContext<'a> {
+    context: &'a dyn TransactionContext,
+    namespaces: Vec<String>,
+}
+
+impl<'a> NamespaceContext<'a> {
+    pub fn new(context: &'a dyn TransactionContext, namespaces: Vec<String>) -> Self {
+        NamespaceContext {
+            context,
+            namespaces,
+        }
+    }
+
+    fn check<'b, I: IntoIterator<Item = &'b String>>(
+        &self,
+        operation: &str,
+        addresses: I,
+    ) -> Result<(), WasmSdkError> {
+        for address in addresses {
+            if !in_namespaces(&self.namespaces, address) {
+                return Err(WasmSdkError::InvalidTransaction(format!(
+                    "unable to {} {}, which is outside the namespaces {}",
+                    operation,
+                    address,
+                    self.namespaces.join(", ")
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> TransactionContext for NamespaceContext<'a> {
+    fn get_state_entries(
+        &self,
+        addresses: &[String],
+    ) -> Result<Vec<(String, Vec<u8>)>, WasmSdkError> {
+        self.check("read", addresses)?;
+        self.context.get_state_entries(addresses)
+    }
+
+    fn set_state_entries(&self, entries: Vec<(String, Vec<u8>)>) -> Result<(), WasmSdkError> {
+        self.check("write", entries.iter().map(|(address, _)| address))?;
+        self.context.set_state_entries(entries)
+    }
+
+    fn delete_state_entries(&self, addresses: &[String]) -> Result<Vec<String>, WasmSdkError> {
+        self.check("delete", addresses)?;
+        self.context.delete_state_entries(addresses)
+    }
+
+    fn add_event(
+        &self,
+        event_type: String,
+        attributes: Vec<(String, String)>,
+        data: &[u8],
+    ) -> Result<(), WasmSdkError> {
+        self.context.add_event(event_type, attributes, data)
+    }
+}
@@ -0,0 +1,83 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Compression of the data a contract stores in state.
+//!
+//! Compressed data is DEFLATE compressed and prefixed with `MARKER`. Data without the marker is
+//! stored as it is, so entries written before compression was enabled are still read. Data which
+//! begins with the marker is always compressed, so that it is not mistaken for compressed data.
+
+use miniz_oxide::deflate::compress_to_vec;
+use miniz_oxide::inflate::decompress_to_vec;
+
+use crate::WasmSdkError;
+
+/// The prefix of compressed data
+pub const MARKER: &[u8] = b"\0sbz1";
+
+/// The DEFLATE compression level, from 0 to 10
+const LEVEL: u8 = 6;
+
+/// Compresses the data if it is at least `threshold` bytes long and compressing makes it
+/// smaller, or if it begins with the marker.
+pub fn compress(data: Vec<u8>, threshold: usize) -> Vec<u8> {
+    let escape = data.starts_with(MARKER);
+    if data.len() < threshold && !escape {
+        return data;
+    }
+
+    let mut compressed = MARKER.to_vec();
+    compressed.extend(compress_to_vec(&data, LEVEL));
+    if compressed.len() < data.len() || escape {
+        compressed
+    } else {
+        data
+    }
+}
+
+/// Decompresses the data if it begins with the marker, and otherwise returns it as it is.
+pub fn decompress(data: Vec<u8>) -> Result<Vec<u8>, WasmSdkError> {
+    if !data.starts_with(MARKER) {
+        return Ok(data);
+    }
+
+    decompress_to_vec(&data[MARKER.len()..]).map_err(|err| {
+        WasmSdkError::InvalidTransaction(format!("unable to decompress state: {:?}", err))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // check that data over the threshold is compressed and restored, that small or
+    // uncompressed data is left as it is, and that data beginning with the marker survives
+    fn check_compression() {
+        let large = vec![b'a'; 1024];
+        let compressed = compress(large.clone(), 512);
+        assert!(compressed.starts_with(MARKER));
+        assert!(compressed.len() < large.len());
+        assert_eq!(decompress(compressed).unwrap(), large);
+
+        assert_eq!(compress(b"small".to_vec(), 512), b"small".to_vec());
+        assert_eq!(decompress(b"small".to_vec()).unwrap(), b"small".to_vec());
+
+        let mut marked = MARKER.to_vec();
+        marked.extend(b"data");
+        let compressed = compress(marked.clone(), 512);
+        assert_ne!(compressed, marked);
+        assert_eq!(decompress(compressed).unwrap(), marked);
+    }
+}
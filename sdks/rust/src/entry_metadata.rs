@@ -0,0 +1,115 @@
+// Copyright 2019 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The provenance of the entries a contract stores in state.
+//!
+//! An entry with metadata is prefixed with `MARKER`, followed by the signatures of the
+//! transactions which created and last modified it, each as a two byte big-endian length and the
+//! signature, and then the entry's data. Data without the marker has no metadata.
+
+use crate::WasmSdkError;
+
+/// The prefix of an entry with metadata
+pub const MARKER: &[u8] = b"\0sbm1";
+
+/// The transactions which created and last modified an entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryMetadata {
+    created_by: String,
+    modified_by: String,
+}
+
+impl EntryMetadata {
+    pub fn new(created_by: String, modified_by: String) -> Self {
+        EntryMetadata {
+            created_by,
+            modified_by,
+        }
+    }
+
+    /// The signature of the transaction which created the entry
+    pub fn created_by(&self) -> &str {
+        &self.created_by
+    }
+
+    /// The signature of the transaction which last set the entry
+    pub fn modified_by(&self) -> &str {
+        &self.modified_by
+    }
+}
+
+/// Returns the data prefixed with the metadata.
+pub fn wrap(metadata: &EntryMetadata, data: Vec<u8>) -> Vec<u8> {
+    let mut wrapped = MARKER.to_vec();
+    for signature in &[&metadata.created_by, &metadata.modified_by] {
+        // Transaction signatures are 128 hex characters, so the length always fits
+        wrapped.extend((signature.len() as u16).to_be_bytes().iter());
+        wrapped.extend(signature.as_bytes());
+    }
+    wrapped.extend(data);
+    wrapped
+}
+
+/// Splits the metadata from the data if it begins with the marker, and otherwise returns the
+/// data as it is.
+pub fn unwrap(data: Vec<u8>) -> Result<(Option<EntryMetadata>, Vec<u8>), WasmSdkError> {
+    if !data.starts_with(MARKER) {
+        return Ok((None, data));
+    }
+
+    let mut offset = MARKER.len();
+    let created_by = read_signature(&data, &mut offset)?;
+    let modified_by = read_signature(&data, &mut offset)?;
+
+    Ok((
+        Some(EntryMetadata::new(created_by, modified_by)),
+        data[offset..].to_vec(),
+    ))
+}
+
+fn read_signature(data: &[u8], offset: &mut usize) -> Result<String, WasmSdkError> {
+    let truncated = || WasmSdkError::InvalidTransaction("entry metadata is truncated".into());
+
+    let length = data.get(*offset..*offset + 2).ok_or_else(truncated)?;
+    let length = u16::from_be_bytes([length[0], length[1]]) as usize;
+    *offset += 2;
+    let signature = data.get(*offset..*offset + length).ok_or_else(truncated)?;
+    *offset += length;
+
+    String::from_utf8(signature.to_vec()).map_err(|_| {
+        WasmSdkError::InvalidTransaction("entry metadata signature is not UTF-8".into())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // check that metadata is split from the data it was wrapped around, that data without the
+    // marker has none, and that truncated metadata is an error
+    fn check_entry_metadata() {
+        let metadata = EntryMetadata::new("created".into(), "modified".into());
+        let wrapped = wrap(&metadata, b"data".to_vec());
+        assert!(wrapped.starts_with(MARKER));
+        assert_eq!(
+            unwrap(wrapped.clone()).unwrap(),
+            (Some(metadata), b"data".to_vec())
+        );
+
+        assert_eq!(unwrap(b"data".to_vec()).unwrap(), (None, b"data".to_vec()));
+
+        assert!(unwrap(wrapped[..MARKER.len() + 4].to_vec()).is_err());
+    }
+}
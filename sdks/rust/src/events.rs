@@ -0,0 +1,202 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Builds the attributes of the events a contract adds with `TransactionContext::add_event`.
+//!
+//! The validator does not limit attributes, but subscribers filter on them, so the builder keeps
+//! keys to a consistent form and both keys and values to a bounded size.
+
+use std::error::Error as StdError;
+
+use crate::WasmSdkError;
+
+/// The longest attribute key accepted, in bytes
+pub const MAX_KEY_LENGTH: usize = 128;
+
+/// The longest attribute value accepted, in bytes
+pub const MAX_VALUE_LENGTH: usize = 1024;
+
+/// The most attributes an event may have
+pub const MAX_ATTRIBUTES: usize = 64;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum AttributesBuildError {
+    InvalidKey(String),
+    ValueTooLong(String),
+    TooManyAttributes(usize),
+}
+
+impl StdError for AttributesBuildError {}
+
+impl std::fmt::Display for AttributesBuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Self::InvalidKey(ref key) => write!(
+                f,
+                "invalid attribute key '{}': keys must be 1 to {} characters of a-z, 0-9, '_', \
+                 '-' or '.'",
+                key, MAX_KEY_LENGTH
+            ),
+            Self::ValueTooLong(ref key) => write!(
+                f,
+                "value of attribute '{}' is longer than {} bytes",
+                key, MAX_VALUE_LENGTH
+            ),
+            Self::TooManyAttributes(count) => write!(
+                f,
+                "{} attributes given; at most {} are allowed",
+                count, MAX_ATTRIBUTES
+            ),
+        }
+    }
+}
+
+impl From<AttributesBuildError> for WasmSdkError {
+    fn from(err: AttributesBuildError) -> Self {
+        WasmSdkError::InvalidTransaction(err.to_string())
+    }
+}
+
+/// Builds event attributes, formatting typed values consistently.
+///
+/// ```
+/// use sabre_sdk::events::AttributesBuilder;
+///
+/// let attributes = AttributesBuilder::new()
+///     .with_str("name", "a")
+///     .with_u64("value", 12)
+///     .with_bool("created", true)
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(attributes[1], ("value".to_string(), "12".to_string()));
+/// ```
+#[derive(Default, Clone)]
+pub struct AttributesBuilder {
+    attributes: Vec<(String, String)>,
+}
+
+impl AttributesBuilder {
+    pub fn new() -> Self {
+        AttributesBuilder::default()
+    }
+
+    pub fn with_str(mut self, key: &str, value: &str) -> AttributesBuilder {
+        self.attributes.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn with_u64(self, key: &str, value: u64) -> AttributesBuilder {
+        self.with_str(key, &value.to_string())
+    }
+
+    pub fn with_i64(self, key: &str, value: i64) -> AttributesBuilder {
+        self.with_str(key, &value.to_string())
+    }
+
+    /// Adds the value as "true" or "false".
+    pub fn with_bool(self, key: &str, value: bool) -> AttributesBuilder {
+        self.with_str(key, if value { "true" } else { "false" })
+    }
+
+    /// Adds the value as lowercase hex, the form of state addresses and public keys.
+    pub fn with_hex(self, key: &str, value: &[u8]) -> AttributesBuilder {
+        let hex = value
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        self.with_str(key, &hex)
+    }
+
+    pub fn build(self) -> Result<Vec<(String, String)>, AttributesBuildError> {
+        if self.attributes.len() > MAX_ATTRIBUTES {
+            return Err(AttributesBuildError::TooManyAttributes(
+                self.attributes.len(),
+            ));
+        }
+
+        for (key, value) in &self.attributes {
+            if !is_valid_key(key) {
+                return Err(AttributesBuildError::InvalidKey(key.clone()));
+            }
+            if value.len() > MAX_VALUE_LENGTH {
+                return Err(AttributesBuildError::ValueTooLong(key.clone()));
+            }
+        }
+
+        Ok(self.attributes)
+    }
+}
+
+fn is_valid_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.len() <= MAX_KEY_LENGTH
+        && key.chars().all(|c| {
+            c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-' || c == '.'
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // check that typed values are formatted consistently
+    fn check_attributes_builder() {
+        let attributes = AttributesBuilder::new()
+            .with_str("name", "a")
+            .with_i64("delta", -3)
+            .with_bool("created", false)
+            .with_hex("signer", &[0x02, 0xab])
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            attributes,
+            vec![
+                ("name".to_string(), "a".to_string()),
+                ("delta".to_string(), "-3".to_string()),
+                ("created".to_string(), "false".to_string()),
+                ("signer".to_string(), "02ab".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    // check that invalid keys, long values and too many attributes are rejected
+    fn check_attributes_builder_limits() {
+        assert_eq!(
+            AttributesBuilder::new().with_str("Name", "a").build(),
+            Err(AttributesBuildError::InvalidKey("Name".into()))
+        );
+        assert_eq!(
+            AttributesBuilder::new().with_str("", "a").build(),
+            Err(AttributesBuildError::InvalidKey("".into()))
+        );
+        assert_eq!(
+            AttributesBuilder::new()
+                .with_str("name", &"a".repeat(MAX_VALUE_LENGTH + 1))
+                .build(),
+            Err(AttributesBuildError::ValueTooLong("name".into()))
+        );
+
+        let builder = (0..=MAX_ATTRIBUTES).fold(AttributesBuilder::new(), |builder, i| {
+            builder.with_u64("count", i as u64)
+        });
+        assert_eq!(
+            builder.build(),
+            Err(AttributesBuildError::TooManyAttributes(MAX_ATTRIBUTES + 1))
+        );
+    }
+}
@@ -0,0 +1,231 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fixed-point decimal arithmetic for contracts.
+//!
+//! Every node must apply a transaction to the same result. Floating point arithmetic is not
+//! guaranteed to do so in WebAssembly: the bits of a NaN may differ between executors, and a
+//! contract compiled with different flags may round differently. Contracts should not use `f32`
+//! or `f64`, and can enforce it with `#![deny(clippy::float_arithmetic)]`. `sabre contract lint`
+//! reports the floating point instructions in a compiled contract, and `sabre upload` warns of
+//! them, or refuses the contract with `--deny-floats`.
+//!
+//! `Fixed<DECIMALS>` holds a decimal number as an `i128` count of `10^-DECIMALS` units, so its
+//! arithmetic is integer arithmetic. Results are truncated toward zero, and operations return
+//! `None` on overflow or division by zero. `DECIMALS` must be at most 38.
+//!
+//! ```
+//! use sabre_sdk::fixed::Fixed;
+//!
+//! let price: Fixed<2> = "19.99".parse().unwrap();
+//! let total = price.checked_mul(Fixed::from_int(3)).unwrap();
+//! assert_eq!(total.to_string(), "59.97");
+//! ```
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::str::FromStr;
+
+/// A decimal number with `DECIMALS` digits after the decimal point
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fixed<const DECIMALS: u32> {
+    units: i128,
+}
+
+impl<const DECIMALS: u32> Fixed<DECIMALS> {
+    /// The number of units in one
+    const SCALE: i128 = 10i128.pow(DECIMALS);
+
+    pub const ZERO: Self = Fixed { units: 0 };
+    pub const ONE: Self = Fixed { units: Self::SCALE };
+
+    /// Creates a number from its count of `10^-DECIMALS` units.
+    pub const fn from_units(units: i128) -> Self {
+        Fixed { units }
+    }
+
+    /// Returns the count of `10^-DECIMALS` units, which is how the number should be stored.
+    pub const fn units(self) -> i128 {
+        self.units
+    }
+
+    /// Creates a number from an integer. Integers too large for the scale saturate.
+    pub fn from_int(int: i64) -> Self {
+        Fixed {
+            units: i128::from(int).saturating_mul(Self::SCALE),
+        }
+    }
+
+    /// Returns the integer part of the number, truncated toward zero.
+    pub fn trunc(self) -> i128 {
+        self.units / Self::SCALE
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.units.checked_add(other.units).map(Self::from_units)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.units.checked_sub(other.units).map(Self::from_units)
+    }
+
+    pub fn checked_mul(self, other: Self) -> Option<Self> {
+        self.units
+            .checked_mul(other.units)
+            .map(|units| Self::from_units(units / Self::SCALE))
+    }
+
+    pub fn checked_div(self, other: Self) -> Option<Self> {
+        self.units
+            .checked_mul(Self::SCALE)?
+            .checked_div(other.units)
+            .map(Self::from_units)
+    }
+
+    pub fn checked_neg(self) -> Option<Self> {
+        self.units.checked_neg().map(Self::from_units)
+    }
+}
+
+impl<const DECIMALS: u32> fmt::Display for Fixed<DECIMALS> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.units < 0 { "-" } else { "" };
+        let units = self.units.unsigned_abs();
+        let scale = Self::SCALE as u128;
+
+        if DECIMALS == 0 {
+            write!(f, "{}{}", sign, units)
+        } else {
+            write!(
+                f,
+                "{}{}.{:0width$}",
+                sign,
+                units / scale,
+                units % scale,
+                width = DECIMALS as usize
+            )
+        }
+    }
+}
+
+impl<const DECIMALS: u32> FromStr for Fixed<DECIMALS> {
+    type Err = FixedParseError;
+
+    /// Parses a decimal such as "-12.5". Digits beyond `DECIMALS` places are rejected rather
+    /// than rounded.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || FixedParseError(s.to_string());
+
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, s),
+        };
+        let (int, frac) = match digits.split_once('.') {
+            Some((int, frac)) if !frac.is_empty() => (int, frac),
+            Some(_) => return Err(err()),
+            None => (digits, ""),
+        };
+        if int.is_empty()
+            || frac.len() > DECIMALS as usize
+            || !int.bytes().chain(frac.bytes()).all(|b| b.is_ascii_digit())
+        {
+            return Err(err());
+        }
+
+        let frac_units = format!("{:0<width$}", frac, width = DECIMALS as usize);
+        let units = int
+            .parse::<i128>()
+            .ok()
+            .and_then(|int| int.checked_mul(Self::SCALE))
+            .and_then(|units| {
+                if frac_units.is_empty() {
+                    Some(units)
+                } else {
+                    units.checked_add(frac_units.parse::<i128>().ok()?)
+                }
+            })
+            .ok_or_else(err)?;
+
+        Ok(Fixed::from_units(if negative { -units } else { units }))
+    }
+}
+
+/// The string is not a decimal with at most the number's decimal places, or is out of range
+#[derive(Debug, PartialEq, Eq)]
+pub struct FixedParseError(String);
+
+impl StdError for FixedParseError {}
+
+impl fmt::Display for FixedParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid fixed-point decimal: {}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Amount = Fixed<4>;
+
+    fn amount(s: &str) -> Amount {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    // check that decimals are parsed and displayed with the number's decimal places, and that
+    // malformed or over-precise strings are rejected
+    fn check_fixed_parse_display() {
+        assert_eq!(amount("12.5").units(), 125_000);
+        assert_eq!(amount("12.5").to_string(), "12.5000");
+        assert_eq!(amount("-0.0001").to_string(), "-0.0001");
+        assert_eq!(amount("7").to_string(), "7.0000");
+        assert_eq!("42".parse::<Fixed<0>>().unwrap().to_string(), "42");
+
+        for invalid in &["", "-", ".5", "5.", "1.23456", "1e3", "+1", "1.2.3", "--1"] {
+            assert!(invalid.parse::<Amount>().is_err(), "{}", invalid);
+        }
+        assert!("170141183460469231731687303715884105727"
+            .parse::<Amount>()
+            .is_err());
+    }
+
+    #[test]
+    // check that arithmetic is exact to the number's decimal places, truncates toward zero, and
+    // reports overflow and division by zero
+    fn check_fixed_arithmetic() {
+        assert_eq!(
+            amount("0.1").checked_add(amount("0.2")),
+            Some(amount("0.3"))
+        );
+        assert_eq!(amount("1").checked_sub(amount("1.5")), Some(amount("-0.5")));
+        assert_eq!(
+            amount("1.5").checked_mul(amount("-2.25")),
+            Some(amount("-3.375"))
+        );
+        assert_eq!(amount("1").checked_div(amount("3")), Some(amount("0.3333")));
+        assert_eq!(
+            amount("-1").checked_div(amount("3")),
+            Some(amount("-0.3333"))
+        );
+        assert_eq!(Amount::ONE.checked_div(Amount::ZERO), None);
+        assert_eq!(Amount::from_int(10).trunc(), 10);
+        assert_eq!(amount("-2.9").trunc(), -2);
+
+        let max = Amount::from_units(i128::MAX);
+        assert_eq!(max.checked_add(Amount::from_units(1)), None);
+        assert_eq!(max.checked_mul(Amount::from_int(2)), None);
+        assert_eq!(Amount::from_units(i128::MIN).checked_neg(), None);
+    }
+}
@@ -0,0 +1,421 @@
+// Copyright 2019 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Secondary indexes of the entries a contract stores, for lookup by an attribute rather than
+//! by address.
+//!
+//! An `Index` keeps an entry for each value of the attribute, listing the keys of the primary
+//! entries which have that value. Keys are normalized to lowercase and listed once, in order.
+//! Index entries are written through the same context as the primary entries, so through a
+//! `KeyValueTransactionContext` they are committed, or rolled back, together:
+//!
+//! ```ignore
+//! let owners = Index::new("cad11d01", "owner")?;
+//! let staging = KeyValueTransactionContext::new(&context);
+//! staging.set_state_entry(asset_address.clone(), asset.to_bytes()?)?;
+//! owners.update(&staging, &asset_address, Some(&old_owner), Some(&new_owner))?;
+//! staging.commit()?;
+//! ```
+//!
+//! An index entry holds the attribute value, so that values whose addresses collide are told
+//! apart, followed by the keys. Each is stored as a two byte big-endian length and its UTF-8
+//! bytes.
+
+use std::collections::BTreeSet;
+
+use sha2::{Digest, Sha512};
+
+use crate::protocol::namespaces::ADDRESS_LENGTH;
+use crate::{TransactionContext, WasmSdkError};
+
+/// A secondary index from the values of an attribute to the keys of the entries with them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Index {
+    prefix: String,
+    name: String,
+}
+
+/// A disagreement between an index and the entries it indexes, found by `Index::check`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexInconsistency {
+    /// The key is not listed under its value
+    Missing { value: String, key: String },
+    /// The key is listed under a value it does not have
+    Stale { value: String, key: String },
+}
+
+impl Index {
+    /// Creates an index whose entries are stored under `prefix`, which must be an even number
+    /// of hex characters within one of the contract's outputs. Indexes sharing a prefix must
+    /// have different names.
+    pub fn new(prefix: &str, name: &str) -> Result<Self, WasmSdkError> {
+        if prefix.len() % 2 != 0
+            || prefix.len() >= ADDRESS_LENGTH
+            || !prefix.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            return Err(WasmSdkError::InternalError(format!(
+                "index prefix '{}' is not an even number of hex characters shorter than an \
+                 address",
+                prefix
+            )));
+        }
+
+        Ok(Index {
+            prefix: prefix.to_lowercase(),
+            name: name.to_string(),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the address of the index entry listing the keys with the value.
+    pub fn address(&self, value: &str) -> String {
+        let mut hasher = Sha512::new();
+        hasher.update(self.name.as_bytes());
+        hasher.update(b"\0");
+        hasher.update(value.as_bytes());
+        let hash = hasher.finalize();
+
+        let hash_length = (ADDRESS_LENGTH - self.prefix.len()) / 2;
+        let mut address = self.prefix.clone();
+        for byte in &hash[..hash_length] {
+            address.push_str(&format!("{:02x}", byte));
+        }
+        address
+    }
+
+    /// Returns the keys with the value, in order.
+    pub fn keys(
+        &self,
+        context: &dyn TransactionContext,
+        value: &str,
+    ) -> Result<Vec<String>, WasmSdkError> {
+        Ok(self.read(context, value)?.into_iter().collect())
+    }
+
+    /// Returns an iterator over the keys with the value, in order.
+    pub fn iter(
+        &self,
+        context: &dyn TransactionContext,
+        value: &str,
+    ) -> Result<impl Iterator<Item = String>, WasmSdkError> {
+        Ok(self.read(context, value)?.into_iter())
+    }
+
+    /// Lists the key under the value, returning false if it was already listed.
+    pub fn insert(
+        &self,
+        context: &dyn TransactionContext,
+        value: &str,
+        key: &str,
+    ) -> Result<bool, WasmSdkError> {
+        let mut keys = self.read(context, value)?;
+        if !keys.insert(normalize(key)) {
+            return Ok(false);
+        }
+        self.write(context, value, keys)?;
+        Ok(true)
+    }
+
+    /// Removes the key from the value, returning false if it was not listed. The index entry is
+    /// deleted once no keys are listed in it.
+    pub fn remove(
+        &self,
+        context: &dyn TransactionContext,
+        value: &str,
+        key: &str,
+    ) -> Result<bool, WasmSdkError> {
+        let mut keys = self.read(context, value)?;
+        if !keys.remove(&normalize(key)) {
+            return Ok(false);
+        }
+        self.write(context, value, keys)?;
+        Ok(true)
+    }
+
+    /// Moves the key from its old value to its new one, where None is an entry which is not set
+    /// or does not have the attribute. Call this with each write of a primary entry.
+    pub fn update(
+        &self,
+        context: &dyn TransactionContext,
+        key: &str,
+        old_value: Option<&str>,
+        new_value: Option<&str>,
+    ) -> Result<(), WasmSdkError> {
+        if old_value == new_value {
+            return Ok(());
+        }
+        if let Some(old_value) = old_value {
+            self.remove(context, old_value, key)?;
+        }
+        if let Some(new_value) = new_value {
+            self.insert(context, new_value, key)?;
+        }
+        Ok(())
+    }
+
+    /// Checks the index against the keys given with their current values, returning each key
+    /// missing from its value and each listed under one of the given values it does not have.
+    /// Keys listed under values which are not given cannot be found stale.
+    pub fn check<'a, I>(
+        &self,
+        context: &dyn TransactionContext,
+        entries: I,
+    ) -> Result<Vec<IndexInconsistency>, WasmSdkError>
+    where
+        I: IntoIterator<Item = (&'a str, Option<&'a str>)>,
+    {
+        let entries = entries
+            .into_iter()
+            .map(|(key, value)| (normalize(key), value))
+            .collect::<Vec<_>>();
+        let values = entries
+            .iter()
+            .filter_map(|(_, value)| *value)
+            .collect::<BTreeSet<_>>();
+
+        let mut inconsistencies = Vec::new();
+        for value in values {
+            let keys = self.read(context, value)?;
+            for (key, key_value) in &entries {
+                let listed = keys.contains(key);
+                if *key_value == Some(value) && !listed {
+                    inconsistencies.push(IndexInconsistency::Missing {
+                        value: value.to_string(),
+                        key: key.clone(),
+                    });
+                } else if *key_value != Some(value) && listed {
+                    inconsistencies.push(IndexInconsistency::Stale {
+                        value: value.to_string(),
+                        key: key.clone(),
+                    });
+                }
+            }
+        }
+
+        Ok(inconsistencies)
+    }
+
+    fn read(
+        &self,
+        context: &dyn TransactionContext,
+        value: &str,
+    ) -> Result<BTreeSet<String>, WasmSdkError> {
+        let data = match context.get_state_entry(&self.address(value))? {
+            Some(data) => data,
+            None => return Ok(BTreeSet::new()),
+        };
+
+        let mut offset = 0;
+        let stored_value = read_string(&data, &mut offset)?;
+        if stored_value != value {
+            return Err(WasmSdkError::InternalError(format!(
+                "index {} entry for '{}' collides with the entry for '{}'",
+                self.name, value, stored_value
+            )));
+        }
+        let mut keys = BTreeSet::new();
+        while offset < data.len() {
+            keys.insert(read_string(&data, &mut offset)?);
+        }
+        Ok(keys)
+    }
+
+    fn write(
+        &self,
+        context: &dyn TransactionContext,
+        value: &str,
+        keys: BTreeSet<String>,
+    ) -> Result<(), WasmSdkError> {
+        let address = self.address(value);
+        if keys.is_empty() {
+            context.delete_state_entry(&address)?;
+            return Ok(());
+        }
+
+        let mut data = Vec::new();
+        write_string(&mut data, value)?;
+        for key in &keys {
+            write_string(&mut data, key)?;
+        }
+        context.set_state_entry(address, data)
+    }
+}
+
+fn normalize(key: &str) -> String {
+    key.to_lowercase()
+}
+
+fn write_string(data: &mut Vec<u8>, string: &str) -> Result<(), WasmSdkError> {
+    if string.len() > u16::MAX as usize {
+        return Err(WasmSdkError::InvalidTransaction(format!(
+            "index values and keys may not be longer than {} bytes",
+            u16::MAX
+        )));
+    }
+    data.extend((string.len() as u16).to_be_bytes().iter());
+    data.extend(string.as_bytes());
+    Ok(())
+}
+
+fn read_string(data: &[u8], offset: &mut usize) -> Result<String, WasmSdkError> {
+    let truncated = || WasmSdkError::InvalidTransaction("index entry is truncated".into());
+
+    let length = data.get(*offset..*offset + 2).ok_or_else(truncated)?;
+    let length = u16::from_be_bytes([length[0], length[1]]) as usize;
+    *offset += 2;
+    let string = data.get(*offset..*offset + length).ok_or_else(truncated)?;
+    *offset += length;
+
+    String::from_utf8(string.to_vec())
+        .map_err(|_| WasmSdkError::InvalidTransaction("index entry is not UTF-8".into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    use crate::middleware::KeyValueTransactionContext;
+
+    /// A context backed by a map
+    #[derive(Default)]
+    struct MockContext {
+        state: RefCell<HashMap<String, Vec<u8>>>,
+    }
+
+    impl TransactionContext for MockContext {
+        fn get_state_entries(
+            &self,
+            addresses: &[String],
+        ) -> Result<Vec<(String, Vec<u8>)>, WasmSdkError> {
+            let state = self.state.borrow();
+            Ok(addresses
+                .iter()
+                .filter_map(|address| {
+                    state
+                        .get(address)
+                        .map(|data| (address.clone(), data.clone()))
+                })
+                .collect())
+        }
+
+        fn set_state_entries(&self, entries: Vec<(String, Vec<u8>)>) -> Result<(), WasmSdkError> {
+            self.state.borrow_mut().extend(entries);
+            Ok(())
+        }
+
+        fn delete_state_entries(&self, addresses: &[String]) -> Result<Vec<String>, WasmSdkError> {
+            let mut state = self.state.borrow_mut();
+            Ok(addresses
+                .iter()
+                .filter(|address| state.remove(*address).is_some())
+                .cloned()
+                .collect())
+        }
+
+        fn add_event(
+            &self,
+            _event_type: String,
+            _attributes: Vec<(String, String)>,
+            _data: &[u8],
+        ) -> Result<(), WasmSdkError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    // check that keys are listed under their values in order, move between values on update,
+    // are rolled back with the staged changes they were made with, and that an index entry is
+    // deleted once it lists no keys
+    fn check_index() {
+        let inner = MockContext::default();
+        let index = Index::new("cad11d01", "owner").unwrap();
+        assert_eq!(index.address("alice").len(), ADDRESS_LENGTH);
+        assert!(index.address("alice").starts_with("cad11d01"));
+        assert_ne!(index.address("alice"), index.address("bob"));
+
+        let context = KeyValueTransactionContext::new(&inner);
+        assert!(index.insert(&context, "alice", "CAD11D00BB").unwrap());
+        assert!(index.insert(&context, "alice", "cad11d00aa").unwrap());
+        assert!(!index.insert(&context, "alice", "cad11d00bb").unwrap());
+        assert_eq!(
+            index.keys(&context, "alice").unwrap(),
+            vec!["cad11d00aa".to_string(), "cad11d00bb".to_string()]
+        );
+        context.commit().unwrap();
+
+        let context = KeyValueTransactionContext::new(&inner);
+        context.checkpoint();
+        index
+            .update(&context, "cad11d00aa", Some("alice"), Some("bob"))
+            .unwrap();
+        assert_eq!(
+            index.iter(&context, "bob").unwrap().collect::<Vec<_>>(),
+            vec!["cad11d00aa".to_string()]
+        );
+        context.rollback();
+        assert_eq!(index.keys(&context, "bob").unwrap(), Vec::<String>::new());
+
+        index
+            .update(&context, "cad11d00aa", Some("alice"), None)
+            .unwrap();
+        index
+            .update(&context, "cad11d00bb", Some("alice"), None)
+            .unwrap();
+        context.commit().unwrap();
+        assert!(inner.state.borrow().is_empty());
+
+        assert!(Index::new("cad", "owner").is_err());
+    }
+
+    #[test]
+    // check that keys missing from their value, or listed under a value they do not have, are
+    // found inconsistent
+    fn check_index_consistency() {
+        let context = MockContext::default();
+        let index = Index::new("cad11d01", "owner").unwrap();
+        index.insert(&context, "alice", "aa").unwrap();
+        index.insert(&context, "alice", "bb").unwrap();
+
+        assert_eq!(
+            index
+                .check(&context, vec![("aa", Some("alice")), ("bb", Some("alice"))])
+                .unwrap(),
+            vec![]
+        );
+        assert_eq!(
+            index
+                .check(
+                    &context,
+                    vec![("aa", Some("alice")), ("bb", None), ("cc", Some("bob"))]
+                )
+                .unwrap(),
+            vec![
+                IndexInconsistency::Stale {
+                    value: "alice".into(),
+                    key: "bb".into()
+                },
+                IndexInconsistency::Missing {
+                    value: "bob".into(),
+                    key: "cc".into()
+                },
+            ]
+        );
+    }
+}
@@ -14,36 +14,111 @@
 
 #![allow(clippy::missing_safety_doc, renamed_and_removed_lints)]
 
+#[cfg(feature = "contract-native")]
+pub mod adapter;
+#[cfg(feature = "state-compression")]
+pub mod compression;
+#[cfg(feature = "simple-state")]
+pub mod entry_metadata;
+#[cfg(feature = "contract-native")]
+pub mod events;
+#[cfg(feature = "contract-wasm")]
 mod externs;
+pub mod fixed;
+#[cfg(feature = "simple-state")]
+pub mod index;
+#[cfg(feature = "contract-wasm")]
 pub mod log;
+#[cfg(feature = "contract-native")]
+pub mod middleware;
+#[cfg(feature = "protocol")]
 pub mod protocol;
+#[cfg(feature = "protocol")]
 pub mod protos;
-
+#[cfg(feature = "simple-state")]
+pub mod schema_version;
+#[cfg(feature = "proto-schemas")]
+pub mod schemas;
+#[cfg(feature = "contract-native")]
+pub mod status;
+#[cfg(feature = "contract-native")]
+pub mod testing;
+#[cfg(feature = "contract-native")]
+pub mod time;
+pub mod trace;
+
+#[cfg(feature = "contract-native")]
 use std::collections::HashMap;
+#[cfg(feature = "contract-native")]
 use std::string::FromUtf8Error;
 
+#[cfg(feature = "contract-wasm")]
 pub use crate::externs::{WasmPtr, WasmPtrList};
-
+#[cfg(feature = "contract-native")]
+use crate::protocol::compute_setting_address;
+#[cfg(feature = "contract-wasm")]
+use crate::protocol::org_id::OrgId;
+#[cfg(feature = "contract-wasm")]
+use crate::protocol::payload::split_relayed_signature;
+#[cfg(feature = "contract-native")]
+use crate::protocol::settings::Setting;
+#[cfg(feature = "contract-native")]
+use crate::protos::FromBytes;
+
+#[cfg(feature = "contract-native")]
 pub struct Header {
     signer: String,
+    transaction_signer: Option<String>,
 }
 
+#[cfg(feature = "contract-native")]
 impl Header {
     pub fn new(signer: String) -> Header {
-        Header { signer }
+        Header {
+            signer,
+            transaction_signer: None,
+        }
+    }
+
+    /// Creates a header for a transaction that was signed by `transaction_signer` on behalf of
+    /// the verified `delegated_signer`.
+    pub fn new_delegated(transaction_signer: String, delegated_signer: String) -> Header {
+        Header {
+            signer: delegated_signer,
+            transaction_signer: Some(transaction_signer),
+        }
     }
 
+    /// Returns the public key of the signer the contract is executed for. If the transaction
+    /// carried a verified delegation, this is the delegated signer's key.
     pub fn get_signer_public_key(&self) -> &str {
         &self.signer
     }
+
+    /// Returns the public key that signed the transaction. This differs from
+    /// `get_signer_public_key` only when the transaction was submitted by a relayer on behalf of
+    /// a delegated signer.
+    pub fn get_transaction_signer_public_key(&self) -> &str {
+        self.transaction_signer.as_deref().unwrap_or(&self.signer)
+    }
+
+    /// Returns the verified delegated signer's public key if the transaction was submitted on
+    /// behalf of another signer.
+    pub fn get_delegated_signer_public_key(&self) -> Option<&str> {
+        self.transaction_signer
+            .as_ref()
+            .map(|_| self.signer.as_str())
+    }
 }
 
+#[cfg(feature = "contract-native")]
 pub struct TpProcessRequest<'a> {
     payload: Vec<u8>,
     header: &'a mut Header,
     signature: String,
 }
 
+#[cfg(feature = "contract-native")]
 impl<'a> TpProcessRequest<'a> {
     pub fn new(payload: Vec<u8>, header: &'a mut Header, signature: String) -> TpProcessRequest {
         TpProcessRequest {
@@ -66,6 +141,7 @@ impl<'a> TpProcessRequest<'a> {
     }
 }
 
+#[cfg(feature = "contract-native")]
 pub trait TransactionContext {
     #[deprecated(
         since = "0.2.0",
@@ -108,6 +184,30 @@ pub trait TransactionContext {
         addresses: &[String],
     ) -> Result<Vec<(String, Vec<u8>)>, WasmSdkError>;
 
+    /// get_setting queries the validator state for the Sawtooth setting with the given key. If
+    /// the setting is set, its value is returned. The setting's address, or the settings
+    /// namespace 000000, must be one of the contract's inputs.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - the setting key, e.g. "sawtooth.swa.administrators"
+    fn get_setting(&self, key: &str) -> Result<Option<String>, WasmSdkError> {
+        let address = compute_setting_address(key)
+            .map_err(|err| WasmSdkError::InvalidTransaction(err.to_string()))?
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+
+        match self.get_state_entry(&address)? {
+            Some(bytes) => {
+                let setting = Setting::from_bytes(&bytes)
+                    .map_err(|err| WasmSdkError::InvalidTransaction(err.to_string()))?;
+                Ok(setting.get_value(key).map(String::from))
+            }
+            None => Ok(None),
+        }
+    }
+
     #[deprecated(
         since = "0.2.0",
         note = "please use `set_state_entry` or `set_state_entries` instead"
@@ -199,15 +299,18 @@ pub trait TransactionContext {
     ) -> Result<(), WasmSdkError>;
 }
 
+#[cfg(feature = "contract-wasm")]
 #[derive(Default)]
 pub struct SabreTransactionContext {}
 
+#[cfg(feature = "contract-wasm")]
 impl SabreTransactionContext {
     pub fn new() -> SabreTransactionContext {
         SabreTransactionContext {}
     }
 }
 
+#[cfg(feature = "contract-wasm")]
 impl TransactionContext for SabreTransactionContext {
     fn get_state_entries(
         &self,
@@ -359,6 +462,7 @@ impl TransactionContext for SabreTransactionContext {
 }
 
 // Mimics the sawtooth sdk TransactionHandler
+#[cfg(feature = "contract-native")]
 pub trait TransactionHandler {
     fn family_name(&self) -> String;
     fn family_versions(&self) -> Vec<String>;
@@ -379,6 +483,7 @@ pub trait TransactionHandler {
 ///
 /// This function is unsafe due to the call to WasmBuffer::from_raw which converts a WasmPtr
 /// to a WasmBuffer to access location in executor memory
+#[cfg(feature = "contract-wasm")]
 pub unsafe fn execute_entrypoint<F>(
     payload_ptr: WasmPtr,
     signer_ptr: WasmPtr,
@@ -412,9 +517,17 @@ where
         return -1;
     };
 
-    let mut header = Header::new(signer);
+    crate::log::init();
+
+    // The transaction processor appends the relayer's public key to the signature of a
+    // transaction submitted under a delegation
+    let (signature, relayer) = split_relayed_signature(&signature);
+    let mut header = match relayer {
+        Some(relayer) => Header::new_delegated(relayer.to_string(), signer),
+        None => Header::new(signer),
+    };
     match apply(
-        &TpProcessRequest::new(payload, &mut header, signature),
+        &TpProcessRequest::new(payload, &mut header, signature.to_string()),
         &mut SabreTransactionContext::new(),
     ) {
         Ok(r) => {
@@ -429,6 +542,7 @@ where
     }
 }
 
+#[cfg(feature = "contract-wasm")]
 pub struct Request {
     roles: Vec<String>,
     org_id: String,
@@ -436,6 +550,7 @@ pub struct Request {
     payload: Vec<u8>,
 }
 
+#[cfg(feature = "contract-wasm")]
 impl Request {
     pub fn new(
         roles: Vec<String>,
@@ -459,6 +574,11 @@ impl Request {
         self.org_id.clone()
     }
 
+    /// Returns the org id of the request, validated.
+    pub fn org_id(&self) -> Result<OrgId, WasmSdkError> {
+        OrgId::new(&self.org_id).map_err(|err| WasmSdkError::InvalidTransaction(err.to_string()))
+    }
+
     pub fn get_public_key(&self) -> String {
         self.public_key.clone()
     }
@@ -483,11 +603,13 @@ impl Request {
 /// It offers methods for accessing the data stored at the
 /// location referenced by the raw pointer.
 ///
+#[cfg(feature = "contract-wasm")]
 pub struct WasmBuffer {
     raw: WasmPtr,
     data: Vec<u8>,
 }
 
+#[cfg(feature = "contract-wasm")]
 impl WasmBuffer {
     pub unsafe fn new(buffer: &[u8]) -> Result<WasmBuffer, WasmSdkError> {
         let raw = externs::alloc(buffer.len());
@@ -549,6 +671,7 @@ impl WasmBuffer {
     }
 }
 
+#[cfg(feature = "contract-native")]
 #[derive(Debug)]
 pub enum WasmSdkError {
     InvalidTransaction(String),
@@ -561,6 +684,7 @@ pub enum WasmSdkError {
     ProtobufError(protobuf::ProtobufError),
 }
 
+#[cfg(feature = "contract-native")]
 impl std::fmt::Display for WasmSdkError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match *self {
@@ -576,24 +700,28 @@ impl std::fmt::Display for WasmSdkError {
     }
 }
 
+#[cfg(feature = "contract-native")]
 impl From<FromUtf8Error> for WasmSdkError {
     fn from(e: FromUtf8Error) -> Self {
         WasmSdkError::Utf8EncodeError(e)
     }
 }
 
+#[cfg(feature = "contract-native")]
 impl From<protobuf::ProtobufError> for WasmSdkError {
     fn from(e: protobuf::ProtobufError) -> Self {
         WasmSdkError::ProtobufError(e)
     }
 }
 
+#[cfg(feature = "contract-native")]
 #[derive(Debug)]
 pub enum ApplyError {
     InvalidTransaction(String),
     InternalError(String),
 }
 
+#[cfg(feature = "contract-native")]
 impl std::fmt::Display for ApplyError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match *self {
@@ -603,6 +731,7 @@ impl std::fmt::Display for ApplyError {
     }
 }
 
+#[cfg(feature = "contract-native")]
 impl From<WasmSdkError> for ApplyError {
     fn from(e: WasmSdkError) -> Self {
         match e {
@@ -612,6 +741,7 @@ impl From<WasmSdkError> for ApplyError {
     }
 }
 
+#[cfg(feature = "contract-wasm")]
 unsafe fn ptr_to_vec(ptr: WasmPtr) -> Result<Option<Vec<u8>>, WasmSdkError> {
     let mut vec = Vec::new();
 
@@ -632,6 +762,7 @@ unsafe fn ptr_to_vec(ptr: WasmPtr) -> Result<Option<Vec<u8>>, WasmSdkError> {
     Ok(Some(vec))
 }
 
+#[cfg(feature = "contract-wasm")]
 #[derive(PartialOrd, PartialEq, Eq, Copy, Clone)]
 pub enum LogLevel {
     Trace,
@@ -641,6 +772,7 @@ pub enum LogLevel {
     Error,
 }
 
+#[cfg(feature = "contract-wasm")]
 pub fn log_message(log_level: LogLevel, log_string: String) {
     unsafe {
         // WasmBuffer was created properly, log message otherwise ignore
@@ -656,6 +788,7 @@ pub fn log_message(log_level: LogLevel, log_string: String) {
     }
 }
 
+#[cfg(feature = "contract-wasm")]
 pub fn log_level() -> LogLevel {
     unsafe {
         match externs::log_level() {
@@ -668,6 +801,7 @@ pub fn log_level() -> LogLevel {
     }
 }
 
+#[cfg(feature = "contract-wasm")]
 pub fn log_enabled(lvl: LogLevel) -> bool {
     lvl >= log_level()
 }
@@ -15,6 +15,59 @@
 //! The following are logging macros that will be used when running a smart contract in
 //! Sabre. The log level is the same level set on the sabre transaction processor running the
 //! smart contract.
+//!
+//! The `log` crate's macros may be used as well; `execute_entrypoint` installs a `SabreLogger`
+//! which forwards their records to the transaction processor.
+
+use crate::LogLevel;
+
+/// A `log::Log` implementation which forwards records to the logger of the sabre transaction
+/// processor running the smart contract.
+pub struct SabreLogger;
+
+static LOGGER: SabreLogger = SabreLogger;
+
+impl ::log::Log for SabreLogger {
+    fn enabled(&self, metadata: &::log::Metadata) -> bool {
+        crate::log_enabled(to_log_level(metadata.level()))
+    }
+
+    fn log(&self, record: &::log::Record) {
+        if self.enabled(record.metadata()) {
+            crate::log_message(to_log_level(record.level()), record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn to_log_level(level: ::log::Level) -> LogLevel {
+    match level {
+        ::log::Level::Trace => LogLevel::Trace,
+        ::log::Level::Debug => LogLevel::Debug,
+        ::log::Level::Info => LogLevel::Info,
+        ::log::Level::Warn => LogLevel::Warn,
+        ::log::Level::Error => LogLevel::Error,
+    }
+}
+
+fn to_level_filter(level: LogLevel) -> ::log::LevelFilter {
+    match level {
+        LogLevel::Trace => ::log::LevelFilter::Trace,
+        LogLevel::Debug => ::log::LevelFilter::Debug,
+        LogLevel::Info => ::log::LevelFilter::Info,
+        LogLevel::Warn => ::log::LevelFilter::Warn,
+        LogLevel::Error => ::log::LevelFilter::Error,
+    }
+}
+
+/// Installs the `SabreLogger` as the `log` crate's logger, at the transaction processor's log
+/// level. Installing it more than once has no further effect.
+pub fn init() {
+    if ::log::set_logger(&LOGGER).is_ok() {
+        ::log::set_max_level(to_level_filter(crate::log_level()));
+    }
+}
 
 #[macro_export]
 macro_rules! log {
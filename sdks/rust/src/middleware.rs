@@ -0,0 +1,1088 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Wrappers which add behavior, such as logging, metrics or caching, around any
+//! `TransactionContext`.
+//!
+//! Wrappers compose by nesting, as each is itself a `TransactionContext`:
+//!
+//! ```ignore
+//! let context = SabreTransactionContext::new();
+//! let context = CachingContext::new(&context);
+//! let context = MiddlewareContext::new(&context, LoggingMiddleware::new(LogLevel::Debug));
+//! ```
+//!
+//! `KeyValueTransactionContext` stages changes in memory until they are committed, so that
+//! multi-step contract logic can roll back the steps that failed.
+
+use std::cell::{Cell, RefCell};
+#[cfg(feature = "simple-state")]
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+#[cfg(feature = "simple-state")]
+use crate::entry_metadata::{self, EntryMetadata};
+#[cfg(feature = "simple-state")]
+use crate::schema_version::{self, Migration};
+#[cfg(feature = "contract-wasm")]
+use crate::{log_enabled, log_message, LogLevel};
+use crate::{TransactionContext, WasmSdkError};
+
+/// Hooks run before and after each operation on a `TransactionContext`.
+///
+/// A `before_*` hook which returns an error prevents the operation; the error is returned to
+/// the caller. `after_*` hooks are only run for operations which succeeded.
+pub trait ContextMiddleware {
+    fn before_get(&self, _addresses: &[String]) -> Result<(), WasmSdkError> {
+        Ok(())
+    }
+
+    fn after_get(&self, _addresses: &[String], _entries: &[(String, Vec<u8>)]) {}
+
+    fn before_set(&self, _entries: &[(String, Vec<u8>)]) -> Result<(), WasmSdkError> {
+        Ok(())
+    }
+
+    fn after_set(&self, _entries: &[(String, Vec<u8>)]) {}
+
+    fn before_delete(&self, _addresses: &[String]) -> Result<(), WasmSdkError> {
+        Ok(())
+    }
+
+    fn after_delete(&self, _addresses: &[String], _deleted: &[String]) {}
+
+    fn before_event(
+        &self,
+        _event_type: &str,
+        _attributes: &[(String, String)],
+        _data: &[u8],
+    ) -> Result<(), WasmSdkError> {
+        Ok(())
+    }
+
+    fn after_event(&self, _event_type: &str, _attributes: &[(String, String)], _data: &[u8]) {}
+}
+
+/// A `TransactionContext` which runs a middleware's hooks around each operation of the context
+/// it wraps.
+pub struct MiddlewareContext<'a, M: ContextMiddleware> {
+    context: &'a dyn TransactionContext,
+    middleware: M,
+}
+
+impl<'a, M: ContextMiddleware> MiddlewareContext<'a, M> {
+    pub fn new(context: &'a dyn TransactionContext, middleware: M) -> Self {
+        MiddlewareContext {
+            context,
+            middleware,
+        }
+    }
+
+    pub fn middleware(&self) -> &M {
+        &self.middleware
+    }
+}
+
+impl<'a, M: ContextMiddleware> TransactionContext for MiddlewareContext<'a, M> {
+    fn get_state_entries(
+        &self,
+        addresses: &[String],
+    ) -> Result<Vec<(String, Vec<u8>)>, WasmSdkError> {
+        self.middleware.before_get(addresses)?;
+        let entries = self.context.get_state_entries(addresses)?;
+        self.middleware.after_get(addresses, &entries);
+        Ok(entries)
+    }
+
+    fn set_state_entries(&self, entries: Vec<(String, Vec<u8>)>) -> Result<(), WasmSdkError> {
+        self.middleware.before_set(&entries)?;
+        // The entries are kept for the after hook, as the wrapped context takes ownership
+        self.context.set_state_entries(entries.clone())?;
+        self.middleware.after_set(&entries);
+        Ok(())
+    }
+
+    fn delete_state_entries(&self, addresses: &[String]) -> Result<Vec<String>, WasmSdkError> {
+        self.middleware.before_delete(addresses)?;
+        let deleted = self.context.delete_state_entries(addresses)?;
+        self.middleware.after_delete(addresses, &deleted);
+        Ok(deleted)
+    }
+
+    fn add_event(
+        &self,
+        event_type: String,
+        attributes: Vec<(String, String)>,
+        data: &[u8],
+    ) -> Result<(), WasmSdkError> {
+        self.middleware
+            .before_event(&event_type, &attributes, data)?;
+        self.context
+            .add_event(event_type.clone(), attributes.clone(), data)?;
+        self.middleware.after_event(&event_type, &attributes, data);
+        Ok(())
+    }
+}
+
+/// Logs each operation on the context at the given level, using the transaction processor's
+/// logger.
+#[cfg(feature = "contract-wasm")]
+pub struct LoggingMiddleware {
+    level: LogLevel,
+}
+
+#[cfg(feature = "contract-wasm")]
+impl LoggingMiddleware {
+    pub fn new(level: LogLevel) -> Self {
+        LoggingMiddleware { level }
+    }
+
+    fn log(&self, message: String) {
+        if log_enabled(self.level) {
+            log_message(self.level, message);
+        }
+    }
+}
+
+#[cfg(feature = "contract-wasm")]
+impl ContextMiddleware for LoggingMiddleware {
+    fn after_get(&self, addresses: &[String], entries: &[(String, Vec<u8>)]) {
+        self.log(format!(
+            "Got {} of {} state entries: {:?}",
+            entries.len(),
+            addresses.len(),
+            addresses
+        ));
+    }
+
+    fn after_set(&self, entries: &[(String, Vec<u8>)]) {
+        self.log(format!(
+            "Set state entries: {:?}",
+            entries
+                .iter()
+                .map(|(address, _)| address)
+                .collect::<Vec<_>>()
+        ));
+    }
+
+    fn after_delete(&self, _addresses: &[String], deleted: &[String]) {
+        self.log(format!("Deleted state entries: {:?}", deleted));
+    }
+
+    fn after_event(&self, event_type: &str, attributes: &[(String, String)], _data: &[u8]) {
+        self.log(format!(
+            "Added event {} with attributes {:?}",
+            event_type, attributes
+        ));
+    }
+}
+
+/// Counts the operations on the context and the bytes read and written.
+#[derive(Default)]
+pub struct MetricsMiddleware {
+    gets: Cell<u64>,
+    sets: Cell<u64>,
+    deletes: Cell<u64>,
+    events: Cell<u64>,
+    bytes_read: Cell<u64>,
+    bytes_written: Cell<u64>,
+}
+
+impl MetricsMiddleware {
+    pub fn new() -> Self {
+        MetricsMiddleware::default()
+    }
+
+    /// The number of state entries read
+    pub fn gets(&self) -> u64 {
+        self.gets.get()
+    }
+
+    /// The number of state entries set
+    pub fn sets(&self) -> u64 {
+        self.sets.get()
+    }
+
+    /// The number of state entries deleted
+    pub fn deletes(&self) -> u64 {
+        self.deletes.get()
+    }
+
+    /// The number of events added
+    pub fn events(&self) -> u64 {
+        self.events.get()
+    }
+
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read.get()
+    }
+
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written.get()
+    }
+}
+
+fn increment(counter: &Cell<u64>, amount: usize) {
+    counter.set(counter.get() + amount as u64);
+}
+
+impl ContextMiddleware for MetricsMiddleware {
+    fn after_get(&self, _addresses: &[String], entries: &[(String, Vec<u8>)]) {
+        increment(&self.gets, entries.len());
+        increment(
+            &self.bytes_read,
+            entries.iter().map(|(_, data)| data.len()).sum(),
+        );
+    }
+
+    fn after_set(&self, entries: &[(String, Vec<u8>)]) {
+        increment(&self.sets, entries.len());
+        increment(
+            &self.bytes_written,
+            entries.iter().map(|(_, data)| data.len()).sum(),
+        );
+    }
+
+    fn after_delete(&self, _addresses: &[String], deleted: &[String]) {
+        increment(&self.deletes, deleted.len());
+    }
+
+    fn after_event(&self, _event_type: &str, _attributes: &[(String, String)], _data: &[u8]) {
+        increment(&self.events, 1);
+    }
+}
+
+/// A `TransactionContext` which caches the state read through it, so that each address is read
+/// from the wrapped context at most once. Entries set or deleted through the cache update it.
+pub struct CachingContext<'a> {
+    context: &'a dyn TransactionContext,
+    // None records that an address is not set
+    cache: RefCell<HashMap<String, Option<Vec<u8>>>>,
+}
+
+impl<'a> CachingContext<'a> {
+    pub fn new(context: &'a dyn TransactionContext) -> Self {
+        CachingContext {
+            context,
+            cache: RefCell::new(HashMap::new()),
+        }
+    }
+}
+
+impl<'a> TransactionContext for CachingContext<'a> {
+    fn get_state_entries(
+        &self,
+        addresses: &[String],
+    ) -> Result<Vec<(String, Vec<u8>)>, WasmSdkError> {
+        let uncached = addresses
+            .iter()
+            .filter(|address| !self.cache.borrow().contains_key(*address))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if !uncached.is_empty() {
+            let entries = self.context.get_state_entries(&uncached)?;
+            let mut cache = self.cache.borrow_mut();
+            for address in uncached {
+                cache.insert(address, None);
+            }
+            for (address, data) in entries {
+                cache.insert(address, Some(data));
+            }
+        }
+
+        let cache = self.cache.borrow();
+        Ok(addresses
+            .iter()
+            .filter_map(|address| match cache.get(address) {
+                Some(Some(data)) => Some((address.clone(), data.clone())),
+                _ => None,
+            })
+            .collect())
+    }
+
+    fn set_state_entries(&self, entries: Vec<(String, Vec<u8>)>) -> Result<(), WasmSdkError> {
+        self.context.set_state_entries(entries.clone())?;
+        self.cache.borrow_mut().extend(
+            entries
+                .into_iter()
+                .map(|(address, data)| (address, Some(data))),
+        );
+        Ok(())
+    }
+
+    fn delete_state_entries(&self, addresses: &[String]) -> Result<Vec<String>, WasmSdkError> {
+        let deleted = self.context.delete_state_entries(addresses)?;
+        let mut cache = self.cache.borrow_mut();
+        for address in addresses {
+            cache.insert(address.clone(), None);
+        }
+        Ok(deleted)
+    }
+
+    fn add_event(
+        &self,
+        event_type: String,
+        attributes: Vec<(String, String)>,
+        data: &[u8],
+    ) -> Result<(), WasmSdkError> {
+        self.context.add_event(event_type, attributes, data)
+    }
+}
+
+/// An event added through a `KeyValueTransactionContext`, awaiting commit
+#[cfg(feature = "simple-state")]
+struct StagedEvent {
+    event_type: String,
+    attributes: Vec<(String, String)>,
+    data: Vec<u8>,
+}
+
+/// The staged changes as of a checkpoint
+#[cfg(feature = "simple-state")]
+struct Checkpoint {
+    writes: BTreeMap<String, Option<Vec<u8>>>,
+    events: usize,
+}
+
+/// A `TransactionContext` which stages the entries set and deleted, and the events added,
+/// through it in memory. Nothing reaches the wrapped context until `commit` is called; changes
+/// which are never committed are discarded.
+///
+/// `checkpoint` records the staged changes, and `rollback` returns to the most recent
+/// checkpoint, so that a step of a contract which fails can be undone without failing the
+/// transaction:
+///
+/// ```ignore
+/// let staging = KeyValueTransactionContext::new(&context);
+/// staging.checkpoint();
+/// if apply_step(&staging).is_err() {
+///     staging.rollback();
+/// }
+/// staging.commit()?;
+/// ```
+#[cfg(feature = "simple-state")]
+pub struct KeyValueTransactionContext<'a> {
+    context: &'a dyn TransactionContext,
+    // None records that an address is deleted
+    writes: RefCell<BTreeMap<String, Option<Vec<u8>>>>,
+    events: RefCell<Vec<StagedEvent>>,
+    checkpoints: RefCell<Vec<Checkpoint>>,
+    // The size from which entries are compressed, if compression is enabled
+    #[cfg(feature = "state-compression")]
+    compression_threshold: Option<usize>,
+    // The signature of the transaction recorded in the metadata of entries, if enabled
+    transaction_id: Option<String>,
+    // The metadata of entries read from the wrapped context; None if unset or stored without
+    metadata: RefCell<HashMap<String, Option<EntryMetadata>>>,
+    // Upgrades entries stored with older schema versions, if entries are versioned
+    migration: Option<&'a dyn Migration>,
+    // The schema versions entries read from the wrapped context were stored with
+    schema_versions: RefCell<HashMap<String, u32>>,
+}
+
+#[cfg(feature = "simple-state")]
+impl<'a> KeyValueTransactionContext<'a> {
+    pub fn new(context: &'a dyn TransactionContext) -> Self {
+        KeyValueTransactionContext {
+            context,
+            writes: RefCell::new(BTreeMap::new()),
+            events: RefCell::new(Vec::new()),
+            checkpoints: RefCell::new(Vec::new()),
+            #[cfg(feature = "state-compression")]
+            compression_threshold: None,
+            transaction_id: None,
+            metadata: RefCell::new(HashMap::new()),
+            migration: None,
+            schema_versions: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Creates a context which compresses the entries of at least `threshold` bytes it commits,
+    /// and decompresses the compressed entries it reads. Entries which were stored uncompressed
+    /// are read as they are, and compressed when they are next set.
+    #[cfg(feature = "state-compression")]
+    pub fn with_compression(context: &'a dyn TransactionContext, threshold: usize) -> Self {
+        KeyValueTransactionContext {
+            compression_threshold: Some(threshold),
+            ..KeyValueTransactionContext::new(context)
+        }
+    }
+
+    /// Records the transactions which create and modify entries in their metadata, read by
+    /// `get_entry_metadata`. `transaction_id` is the signature of the transaction being applied.
+    ///
+    /// Recording metadata costs a read of each entry set which was not already read, to keep its
+    /// creator. Entries stored without metadata are read as they are, and are given the
+    /// transaction which next sets them as their creator. Contracts which enable metadata must
+    /// keep it enabled, as entries with metadata are only understood by contexts recording it.
+    pub fn with_entry_metadata(mut self, transaction_id: String) -> Self {
+        self.transaction_id = Some(transaction_id);
+        self
+    }
+
+    /// Versions the entries the context commits with the migration's current schema version, and
+    /// has the migration upgrade the entries it reads which were stored with an older version.
+    /// Entries stored before versioning was enabled are read as version 0, and entries with a
+    /// newer version than the current one are an error. Migrated entries are stored with the
+    /// current version when they are next set.
+    pub fn with_schema_version(mut self, migration: &'a dyn Migration) -> Self {
+        self.migration = Some(migration);
+        self
+    }
+
+    /// Returns the schema version the entry was stored with, including staged changes, or None
+    /// if it is not set or entries are not versioned by this context.
+    pub fn get_schema_version(&self, address: &str) -> Result<Option<u32>, WasmSdkError> {
+        let migration = match self.migration {
+            Some(migration) => migration,
+            None => return Ok(None),
+        };
+
+        let staged = self.writes.borrow().get(address).map(Option::is_some);
+        match staged {
+            Some(true) => Ok(Some(migration.current_version())),
+            Some(false) => Ok(None),
+            None => {
+                self.read_stored(&[address.to_string()])?;
+                Ok(self.schema_versions.borrow().get(address).copied())
+            }
+        }
+    }
+
+    /// Returns the transactions which created and last modified the entry, including staged
+    /// changes, or None if it is not set, was stored without metadata, or metadata is not
+    /// recorded by this context.
+    pub fn get_entry_metadata(&self, address: &str) -> Result<Option<EntryMetadata>, WasmSdkError> {
+        let transaction_id = match &self.transaction_id {
+            Some(transaction_id) => transaction_id,
+            None => return Ok(None),
+        };
+
+        let staged = self.writes.borrow().get(address).map(Option::is_some);
+        match staged {
+            Some(true) => Ok(Some(self.metadata_for_set(address, transaction_id)?)),
+            Some(false) => Ok(None),
+            None => {
+                self.read_stored(&[address.to_string()])?;
+                Ok(self.metadata.borrow().get(address).cloned().flatten())
+            }
+        }
+    }
+
+    /// Returns the metadata of an entry set by the transaction, keeping its creator.
+    fn metadata_for_set(
+        &self,
+        address: &str,
+        transaction_id: &str,
+    ) -> Result<EntryMetadata, WasmSdkError> {
+        if !self.metadata.borrow().contains_key(address) {
+            self.read_stored(&[address.to_string()])?;
+        }
+        let created_by = self
+            .metadata
+            .borrow()
+            .get(address)
+            .cloned()
+            .flatten()
+            .map(|metadata| metadata.created_by().to_string())
+            .unwrap_or_else(|| transaction_id.to_string());
+
+        Ok(EntryMetadata::new(created_by, transaction_id.to_string()))
+    }
+
+    /// Reads entries from the wrapped context, returning their data as it was set and recording
+    /// their metadata and schema versions. Entries stored with an older schema version are
+    /// migrated.
+    fn read_stored(&self, addresses: &[String]) -> Result<HashMap<String, Vec<u8>>, WasmSdkError> {
+        let mut read = HashMap::new();
+        let mut outdated: BTreeMap<u32, HashMap<String, Vec<u8>>> = BTreeMap::new();
+        for (address, data) in self.context.get_state_entries(addresses)? {
+            let data = self.decompress(data)?;
+            let data = if self.transaction_id.is_some() {
+                let (metadata, data) = entry_metadata::unwrap(data)?;
+                self.metadata.borrow_mut().insert(address.clone(), metadata);
+                data
+            } else {
+                data
+            };
+
+            let migration = match self.migration {
+                Some(migration) => migration,
+                None => {
+                    read.insert(address, data);
+                    continue;
+                }
+            };
+            let (version, data) = schema_version::unwrap(data)?;
+            self.schema_versions
+                .borrow_mut()
+                .insert(address.clone(), version);
+            if version > migration.current_version() {
+                return Err(WasmSdkError::InvalidTransaction(format!(
+                    "entry {} has schema version {}, newer than the current version {}",
+                    address,
+                    version,
+                    migration.current_version()
+                )));
+            } else if version < migration.current_version() {
+                outdated.entry(version).or_default().insert(address, data);
+            } else {
+                read.insert(address, data);
+            }
+        }
+
+        if let Some(migration) = self.migration {
+            for (version, entries) in outdated {
+                read.extend(migration.migrate(version, entries)?);
+            }
+        }
+
+        if self.transaction_id.is_some() {
+            let mut metadata = self.metadata.borrow_mut();
+            for address in addresses {
+                if !read.contains_key(address) {
+                    metadata.insert(address.clone(), None);
+                }
+            }
+        }
+
+        Ok(read)
+    }
+
+    /// Returns the data as it is stored in the wrapped context.
+    fn encode(&self, address: &str, data: Vec<u8>) -> Result<Vec<u8>, WasmSdkError> {
+        let data = match self.migration {
+            Some(migration) => {
+                self.schema_versions
+                    .borrow_mut()
+                    .insert(address.to_string(), migration.current_version());
+                schema_version::wrap(migration.current_version(), data)
+            }
+            None => data,
+        };
+        let data = match &self.transaction_id {
+            Some(transaction_id) => {
+                let metadata = self.metadata_for_set(address, transaction_id)?;
+                let data = entry_metadata::wrap(&metadata, data);
+                self.metadata
+                    .borrow_mut()
+                    .insert(address.to_string(), Some(metadata));
+                data
+            }
+            None => data,
+        };
+
+        #[cfg(feature = "state-compression")]
+        {
+            if let Some(threshold) = self.compression_threshold {
+                return Ok(crate::compression::compress(data, threshold));
+            }
+        }
+        Ok(data)
+    }
+
+    /// Returns the data read from the wrapped context decompressed, if compression is enabled.
+    fn decompress(&self, data: Vec<u8>) -> Result<Vec<u8>, WasmSdkError> {
+        #[cfg(feature = "state-compression")]
+        {
+            if self.compression_threshold.is_some() {
+                return crate::compression::decompress(data);
+            }
+        }
+        Ok(data)
+    }
+
+    /// Records the staged changes, to be returned to by the next `rollback`. Checkpoints nest.
+    pub fn checkpoint(&self) {
+        self.checkpoints.borrow_mut().push(Checkpoint {
+            writes: self.writes.borrow().clone(),
+            events: self.events.borrow().len(),
+        });
+    }
+
+    /// Discards the changes staged since the most recent checkpoint, and removes it. Without a
+    /// checkpoint, all staged changes are discarded.
+    pub fn rollback(&self) {
+        match self.checkpoints.borrow_mut().pop() {
+            Some(checkpoint) => {
+                *self.writes.borrow_mut() = checkpoint.writes;
+                self.events.borrow_mut().truncate(checkpoint.events);
+            }
+            None => {
+                self.writes.borrow_mut().clear();
+                self.events.borrow_mut().clear();
+            }
+        }
+    }
+
+    /// Sets, deletes and adds the staged entries and events in the wrapped context, and removes
+    /// all checkpoints. The staged changes are kept by address, so entries are set, and deleted,
+    /// in address order whatever order they were staged in, and every node hands the wrapped
+    /// context the same calls.
+    pub fn commit(&self) -> Result<(), WasmSdkError> {
+        let writes = std::mem::take(&mut *self.writes.borrow_mut());
+        let events = std::mem::take(&mut *self.events.borrow_mut());
+        self.checkpoints.borrow_mut().clear();
+
+        // The creators of the entries set are read together, rather than one entry at a time
+        if self.transaction_id.is_some() {
+            let unread = {
+                let metadata = self.metadata.borrow();
+                writes
+                    .iter()
+                    .filter(|(address, data)| data.is_some() && !metadata.contains_key(*address))
+                    .map(|(address, _)| address.clone())
+                    .collect::<Vec<_>>()
+            };
+            if !unread.is_empty() {
+                self.read_stored(&unread)?;
+            }
+        }
+
+        let mut entries = Vec::new();
+        let mut deletions = Vec::new();
+        for (address, data) in writes {
+            match data {
+                Some(data) => {
+                    let data = self.encode(&address, data)?;
+                    entries.push((address, data));
+                }
+                None => {
+                    self.metadata.borrow_mut().insert(address.clone(), None);
+                    self.schema_versions.borrow_mut().remove(&address);
+                    deletions.push(address);
+                }
+            }
+        }
+
+        if !entries.is_empty() {
+            self.context.set_state_entries(entries)?;
+        }
+        if !deletions.is_empty() {
+            self.context.delete_state_entries(&deletions)?;
+        }
+        for event in events {
+            self.context
+                .add_event(event.event_type, event.attributes, &event.data)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "simple-state")]
+impl<'a> TransactionContext for KeyValueTransactionContext<'a> {
+    fn get_state_entries(
+        &self,
+        addresses: &[String],
+    ) -> Result<Vec<(String, Vec<u8>)>, WasmSdkError> {
+        let unstaged = {
+            let writes = self.writes.borrow();
+            addresses
+                .iter()
+                .filter(|address| !writes.contains_key(*address))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        let mut read = if unstaged.is_empty() {
+            HashMap::new()
+        } else {
+            self.read_stored(&unstaged)?
+        };
+
+        let writes = self.writes.borrow();
+        Ok(addresses
+            .iter()
+            .filter_map(|address| {
+                let data = match writes.get(address) {
+                    Some(staged) => staged.clone(),
+                    None => read.remove(address),
+                };
+                data.map(|data| (address.clone(), data))
+            })
+            .collect())
+    }
+
+    fn set_state_entries(&self, entries: Vec<(String, Vec<u8>)>) -> Result<(), WasmSdkError> {
+        self.writes.borrow_mut().extend(
+            entries
+                .into_iter()
+                .map(|(address, data)| (address, Some(data))),
+        );
+        Ok(())
+    }
+
+    fn delete_state_entries(&self, addresses: &[String]) -> Result<Vec<String>, WasmSdkError> {
+        let deleted = self
+            .get_state_entries(addresses)?
+            .into_iter()
+            .map(|(address, _)| address)
+            .collect();
+        let mut writes = self.writes.borrow_mut();
+        for address in addresses {
+            writes.insert(address.clone(), None);
+        }
+        Ok(deleted)
+    }
+
+    fn add_event(
+        &self,
+        event_type: String,
+        attributes: Vec<(String, String)>,
+        data: &[u8],
+    ) -> Result<(), WasmSdkError> {
+        self.events.borrow_mut().push(StagedEvent {
+            event_type,
+            attributes,
+            data: data.to_vec(),
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A context backed by a map, which counts the reads made of it and records the types of the
+    /// events added
+    #[derive(Default)]
+    struct MockContext {
+        state: RefCell<HashMap<String, Vec<u8>>>,
+        reads: Cell<usize>,
+        events: RefCell<Vec<String>>,
+    }
+
+    impl TransactionContext for MockContext {
+        fn get_state_entries(
+            &self,
+            addresses: &[String],
+        ) -> Result<Vec<(String, Vec<u8>)>, WasmSdkError> {
+            self.reads.set(self.reads.get() + addresses.len());
+            let state = self.state.borrow();
+            Ok(addresses
+                .iter()
+                .filter_map(|address| {
+                    state
+                        .get(address)
+                        .map(|data| (address.clone(), data.clone()))
+                })
+                .collect())
+        }
+
+        fn set_state_entries(&self, entries: Vec<(String, Vec<u8>)>) -> Result<(), WasmSdkError> {
+            self.state.borrow_mut().extend(entries);
+            Ok(())
+        }
+
+        fn delete_state_entries(&self, addresses: &[String]) -> Result<Vec<String>, WasmSdkError> {
+            let mut state = self.state.borrow_mut();
+            Ok(addresses
+                .iter()
+                .filter(|address| state.remove(*address).is_some())
+                .cloned()
+                .collect())
+        }
+
+        fn add_event(
+            &self,
+            event_type: String,
+            _attributes: Vec<(String, String)>,
+            _data: &[u8],
+        ) -> Result<(), WasmSdkError> {
+            self.events.borrow_mut().push(event_type);
+            Ok(())
+        }
+    }
+
+    /// Refuses to set any entry
+    struct ReadOnlyMiddleware;
+
+    /// Records the addresses set and deleted, in the order they are passed on
+    #[derive(Default)]
+    struct RecordingMiddleware {
+        sets: RefCell<Vec<String>>,
+        deletes: RefCell<Vec<String>>,
+    }
+
+    impl ContextMiddleware for RecordingMiddleware {
+        fn after_set(&self, entries: &[(String, Vec<u8>)]) {
+            self.sets
+                .borrow_mut()
+                .extend(entries.iter().map(|(address, _)| address.clone()));
+        }
+
+        fn after_delete(&self, addresses: &[String], _deleted: &[String]) {
+            self.deletes.borrow_mut().extend(addresses.iter().cloned());
+        }
+    }
+
+    impl ContextMiddleware for ReadOnlyMiddleware {
+        fn before_set(&self, _entries: &[(String, Vec<u8>)]) -> Result<(), WasmSdkError> {
+            Err(WasmSdkError::InvalidTransaction("read only".into()))
+        }
+    }
+
+    #[test]
+    // check that the metrics middleware counts the operations and bytes passed through it
+    fn check_metrics_middleware() {
+        let inner = MockContext::default();
+        let context = MiddlewareContext::new(&inner, MetricsMiddleware::new());
+
+        context
+            .set_state_entries(vec![
+                ("a".into(), b"abc".to_vec()),
+                ("b".into(), b"de".to_vec()),
+            ])
+            .unwrap();
+        assert_eq!(context.get_state_entry("a").unwrap(), Some(b"abc".to_vec()));
+        assert_eq!(context.get_state_entry("c").unwrap(), None);
+        context.delete_state_entry("b").unwrap();
+        context.add_event("test".into(), vec![], b"").unwrap();
+
+        let metrics = context.middleware();
+        assert_eq!(metrics.sets(), 2);
+        assert_eq!(metrics.bytes_written(), 5);
+        assert_eq!(metrics.gets(), 1);
+        assert_eq!(metrics.bytes_read(), 3);
+        assert_eq!(metrics.deletes(), 1);
+        assert_eq!(metrics.events(), 1);
+    }
+
+    #[test]
+    // check that an error from a before hook prevents the operation
+    fn check_middleware_before_hook_error() {
+        let inner = MockContext::default();
+        let context = MiddlewareContext::new(&inner, ReadOnlyMiddleware);
+
+        assert!(context
+            .set_state_entry("a".into(), b"abc".to_vec())
+            .is_err());
+        assert!(inner.state.borrow().is_empty());
+    }
+
+    #[test]
+    // check that the caching context reads each address once, including unset addresses, and
+    // that sets and deletes through it are reflected in later reads
+    fn check_caching_context() {
+        let inner = MockContext::default();
+        inner.state.borrow_mut().insert("a".into(), b"abc".to_vec());
+        let context = CachingContext::new(&inner);
+
+        assert_eq!(context.get_state_entry("a").unwrap(), Some(b"abc".to_vec()));
+        assert_eq!(context.get_state_entry("a").unwrap(), Some(b"abc".to_vec()));
+        assert_eq!(context.get_state_entry("b").unwrap(), None);
+        assert_eq!(context.get_state_entry("b").unwrap(), None);
+        assert_eq!(inner.reads.get(), 2);
+
+        context.set_state_entry("b".into(), b"de".to_vec()).unwrap();
+        assert_eq!(context.get_state_entry("b").unwrap(), Some(b"de".to_vec()));
+        context.delete_state_entry("a").unwrap();
+        assert_eq!(context.get_state_entry("a").unwrap(), None);
+        assert_eq!(inner.reads.get(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "simple-state")]
+    // check that the staging context reads its own staged changes, leaves the wrapped context
+    // untouched until commit, and discards the changes made since a checkpoint on rollback
+    fn check_key_value_transaction_context() {
+        let inner = MockContext::default();
+        inner.state.borrow_mut().insert("a".into(), b"abc".to_vec());
+        let context = KeyValueTransactionContext::new(&inner);
+
+        context.set_state_entry("b".into(), b"de".to_vec()).unwrap();
+        context.checkpoint();
+        context.set_state_entry("b".into(), b"fg".to_vec()).unwrap();
+        assert_eq!(context.delete_state_entry("a").unwrap(), Some("a".into()));
+        context.add_event("step".into(), vec![], b"").unwrap();
+        assert_eq!(context.get_state_entry("a").unwrap(), None);
+        assert_eq!(context.get_state_entry("b").unwrap(), Some(b"fg".to_vec()));
+        assert!(inner.state.borrow().get("b").is_none());
+
+        context.rollback();
+        assert_eq!(context.get_state_entry("a").unwrap(), Some(b"abc".to_vec()));
+        assert_eq!(context.get_state_entry("b").unwrap(), Some(b"de".to_vec()));
+
+        context.add_event("done".into(), vec![], b"").unwrap();
+        context.commit().unwrap();
+        assert_eq!(inner.state.borrow().get("a"), Some(&b"abc".to_vec()));
+        assert_eq!(inner.state.borrow().get("b"), Some(&b"de".to_vec()));
+        assert_eq!(*inner.events.borrow(), vec!["done".to_string()]);
+
+        context.set_state_entry("c".into(), b"h".to_vec()).unwrap();
+        context.rollback();
+        context.commit().unwrap();
+        assert!(inner.state.borrow().get("c").is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "simple-state")]
+    // check that staged entries are set, and deleted, in the wrapped context in address order,
+    // whatever order they were staged in
+    fn check_key_value_transaction_context_commit_order() {
+        let inner = MockContext::default();
+        for address in ["x", "y", "z"] {
+            inner
+                .state
+                .borrow_mut()
+                .insert(address.into(), b"abc".to_vec());
+        }
+        let recording = MiddlewareContext::new(&inner, RecordingMiddleware::default());
+        let context = KeyValueTransactionContext::new(&recording);
+
+        context.set_state_entry("c".into(), b"c".to_vec()).unwrap();
+        context
+            .set_state_entries(vec![
+                ("a".into(), b"a".to_vec()),
+                ("d".into(), b"d".to_vec()),
+                ("b".into(), b"b".to_vec()),
+            ])
+            .unwrap();
+        context.delete_state_entry("z").unwrap();
+        context.delete_state_entry("x").unwrap();
+        context.commit().unwrap();
+
+        let middleware = recording.middleware();
+        assert_eq!(*middleware.sets.borrow(), vec!["a", "b", "c", "d"]);
+        assert_eq!(*middleware.deletes.borrow(), vec!["x", "z"]);
+    }
+
+    #[cfg(feature = "state-compression")]
+    #[test]
+    // check that large entries are compressed in the wrapped context and read back as they were
+    // set, and that entries stored uncompressed are still read
+    fn check_key_value_transaction_context_compression() {
+        let inner = MockContext::default();
+        inner.state.borrow_mut().insert("a".into(), b"abc".to_vec());
+        let large = vec![b'x'; 1024];
+
+        let context = KeyValueTransactionContext::with_compression(&inner, 512);
+        context.set_state_entry("b".into(), large.clone()).unwrap();
+        context.commit().unwrap();
+        assert!(inner.state.borrow()["b"].len() < large.len());
+
+        let context = KeyValueTransactionContext::with_compression(&inner, 512);
+        assert_eq!(context.get_state_entry("a").unwrap(), Some(b"abc".to_vec()));
+        assert_eq!(context.get_state_entry("b").unwrap(), Some(large));
+    }
+
+    #[cfg(feature = "simple-state")]
+    #[test]
+    // check that entries set with metadata keep the transaction which created them, record the
+    // one which last set them, and are read back as they were set
+    fn check_key_value_transaction_context_entry_metadata() {
+        let inner = MockContext::default();
+        inner.state.borrow_mut().insert("a".into(), b"abc".to_vec());
+
+        let context = KeyValueTransactionContext::new(&inner).with_entry_metadata("t1".into());
+        assert_eq!(context.get_entry_metadata("a").unwrap(), None);
+        context.set_state_entry("a".into(), b"de".to_vec()).unwrap();
+        context.set_state_entry("b".into(), b"fg".to_vec()).unwrap();
+        assert_eq!(
+            context.get_entry_metadata("b").unwrap(),
+            Some(EntryMetadata::new("t1".into(), "t1".into()))
+        );
+        context.commit().unwrap();
+        assert!(inner.state.borrow()["b"].starts_with(entry_metadata::MARKER));
+
+        let context = KeyValueTransactionContext::new(&inner).with_entry_metadata("t2".into());
+        assert_eq!(context.get_state_entry("b").unwrap(), Some(b"fg".to_vec()));
+        context.set_state_entry("b".into(), b"hi".to_vec()).unwrap();
+        context.commit().unwrap();
+
+        let context = KeyValueTransactionContext::new(&inner).with_entry_metadata("t3".into());
+        assert_eq!(
+            context.get_entry_metadata("a").unwrap(),
+            Some(EntryMetadata::new("t1".into(), "t1".into()))
+        );
+        assert_eq!(
+            context.get_entry_metadata("b").unwrap(),
+            Some(EntryMetadata::new("t1".into(), "t2".into()))
+        );
+        assert_eq!(context.get_state_entry("b").unwrap(), Some(b"hi".to_vec()));
+        context.delete_state_entry("b").unwrap();
+        assert_eq!(context.get_entry_metadata("b").unwrap(), None);
+    }
+
+    /// Upgrades entries to version 2, where their data is upper case and ends with the version
+    /// they were migrated from
+    #[cfg(feature = "simple-state")]
+    struct UpperCaseMigration;
+
+    #[cfg(feature = "simple-state")]
+    impl Migration for UpperCaseMigration {
+        fn current_version(&self) -> u32 {
+            2
+        }
+
+        fn migrate(
+            &self,
+            from_version: u32,
+            entries: HashMap<String, Vec<u8>>,
+        ) -> Result<HashMap<String, Vec<u8>>, WasmSdkError> {
+            Ok(entries
+                .into_iter()
+                .map(|(address, data)| {
+                    let mut data = data.to_ascii_uppercase();
+                    data.extend(from_version.to_string().into_bytes());
+                    (address, data)
+                })
+                .collect())
+        }
+    }
+
+    #[cfg(feature = "simple-state")]
+    #[test]
+    // check that entries stored without a version or with an older one are migrated as they are
+    // read, that entries are set with the current version, and that entries with a newer
+    // version are an error
+    fn check_key_value_transaction_context_schema_version() {
+        let inner = MockContext::default();
+        inner.state.borrow_mut().insert("a".into(), b"abc".to_vec());
+        inner
+            .state
+            .borrow_mut()
+            .insert("b".into(), schema_version::wrap(1, b"de".to_vec()));
+        inner
+            .state
+            .borrow_mut()
+            .insert("c".into(), schema_version::wrap(3, b"fg".to_vec()));
+
+        let migration = UpperCaseMigration;
+        let context = KeyValueTransactionContext::new(&inner).with_schema_version(&migration);
+        assert_eq!(context.get_schema_version("a").unwrap(), Some(0));
+        assert_eq!(
+            context.get_state_entry("a").unwrap(),
+            Some(b"ABC0".to_vec())
+        );
+        assert_eq!(context.get_state_entry("b").unwrap(), Some(b"DE1".to_vec()));
+        assert!(context.get_state_entry("c").is_err());
+
+        context
+            .set_state_entry("a".into(), b"ABC".to_vec())
+            .unwrap();
+        assert_eq!(context.get_schema_version("a").unwrap(), Some(2));
+        context.commit().unwrap();
+        assert_eq!(
+            inner.state.borrow()["a"],
+            schema_version::wrap(2, b"ABC".to_vec())
+        );
+
+        let context = KeyValueTransactionContext::new(&inner).with_schema_version(&migration);
+        assert_eq!(context.get_state_entry("a").unwrap(), Some(b"ABC".to_vec()));
+        assert_eq!(context.get_schema_version("d").unwrap(), None);
+    }
+}
@@ -0,0 +1,255 @@
+// Copyright 2019 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Computes and validates state addresses of a given length.
+//!
+//! Sawtooth addresses are 70 hex characters, but other platforms, such as Splinter with custom
+//! state, may use other lengths. An address is a namespace prefix followed by as much of a
+//! SHA-512 hash as fills the rest of the address. Setting addresses are laid out by the Sawtooth
+//! settings family, so `compute_setting_address` is not affected by the address length.
+
+use sha2::{Digest, Sha512};
+
+use super::namespaces::ADDRESS_LENGTH;
+use super::{
+    AddressingError, AGENT_ADDRESS_PREFIX_BYTES, CONTRACT_ADDRESS_PREFIX_BYTES,
+    CONTRACT_REGISTRY_ADDRESS_PREFIX_BYTES, DELEGATION_ADDRESS_PREFIX_BYTES,
+    NAMESPACE_REGISTRY_ADDRESS_PREFIX_BYTES, ORG_ADDRESS_PREFIX_BYTES,
+};
+
+/// The shortest address length, which leaves a byte of hash after the longest prefix
+pub const MIN_ADDRESS_LENGTH: usize = 10;
+
+/// The longest address length, which holds a whole SHA-512 hash after a three byte prefix
+pub const MAX_ADDRESS_LENGTH: usize = 134;
+
+/// Computes and validates addresses of a fixed length, in hex characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Addresser {
+    address_length: usize,
+}
+
+impl Default for Addresser {
+    fn default() -> Self {
+        Addresser {
+            address_length: ADDRESS_LENGTH,
+        }
+    }
+}
+
+impl Addresser {
+    /// Creates an addresser for Sawtooth's 70 character addresses.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an addresser for addresses of the given number of hex characters, which must be
+    /// even and between `MIN_ADDRESS_LENGTH` and `MAX_ADDRESS_LENGTH`.
+    pub fn with_address_length(address_length: usize) -> Result<Self, AddressingError> {
+        if address_length % 2 != 0
+            || address_length < MIN_ADDRESS_LENGTH
+            || address_length > MAX_ADDRESS_LENGTH
+        {
+            return Err(AddressingError::InvalidInput(format!(
+                "address length {} is not an even number from {} to {}",
+                address_length, MIN_ADDRESS_LENGTH, MAX_ADDRESS_LENGTH
+            )));
+        }
+
+        Ok(Addresser { address_length })
+    }
+
+    pub fn address_length(&self) -> usize {
+        self.address_length
+    }
+
+    /// Compute a state address for a given namespace registry.
+    ///
+    /// # Arguments
+    ///
+    /// * `namespace` - the address prefix for this namespace
+    pub fn namespace_registry_address(&self, namespace: &str) -> Result<Vec<u8>, AddressingError> {
+        let prefix = match namespace.get(..6) {
+            Some(x) => x,
+            None => {
+                return Err(AddressingError::InvalidInput(format!(
+                    "namespace '{}' is less than 6 characters long",
+                    namespace,
+                )));
+            }
+        };
+        Ok(self.hashed_address(NAMESPACE_REGISTRY_ADDRESS_PREFIX_BYTES, prefix.as_bytes()))
+    }
+
+    /// Compute a state address for a given contract registry.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the contract registry
+    pub fn contract_registry_address(&self, name: &str) -> Result<Vec<u8>, AddressingError> {
+        Ok(self.hashed_address(CONTRACT_REGISTRY_ADDRESS_PREFIX_BYTES, name.as_bytes()))
+    }
+
+    /// Compute a state address for a given contract.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the contract
+    /// * `version` - the version of the contract
+    pub fn contract_address(&self, name: &str, version: &str) -> Result<Vec<u8>, AddressingError> {
+        let s = String::from(name) + "," + version;
+        Ok(self.hashed_address(CONTRACT_ADDRESS_PREFIX_BYTES, s.as_bytes()))
+    }
+
+    /// Compute the state address recording that a delegation has been used.
+    ///
+    /// # Arguments
+    ///
+    /// * `signer_public_key` - the public key of the delegated signer
+    /// * `nonce` - the nonce of the delegation
+    pub fn delegation_address(
+        &self,
+        signer_public_key: &str,
+        nonce: &str,
+    ) -> Result<Vec<u8>, AddressingError> {
+        let s = String::from(signer_public_key) + "," + nonce;
+        Ok(self.hashed_address(DELEGATION_ADDRESS_PREFIX_BYTES, s.as_bytes()))
+    }
+
+    /// Compute a state address for a given agent name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the agent's name
+    pub fn agent_address(&self, name: &[u8]) -> Result<Vec<u8>, AddressingError> {
+        Ok(self.hashed_address(AGENT_ADDRESS_PREFIX_BYTES, name))
+    }
+
+    /// Compute a state address for a given organization id.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - the organization's id
+    pub fn org_address(&self, id: &str) -> Result<Vec<u8>, AddressingError> {
+        Ok(self.hashed_address(ORG_ADDRESS_PREFIX_BYTES, id.as_bytes()))
+    }
+
+    /// Checks that the prefix is an even number of hex characters, no longer than an address.
+    pub fn validate_prefix(&self, prefix: &str) -> Result<(), AddressingError> {
+        if prefix.len() % 2 != 0 {
+            return Err(AddressingError::InvalidInput(format!(
+                "prefix '{}' has an odd number of characters",
+                prefix
+            )));
+        }
+        if prefix.len() > self.address_length {
+            return Err(AddressingError::InvalidInput(format!(
+                "prefix '{}' is longer than an address",
+                prefix
+            )));
+        }
+        if !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(AddressingError::InvalidInput(format!(
+                "prefix '{}' is not hex",
+                prefix
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the address is a full length hex address.
+    pub fn validate_address(&self, address: &str) -> Result<(), AddressingError> {
+        self.validate_prefix(address)?;
+        if address.len() != self.address_length {
+            return Err(AddressingError::InvalidInput(format!(
+                "address '{}' is not {} characters long",
+                address, self.address_length
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the prefix followed by the start of the SHA-512 hash of the data, filling the
+    /// address.
+    fn hashed_address(&self, prefix: &[u8], data: &[u8]) -> Vec<u8> {
+        let hash = Sha512::digest(data);
+        [prefix, &hash[..self.address_length / 2 - prefix.len()]].concat()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    // check that the default addresser computes the 70 character addresses Sawtooth Sabre has
+    // always used
+    fn check_addresser_default() {
+        let addresser = Addresser::new();
+
+        assert_eq!(addresser.address_length(), 70);
+        assert_eq!(
+            to_hex(
+                &addresser
+                    .contract_address("intkey_multiply", "1.0")
+                    .unwrap()
+            ),
+            "00ec029ffc199d39909db5e56d6819d728e1fd4d804199e249decc54e3fe1e428958ab"
+        );
+        assert_eq!(
+            to_hex(&addresser.agent_address(b"agent").unwrap()),
+            "cad11d00b27ba2d3f698f54a5dcd2f05ca5d95e274ea00108e7b5a60ab1997cbbb3283"
+        );
+    }
+
+    #[test]
+    // check that addresses of other lengths have the namespace prefix and the start of the same
+    // hash, and are validated against their length
+    fn check_addresser_lengths() {
+        let default = Addresser::new()
+            .contract_address("intkey_multiply", "1.0")
+            .unwrap();
+
+        for length in &[MIN_ADDRESS_LENGTH, 64, 70, 128, MAX_ADDRESS_LENGTH] {
+            let addresser = Addresser::with_address_length(*length).unwrap();
+
+            let address = addresser
+                .contract_address("intkey_multiply", "1.0")
+                .unwrap();
+            assert_eq!(address.len() * 2, *length);
+            assert_eq!(&address[..3], CONTRACT_ADDRESS_PREFIX_BYTES);
+            let shared = address.len().min(default.len());
+            assert_eq!(address[..shared], default[..shared]);
+
+            let agent = addresser.agent_address(b"agent").unwrap();
+            assert_eq!(agent.len() * 2, *length);
+            assert_eq!(&agent[..4], AGENT_ADDRESS_PREFIX_BYTES);
+
+            assert!(addresser.validate_address(&"0".repeat(*length)).is_ok());
+            assert!(addresser.validate_address(&"0".repeat(length - 2)).is_err());
+            assert!(addresser.validate_prefix(&"0".repeat(*length)).is_ok());
+            assert!(addresser.validate_prefix(&"0".repeat(length + 2)).is_err());
+        }
+
+        for length in &[0, 8, 69, MAX_ADDRESS_LENGTH + 2] {
+            assert!(Addresser::with_address_length(*length).is_err());
+        }
+    }
+}
@@ -0,0 +1,162 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Native representations of the block information stored by the Sawtooth BlockInfo transaction
+//! family in the 00b10c namespace.
+
+use protobuf::Message;
+
+use crate::protos;
+use crate::protos::{FromBytes, FromProto, IntoNative, ProtoConversionError};
+
+pub const BLOCK_INFO_NAMESPACE: &str = "00b10c";
+
+/// The address of the BlockInfo configuration
+pub const BLOCK_INFO_CONFIG_ADDRESS: &str =
+    "00b10c0100000000000000000000000000000000000000000000000000000000000000";
+
+/// Computes the address of the information about the given block.
+pub fn compute_block_info_address(block_num: u64) -> String {
+    format!("{}00{:062x}", BLOCK_INFO_NAMESPACE, block_num)
+}
+
+/// Native implementation for BlockInfoConfig
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct BlockInfoConfig {
+    latest_block: u64,
+    oldest_block: u64,
+    target_count: u64,
+    sync_tolerance: u64,
+}
+
+impl BlockInfoConfig {
+    /// The number of the most recent block whose information is stored
+    pub fn latest_block(&self) -> u64 {
+        self.latest_block
+    }
+
+    /// The number of the oldest block whose information is still stored
+    pub fn oldest_block(&self) -> u64 {
+        self.oldest_block
+    }
+
+    pub fn target_count(&self) -> u64 {
+        self.target_count
+    }
+
+    pub fn sync_tolerance(&self) -> u64 {
+        self.sync_tolerance
+    }
+}
+
+impl FromProto<protos::block_info::BlockInfoConfig> for BlockInfoConfig {
+    fn from_proto(
+        proto: protos::block_info::BlockInfoConfig,
+    ) -> Result<Self, ProtoConversionError> {
+        Ok(BlockInfoConfig {
+            latest_block: proto.get_latest_block(),
+            oldest_block: proto.get_oldest_block(),
+            target_count: proto.get_target_count(),
+            sync_tolerance: proto.get_sync_tolerance(),
+        })
+    }
+}
+
+impl FromBytes<BlockInfoConfig> for BlockInfoConfig {
+    fn from_bytes(bytes: &[u8]) -> Result<BlockInfoConfig, ProtoConversionError> {
+        let proto: protos::block_info::BlockInfoConfig =
+            Message::parse_from_bytes(bytes).map_err(|_| {
+                ProtoConversionError::SerializationError(
+                    "Unable to get BlockInfoConfig from bytes".to_string(),
+                )
+            })?;
+        proto.into_native()
+    }
+}
+
+impl IntoNative<BlockInfoConfig> for protos::block_info::BlockInfoConfig {}
+
+/// Native implementation for BlockInfo
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct BlockInfo {
+    block_num: u64,
+    previous_block_id: String,
+    signer_public_key: String,
+    header_signature: String,
+    timestamp: u64,
+}
+
+impl BlockInfo {
+    pub fn block_num(&self) -> u64 {
+        self.block_num
+    }
+
+    pub fn previous_block_id(&self) -> &str {
+        &self.previous_block_id
+    }
+
+    pub fn signer_public_key(&self) -> &str {
+        &self.signer_public_key
+    }
+
+    pub fn header_signature(&self) -> &str {
+        &self.header_signature
+    }
+
+    /// Approximately when the block was committed, in seconds since the Unix epoch
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+}
+
+impl FromProto<protos::block_info::BlockInfo> for BlockInfo {
+    fn from_proto(proto: protos::block_info::BlockInfo) -> Result<Self, ProtoConversionError> {
+        Ok(BlockInfo {
+            block_num: proto.get_block_num(),
+            previous_block_id: proto.get_previous_block_id().to_string(),
+            signer_public_key: proto.get_signer_public_key().to_string(),
+            header_signature: proto.get_header_signature().to_string(),
+            timestamp: proto.get_timestamp(),
+        })
+    }
+}
+
+impl FromBytes<BlockInfo> for BlockInfo {
+    fn from_bytes(bytes: &[u8]) -> Result<BlockInfo, ProtoConversionError> {
+        let proto: protos::block_info::BlockInfo =
+            Message::parse_from_bytes(bytes).map_err(|_| {
+                ProtoConversionError::SerializationError(
+                    "Unable to get BlockInfo from bytes".to_string(),
+                )
+            })?;
+        proto.into_native()
+    }
+}
+
+impl IntoNative<BlockInfo> for protos::block_info::BlockInfo {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // check that block info addresses are the namespace, 00 and the block number in hex
+    fn check_compute_block_info_address() {
+        let address = compute_block_info_address(255);
+        assert_eq!(address.len(), 70);
+        assert!(address.starts_with("00b10c00"));
+        assert!(address.ends_with("0ff"));
+        assert_eq!(BLOCK_INFO_CONFIG_ADDRESS.len(), 70);
+    }
+}
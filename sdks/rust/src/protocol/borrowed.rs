@@ -0,0 +1,304 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Borrowed views of large Sabre state entries
+//!
+//! Decoding a stored `ContractList` into native types copies each contract's wasm, which may be
+//! megabytes, out of the entry. The views here only find where each field is in the entry, and
+//! borrow the fields, the wasm included, from the bytes they were decoded from. They are meant
+//! for reading a contract, such as to hash or execute it; use `ContractList` to modify one.
+
+use std::convert::TryFrom;
+use std::str;
+
+use crate::protos::ProtoConversionError;
+
+/// A `Contract` borrowed from the bytes it was decoded from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractRef<'a> {
+    name: &'a str,
+    version: &'a str,
+    inputs: Vec<&'a str>,
+    outputs: Vec<&'a str>,
+    creator: &'a str,
+    contract: &'a [u8],
+}
+
+impl<'a> ContractRef<'a> {
+    /// Decodes a `Contract` without copying its fields.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, ProtoConversionError> {
+        let mut contract = ContractRef {
+            name: "",
+            version: "",
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            creator: "",
+            contract: &[],
+        };
+
+        for field in FieldReader::new(bytes) {
+            match field? {
+                (1, Field::LengthDelimited(value)) => contract.name = to_str(value)?,
+                (2, Field::LengthDelimited(value)) => contract.version = to_str(value)?,
+                (3, Field::LengthDelimited(value)) => contract.inputs.push(to_str(value)?),
+                (4, Field::LengthDelimited(value)) => contract.outputs.push(to_str(value)?),
+                (5, Field::LengthDelimited(value)) => contract.creator = to_str(value)?,
+                (6, Field::LengthDelimited(value)) => contract.contract = value,
+                (1..=6, _) => {
+                    return Err(ProtoConversionError::SerializationError(
+                        "Unable to get Contract from bytes".to_string(),
+                    ))
+                }
+                // Fields unknown to this version are skipped, as protobuf does
+                _ => (),
+            }
+        }
+
+        Ok(contract)
+    }
+
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    pub fn version(&self) -> &'a str {
+        self.version
+    }
+
+    pub fn inputs(&self) -> &[&'a str] {
+        &self.inputs
+    }
+
+    pub fn outputs(&self) -> &[&'a str] {
+        &self.outputs
+    }
+
+    pub fn creator(&self) -> &'a str {
+        self.creator
+    }
+
+    /// The contract's wasm
+    pub fn contract(&self) -> &'a [u8] {
+        self.contract
+    }
+}
+
+/// A `ContractList` borrowed from the bytes it was decoded from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractListRef<'a> {
+    contracts: Vec<ContractRef<'a>>,
+}
+
+impl<'a> ContractListRef<'a> {
+    /// Decodes a `ContractList` without copying its contracts.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, ProtoConversionError> {
+        let mut contracts = Vec::new();
+        for field in FieldReader::new(bytes) {
+            match field? {
+                (1, Field::LengthDelimited(value)) => {
+                    contracts.push(ContractRef::from_bytes(value)?)
+                }
+                (1, _) => {
+                    return Err(ProtoConversionError::SerializationError(
+                        "Unable to get ContractList from bytes".to_string(),
+                    ))
+                }
+                _ => (),
+            }
+        }
+
+        Ok(ContractListRef { contracts })
+    }
+
+    pub fn contracts(&self) -> &[ContractRef<'a>] {
+        &self.contracts
+    }
+
+    /// Returns the contract with the given name and version, if the list holds it.
+    pub fn find(&self, name: &str, version: &str) -> Option<&ContractRef<'a>> {
+        self.contracts
+            .iter()
+            .find(|contract| contract.name == name && contract.version == version)
+    }
+}
+
+fn to_str(bytes: &[u8]) -> Result<&str, ProtoConversionError> {
+    str::from_utf8(bytes).map_err(|_| {
+        ProtoConversionError::SerializationError("string field is not valid UTF-8".to_string())
+    })
+}
+
+/// A field of a protobuf message, as found on the wire
+enum Field<'a> {
+    Varint,
+    Fixed64,
+    LengthDelimited(&'a [u8]),
+    Fixed32,
+}
+
+/// Reads the fields of a protobuf message, as (field number, value) pairs, in the order they
+/// are encoded
+struct FieldReader<'a> {
+    bytes: &'a [u8],
+    failed: bool,
+}
+
+impl<'a> FieldReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        FieldReader {
+            bytes,
+            failed: false,
+        }
+    }
+
+    fn read_varint(&mut self) -> Result<u64, ProtoConversionError> {
+        let mut value = 0u64;
+        for (i, byte) in self.bytes.iter().enumerate().take(10) {
+            value |= u64::from(byte & 0x7f) << (7 * i);
+            if byte & 0x80 == 0 {
+                self.bytes = &self.bytes[i + 1..];
+                return Ok(value);
+            }
+        }
+
+        Err(malformed("varint is truncated or too long"))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ProtoConversionError> {
+        if len > self.bytes.len() {
+            return Err(malformed("field is truncated"));
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn read_field(&mut self) -> Result<(u32, Field<'a>), ProtoConversionError> {
+        let key = self.read_varint()?;
+        let number = u32::try_from(key >> 3)
+            .ok()
+            .filter(|number| *number > 0)
+            .ok_or_else(|| malformed("invalid field number"))?;
+
+        let field = match key & 0x7 {
+            0 => {
+                self.read_varint()?;
+                Field::Varint
+            }
+            1 => {
+                self.take(8)?;
+                Field::Fixed64
+            }
+            2 => {
+                let len = usize::try_from(self.read_varint()?)
+                    .map_err(|_| malformed("field is too long"))?;
+                Field::LengthDelimited(self.take(len)?)
+            }
+            5 => {
+                self.take(4)?;
+                Field::Fixed32
+            }
+            wire_type => {
+                return Err(malformed(&format!("unsupported wire type {}", wire_type)));
+            }
+        };
+
+        Ok((number, field))
+    }
+}
+
+impl<'a> Iterator for FieldReader<'a> {
+    type Item = Result<(u32, Field<'a>), ProtoConversionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.bytes.is_empty() {
+            return None;
+        }
+
+        let field = self.read_field();
+        self.failed = field.is_err();
+        Some(field)
+    }
+}
+
+fn malformed(reason: &str) -> ProtoConversionError {
+    ProtoConversionError::SerializationError(format!("Malformed protobuf: {}", reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::protocol::state::{ContractBuilder, ContractListBuilder};
+    use crate::protos::IntoBytes;
+
+    fn contract(version: &str, wasm: Vec<u8>) -> crate::protocol::state::Contract {
+        ContractBuilder::new()
+            .with_name("intkey_multiply".to_string())
+            .with_version(version.to_string())
+            .with_inputs(vec!["1cf126".to_string(), "cad11d".to_string()])
+            .with_outputs(vec!["1cf126".to_string()])
+            .with_creator("creator".to_string())
+            .with_contract(wasm)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    // check that a contract list is decoded to the same fields as the native types, and that the
+    // wasm is borrowed from the encoded bytes rather than copied
+    fn check_contract_list_ref() {
+        let bytes = ContractListBuilder::new()
+            .with_contracts(vec![
+                contract("1.0", vec![1; 1024]),
+                contract("2.0", vec![2; 4096]),
+            ])
+            .build()
+            .unwrap()
+            .into_bytes()
+            .unwrap();
+
+        let list = ContractListRef::from_bytes(&bytes).unwrap();
+        assert_eq!(list.contracts().len(), 2);
+
+        let found = list.find("intkey_multiply", "2.0").unwrap();
+        assert_eq!(found.name(), "intkey_multiply");
+        assert_eq!(found.inputs(), ["1cf126", "cad11d"]);
+        assert_eq!(found.outputs(), ["1cf126"]);
+        assert_eq!(found.creator(), "creator");
+        assert_eq!(found.contract(), &[2; 4096][..]);
+        assert!(bytes.as_ptr_range().contains(&found.contract().as_ptr()));
+
+        assert!(list.find("intkey_multiply", "3.0").is_none());
+    }
+
+    #[test]
+    // check that truncated and malformed bytes are rejected rather than read past
+    fn check_contract_ref_malformed() {
+        let bytes = contract("1.0", vec![1; 64]).into_bytes().unwrap();
+        assert!(ContractRef::from_bytes(&bytes).is_ok());
+        assert!(ContractRef::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        // field 6 encoded as a varint
+        assert!(ContractRef::from_bytes(&[0x30, 0x01]).is_err());
+        // a string which is not UTF-8
+        assert!(ContractRef::from_bytes(&[0x0a, 0x01, 0xff]).is_err());
+        // an unknown field is skipped
+        assert_eq!(
+            ContractRef::from_bytes(&[0x38, 0x01, 0x0a, 0x01, b'a'])
+                .unwrap()
+                .name(),
+            "a"
+        );
+    }
+}
@@ -12,23 +12,49 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod addresser;
+pub mod block_info;
+pub mod borrowed;
+pub mod namespaces;
+pub mod org_id;
 pub mod payload;
+pub mod permissions;
+pub mod settings;
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+pub mod size;
 pub mod state;
 
 use std::error::Error;
 
-use sha2::{Digest, Sha512};
+use sha2::{Digest, Sha256};
 
+use addresser::Addresser;
+
+/// The transaction family name of Sabre transactions
+pub const SABRE_FAMILY_NAME: &str = "sabre";
 pub const SABRE_PROTOCOL_VERSION: &str = "1";
+/// The setting listing, comma separated, the Sabre protocol versions supported by the network's
+/// transaction processors
+pub const PROTOCOL_VERSIONS_SETTING_KEY: &str = "sabre.protocol.versions";
 
 pub const ADMINISTRATORS_SETTING_KEY: &str = "sawtooth.swa.administrators";
+/// The setting limiting the number of events a single transaction may emit
+pub const MAX_EVENTS_SETTING_KEY: &str = "sabre.events.max_per_transaction";
+/// The setting which, when "true", replaces the events of a transaction over the limit with a
+/// single digest event instead of rejecting the transaction
+pub const AGGREGATE_EVENTS_SETTING_KEY: &str = "sabre.events.aggregate";
+/// The setting which, when "true", has each transaction emit the number of writes and bytes
+/// written to each namespace
+pub const WRITE_STATS_SETTING_KEY: &str = "sabre.stats.namespace_writes";
 
 pub const ADMINISTRATORS_SETTING_ADDRESS: &str =
     "000000a87cb5eafdcca6a814e4add97c4b517d3c530c2f44b31d18e3b0c44298fc1c14";
+pub const SETTINGS_ADDRESS_PREFIX: &str = "000000";
 pub const NAMESPACE_REGISTRY_ADDRESS_PREFIX: &str = "00ec00";
 pub const CONTRACT_REGISTRY_ADDRESS_PREFIX: &str = "00ec01";
 pub const CONTRACT_ADDRESS_PREFIX: &str = "00ec02";
 pub const SMART_PERMISSION_ADDRESS_PREFIX: &str = "00ec03";
+pub const DELEGATION_ADDRESS_PREFIX: &str = "00ec04";
 pub const AGENT_ADDRESS_PREFIX: &str = "cad11d00";
 pub const ORG_ADDRESS_PREFIX: &str = "cad11d01";
 
@@ -36,30 +62,51 @@ pub const ADMINISTRATORS_SETTING_ADDRESS_BYTES: &[u8] = &[
     0, 0, 0, 168, 124, 181, 234, 253, 204, 166, 168, 20, 228, 173, 217, 124, 75, 81, 125, 60, 83,
     12, 47, 68, 179, 29, 24, 227, 176, 196, 66, 152, 252, 28, 20,
 ];
+pub const SETTINGS_ADDRESS_PREFIX_BYTES: &[u8] = &[0, 0, 0];
 pub const NAMESPACE_REGISTRY_ADDRESS_PREFIX_BYTES: &[u8] = &[0, 236, 0];
 pub const CONTRACT_REGISTRY_ADDRESS_PREFIX_BYTES: &[u8] = &[0, 236, 1];
 pub const CONTRACT_ADDRESS_PREFIX_BYTES: &[u8] = &[0, 236, 2];
 pub const SMART_PERMISSION_ADDRESS_PREFIX_BYTES: &[u8] = &[0, 236, 3];
+pub const DELEGATION_ADDRESS_PREFIX_BYTES: &[u8] = &[0, 236, 4];
 pub const AGENT_ADDRESS_PREFIX_BYTES: &[u8] = &[202, 209, 29, 0];
 pub const ORG_ADDRESS_PREFIX_BYTES: &[u8] = &[202, 209, 29, 1];
 
+/// The maximum number of parts of a setting key which are hashed into its address
+const SETTING_KEY_MAX_PARTS: usize = 4;
+
+/// Compute a state address for a given Sawtooth setting.
+///
+/// The key is split on '.' into at most four parts, the last of which holds the remainder of the
+/// key. Each part, or an empty string for missing parts, contributes the first 8 bytes of its
+/// SHA-256 hash.
+///
+/// # Arguments
+///
+/// * `key` - the setting key, e.g. "sawtooth.swa.administrators"
+pub fn compute_setting_address(key: &str) -> Result<Vec<u8>, AddressingError> {
+    if key.is_empty() {
+        return Err(AddressingError::InvalidInput(
+            "setting key is empty".to_string(),
+        ));
+    }
+
+    let mut parts = key.splitn(SETTING_KEY_MAX_PARTS, '.').collect::<Vec<_>>();
+    parts.resize(SETTING_KEY_MAX_PARTS, "");
+
+    let mut address = SETTINGS_ADDRESS_PREFIX_BYTES.to_vec();
+    for part in parts {
+        address.extend_from_slice(&Sha256::digest(part.as_bytes())[..8]);
+    }
+    Ok(address)
+}
+
 /// Compute a state address for a given namespace registry.
 ///
 /// # Arguments
 ///
 /// * `namespace` - the address prefix for this namespace
 pub fn compute_namespace_registry_address(namespace: &str) -> Result<Vec<u8>, AddressingError> {
-    let prefix = match namespace.get(..6) {
-        Some(x) => x,
-        None => {
-            return Err(AddressingError::InvalidInput(format!(
-                "namespace '{}' is less than 6 characters long",
-                namespace,
-            )));
-        }
-    };
-    let hash = Sha512::digest(prefix.as_bytes());
-    Ok([NAMESPACE_REGISTRY_ADDRESS_PREFIX_BYTES, &hash[..32]].concat())
+    Addresser::new().namespace_registry_address(namespace)
 }
 
 /// Compute a state address for a given contract registry.
@@ -68,8 +115,7 @@ pub fn compute_namespace_registry_address(namespace: &str) -> Result<Vec<u8>, Ad
 ///
 /// * `name` - the name of the contract registry
 pub fn compute_contract_registry_address(name: &str) -> Result<Vec<u8>, AddressingError> {
-    let hash = Sha512::digest(name.as_bytes());
-    Ok([CONTRACT_REGISTRY_ADDRESS_PREFIX_BYTES, &hash[..32]].concat())
+    Addresser::new().contract_registry_address(name)
 }
 
 /// Compute a state address for a given contract.
@@ -79,9 +125,20 @@ pub fn compute_contract_registry_address(name: &str) -> Result<Vec<u8>, Addressi
 /// * `name` - the name of the contract
 /// * `version` - the version of the contract
 pub fn compute_contract_address(name: &str, version: &str) -> Result<Vec<u8>, AddressingError> {
-    let s = String::from(name) + "," + version;
-    let hash = Sha512::digest(s.as_bytes());
-    Ok([CONTRACT_ADDRESS_PREFIX_BYTES, &hash[..32]].concat())
+    Addresser::new().contract_address(name, version)
+}
+
+/// Compute the state address recording that a delegation has been used.
+///
+/// # Arguments
+///
+/// * `signer_public_key` - the public key of the delegated signer
+/// * `nonce` - the nonce of the delegation
+pub fn compute_delegation_address(
+    signer_public_key: &str,
+    nonce: &str,
+) -> Result<Vec<u8>, AddressingError> {
+    Addresser::new().delegation_address(signer_public_key, nonce)
 }
 
 /// Compute a state address for a given agent name.
@@ -90,8 +147,7 @@ pub fn compute_contract_address(name: &str, version: &str) -> Result<Vec<u8>, Ad
 ///
 /// * `name` - the agent's name
 pub fn compute_agent_address(name: &[u8]) -> Result<Vec<u8>, AddressingError> {
-    let hash = Sha512::digest(name);
-    Ok([AGENT_ADDRESS_PREFIX_BYTES, &hash[..31]].concat())
+    Addresser::new().agent_address(name)
 }
 
 /// Compute a state address for a given organization id.
@@ -100,8 +156,7 @@ pub fn compute_agent_address(name: &[u8]) -> Result<Vec<u8>, AddressingError> {
 ///
 /// * `id` - the organization's id
 pub fn compute_org_address(id: &str) -> Result<Vec<u8>, AddressingError> {
-    let hash = Sha512::digest(id.as_bytes());
-    Ok([ORG_ADDRESS_PREFIX_BYTES, &hash[..31]].concat())
+    Addresser::new().org_address(id)
 }
 
 #[derive(Debug)]
@@ -0,0 +1,172 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The namespaces of global state used by Sabre, and validation of addresses and prefixes.
+
+use super::addresser::Addresser;
+use super::{
+    AddressingError, CONTRACT_ADDRESS_PREFIX, CONTRACT_ADDRESS_PREFIX_BYTES,
+    CONTRACT_REGISTRY_ADDRESS_PREFIX, CONTRACT_REGISTRY_ADDRESS_PREFIX_BYTES,
+    DELEGATION_ADDRESS_PREFIX, DELEGATION_ADDRESS_PREFIX_BYTES, NAMESPACE_REGISTRY_ADDRESS_PREFIX,
+    NAMESPACE_REGISTRY_ADDRESS_PREFIX_BYTES, SETTINGS_ADDRESS_PREFIX,
+    SETTINGS_ADDRESS_PREFIX_BYTES, SMART_PERMISSION_ADDRESS_PREFIX,
+    SMART_PERMISSION_ADDRESS_PREFIX_BYTES,
+};
+
+/// The length of a Sawtooth global state address, in hex characters. Use an `Addresser` for
+/// other lengths.
+pub const ADDRESS_LENGTH: usize = 70;
+
+/// A namespace of global state which Sabre reads or writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SabreNamespace {
+    NamespaceRegistry,
+    ContractRegistry,
+    Contract,
+    SmartPermission,
+    Delegation,
+    Settings,
+}
+
+impl SabreNamespace {
+    pub const ALL: [SabreNamespace; 6] = [
+        SabreNamespace::NamespaceRegistry,
+        SabreNamespace::ContractRegistry,
+        SabreNamespace::Contract,
+        SabreNamespace::SmartPermission,
+        SabreNamespace::Delegation,
+        SabreNamespace::Settings,
+    ];
+
+    /// The namespaces the Sabre transaction processor registers with the validator
+    pub const REGISTERED: [SabreNamespace; 4] = [
+        SabreNamespace::NamespaceRegistry,
+        SabreNamespace::ContractRegistry,
+        SabreNamespace::Contract,
+        SabreNamespace::Delegation,
+    ];
+
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            SabreNamespace::NamespaceRegistry => NAMESPACE_REGISTRY_ADDRESS_PREFIX,
+            SabreNamespace::ContractRegistry => CONTRACT_REGISTRY_ADDRESS_PREFIX,
+            SabreNamespace::Contract => CONTRACT_ADDRESS_PREFIX,
+            SabreNamespace::SmartPermission => SMART_PERMISSION_ADDRESS_PREFIX,
+            SabreNamespace::Delegation => DELEGATION_ADDRESS_PREFIX,
+            SabreNamespace::Settings => SETTINGS_ADDRESS_PREFIX,
+        }
+    }
+
+    pub fn prefix_bytes(&self) -> &'static [u8] {
+        match self {
+            SabreNamespace::NamespaceRegistry => NAMESPACE_REGISTRY_ADDRESS_PREFIX_BYTES,
+            SabreNamespace::ContractRegistry => CONTRACT_REGISTRY_ADDRESS_PREFIX_BYTES,
+            SabreNamespace::Contract => CONTRACT_ADDRESS_PREFIX_BYTES,
+            SabreNamespace::SmartPermission => SMART_PERMISSION_ADDRESS_PREFIX_BYTES,
+            SabreNamespace::Delegation => DELEGATION_ADDRESS_PREFIX_BYTES,
+            SabreNamespace::Settings => SETTINGS_ADDRESS_PREFIX_BYTES,
+        }
+    }
+
+    /// Returns a description of the entries stored in the namespace.
+    pub fn description(&self) -> &'static str {
+        match self {
+            SabreNamespace::NamespaceRegistry => "namespace registry",
+            SabreNamespace::ContractRegistry => "contract registry",
+            SabreNamespace::Contract => "contract",
+            SabreNamespace::SmartPermission => "smart permission",
+            SabreNamespace::Delegation => "used delegation",
+            SabreNamespace::Settings => "setting",
+        }
+    }
+
+    /// Returns the namespace containing the hex address, if it is one of Sabre's.
+    pub fn of_address(address: &str) -> Option<SabreNamespace> {
+        SabreNamespace::ALL
+            .iter()
+            .copied()
+            .find(|namespace| address.starts_with(namespace.prefix()))
+    }
+
+    pub fn contains(&self, address: &str) -> bool {
+        address.starts_with(self.prefix())
+    }
+}
+
+/// Checks that the prefix is an even number of hex characters, no longer than an address.
+pub fn validate_prefix(prefix: &str) -> Result<(), AddressingError> {
+    Addresser::new().validate_prefix(prefix)
+}
+
+/// Checks that the address is a full length hex address.
+pub fn validate_address(address: &str) -> Result<(), AddressingError> {
+    Addresser::new().validate_address(address)
+}
+
+/// Returns whether the hex address is in the contract namespace.
+pub fn is_contract_address(address: &str) -> bool {
+    SabreNamespace::Contract.contains(address)
+}
+
+/// Returns whether the hex address is in the namespace or contract registry namespaces.
+pub fn is_registry_address(address: &str) -> bool {
+    SabreNamespace::NamespaceRegistry.contains(address)
+        || SabreNamespace::ContractRegistry.contains(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::protocol::{compute_contract_address, compute_contract_registry_address};
+
+    fn to_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    // check that each namespace's prefix matches its bytes, and that addresses are found in the
+    // namespace they were computed for
+    fn check_namespaces() {
+        for namespace in SabreNamespace::ALL.iter() {
+            assert_eq!(to_hex(namespace.prefix_bytes()), namespace.prefix());
+        }
+
+        let contract = to_hex(&compute_contract_address("intkey_multiply", "1.0").unwrap());
+        let registry = to_hex(&compute_contract_registry_address("intkey_multiply").unwrap());
+
+        assert_eq!(
+            SabreNamespace::of_address(&contract),
+            Some(SabreNamespace::Contract)
+        );
+        assert!(is_contract_address(&contract));
+        assert!(!is_registry_address(&contract));
+        assert!(is_registry_address(&registry));
+        assert_eq!(SabreNamespace::of_address("1cf126"), None);
+    }
+
+    #[test]
+    // check that prefixes must be even length hex no longer than an address, and that addresses
+    // must be full length
+    fn check_validate_prefix() {
+        assert!(validate_prefix("1cf126").is_ok());
+        assert!(validate_prefix("").is_ok());
+        assert!(validate_prefix("1cf12").is_err());
+        assert!(validate_prefix("1cf12g").is_err());
+        assert!(validate_prefix(&"0".repeat(ADDRESS_LENGTH + 2)).is_err());
+
+        assert!(validate_address(&"0".repeat(ADDRESS_LENGTH)).is_ok());
+        assert!(validate_address("1cf126").is_err());
+    }
+}
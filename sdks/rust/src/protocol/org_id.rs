@@ -0,0 +1,135 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The ids of the organizations smart permissions are evaluated for.
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::str::FromStr;
+
+use super::compute_org_address;
+
+/// The longest org id accepted, in bytes
+pub const MAX_ORG_ID_LENGTH: usize = 256;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum OrgIdError {
+    Empty,
+    TooLong(usize),
+    InvalidCharacter(char),
+}
+
+impl StdError for OrgIdError {}
+
+impl fmt::Display for OrgIdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OrgIdError::Empty => f.write_str("invalid org id: must not be empty"),
+            OrgIdError::TooLong(length) => write!(
+                f,
+                "invalid org id: {} bytes long; at most {} are allowed",
+                length, MAX_ORG_ID_LENGTH
+            ),
+            OrgIdError::InvalidCharacter(c) => write!(
+                f,
+                "invalid org id: '{}' is not allowed; org ids are made of a-z, A-Z, 0-9, '-', \
+                 '_' and '.'",
+                c
+            ),
+        }
+    }
+}
+
+/// A validated org id: 1 to 256 characters of ASCII letters, digits, '-', '_' or '.'
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OrgId(String);
+
+impl OrgId {
+    pub fn new(id: &str) -> Result<OrgId, OrgIdError> {
+        if id.is_empty() {
+            return Err(OrgIdError::Empty);
+        }
+        if id.len() > MAX_ORG_ID_LENGTH {
+            return Err(OrgIdError::TooLong(id.len()));
+        }
+        if let Some(c) = id
+            .chars()
+            .find(|c| !(c.is_ascii_alphanumeric() || *c == '-' || *c == '_' || *c == '.'))
+        {
+            return Err(OrgIdError::InvalidCharacter(c));
+        }
+
+        Ok(OrgId(id.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the address of the organization in state.
+    pub fn address(&self) -> Vec<u8> {
+        compute_org_address(&self.0).expect("org addresses are computed from any string")
+    }
+}
+
+impl FromStr for OrgId {
+    type Err = OrgIdError;
+
+    fn from_str(id: &str) -> Result<OrgId, OrgIdError> {
+        OrgId::new(id)
+    }
+}
+
+impl AsRef<str> for OrgId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for OrgId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<OrgId> for String {
+    fn from(id: OrgId) -> String {
+        id.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // check that org ids are validated for length and characters, and address like the string
+    fn check_org_id() {
+        let id: OrgId = "acme-corp_1.0".parse().unwrap();
+        assert_eq!(id.as_str(), "acme-corp_1.0");
+        assert_eq!(id.address(), compute_org_address("acme-corp_1.0").unwrap());
+
+        assert_eq!(OrgId::new(""), Err(OrgIdError::Empty));
+        assert_eq!(
+            OrgId::new(&"a".repeat(MAX_ORG_ID_LENGTH + 1)),
+            Err(OrgIdError::TooLong(MAX_ORG_ID_LENGTH + 1))
+        );
+        assert!(OrgId::new(&"a".repeat(MAX_ORG_ID_LENGTH)).is_ok());
+        assert_eq!(
+            OrgId::new("acme corp"),
+            Err(OrgIdError::InvalidCharacter(' '))
+        );
+        assert_eq!(OrgId::new("acmé"), Err(OrgIdError::InvalidCharacter('é')));
+    }
+}
@@ -12,26 +12,40 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+use cylinder::{PublicKey, Signature, Signer, Verifier};
 use protobuf::Message;
 use protobuf::RepeatedField;
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
 use sawtooth::transact::protocol::transaction::{HashMethod, TransactionBuilder};
 
 use std::error::Error as StdError;
 
+use sha2::{Digest, Sha512};
+
+#[cfg(feature = "payload-compression")]
+use miniz_oxide::deflate::compress_to_vec;
+#[cfg(feature = "payload-compression")]
+use miniz_oxide::inflate::decompress_to_vec_with_limit;
+
 use crate::protos;
 use crate::protos::{
     FromBytes, FromNative, FromProto, IntoBytes, IntoNative, IntoProto, ProtoConversionError,
 };
 
 use super::AddressingError;
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
 use super::{
-    compute_contract_address, compute_contract_registry_address,
-    compute_namespace_registry_address, ADMINISTRATORS_SETTING_ADDRESS_BYTES,
-    SABRE_PROTOCOL_VERSION,
+    compute_contract_address, compute_contract_registry_address, compute_delegation_address,
+    compute_namespace_registry_address, compute_setting_address,
+    ADMINISTRATORS_SETTING_ADDRESS_BYTES, AGGREGATE_EVENTS_SETTING_KEY, MAX_EVENTS_SETTING_KEY,
+    SABRE_FAMILY_NAME, SABRE_PROTOCOL_VERSION, WRITE_STATS_SETTING_KEY,
 };
 
+/// The DEFLATE level execute contract payloads are compressed with, from 0 to 10
+#[cfg(feature = "payload-compression")]
+const COMPRESSION_LEVEL: u8 = 6;
+
 /// Native implementation for SabrePayload_Action
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Action {
@@ -437,6 +451,9 @@ pub struct ExecuteContractAction {
     inputs: Vec<String>,
     outputs: Vec<String>,
     payload: Vec<u8>,
+    delegation: Option<Delegation>,
+    contract_sha512: Option<String>,
+    payload_compressed: bool,
 }
 
 impl ExecuteContractAction {
@@ -459,18 +476,66 @@ impl ExecuteContractAction {
     pub fn payload(&self) -> &[u8] {
         &self.payload
     }
+
+    /// The delegation under which a relayer submitted this action, if any.
+    pub fn delegation(&self) -> Option<&Delegation> {
+        self.delegation.as_ref()
+    }
+
+    /// The sha512 the stored contract must have for it to be executed, if it is pinned.
+    pub fn contract_sha512(&self) -> Option<&str> {
+        self.contract_sha512.as_deref()
+    }
+
+    /// Whether the payload is DEFLATE compressed.
+    pub fn payload_compressed(&self) -> bool {
+        self.payload_compressed
+    }
+
+    /// Returns the action with its payload decompressed, or the action as it is if its payload
+    /// is not compressed. Payloads which decompress to more than `max_size` bytes are rejected.
+    #[cfg(feature = "payload-compression")]
+    pub fn into_decompressed(self, max_size: usize) -> Result<Self, ProtoConversionError> {
+        if !self.payload_compressed {
+            return Ok(self);
+        }
+
+        let payload = decompress_to_vec_with_limit(&self.payload, max_size).map_err(|err| {
+            ProtoConversionError::SerializationError(format!(
+                "Unable to decompress ExecuteContractAction payload: {:?}",
+                err.status
+            ))
+        })?;
+
+        Ok(ExecuteContractAction {
+            payload,
+            payload_compressed: false,
+            ..self
+        })
+    }
 }
 
 impl FromProto<protos::payload::ExecuteContractAction> for ExecuteContractAction {
     fn from_proto(
         proto: protos::payload::ExecuteContractAction,
     ) -> Result<Self, ProtoConversionError> {
+        let delegation = if proto.has_delegation() {
+            Some(Delegation::from_proto(proto.get_delegation().clone())?)
+        } else {
+            None
+        };
+
         Ok(ExecuteContractAction {
             name: proto.get_name().to_string(),
             version: proto.get_version().to_string(),
             inputs: proto.get_inputs().to_vec(),
             outputs: proto.get_outputs().to_vec(),
             payload: proto.get_payload().to_vec(),
+            delegation,
+            contract_sha512: Some(proto.get_contract_sha512())
+                .filter(|sha512| !sha512.is_empty())
+                .map(String::from),
+            payload_compressed: proto.get_payload_compressed(),
         })
     }
 }
@@ -489,6 +554,13 @@ impl FromNative<ExecuteContractAction> for protos::payload::ExecuteContractActio
             execute_contract_action.outputs().to_vec(),
         ));
         proto.set_payload(execute_contract_action.payload().to_vec());
+        if let Some(delegation) = execute_contract_action.delegation {
+            proto.set_delegation(delegation.into_proto()?);
+        }
+        if let Some(contract_sha512) = execute_contract_action.contract_sha512 {
+            proto.set_contract_sha512(contract_sha512);
+        }
+        proto.set_payload_compressed(execute_contract_action.payload_compressed);
         Ok(proto)
     }
 }
@@ -528,6 +600,10 @@ pub struct ExecuteContractActionBuilder {
     inputs: Vec<String>,
     outputs: Vec<String>,
     payload: Vec<u8>,
+    delegation: Option<Delegation>,
+    contract_sha512: Option<String>,
+    #[cfg(feature = "payload-compression")]
+    compression_threshold: Option<usize>,
 }
 
 impl ExecuteContractActionBuilder {
@@ -560,6 +636,29 @@ impl ExecuteContractActionBuilder {
         self
     }
 
+    /// Sets the delegation used when submitting the action on behalf of another signer. The
+    /// delegation must be built for the same name, version and payload as the action.
+    pub fn with_delegation(mut self, delegation: Delegation) -> ExecuteContractActionBuilder {
+        self.delegation = Some(delegation);
+        self
+    }
+
+    /// Pins the contract to the given hex encoded sha512, so the action is only executed
+    /// against a stored contract with that hash.
+    pub fn with_contract_sha512(mut self, contract_sha512: String) -> ExecuteContractActionBuilder {
+        self.contract_sha512 = Some(contract_sha512);
+        self
+    }
+
+    /// Compresses payloads of at least `threshold` bytes, if compressing makes them smaller.
+    /// The transaction processor decompresses the payload before executing the contract, and a
+    /// delegation is still signed over the uncompressed payload.
+    #[cfg(feature = "payload-compression")]
+    pub fn with_compression_threshold(mut self, threshold: usize) -> ExecuteContractActionBuilder {
+        self.compression_threshold = Some(threshold);
+        self
+    }
+
     pub fn build(self) -> Result<ExecuteContractAction, ActionBuildError> {
         let name = self.name.ok_or_else(|| {
             ActionBuildError::MissingField("'name' field is required".to_string())
@@ -582,12 +681,30 @@ impl ExecuteContractActionBuilder {
             }
         };
 
+        #[cfg(feature = "payload-compression")]
+        let (payload, payload_compressed) = match self.compression_threshold {
+            Some(threshold) if payload.len() >= threshold => {
+                let compressed = compress_to_vec(&payload, COMPRESSION_LEVEL);
+                if compressed.len() < payload.len() {
+                    (compressed, true)
+                } else {
+                    (payload, false)
+                }
+            }
+            _ => (payload, false),
+        };
+        #[cfg(not(feature = "payload-compression"))]
+        let payload_compressed = false;
+
         Ok(ExecuteContractAction {
             name,
             version,
             inputs,
             outputs,
             payload,
+            delegation: self.delegation,
+            contract_sha512: self.contract_sha512,
+            payload_compressed,
         })
     }
 
@@ -597,6 +714,297 @@ impl ExecuteContractActionBuilder {
     }
 }
 
+/// Native implementation for Delegation
+///
+/// A delegation allows a relayer to submit an `ExecuteContractAction` on behalf of another
+/// signer. The delegated signer signs a digest binding the contract name and version, the
+/// contract payload, the relayer's public key and a nonce; the handler verifies the signature
+/// before the contract is executed, and rejects a delegation whose nonce has already been used.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Delegation {
+    signer_public_key: String,
+    signature: String,
+    relayer_public_key: String,
+    nonce: String,
+}
+
+impl Delegation {
+    pub fn signer_public_key(&self) -> &str {
+        &self.signer_public_key
+    }
+
+    pub fn signature(&self) -> &str {
+        &self.signature
+    }
+
+    /// The public key of the relayer the delegation was issued to, which must sign the
+    /// transaction.
+    pub fn relayer_public_key(&self) -> &str {
+        &self.relayer_public_key
+    }
+
+    /// The value which makes the delegation single use.
+    pub fn nonce(&self) -> &str {
+        &self.nonce
+    }
+
+    /// Verifies that the delegation's signature is a valid signature by the delegated signer
+    /// over the delegation digest of the contract and payload.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the contract the delegation was created for
+    /// * `version` - the version of the contract the delegation was created for
+    /// * `payload` - the contract payload the delegation was created for
+    /// * `verifier` - the verifier for the signing algorithm used by the delegated signer
+    #[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+    pub fn verify(
+        &self,
+        name: &str,
+        version: &str,
+        payload: &[u8],
+        verifier: &dyn Verifier,
+    ) -> Result<bool, DelegationError> {
+        let public_key = PublicKey::from_hex(&self.signer_public_key).map_err(|err| {
+            DelegationError::InvalidPublicKey(format!(
+                "unable to parse delegated signer public key: {}",
+                err
+            ))
+        })?;
+        let signature = Signature::from_hex(&self.signature).map_err(|err| {
+            DelegationError::InvalidSignature(format!(
+                "unable to parse delegation signature: {}",
+                err
+            ))
+        })?;
+
+        let digest = delegation_digest(
+            name,
+            version,
+            payload,
+            &self.relayer_public_key,
+            &self.nonce,
+        );
+        verifier
+            .verify(&digest, &signature, &public_key)
+            .map_err(|err| DelegationError::VerificationFailed(err.to_string()))
+    }
+}
+
+/// Returns the SHA-512 digest a delegated signer signs. Each field is prefixed with its length,
+/// so no two sets of fields have the same digest.
+pub fn delegation_digest(
+    name: &str,
+    version: &str,
+    payload: &[u8],
+    relayer_public_key: &str,
+    nonce: &str,
+) -> Vec<u8> {
+    let mut hasher = Sha512::new();
+    for field in &[
+        name.as_bytes(),
+        version.as_bytes(),
+        payload,
+        relayer_public_key.as_bytes(),
+        nonce.as_bytes(),
+    ] {
+        hasher.update((field.len() as u64).to_be_bytes());
+        hasher.update(field);
+    }
+    hasher.finalize().to_vec()
+}
+
+/// Separates the transaction signature from the relayer's public key in the signature the
+/// transaction processor hands a contract executed under a delegation. The Sabre handler only
+/// passes the contract a signer and a signature, and the signer must stay the delegated signer
+/// for namespace permissions to be checked against it.
+const RELAYED_SIGNATURE_SEPARATOR: char = '/';
+
+/// Returns the signature to hand a contract executed under a delegation, carrying the public key
+/// of the relayer which signed the transaction.
+pub fn join_relayed_signature(signature: &str, relayer_public_key: &str) -> String {
+    format!(
+        "{}{}{}",
+        signature, RELAYED_SIGNATURE_SEPARATOR, relayer_public_key
+    )
+}
+
+/// Splits the signature handed to a contract into the transaction signature and, if the
+/// contract was executed under a delegation, the relayer's public key.
+pub fn split_relayed_signature(signature: &str) -> (&str, Option<&str>) {
+    match signature.split_once(RELAYED_SIGNATURE_SEPARATOR) {
+        Some((signature, relayer_public_key)) => (signature, Some(relayer_public_key)),
+        None => (signature, None),
+    }
+}
+
+impl FromProto<protos::payload::Delegation> for Delegation {
+    fn from_proto(proto: protos::payload::Delegation) -> Result<Self, ProtoConversionError> {
+        Ok(Delegation {
+            signer_public_key: proto.get_signer_public_key().to_string(),
+            signature: proto.get_signature().to_string(),
+            relayer_public_key: proto.get_relayer_public_key().to_string(),
+            nonce: proto.get_nonce().to_string(),
+        })
+    }
+}
+
+impl FromNative<Delegation> for protos::payload::Delegation {
+    fn from_native(delegation: Delegation) -> Result<Self, ProtoConversionError> {
+        let mut proto = protos::payload::Delegation::new();
+        proto.set_signer_public_key(delegation.signer_public_key().to_string());
+        proto.set_signature(delegation.signature().to_string());
+        proto.set_relayer_public_key(delegation.relayer_public_key().to_string());
+        proto.set_nonce(delegation.nonce().to_string());
+        Ok(proto)
+    }
+}
+
+impl FromBytes<Delegation> for Delegation {
+    fn from_bytes(bytes: &[u8]) -> Result<Delegation, ProtoConversionError> {
+        let proto: protos::payload::Delegation =
+            Message::parse_from_bytes(bytes).map_err(|_| {
+                ProtoConversionError::SerializationError(
+                    "Unable to get Delegation from bytes".to_string(),
+                )
+            })?;
+        proto.into_native()
+    }
+}
+
+impl IntoBytes for Delegation {
+    fn into_bytes(self) -> Result<Vec<u8>, ProtoConversionError> {
+        let proto = self.into_proto()?;
+        let bytes = proto.write_to_bytes().map_err(|_| {
+            ProtoConversionError::SerializationError(
+                "Unable to get bytes from Delegation".to_string(),
+            )
+        })?;
+        Ok(bytes)
+    }
+}
+
+impl IntoProto<protos::payload::Delegation> for Delegation {}
+impl IntoNative<Delegation> for protos::payload::Delegation {}
+
+#[derive(Debug)]
+pub enum DelegationError {
+    InvalidPublicKey(String),
+    InvalidSignature(String),
+    MissingField(String),
+    SigningError(String),
+    VerificationFailed(String),
+}
+
+impl StdError for DelegationError {}
+
+impl std::fmt::Display for DelegationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            DelegationError::InvalidPublicKey(ref s) => write!(f, "InvalidPublicKey: {}", s),
+            DelegationError::InvalidSignature(ref s) => write!(f, "InvalidSignature: {}", s),
+            DelegationError::MissingField(ref s) => write!(f, "MissingField: {}", s),
+            DelegationError::SigningError(ref s) => write!(f, "SigningError: {}", s),
+            DelegationError::VerificationFailed(ref s) => {
+                write!(f, "VerificationFailed: {}", s)
+            }
+        }
+    }
+}
+
+/// Builder used to create a Delegation by signing a contract payload
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+#[derive(Default, Clone)]
+pub struct DelegationBuilder {
+    name: Option<String>,
+    version: Option<String>,
+    payload: Vec<u8>,
+    relayer_public_key: Option<String>,
+    nonce: Option<String>,
+}
+
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+impl DelegationBuilder {
+    pub fn new() -> Self {
+        DelegationBuilder::default()
+    }
+
+    /// Sets the name of the contract the delegated signer authorizes executing.
+    pub fn with_name(mut self, name: String) -> DelegationBuilder {
+        self.name = Some(name);
+        self
+    }
+
+    /// Sets the version of the contract the delegated signer authorizes executing.
+    pub fn with_version(mut self, version: String) -> DelegationBuilder {
+        self.version = Some(version);
+        self
+    }
+
+    /// Sets the contract payload that the delegated signer authorizes.
+    pub fn with_payload(mut self, payload: Vec<u8>) -> DelegationBuilder {
+        self.payload = payload;
+        self
+    }
+
+    /// Sets the public key of the relayer allowed to submit the delegated action.
+    pub fn with_relayer_public_key(mut self, relayer_public_key: String) -> DelegationBuilder {
+        self.relayer_public_key = Some(relayer_public_key);
+        self
+    }
+
+    /// Sets the nonce which makes the delegation single use. A delegated signer must not reuse
+    /// a nonce, as the delegation is rejected once a delegation with its nonce has been used.
+    pub fn with_nonce(mut self, nonce: String) -> DelegationBuilder {
+        self.nonce = Some(nonce);
+        self
+    }
+
+    /// Signs the delegation digest with the delegated signer's key and returns the resulting
+    /// delegation.
+    pub fn build(self, signer: &dyn Signer) -> Result<Delegation, DelegationError> {
+        let name = self
+            .name
+            .ok_or_else(|| DelegationError::MissingField("'name' field is required".to_string()))?;
+        let version = self.version.ok_or_else(|| {
+            DelegationError::MissingField("'version' field is required".to_string())
+        })?;
+        if self.payload.is_empty() {
+            return Err(DelegationError::MissingField(
+                "'payload' field is required".to_string(),
+            ));
+        }
+        let relayer_public_key = self.relayer_public_key.ok_or_else(|| {
+            DelegationError::MissingField("'relayer_public_key' field is required".to_string())
+        })?;
+        let nonce = match self.nonce {
+            Some(nonce) if !nonce.is_empty() => nonce,
+            _ => {
+                return Err(DelegationError::MissingField(
+                    "'nonce' field is required".to_string(),
+                ))
+            }
+        };
+
+        let signer_public_key = signer
+            .public_key()
+            .map_err(|err| DelegationError::SigningError(err.to_string()))?
+            .as_hex();
+        let digest = delegation_digest(&name, &version, &self.payload, &relayer_public_key, &nonce);
+        let signature = signer
+            .sign(&digest)
+            .map_err(|err| DelegationError::SigningError(err.to_string()))?
+            .as_hex();
+
+        Ok(Delegation {
+            signer_public_key,
+            signature,
+            relayer_public_key,
+            nonce,
+        })
+    }
+}
+
 /// Native implementation for CreateContractRegistryAction
 #[derive(Default, Debug, Clone, PartialEq, Eq)]
 pub struct CreateContractRegistryAction {
@@ -1797,7 +2205,7 @@ impl SabrePayloadBuilder {
 
     /// Convert the `SabrePayloadBuilder` into a `TransactionBuilder`, filling in all required
     /// fields.
-    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg(all(feature = "client", not(target_arch = "wasm32")))]
     pub fn into_transaction_builder(self) -> Result<TransactionBuilder, SabrePayloadBuildError> {
         let payload = self.build()?;
 
@@ -1815,14 +2223,27 @@ impl SabrePayloadBuilder {
                 version,
                 inputs,
                 outputs,
+                delegation,
                 ..
             }) => {
-                let addresses = vec![
+                let mut addresses = vec![
                     compute_contract_registry_address(name)?,
                     compute_contract_address(name, version)?,
                 ];
+                // The handler records each delegation it accepts, so it cannot be used again
+                if let Some(delegation) = delegation {
+                    addresses.push(compute_delegation_address(
+                        delegation.signer_public_key(),
+                        delegation.nonce(),
+                    )?);
+                }
 
+                // The handler reads the settings limiting the contract's events and enabling
+                // its write stats
                 let mut input_addresses = addresses.clone();
+                input_addresses.push(compute_setting_address(MAX_EVENTS_SETTING_KEY)?);
+                input_addresses.push(compute_setting_address(AGGREGATE_EVENTS_SETTING_KEY)?);
+                input_addresses.push(compute_setting_address(WRITE_STATS_SETTING_KEY)?);
                 for input in inputs {
                     let namespace = match input.get(..6) {
                         Some(namespace) => namespace,
@@ -1898,7 +2319,7 @@ impl SabrePayloadBuilder {
         })?;
 
         Ok(TransactionBuilder::new()
-            .with_family_name("sabre".into())
+            .with_family_name(SABRE_FAMILY_NAME.into())
             .with_family_version(SABRE_PROTOCOL_VERSION.into())
             .with_inputs(input_addresses)
             .with_outputs(output_addresses)
@@ -1907,7 +2328,7 @@ impl SabrePayloadBuilder {
     }
 }
 
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
 fn parse_hex(hex: &str) -> Result<Vec<u8>, AddressingError> {
     if hex.len() % 2 != 0 {
         return Err(AddressingError::InvalidInput(format!(
@@ -1927,10 +2348,10 @@ fn parse_hex(hex: &str) -> Result<Vec<u8>, AddressingError> {
 }
 
 #[cfg(test)]
-#[cfg(not(target_arch = "wasm32"))]
 mod tests {
     use super::*;
 
+    #[cfg(all(feature = "client", not(target_arch = "wasm32")))]
     use cylinder::{secp256k1::Secp256k1Context, Context, Signer};
 
     #[test]
@@ -2041,6 +2462,165 @@ mod tests {
         assert_eq!(execute, original);
     }
 
+    #[test]
+    #[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+    // check that an execute contract with a delegation can be converted to bytes and back, and
+    // that the delegation verifies only against the contract and payload it was created for
+    fn check_execute_contract_action_delegation() {
+        let context = Secp256k1Context::new();
+        let delegated_signer = context.new_signer(context.new_random_private_key());
+
+        let delegation = DelegationBuilder::new()
+            .with_name("TestContract".to_string())
+            .with_version("0.1".to_string())
+            .with_payload(b"test_payload".to_vec())
+            .with_relayer_public_key("02abcd".to_string())
+            .with_nonce("1".to_string())
+            .build(&*delegated_signer)
+            .unwrap();
+
+        let original = ExecuteContractActionBuilder::new()
+            .with_name("TestContract".to_string())
+            .with_version("0.1".to_string())
+            .with_inputs(vec!["test".to_string(), "input".to_string()])
+            .with_outputs(vec!["test".to_string(), "output".to_string()])
+            .with_payload(b"test_payload".to_vec())
+            .with_delegation(delegation.clone())
+            .build()
+            .unwrap();
+
+        let bytes = original.clone().into_bytes().unwrap();
+
+        let execute = ExecuteContractAction::from_bytes(&bytes).unwrap();
+        assert_eq!(execute, original);
+        assert_eq!(execute.delegation(), Some(&delegation));
+        assert_eq!(execute.contract_sha512(), None);
+
+        let verifier = context.new_verifier();
+        assert!(delegation
+            .verify("TestContract", "0.1", b"test_payload", &*verifier)
+            .unwrap());
+        assert!(!delegation
+            .verify("TestContract", "0.1", b"other_payload", &*verifier)
+            .unwrap());
+        assert!(!delegation
+            .verify("OtherContract", "0.1", b"test_payload", &*verifier)
+            .unwrap());
+        assert!(!delegation
+            .verify("TestContract", "0.2", b"test_payload", &*verifier)
+            .unwrap());
+    }
+
+    #[test]
+    #[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+    // check that a delegation handed to another relayer, or given another nonce, no longer
+    // verifies, and that a delegation requires a nonce
+    fn check_delegation_rebound() {
+        let context = Secp256k1Context::new();
+        let delegated_signer = context.new_signer(context.new_random_private_key());
+        let verifier = context.new_verifier();
+
+        let builder = DelegationBuilder::new()
+            .with_name("TestContract".to_string())
+            .with_version("0.1".to_string())
+            .with_payload(b"test_payload".to_vec())
+            .with_relayer_public_key("02abcd".to_string());
+        assert!(builder.clone().build(&*delegated_signer).is_err());
+
+        let delegation = builder
+            .with_nonce("1".to_string())
+            .build(&*delegated_signer)
+            .unwrap();
+
+        let rebound = Delegation {
+            relayer_public_key: "03ef01".to_string(),
+            ..delegation.clone()
+        };
+        assert!(!rebound
+            .verify("TestContract", "0.1", b"test_payload", &*verifier)
+            .unwrap());
+
+        let renonced = Delegation {
+            nonce: "2".to_string(),
+            ..delegation
+        };
+        assert!(!renonced
+            .verify("TestContract", "0.1", b"test_payload", &*verifier)
+            .unwrap());
+    }
+
+    #[test]
+    // check that a relayer's public key can be carried in a contract's signature and split out
+    fn check_relayed_signature() {
+        let signature = join_relayed_signature("abc123", "02abcd");
+        assert_eq!(
+            split_relayed_signature(&signature),
+            ("abc123", Some("02abcd"))
+        );
+        assert_eq!(split_relayed_signature("abc123"), ("abc123", None));
+    }
+
+    #[test]
+    // check that an execute contract pinned to a contract sha512 can be converted to bytes and
+    // back
+    fn check_execute_contract_action_sha512() {
+        let original = ExecuteContractActionBuilder::new()
+            .with_name("TestContract".to_string())
+            .with_version("0.1".to_string())
+            .with_payload(b"test_payload".to_vec())
+            .with_contract_sha512("ab".repeat(64))
+            .build()
+            .unwrap();
+
+        let bytes = original.clone().into_bytes().unwrap();
+
+        let execute = ExecuteContractAction::from_bytes(&bytes).unwrap();
+        assert_eq!(execute, original);
+        assert_eq!(execute.contract_sha512(), Some("ab".repeat(64).as_str()));
+    }
+
+    #[test]
+    #[cfg(feature = "payload-compression")]
+    // check that payloads at or over the compression threshold are compressed, survive
+    // conversion to bytes, and decompress to the original payload, and that payloads under the
+    // threshold or which do not compress are left as they are
+    fn check_execute_contract_action_compression() {
+        let builder = ExecuteContractActionBuilder::new()
+            .with_name("TestContract".to_string())
+            .with_version("0.1".to_string())
+            .with_compression_threshold(64);
+
+        let large = vec![b'a'; 1024];
+        let compressed = builder.clone().with_payload(large.clone()).build().unwrap();
+        assert!(compressed.payload_compressed());
+        assert!(compressed.payload().len() < large.len());
+
+        let bytes = compressed.clone().into_bytes().unwrap();
+        let execute = ExecuteContractAction::from_bytes(&bytes).unwrap();
+        assert_eq!(execute, compressed);
+
+        let decompressed = execute.into_decompressed(large.len()).unwrap();
+        assert!(!decompressed.payload_compressed());
+        assert_eq!(decompressed.payload(), large.as_slice());
+        assert!(compressed.into_decompressed(large.len() - 1).is_err());
+
+        let small = builder
+            .clone()
+            .with_payload(vec![b'a'; 63])
+            .build()
+            .unwrap();
+        assert!(!small.payload_compressed());
+        assert_eq!(small.clone().into_decompressed(0).unwrap(), small);
+
+        let incompressible = (0..=255u8).collect::<Vec<_>>();
+        let action = builder
+            .with_payload(incompressible.clone())
+            .build()
+            .unwrap();
+        assert!(!action.payload_compressed());
+        assert_eq!(action.payload(), incompressible.as_slice());
+    }
+
     #[test]
     // check that a create contract registry action is built correctly
     fn check_create_contract_registry_action() {
@@ -2322,6 +2902,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(all(feature = "client", not(target_arch = "wasm32")))]
     // check that a create contract can be converted -> sabre payload builder -> transaction
     // builder -> transaction
     fn create_contract_into_transaction() {
@@ -2351,6 +2932,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(all(feature = "client", not(target_arch = "wasm32")))]
     // check that a delete contract can be converted -> sabre payload builder -> transaction
     // builder -> transaction
     fn delete_contract_into_transaction() {
@@ -2377,6 +2959,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(all(feature = "client", not(target_arch = "wasm32")))]
     // check that an execute contract can be converted -> sabre payload builder -> transaction
     // builder -> transaction
     fn execute_contract_into_transaction() {
@@ -2406,6 +2989,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(all(feature = "client", not(target_arch = "wasm32")))]
     // check that a create contract registry can be converted -> sabre payload builder ->
     // transaction builder -> transaction
     fn create_contract_registry_into_transaction() {
@@ -2432,6 +3016,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(all(feature = "client", not(target_arch = "wasm32")))]
     // check that a delete contract registry can be converted -> sabre payload builder ->
     // transaction builder -> transaction
     fn delete_contract_registry_into_transaction() {
@@ -2457,6 +3042,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(all(feature = "client", not(target_arch = "wasm32")))]
     // check that an update contract registry owners can be converted -> sabre payload builder ->
     // transaction builder -> transaction
     fn update_contract_registry_owners_into_transaction() {
@@ -2483,6 +3069,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(all(feature = "client", not(target_arch = "wasm32")))]
     // check that a create namespace registry can be converted -> sabre payload builder ->
     // transaction builder -> transaction
     fn create_namespace_registry_into_transaction() {
@@ -2509,6 +3096,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(all(feature = "client", not(target_arch = "wasm32")))]
     // check that a delete namespace registry can be converted -> sabre payload builder ->
     // transaction builder -> transaction
     fn delete_namespace_registry_into_transaction() {
@@ -2534,6 +3122,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(all(feature = "client", not(target_arch = "wasm32")))]
     // check that a update namespace registry owners can be converted -> sabre payload builder ->
     // transaction builder -> transaction
     fn update_namespace_registry_owners_into_transaction() {
@@ -2560,6 +3149,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(all(feature = "client", not(target_arch = "wasm32")))]
     // check that a create namespace registry permission can be converted -> sabre payload builder
     // -> transaction builder -> transaction
     fn create_namespace_registry_permission_into_transaction() {
@@ -2588,6 +3178,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(all(feature = "client", not(target_arch = "wasm32")))]
     // check that a delete namespace registry permission can be converted -> sabre payload builder
     // -> transaction builder -> transaction
     fn delete_namespace_registry_permission_into_transaction() {
@@ -2613,6 +3204,7 @@ mod tests {
         assert_eq!(txn_header.payload_hash_method(), &HashMethod::Sha512);
     }
 
+    #[cfg(all(feature = "client", not(target_arch = "wasm32")))]
     fn new_signer() -> Box<dyn Signer> {
         let context = Secp256k1Context::new();
         let key = context.new_random_private_key();
@@ -0,0 +1,312 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Simulation of the effect of namespace registry actions on contracts' access to namespaces.
+//!
+//! Given the current namespace registries and the actions an administrator proposes, `simulate`
+//! reports each contract which would gain or lose read or write access to a namespace, without
+//! anything being submitted.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error as StdError;
+use std::fmt;
+
+use super::payload::Action;
+use super::state::NamespaceRegistry;
+
+/// A contract's access to a namespace
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Access {
+    pub read: bool,
+    pub write: bool,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.read, self.write) {
+            (true, true) => f.write_str("read/write"),
+            (true, false) => f.write_str("read"),
+            (false, true) => f.write_str("write"),
+            (false, false) => f.write_str("none"),
+        }
+    }
+}
+
+/// A change in a contract's access to a namespace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessChange {
+    pub namespace: String,
+    pub contract_name: String,
+    pub before: Access,
+    pub after: Access,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SimulationError {
+    /// The action concerns a namespace registry which does not exist, so it would be rejected
+    NamespaceRegistryNotFound(String),
+    /// The action creates a namespace registry which already exists, so it would be rejected
+    NamespaceRegistryExists(String),
+}
+
+impl StdError for SimulationError {}
+
+impl fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SimulationError::NamespaceRegistryNotFound(ref namespace) => {
+                write!(f, "namespace registry {} does not exist", namespace)
+            }
+            SimulationError::NamespaceRegistryExists(ref namespace) => {
+                write!(f, "namespace registry {} already exists", namespace)
+            }
+        }
+    }
+}
+
+/// Applies the actions, in order, to the registries, and returns the changes in access they
+/// make, ordered by namespace and contract name. Actions which do not concern namespace
+/// registries make no change.
+///
+/// An error is returned if an action would be rejected because of the namespace registries it
+/// concerns. Whether the signer is allowed to submit the actions is not checked.
+pub fn simulate(
+    registries: &[NamespaceRegistry],
+    actions: &[Action],
+) -> Result<Vec<AccessChange>, SimulationError> {
+    let mut namespaces = BTreeSet::new();
+    let mut before = BTreeMap::new();
+    for registry in registries {
+        namespaces.insert(registry.namespace().to_string());
+        for permission in registry.permissions() {
+            before.insert(
+                (
+                    registry.namespace().to_string(),
+                    permission.contract_name().to_string(),
+                ),
+                Access {
+                    read: permission.read(),
+                    write: permission.write(),
+                },
+            );
+        }
+    }
+
+    let mut after = before.clone();
+    for action in actions {
+        match action {
+            Action::CreateNamespaceRegistry(action) => {
+                if !namespaces.insert(action.namespace().to_string()) {
+                    return Err(SimulationError::NamespaceRegistryExists(
+                        action.namespace().to_string(),
+                    ));
+                }
+            }
+            Action::DeleteNamespaceRegistry(action) => {
+                let namespace = require(&namespaces, action.namespace())?;
+                namespaces.remove(namespace);
+                after.retain(|(permitted, _), _| permitted != namespace);
+            }
+            Action::CreateNamespaceRegistryPermission(action) => {
+                let namespace = require(&namespaces, action.namespace())?;
+                // A contract's permission replaces any it already has on the namespace
+                after.insert(
+                    (namespace.to_string(), action.contract_name().to_string()),
+                    Access {
+                        read: action.read(),
+                        write: action.write(),
+                    },
+                );
+            }
+            Action::DeleteNamespaceRegistryPermission(action) => {
+                let namespace = require(&namespaces, action.namespace())?;
+                after.remove(&(namespace.to_string(), action.contract_name().to_string()));
+            }
+            _ => (),
+        }
+    }
+
+    let keys = before.keys().chain(after.keys()).collect::<BTreeSet<_>>();
+    Ok(keys
+        .into_iter()
+        .filter_map(|key| {
+            let was = before.get(key).copied().unwrap_or_default();
+            let is = after.get(key).copied().unwrap_or_default();
+            if was == is {
+                return None;
+            }
+            Some(AccessChange {
+                namespace: key.0.clone(),
+                contract_name: key.1.clone(),
+                before: was,
+                after: is,
+            })
+        })
+        .collect())
+}
+
+/// Returns the namespace if its registry exists.
+fn require<'a>(
+    namespaces: &BTreeSet<String>,
+    namespace: &'a str,
+) -> Result<&'a str, SimulationError> {
+    if namespaces.contains(namespace) {
+        Ok(namespace)
+    } else {
+        Err(SimulationError::NamespaceRegistryNotFound(
+            namespace.to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::protocol::payload::{
+        CreateNamespaceRegistryActionBuilder, CreateNamespaceRegistryPermissionActionBuilder,
+        DeleteNamespaceRegistryActionBuilder, DeleteNamespaceRegistryPermissionActionBuilder,
+    };
+    use crate::protocol::state::{NamespaceRegistryBuilder, PermissionBuilder};
+
+    fn registry(namespace: &str, permissions: &[(&str, bool, bool)]) -> NamespaceRegistry {
+        NamespaceRegistryBuilder::new()
+            .with_namespace(namespace.to_string())
+            .with_owners(vec!["owner".to_string()])
+            .with_permissions(
+                permissions
+                    .iter()
+                    .map(|(contract_name, read, write)| {
+                        PermissionBuilder::new()
+                            .with_contract_name(contract_name.to_string())
+                            .with_read(*read)
+                            .with_write(*write)
+                            .build()
+                            .unwrap()
+                    })
+                    .collect(),
+            )
+            .build()
+            .unwrap()
+    }
+
+    fn grant(namespace: &str, contract_name: &str, read: bool, write: bool) -> Action {
+        Action::CreateNamespaceRegistryPermission(
+            CreateNamespaceRegistryPermissionActionBuilder::new()
+                .with_namespace(namespace.to_string())
+                .with_contract_name(contract_name.to_string())
+                .with_read(read)
+                .with_write(write)
+                .build()
+                .unwrap(),
+        )
+    }
+
+    fn change(
+        namespace: &str,
+        contract_name: &str,
+        before: (bool, bool),
+        after: (bool, bool),
+    ) -> AccessChange {
+        AccessChange {
+            namespace: namespace.to_string(),
+            contract_name: contract_name.to_string(),
+            before: Access {
+                read: before.0,
+                write: before.1,
+            },
+            after: Access {
+                read: after.0,
+                write: after.1,
+            },
+        }
+    }
+
+    #[test]
+    // check that granting, replacing and revoking permissions report the access gained and
+    // lost, and that unchanged permissions are not reported
+    fn check_simulate_permissions() {
+        let registries = vec![
+            registry(
+                "1cf126",
+                &[("intkey_multiply", true, false), ("other", true, true)],
+            ),
+            registry("cad11d", &[("intkey_multiply", true, true)]),
+        ];
+
+        let actions = vec![
+            grant("1cf126", "intkey_multiply", true, true),
+            grant("1cf126", "other", true, true),
+            Action::DeleteNamespaceRegistryPermission(
+                DeleteNamespaceRegistryPermissionActionBuilder::new()
+                    .with_namespace("cad11d".to_string())
+                    .with_contract_name("intkey_multiply".to_string())
+                    .build()
+                    .unwrap(),
+            ),
+            grant("cad11d", "new_contract", true, false),
+        ];
+
+        assert_eq!(
+            simulate(&registries, &actions).unwrap(),
+            vec![
+                change("1cf126", "intkey_multiply", (true, false), (true, true)),
+                change("cad11d", "intkey_multiply", (true, true), (false, false)),
+                change("cad11d", "new_contract", (false, false), (true, false)),
+            ]
+        );
+    }
+
+    #[test]
+    // check that deleting a registry revokes its permissions, that a registry created by an
+    // earlier action may be granted on, and that actions on missing registries are rejected
+    fn check_simulate_registries() {
+        let registries = vec![registry("1cf126", &[("intkey_multiply", true, true)])];
+
+        let actions = vec![
+            Action::DeleteNamespaceRegistry(
+                DeleteNamespaceRegistryActionBuilder::new()
+                    .with_namespace("1cf126".to_string())
+                    .build()
+                    .unwrap(),
+            ),
+            Action::CreateNamespaceRegistry(
+                CreateNamespaceRegistryActionBuilder::new()
+                    .with_namespace("abcdef".to_string())
+                    .with_owners(vec!["owner".to_string()])
+                    .build()
+                    .unwrap(),
+            ),
+            grant("abcdef", "intkey_multiply", false, true),
+        ];
+
+        assert_eq!(
+            simulate(&registries, &actions).unwrap(),
+            vec![
+                change("1cf126", "intkey_multiply", (true, true), (false, false)),
+                change("abcdef", "intkey_multiply", (false, false), (false, true)),
+            ]
+        );
+
+        assert_eq!(
+            simulate(
+                &registries,
+                &[grant("000000", "intkey_multiply", true, false)]
+            ),
+            Err(SimulationError::NamespaceRegistryNotFound(
+                "000000".to_string()
+            ))
+        );
+    }
+}
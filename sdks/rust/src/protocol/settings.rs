@@ -0,0 +1,185 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Native representations of the Sawtooth settings stored in the 000000 namespace.
+
+use protobuf::Message;
+use protobuf::RepeatedField;
+
+use crate::protos;
+use crate::protos::{
+    FromBytes, FromNative, FromProto, IntoBytes, IntoNative, IntoProto, ProtoConversionError,
+};
+
+/// Native implementation for Setting_Entry
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct SettingEntry {
+    key: String,
+    value: String,
+}
+
+impl SettingEntry {
+    pub fn new(key: String, value: String) -> Self {
+        SettingEntry { key, value }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+impl FromProto<protos::setting::Setting_Entry> for SettingEntry {
+    fn from_proto(proto: protos::setting::Setting_Entry) -> Result<Self, ProtoConversionError> {
+        Ok(SettingEntry {
+            key: proto.get_key().to_string(),
+            value: proto.get_value().to_string(),
+        })
+    }
+}
+
+impl FromNative<SettingEntry> for protos::setting::Setting_Entry {
+    fn from_native(native: SettingEntry) -> Result<Self, ProtoConversionError> {
+        let mut proto = protos::setting::Setting_Entry::new();
+
+        proto.set_key(native.key().to_string());
+        proto.set_value(native.value().to_string());
+
+        Ok(proto)
+    }
+}
+
+impl IntoProto<protos::setting::Setting_Entry> for SettingEntry {}
+impl IntoNative<SettingEntry> for protos::setting::Setting_Entry {}
+
+/// Native implementation for Setting
+///
+/// A setting holds every entry stored at a setting address; more than one entry implies the keys'
+/// addresses collide.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Setting {
+    entries: Vec<SettingEntry>,
+}
+
+impl Setting {
+    pub fn new(entries: Vec<SettingEntry>) -> Self {
+        Setting { entries }
+    }
+
+    pub fn entries(&self) -> &[SettingEntry] {
+        &self.entries
+    }
+
+    /// Returns the value of the entry for `key`, if there is one.
+    pub fn get_value(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|entry| entry.key() == key)
+            .map(SettingEntry::value)
+    }
+}
+
+impl FromProto<protos::setting::Setting> for Setting {
+    fn from_proto(proto: protos::setting::Setting) -> Result<Self, ProtoConversionError> {
+        Ok(Setting {
+            entries: proto
+                .get_entries()
+                .iter()
+                .cloned()
+                .map(SettingEntry::from_proto)
+                .collect::<Result<Vec<SettingEntry>, ProtoConversionError>>()?,
+        })
+    }
+}
+
+impl FromNative<Setting> for protos::setting::Setting {
+    fn from_native(native: Setting) -> Result<Self, ProtoConversionError> {
+        let mut proto = protos::setting::Setting::new();
+
+        proto.set_entries(RepeatedField::from_vec(
+            native
+                .entries
+                .into_iter()
+                .map(SettingEntry::into_proto)
+                .collect::<Result<Vec<protos::setting::Setting_Entry>, ProtoConversionError>>()?,
+        ));
+
+        Ok(proto)
+    }
+}
+
+impl FromBytes<Setting> for Setting {
+    fn from_bytes(bytes: &[u8]) -> Result<Setting, ProtoConversionError> {
+        let proto: protos::setting::Setting = Message::parse_from_bytes(bytes).map_err(|_| {
+            ProtoConversionError::SerializationError("Unable to get Setting from bytes".to_string())
+        })?;
+        proto.into_native()
+    }
+}
+
+impl IntoBytes for Setting {
+    fn into_bytes(self) -> Result<Vec<u8>, ProtoConversionError> {
+        let proto = self.into_proto()?;
+        let bytes = proto.write_to_bytes().map_err(|_| {
+            ProtoConversionError::SerializationError("Unable to get bytes from Setting".to_string())
+        })?;
+        Ok(bytes)
+    }
+}
+
+impl IntoProto<protos::setting::Setting> for Setting {}
+impl IntoNative<Setting> for protos::setting::Setting {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::protocol::{
+        compute_setting_address, ADMINISTRATORS_SETTING_ADDRESS_BYTES, ADMINISTRATORS_SETTING_KEY,
+    };
+
+    #[test]
+    // check that a setting address is computed the same way as the settings transaction family
+    fn check_setting_address() {
+        assert_eq!(
+            compute_setting_address(ADMINISTRATORS_SETTING_KEY).unwrap(),
+            ADMINISTRATORS_SETTING_ADDRESS_BYTES
+        );
+        assert_eq!(
+            compute_setting_address("sawtooth.a.b.c.d").unwrap()[..27],
+            compute_setting_address("sawtooth.a.b.x").unwrap()[..27]
+        );
+        assert!(compute_setting_address("").is_err());
+    }
+
+    #[test]
+    // check that a setting can be converted to bytes and back, and that its entries can be found
+    // by key
+    fn check_setting_bytes() {
+        let setting = Setting::new(vec![
+            SettingEntry::new("sawtooth.fee.rate".to_string(), "10".to_string()),
+            SettingEntry::new("sawtooth.fee.limit".to_string(), "100".to_string()),
+        ]);
+
+        let bytes = setting.clone().into_bytes().unwrap();
+        let setting = Setting::from_bytes(&bytes).unwrap();
+
+        assert_eq!(setting.get_value("sawtooth.fee.rate"), Some("10"));
+        assert_eq!(setting.get_value("sawtooth.fee.limit"), Some("100"));
+        assert_eq!(setting.get_value("sawtooth.fee.other"), None);
+    }
+}
@@ -0,0 +1,233 @@
+// Copyright 2019 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Estimates the serialized sizes of transactions and batches, so that batches a REST API or
+//! validator would refuse are caught before they are submitted.
+//!
+//! The sizes are those of the protobuf messages sent, so a batch list's size is the size of the
+//! request body which submits it.
+
+use std::fmt;
+
+use sawtooth::protos::{IntoBytes, ProtoConversionError};
+use sawtooth::transact::protocol::{batch::Batch, transaction::Transaction};
+
+/// The largest request body accepted by the Sawtooth REST API by default (10 MiB)
+pub const DEFAULT_MAX_BATCH_LIST_BYTES: usize = 10 * 1024 * 1024;
+
+/// The most transactions put in a batch by default. Validators do not limit the transactions in
+/// a batch, so this is a client choice, which keeps a batch that fails cheap to retry.
+pub const DEFAULT_MAX_BATCH_TRANSACTIONS: usize = 100;
+
+/// The limits a submission must be within to be accepted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeLimits {
+    /// The most bytes in a submitted batch list
+    pub max_batch_list_bytes: usize,
+    /// The most transactions in a batch, as chosen by the client
+    pub max_batch_transactions: usize,
+}
+
+impl Default for SizeLimits {
+    fn default() -> Self {
+        SizeLimits {
+            max_batch_list_bytes: DEFAULT_MAX_BATCH_LIST_BYTES,
+            max_batch_transactions: DEFAULT_MAX_BATCH_TRANSACTIONS,
+        }
+    }
+}
+
+/// The serialized size of a transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionSize {
+    pub id: String,
+    pub bytes: usize,
+}
+
+/// The serialized size of a batch, and of each of its transactions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchSize {
+    pub id: String,
+    pub bytes: usize,
+    pub transactions: Vec<TransactionSize>,
+}
+
+/// The serialized size of a batch list, and of each of its batches
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchListSize {
+    pub bytes: usize,
+    pub batches: Vec<BatchSize>,
+}
+
+/// A limit a batch list exceeds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SizeViolation {
+    BatchListBytes {
+        bytes: usize,
+        limit: usize,
+    },
+    BatchTransactions {
+        id: String,
+        count: usize,
+        limit: usize,
+    },
+}
+
+impl fmt::Display for SizeViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SizeViolation::BatchListBytes { bytes, limit } => write!(
+                f,
+                "batch list is {} bytes, which exceeds the limit of {} bytes",
+                bytes, limit
+            ),
+            SizeViolation::BatchTransactions { id, count, limit } => write!(
+                f,
+                "batch {} has {} transactions, which exceeds the limit of {}",
+                id, count, limit
+            ),
+        }
+    }
+}
+
+/// Returns the serialized size of the transaction.
+pub fn estimate_transaction(
+    transaction: &Transaction,
+) -> Result<TransactionSize, ProtoConversionError> {
+    Ok(TransactionSize {
+        id: transaction.header_signature().to_string(),
+        bytes: transaction.clone().into_bytes()?.len(),
+    })
+}
+
+/// Returns the serialized size of the batch and of its transactions.
+pub fn estimate_batch(batch: &Batch) -> Result<BatchSize, ProtoConversionError> {
+    Ok(BatchSize {
+        id: batch.header_signature().to_string(),
+        bytes: batch.clone().into_bytes()?.len(),
+        transactions: batch
+            .transactions()
+            .iter()
+            .map(estimate_transaction)
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+/// Returns the serialized size of the batch list which submits the batches, and of each batch.
+pub fn estimate_batch_list(batches: &[Batch]) -> Result<BatchListSize, ProtoConversionError> {
+    Ok(BatchListSize {
+        bytes: batches.to_vec().into_bytes()?.len(),
+        batches: batches
+            .iter()
+            .map(estimate_batch)
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+impl BatchListSize {
+    /// Returns each limit the batch list exceeds.
+    pub fn check(&self, limits: &SizeLimits) -> Vec<SizeViolation> {
+        let mut violations = Vec::new();
+        if self.bytes > limits.max_batch_list_bytes {
+            violations.push(SizeViolation::BatchListBytes {
+                bytes: self.bytes,
+                limit: limits.max_batch_list_bytes,
+            });
+        }
+        for batch in &self.batches {
+            if batch.transactions.len() > limits.max_batch_transactions {
+                violations.push(SizeViolation::BatchTransactions {
+                    id: batch.id.clone(),
+                    count: batch.transactions.len(),
+                    limit: limits.max_batch_transactions,
+                });
+            }
+        }
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cylinder::{secp256k1::Secp256k1Context, Context};
+    use sawtooth::transact::protocol::transaction::HashMethod;
+    use sawtooth::transact::protocol::{batch::BatchBuilder, transaction::TransactionBuilder};
+
+    fn batch(transactions: usize, payload_size: usize) -> Batch {
+        let context = Secp256k1Context::new();
+        let signer = context.new_signer(context.new_random_private_key());
+        let transactions = (0..transactions)
+            .map(|i| {
+                TransactionBuilder::new()
+                    .with_family_name("sabre".into())
+                    .with_family_version("1".into())
+                    .with_inputs(vec![])
+                    .with_outputs(vec![])
+                    .with_nonce(i.to_string().into_bytes())
+                    .with_payload_hash_method(HashMethod::Sha512)
+                    .with_payload(vec![0; payload_size])
+                    .build(&*signer)
+                    .unwrap()
+            })
+            .collect();
+
+        BatchBuilder::new()
+            .with_transactions(transactions)
+            .build(&*signer)
+            .unwrap()
+    }
+
+    #[test]
+    // check that a batch list is at least as large as its batches, which are at least as large
+    // as their transactions, and that exceeding either limit is reported
+    fn check_estimate_batch_list() {
+        let batches = vec![batch(3, 1024), batch(1, 16)];
+        let size = estimate_batch_list(&batches).unwrap();
+
+        assert_eq!(size.batches.len(), 2);
+        assert_eq!(size.batches[0].transactions.len(), 3);
+        assert!(size.batches[0].transactions[0].bytes > 1024);
+        assert!(
+            size.batches[0].bytes
+                > size.batches[0]
+                    .transactions
+                    .iter()
+                    .map(|transaction| transaction.bytes)
+                    .sum::<usize>()
+        );
+        assert!(size.bytes >= size.batches.iter().map(|batch| batch.bytes).sum::<usize>());
+        assert!(size.check(&SizeLimits::default()).is_empty());
+
+        let limits = SizeLimits {
+            max_batch_list_bytes: 1024,
+            max_batch_transactions: 2,
+        };
+        assert_eq!(
+            size.check(&limits),
+            vec![
+                SizeViolation::BatchListBytes {
+                    bytes: size.bytes,
+                    limit: 1024
+                },
+                SizeViolation::BatchTransactions {
+                    id: batches[0].header_signature().to_string(),
+                    count: 3,
+                    limit: 2
+                },
+            ]
+        );
+    }
+}
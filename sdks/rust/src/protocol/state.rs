@@ -904,14 +904,15 @@ impl Contract {
 }
 
 impl FromProto<protos::contract::Contract> for Contract {
-    fn from_proto(proto: protos::contract::Contract) -> Result<Self, ProtoConversionError> {
+    fn from_proto(mut proto: protos::contract::Contract) -> Result<Self, ProtoConversionError> {
+        // The fields are taken rather than copied, as the contract may be megabytes of wasm
         Ok(Contract {
-            name: proto.get_name().to_string(),
-            version: proto.get_version().to_string(),
-            inputs: proto.get_inputs().to_vec(),
-            outputs: proto.get_outputs().to_vec(),
-            creator: proto.get_creator().to_string(),
-            contract: proto.get_contract().to_vec(),
+            name: proto.take_name(),
+            version: proto.take_version(),
+            inputs: proto.take_inputs().into_vec(),
+            outputs: proto.take_outputs().into_vec(),
+            creator: proto.take_creator(),
+            contract: proto.take_contract(),
         })
     }
 }
@@ -1091,12 +1092,11 @@ impl ContractList {
 }
 
 impl FromProto<protos::contract::ContractList> for ContractList {
-    fn from_proto(proto: protos::contract::ContractList) -> Result<Self, ProtoConversionError> {
+    fn from_proto(mut proto: protos::contract::ContractList) -> Result<Self, ProtoConversionError> {
         Ok(ContractList {
             contracts: proto
-                .get_contracts()
-                .iter()
-                .cloned()
+                .take_contracts()
+                .into_iter()
                 .map(Contract::from_proto)
                 .collect::<Result<Vec<Contract>, ProtoConversionError>>()?,
         })
@@ -0,0 +1,83 @@
+// Copyright 2019 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Versions of the layout of the data a contract stores in state.
+//!
+//! A versioned entry is prefixed with `MARKER`, followed by its schema version as a four byte
+//! big-endian number, and then the entry's data. Data without the marker was stored before the
+//! contract versioned its entries, and is read as version 0.
+
+use std::collections::HashMap;
+
+use crate::WasmSdkError;
+
+/// The prefix of a versioned entry
+pub const MARKER: &[u8] = b"\0sbs1";
+
+/// Upgrades the data of entries stored with an older schema version to the layout of the
+/// current version, as the entries are read.
+pub trait Migration {
+    /// The schema version entries are written with
+    fn current_version(&self) -> u32;
+
+    /// Returns the entries, keyed by address, which were stored with `from_version`, with their
+    /// data in the layout of the current version. Entries left out of the result are read as
+    /// unset.
+    fn migrate(
+        &self,
+        from_version: u32,
+        entries: HashMap<String, Vec<u8>>,
+    ) -> Result<HashMap<String, Vec<u8>>, WasmSdkError>;
+}
+
+/// Returns the data prefixed with the schema version.
+pub fn wrap(version: u32, data: Vec<u8>) -> Vec<u8> {
+    let mut wrapped = MARKER.to_vec();
+    wrapped.extend(version.to_be_bytes().iter());
+    wrapped.extend(data);
+    wrapped
+}
+
+/// Splits the schema version from the data if it begins with the marker, and otherwise returns
+/// the data as it is, as version 0.
+pub fn unwrap(data: Vec<u8>) -> Result<(u32, Vec<u8>), WasmSdkError> {
+    if !data.starts_with(MARKER) {
+        return Ok((0, data));
+    }
+
+    let version = data
+        .get(MARKER.len()..MARKER.len() + 4)
+        .ok_or_else(|| WasmSdkError::InvalidTransaction("schema version is truncated".into()))?;
+    let version = u32::from_be_bytes([version[0], version[1], version[2], version[3]]);
+
+    Ok((version, data[MARKER.len() + 4..].to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // check that the version is split from the data it was wrapped around, that data without
+    // the marker is version 0, and that a truncated version is an error
+    fn check_schema_version() {
+        let wrapped = wrap(3, b"data".to_vec());
+        assert!(wrapped.starts_with(MARKER));
+        assert_eq!(unwrap(wrapped.clone()).unwrap(), (3, b"data".to_vec()));
+
+        assert_eq!(unwrap(b"data".to_vec()).unwrap(), (0, b"data".to_vec()));
+
+        assert!(unwrap(wrapped[..MARKER.len() + 2].to_vec()).is_err());
+    }
+}
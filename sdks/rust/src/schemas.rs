@@ -0,0 +1,103 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The Sabre protobuf schemas, for generating clients in other languages.
+
+use protobuf::descriptor::{FileDescriptorProto, FileDescriptorSet};
+use protobuf::{Message, RepeatedField};
+
+use crate::protos;
+use crate::protos::ProtoConversionError;
+
+/// The name and contents of each Sabre .proto file
+pub const PROTO_FILES: &[(&str, &str)] = &[
+    (
+        "block_info.proto",
+        include_str!("../protos/block_info.proto"),
+    ),
+    ("contract.proto", include_str!("../protos/contract.proto")),
+    (
+        "contract_registry.proto",
+        include_str!("../protos/contract_registry.proto"),
+    ),
+    (
+        "namespace_registry.proto",
+        include_str!("../protos/namespace_registry.proto"),
+    ),
+    ("payload.proto", include_str!("../protos/payload.proto")),
+    ("setting.proto", include_str!("../protos/setting.proto")),
+];
+
+/// Returns the contents of the named .proto file, if it is one of the Sabre schemas.
+pub fn proto_file(name: &str) -> Option<&'static str> {
+    PROTO_FILES
+        .iter()
+        .find(|(file_name, _)| *file_name == name)
+        .map(|(_, contents)| *contents)
+}
+
+/// Returns the descriptors of the Sabre .proto files, as compiled into this crate.
+pub fn file_descriptors() -> Vec<&'static FileDescriptorProto> {
+    vec![
+        protos::block_info::file_descriptor_proto(),
+        protos::contract::file_descriptor_proto(),
+        protos::contract_registry::file_descriptor_proto(),
+        protos::namespace_registry::file_descriptor_proto(),
+        protos::payload::file_descriptor_proto(),
+        protos::setting::file_descriptor_proto(),
+    ]
+}
+
+/// Returns a serialized FileDescriptorSet containing the descriptor of every Sabre .proto file.
+pub fn file_descriptor_set() -> Result<Vec<u8>, ProtoConversionError> {
+    let mut descriptor_set = FileDescriptorSet::new();
+    descriptor_set.set_file(RepeatedField::from_vec(
+        file_descriptors().into_iter().cloned().collect(),
+    ));
+
+    descriptor_set.write_to_bytes().map_err(|_| {
+        ProtoConversionError::SerializationError(
+            "Unable to get bytes from FileDescriptorSet".to_string(),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // check that every .proto file has a compiled descriptor, and that the descriptor set can be
+    // parsed back
+    fn check_file_descriptor_set() {
+        assert!(proto_file("payload.proto")
+            .unwrap()
+            .contains("message SabrePayload"));
+        assert_eq!(proto_file("other.proto"), None);
+
+        let bytes = file_descriptor_set().unwrap();
+        let descriptor_set: FileDescriptorSet = Message::parse_from_bytes(&bytes).unwrap();
+
+        let mut names: Vec<&str> = descriptor_set
+            .get_file()
+            .iter()
+            .map(FileDescriptorProto::get_name)
+            .collect();
+        names.sort();
+        let mut expected: Vec<&str> = PROTO_FILES.iter().map(|(name, _)| *name).collect();
+        expected.sort();
+
+        assert_eq!(names, expected);
+    }
+}
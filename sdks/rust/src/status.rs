@@ -0,0 +1,178 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Classifies the errors returned by the Sabre transaction processor, so services relaying them
+//! can choose an HTTP status or gRPC code.
+//!
+//! The messages of invalid transactions, as reported in batch statuses, are matched against the
+//! wording used by the Sabre transaction handler. Messages which match nothing are treated as
+//! invalid arguments.
+
+use crate::ApplyError;
+
+/// A stable category of Sabre error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCategory {
+    /// The signer or contract may not perform the action
+    PermissionDenied,
+    /// A contract, registry or other entry the action refers to does not exist
+    NotFound,
+    /// The transaction is invalid for any other reason
+    InvalidArgument,
+    /// The transaction processor failed, rather than the transaction
+    Internal,
+}
+
+/// Phrases identifying permission errors, in lowercase
+const PERMISSION_DENIED_PHRASES: [&str; 7] = [
+    "does not have permission",
+    "not an owner",
+    "only owners",
+    "not an admin",
+    "only admins",
+    "delegation signature is not valid",
+    "delegation was issued to relayer",
+];
+
+/// Phrases identifying missing entries, in lowercase
+const NOT_FOUND_PHRASES: [&str; 2] = ["does not exist", "not found"];
+
+impl ErrorCategory {
+    /// Classifies the message of an invalid transaction.
+    pub fn of_invalid_transaction(message: &str) -> ErrorCategory {
+        let message = message.to_lowercase();
+        if PERMISSION_DENIED_PHRASES
+            .iter()
+            .any(|phrase| message.contains(phrase))
+        {
+            ErrorCategory::PermissionDenied
+        } else if NOT_FOUND_PHRASES
+            .iter()
+            .any(|phrase| message.contains(phrase))
+        {
+            ErrorCategory::NotFound
+        } else {
+            ErrorCategory::InvalidArgument
+        }
+    }
+
+    pub fn http_status(&self) -> u16 {
+        match self {
+            ErrorCategory::PermissionDenied => 403,
+            ErrorCategory::NotFound => 404,
+            ErrorCategory::InvalidArgument => 400,
+            ErrorCategory::Internal => 500,
+        }
+    }
+
+    pub fn grpc_code(&self) -> i32 {
+        match self {
+            ErrorCategory::PermissionDenied => 7,
+            ErrorCategory::NotFound => 5,
+            ErrorCategory::InvalidArgument => 3,
+            ErrorCategory::Internal => 13,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorCategory::PermissionDenied => "permission-denied",
+            ErrorCategory::NotFound => "not-found",
+            ErrorCategory::InvalidArgument => "invalid-argument",
+            ErrorCategory::Internal => "internal",
+        }
+    }
+}
+
+impl From<&ApplyError> for ErrorCategory {
+    fn from(err: &ApplyError) -> Self {
+        match err {
+            ApplyError::InvalidTransaction(msg) => ErrorCategory::of_invalid_transaction(msg),
+            ApplyError::InternalError(_) => ErrorCategory::Internal,
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // check that messages reported by the Sabre transaction processor are classified
+    fn check_of_invalid_transaction() {
+        let cases = [
+            (
+                "Contract does not exist: intkey_multiply, 1.0",
+                ErrorCategory::NotFound,
+            ),
+            (
+                "The Contract Registry does not exist: intkey_multiply",
+                ErrorCategory::NotFound,
+            ),
+            (
+                "Namespace Registry does not exist 1cf126",
+                ErrorCategory::NotFound,
+            ),
+            (
+                "Contract does not have permission to write to state : 1cf126",
+                ErrorCategory::PermissionDenied,
+            ),
+            (
+                "Delegation signature is not valid for signer 02abcd",
+                ErrorCategory::PermissionDenied,
+            ),
+            (
+                "Delegation was issued to relayer 02abcd, not transaction signer 03ef01",
+                ErrorCategory::PermissionDenied,
+            ),
+            (
+                "Wasm contract returned invalid transaction: intkey_multiply, 1.0",
+                ErrorCategory::InvalidArgument,
+            ),
+            (
+                "Contract emitted 12 events, more than the 10 allowed by \
+                 sabre.events.max_per_transaction",
+                ErrorCategory::InvalidArgument,
+            ),
+        ];
+
+        for (message, category) in cases.iter() {
+            assert_eq!(
+                ErrorCategory::of_invalid_transaction(message),
+                *category,
+                "{}",
+                message
+            );
+        }
+    }
+
+    #[test]
+    // check that internal errors are never blamed on the transaction, and the status mappings
+    fn check_apply_error_category() {
+        let err = ApplyError::InternalError("Contract does not exist".into());
+        assert_eq!(ErrorCategory::from(&err), ErrorCategory::Internal);
+        assert_eq!(ErrorCategory::from(&err).http_status(), 500);
+
+        let err = ApplyError::InvalidTransaction("Namespace Registry does not exist".into());
+        assert_eq!(ErrorCategory::from(&err).http_status(), 404);
+        assert_eq!(ErrorCategory::from(&err).grpc_code(), 5);
+        assert_eq!(ErrorCategory::from(&err).to_string(), "not-found");
+    }
+}
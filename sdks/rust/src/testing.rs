@@ -0,0 +1,308 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers for testing a contract's `apply` natively, outside of the Sabre transaction processor.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use crate::adapter::{apply_handler, contract_namespaces, NamespaceContext};
+use crate::{
+    ApplyError, Header, TpProcessRequest, TransactionContext, TransactionHandler, WasmSdkError,
+};
+
+/// Builds the request a contract's `apply` receives.
+///
+/// ```
+/// use sabre_sdk::testing::TpProcessRequestBuilder;
+///
+/// let mut request = TpProcessRequestBuilder::new()
+///     .with_payload(b"inc a 1".to_vec())
+///     .with_signer_public_key("signer".into())
+///     .build();
+///
+/// assert_eq!(request.as_request().get_payload(), b"inc a 1");
+/// ```
+#[derive(Default, Clone)]
+pub struct TpProcessRequestBuilder {
+    payload: Vec<u8>,
+    signer_public_key: String,
+    transaction_signer_public_key: Option<String>,
+    signature: String,
+}
+
+impl TpProcessRequestBuilder {
+    pub fn new() -> Self {
+        TpProcessRequestBuilder::default()
+    }
+
+    pub fn with_payload(mut self, payload: Vec<u8>) -> TpProcessRequestBuilder {
+        self.payload = payload;
+        self
+    }
+
+    /// Sets the public key of the signer the contract is executed for.
+    pub fn with_signer_public_key(mut self, signer_public_key: String) -> TpProcessRequestBuilder {
+        self.signer_public_key = signer_public_key;
+        self
+    }
+
+    /// Sets the public key that signed the transaction on behalf of the signer, as though the
+    /// transaction carried a verified delegation.
+    pub fn with_transaction_signer_public_key(
+        mut self,
+        transaction_signer_public_key: String,
+    ) -> TpProcessRequestBuilder {
+        self.transaction_signer_public_key = Some(transaction_signer_public_key);
+        self
+    }
+
+    pub fn with_signature(mut self, signature: String) -> TpProcessRequestBuilder {
+        self.signature = signature;
+        self
+    }
+
+    pub fn build(self) -> TestRequest {
+        let header = match self.transaction_signer_public_key {
+            Some(transaction_signer) => {
+                Header::new_delegated(transaction_signer, self.signer_public_key)
+            }
+            None => Header::new(self.signer_public_key),
+        };
+
+        TestRequest {
+            payload: self.payload,
+            header,
+            signature: self.signature,
+        }
+    }
+}
+
+/// Owns the header a `TpProcessRequest` borrows, so a request can be created for each call to
+/// `apply`.
+pub struct TestRequest {
+    payload: Vec<u8>,
+    header: Header,
+    signature: String,
+}
+
+impl TestRequest {
+    pub fn as_request(&mut self) -> TpProcessRequest {
+        TpProcessRequest::new(
+            self.payload.clone(),
+            &mut self.header,
+            self.signature.clone(),
+        )
+    }
+}
+
+/// An event added by a transaction applied by a `PortingHarness`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestEvent {
+    pub event_type: String,
+    pub attributes: Vec<(String, String)>,
+    pub data: Vec<u8>,
+}
+
+/// Holds the state and events of the transactions applied by a `PortingHarness`
+#[derive(Default)]
+struct MemoryContext {
+    state: RefCell<BTreeMap<String, Vec<u8>>>,
+    events: RefCell<Vec<TestEvent>>,
+}
+
+impl TransactionContext for MemoryContext {
+    fn get_state_entries(
+        &self,
+        addresses: &[String],
+    ) -> Result<Vec<(String, Vec<u8>)>, WasmSdkError> {
+        let state = self.state.borrow();
+        Ok(addresses
+            .iter()
+            .filter_map(|address| {
+                state
+                    .get(address)
+                    .map(|data| (address.clone(), data.clone()))
+            })
+            .collect())
+    }
+
+    fn set_state_entries(&self, entries: Vec<(String, Vec<u8>)>) -> Result<(), WasmSdkError> {
+        self.state.borrow_mut().extend(entries);
+        Ok(())
+    }
+
+    fn delete_state_entries(&self, addresses: &[String]) -> Result<Vec<String>, WasmSdkError> {
+        let mut state = self.state.borrow_mut();
+        Ok(addresses
+            .iter()
+            .filter(|address| state.remove(*address).is_some())
+            .cloned()
+            .collect())
+    }
+
+    fn add_event(
+        &self,
+        event_type: String,
+        attributes: Vec<(String, String)>,
+        data: &[u8],
+    ) -> Result<(), WasmSdkError> {
+        self.events.borrow_mut().push(TestEvent {
+            event_type,
+            attributes,
+            data: data.to_vec(),
+        });
+        Ok(())
+    }
+}
+
+/// Applies transactions with a handler ported from the Sawtooth SDK as Sabre would, against
+/// state held in memory, so that its behavior can be compared with the native processor's.
+///
+/// Like Sabre, the harness refuses state access outside the handler's namespaces. The changes
+/// of a transaction which fails are discarded, as the validator discards them.
+pub struct PortingHarness<H: TransactionHandler> {
+    handler: H,
+    namespaces: Vec<String>,
+    context: MemoryContext,
+}
+
+impl<H: TransactionHandler> PortingHarness<H> {
+    /// Creates a harness for the handler, checking that its namespaces can be registered.
+    pub fn new(handler: H) -> Result<Self, ApplyError> {
+        let namespaces = contract_namespaces(&handler)?;
+        Ok(PortingHarness {
+            handler,
+            namespaces,
+            context: MemoryContext::default(),
+        })
+    }
+
+    /// Sets a state entry before transactions are applied.
+    pub fn with_state_entry(self, address: String, data: Vec<u8>) -> Self {
+        self.context.state.borrow_mut().insert(address, data);
+        self
+    }
+
+    /// Applies a transaction, returning the contract's result.
+    pub fn apply(&self, request: &mut TestRequest) -> Result<bool, ApplyError> {
+        let state = self.context.state.borrow().clone();
+        let events = self.context.events.borrow().len();
+
+        let mut context = NamespaceContext::new(&self.context, self.namespaces.clone());
+        let result = apply_handler(&self.handler, &request.as_request(), &mut context);
+        if result.is_err() {
+            *self.context.state.borrow_mut() = state;
+            self.context.events.borrow_mut().truncate(events);
+        }
+
+        result
+    }
+
+    /// Returns the state after the transactions applied so far.
+    pub fn state(&self) -> BTreeMap<String, Vec<u8>> {
+        self.context.state.borrow().clone()
+    }
+
+    /// Returns the events added by the transactions applied so far.
+    pub fn events(&self) -> Vec<TestEvent> {
+        self.context.events.borrow().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // check that the built request carries the payload, signature and signers
+    fn check_tp_process_request_builder() {
+        let mut test_request = TpProcessRequestBuilder::new()
+            .with_payload(vec![1, 2, 3])
+            .with_signer_public_key("signer".into())
+            .with_transaction_signer_public_key("relayer".into())
+            .with_signature("signature".into())
+            .build();
+        let request = test_request.as_request();
+
+        assert_eq!(request.get_payload(), &[1, 2, 3]);
+        assert_eq!(request.get_signature(), "signature");
+        assert_eq!(request.get_header().get_signer_public_key(), "signer");
+        assert_eq!(
+            request.get_header().get_transaction_signer_public_key(),
+            "relayer"
+        );
+        assert_eq!(
+            request.get_header().get_delegated_signer_public_key(),
+            Some("signer")
+        );
+    }
+
+    /// Sets the payload, of the form "address=value", in state and adds an event
+    struct SetHandler;
+
+    impl TransactionHandler for SetHandler {
+        fn family_name(&self) -> String {
+            "set".into()
+        }
+
+        fn family_versions(&self) -> Vec<String> {
+            vec!["1.0".into()]
+        }
+
+        fn namespaces(&self) -> Vec<String> {
+            vec!["abcdef".into()]
+        }
+
+        fn apply(
+            &self,
+            request: &TpProcessRequest,
+            context: &mut dyn TransactionContext,
+        ) -> Result<(), ApplyError> {
+            let payload = String::from_utf8_lossy(request.get_payload()).to_string();
+            let (address, value) = payload
+                .split_once('=')
+                .ok_or_else(|| ApplyError::InvalidTransaction("no value".into()))?;
+            context.add_event("set".into(), vec![], value.as_bytes())?;
+            context.set_state_entry(address.into(), value.as_bytes().to_vec())?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    // check that the porting harness applies transactions to its state, and discards the changes
+    // of transactions which write outside the handler's namespaces
+    fn check_porting_harness() {
+        let harness = PortingHarness::new(SetHandler)
+            .unwrap()
+            .with_state_entry("abcdef01".into(), b"old".to_vec());
+
+        let mut request = TpProcessRequestBuilder::new()
+            .with_payload(b"abcdef01=new".to_vec())
+            .build();
+        assert!(harness.apply(&mut request).unwrap());
+        assert_eq!(harness.state()["abcdef01"], b"new".to_vec());
+        assert_eq!(harness.events().len(), 1);
+
+        let mut request = TpProcessRequestBuilder::new()
+            .with_payload(b"00000001=new".to_vec())
+            .build();
+        assert!(matches!(
+            harness.apply(&mut request),
+            Err(ApplyError::InvalidTransaction(_))
+        ));
+        assert!(!harness.state().contains_key("00000001"));
+        assert_eq!(harness.events().len(), 1);
+    }
+}
@@ -0,0 +1,170 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Deterministic time for contracts.
+//!
+//! A contract must not read a clock, as each node executing a transaction would see a different
+//! time. The timestamp of the most recent block recorded by the Sawtooth BlockInfo transaction
+//! family is the same on every node, so it is read from state instead.
+//!
+//! Reading it requires the network to run the BlockInfo transaction processor with block info
+//! injection enabled, and the contract to have read permission on the 00b10c namespace and to
+//! list `BLOCK_INFO_CONFIG_ADDRESS` and the block info addresses as inputs.
+
+use std::fmt;
+
+use crate::protocol::block_info::{
+    compute_block_info_address, BlockInfo, BlockInfoConfig, BLOCK_INFO_CONFIG_ADDRESS,
+};
+use crate::protos::FromBytes;
+use crate::{TransactionContext, WasmSdkError};
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// A time in whole seconds since the Unix epoch, in UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp {
+    seconds: u64,
+}
+
+impl Timestamp {
+    pub fn from_secs(seconds: u64) -> Self {
+        Timestamp { seconds }
+    }
+
+    pub fn as_secs(&self) -> u64 {
+        self.seconds
+    }
+
+    /// Returns the calendar date and time of the timestamp.
+    pub fn date_time(&self) -> DateTime {
+        let days = self.seconds / SECONDS_PER_DAY;
+        let seconds_of_day = self.seconds % SECONDS_PER_DAY;
+
+        // Converts days since the epoch to a proleptic Gregorian date, counting years from
+        // March so that the leap day is the last of the year
+        let days = days + 719_468;
+        let era = days / 146_097;
+        let day_of_era = days - era * 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_from_march = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+        let month = if month_from_march < 10 {
+            month_from_march + 3
+        } else {
+            month_from_march - 9
+        };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        DateTime {
+            year,
+            month: month as u8,
+            day: day as u8,
+            hour: (seconds_of_day / 3600) as u8,
+            minute: (seconds_of_day % 3600 / 60) as u8,
+            second: (seconds_of_day % 60) as u8,
+        }
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.date_time())
+    }
+}
+
+/// A calendar date and time in UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u64,
+    /// 1 to 12
+    pub month: u8,
+    /// 1 to 31
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl fmt::Display for DateTime {
+    /// Formats the date and time as ISO 8601, such as 2018-06-01T12:00:00Z.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// Returns the timestamp of the most recent block recorded by the BlockInfo transaction family.
+///
+/// Block info is injected at the start of each block for the block before it, so this is the
+/// time the previous block was committed. An error is returned if the network does not record
+/// block info.
+pub fn block_timestamp(context: &dyn TransactionContext) -> Result<Timestamp, WasmSdkError> {
+    let unavailable = || {
+        WasmSdkError::InvalidTransaction(
+            "block info is not available; the network must run the BlockInfo transaction \
+             processor with block info injection enabled"
+                .into(),
+        )
+    };
+
+    let config_bytes = context
+        .get_state_entry(BLOCK_INFO_CONFIG_ADDRESS)?
+        .ok_or_else(unavailable)?;
+    let config = BlockInfoConfig::from_bytes(&config_bytes)
+        .map_err(|err| WasmSdkError::InvalidTransaction(err.to_string()))?;
+
+    let block_info_bytes = context
+        .get_state_entry(&compute_block_info_address(config.latest_block()))?
+        .ok_or_else(unavailable)?;
+    let block_info = BlockInfo::from_bytes(&block_info_bytes)
+        .map_err(|err| WasmSdkError::InvalidTransaction(err.to_string()))?;
+
+    Ok(Timestamp::from_secs(block_info.timestamp()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // check that timestamps are converted to the calendar date and time, including leap days
+    fn check_timestamp_date_time() {
+        assert_eq!(Timestamp::from_secs(0).to_string(), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            Timestamp::from_secs(951_782_400).to_string(),
+            "2000-02-29T00:00:00Z"
+        );
+        assert_eq!(
+            Timestamp::from_secs(1_700_000_000).to_string(),
+            "2023-11-14T22:13:20Z"
+        );
+        assert_eq!(
+            Timestamp::from_secs(4_107_542_399).date_time(),
+            DateTime {
+                year: 2100,
+                month: 2,
+                day: 28,
+                hour: 23,
+                minute: 59,
+                second: 59,
+            }
+        );
+    }
+}
@@ -0,0 +1,201 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! W3C trace context `traceparent` values, which link the processing of a transaction to the
+//! client that submitted it.
+//!
+//! The sabre CLI sets the nonce of each transaction it builds to a traceparent, and the Sabre
+//! transaction processor logs the trace id and attaches the traceparent to the transaction's
+//! events.
+
+use std::error::Error as StdError;
+use std::fmt;
+
+/// The name of the HTTP header and event attribute carrying a traceparent
+pub const TRACEPARENT: &str = "traceparent";
+
+/// The only traceparent version produced
+const VERSION: &str = "00";
+
+/// The flag marking a trace as sampled
+const SAMPLED_FLAG: u8 = 0x01;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct TraceParentError(String);
+
+impl StdError for TraceParentError {}
+
+impl fmt::Display for TraceParentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid traceparent: {}", self.0)
+    }
+}
+
+/// A trace id and the id of the span within it a request was made from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceParent {
+    trace_id: String,
+    parent_id: String,
+    flags: u8,
+}
+
+impl TraceParent {
+    /// Creates a sampled traceparent. The trace id must be 32 and the parent id 16 lowercase hex
+    /// characters, and neither may be all zeros.
+    pub fn new(trace_id: &str, parent_id: &str) -> Result<TraceParent, TraceParentError> {
+        check_id("trace id", trace_id, 32)?;
+        check_id("parent id", parent_id, 16)?;
+
+        Ok(TraceParent {
+            trace_id: trace_id.to_string(),
+            parent_id: parent_id.to_string(),
+            flags: SAMPLED_FLAG,
+        })
+    }
+
+    /// Parses a traceparent header value, such as
+    /// `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`.
+    pub fn parse(value: &str) -> Result<TraceParent, TraceParentError> {
+        let fields: Vec<&str> = value.trim().split('-').collect();
+        let (version, trace_id, parent_id, flags) = match fields.as_slice() {
+            [version, trace_id, parent_id, flags] => (*version, *trace_id, *parent_id, *flags),
+            // Later versions may append fields
+            [version, trace_id, parent_id, flags, ..] if *version != VERSION => {
+                (*version, *trace_id, *parent_id, *flags)
+            }
+            _ => {
+                return Err(TraceParentError(
+                    "expected four '-' separated fields".into(),
+                ))
+            }
+        };
+
+        if version.len() != 2 || !is_lower_hex(version) || version == "ff" {
+            return Err(TraceParentError(format!(
+                "unsupported version '{}'",
+                version
+            )));
+        }
+        if flags.len() != 2 || !is_lower_hex(flags) {
+            return Err(TraceParentError(format!("invalid flags '{}'", flags)));
+        }
+
+        let mut trace_parent = TraceParent::new(trace_id, parent_id)?;
+        trace_parent.flags = u8::from_str_radix(flags, 16)
+            .map_err(|_| TraceParentError(format!("invalid flags '{}'", flags)))?;
+
+        Ok(trace_parent)
+    }
+
+    pub fn trace_id(&self) -> &str {
+        &self.trace_id
+    }
+
+    pub fn parent_id(&self) -> &str {
+        &self.parent_id
+    }
+
+    pub fn is_sampled(&self) -> bool {
+        self.flags & SAMPLED_FLAG != 0
+    }
+
+    /// Returns a traceparent in the same trace, from the given span.
+    pub fn with_parent_id(&self, parent_id: &str) -> Result<TraceParent, TraceParentError> {
+        check_id("parent id", parent_id, 16)?;
+
+        Ok(TraceParent {
+            parent_id: parent_id.to_string(),
+            ..self.clone()
+        })
+    }
+}
+
+impl fmt::Display for TraceParent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}-{}-{:02x}",
+            VERSION, self.trace_id, self.parent_id, self.flags
+        )
+    }
+}
+
+fn check_id(name: &str, id: &str, length: usize) -> Result<(), TraceParentError> {
+    if id.len() != length || !is_lower_hex(id) {
+        return Err(TraceParentError(format!(
+            "{} '{}' is not {} lowercase hex characters",
+            name, id, length
+        )));
+    }
+    if id.bytes().all(|b| b == b'0') {
+        return Err(TraceParentError(format!("{} may not be all zeros", name)));
+    }
+
+    Ok(())
+}
+
+fn is_lower_hex(value: &str) -> bool {
+    value
+        .bytes()
+        .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const PARENT_ID: &str = "00f067aa0ba902b7";
+
+    #[test]
+    // check that a traceparent is parsed and formatted back to the same value
+    fn check_traceparent_round_trip() {
+        let value = format!("00-{}-{}-01", TRACE_ID, PARENT_ID);
+        let trace_parent = TraceParent::parse(&value).unwrap();
+
+        assert_eq!(trace_parent.trace_id(), TRACE_ID);
+        assert_eq!(trace_parent.parent_id(), PARENT_ID);
+        assert!(trace_parent.is_sampled());
+        assert_eq!(trace_parent.to_string(), value);
+
+        let child = trace_parent.with_parent_id("b7ad6b7169203331").unwrap();
+        assert_eq!(child.trace_id(), TRACE_ID);
+        assert_eq!(
+            child.to_string(),
+            format!("00-{}-b7ad6b7169203331-01", TRACE_ID)
+        );
+    }
+
+    #[test]
+    // check that malformed traceparents are rejected
+    fn check_traceparent_invalid() {
+        let invalid = [
+            "",
+            "nonce",
+            &format!("00-{}-{}", TRACE_ID, PARENT_ID),
+            &format!("ff-{}-{}-01", TRACE_ID, PARENT_ID),
+            &format!("00-{}-{}-01-extra", TRACE_ID, PARENT_ID),
+            &format!("00-{}-{}-01", TRACE_ID.to_uppercase(), PARENT_ID),
+            &format!("00-{}-{}-01", "0".repeat(32), PARENT_ID),
+            &format!("00-{}-{}-01", TRACE_ID, "0".repeat(16)),
+            &format!("00-{}-{}-1", TRACE_ID, PARENT_ID),
+        ];
+        for value in invalid.iter() {
+            assert!(TraceParent::parse(value).is_err(), "{}", value);
+        }
+
+        // Later versions may carry more fields
+        assert!(TraceParent::parse(&format!("01-{}-{}-01-extra", TRACE_ID, PARENT_ID)).is_ok());
+    }
+}
@@ -14,9 +14,28 @@
 
 //! Provides a Sawtooth Transaction Handler for executing Sabre transactions.
 
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use cylinder::{secp256k1::Secp256k1Context, Context};
 use protobuf::Message;
+use sabre_sdk::protocol::borrowed::ContractListRef;
+use sabre_sdk::protocol::namespaces::SabreNamespace;
+use sabre_sdk::protocol::payload::{
+    join_relayed_signature, Action, Delegation, SabrePayload, SabrePayloadBuilder,
+};
+use sabre_sdk::protocol::settings::Setting;
+use sabre_sdk::protocol::{
+    compute_contract_address, compute_delegation_address, compute_setting_address,
+    AGGREGATE_EVENTS_SETTING_KEY, CONTRACT_ADDRESS_PREFIX, MAX_EVENTS_SETTING_KEY,
+    WRITE_STATS_SETTING_KEY,
+};
+use sabre_sdk::protos::{FromBytes, IntoBytes};
+use sabre_sdk::trace::{TraceParent, TRACEPARENT};
 use sawtooth_sdk::messages::processor::TpProcessRequest;
 use sawtooth_sdk::processor::handler::ApplyError;
+use sawtooth_sdk::processor::handler::ContextError as SdkContextError;
 use sawtooth_sdk::processor::handler::TransactionContext;
 use sawtooth_sdk::processor::handler::TransactionHandler;
 
@@ -25,26 +44,68 @@ use sawtooth::protos::transaction::TransactionHeader;
 use sawtooth::transact::handler::ContextError;
 use sawtooth::transact::handler::TransactionHandler as TransactHandler;
 use sawtooth::transact::protocol::transaction::Transaction;
+use sha2::{Digest, Sha512};
+
+use crate::health::{ApplyOutcome, Health};
+use crate::host_trace::{HostCall, HostCallTrace};
+#[cfg(feature = "otel")]
+use crate::telemetry;
+
+/// The type of the event emitted in place of a transaction's events when they are aggregated
+const EVENTS_DIGEST_EVENT_TYPE: &str = "sabre/events_digest";
+
+/// The type of the event carrying a transaction's writes per namespace
+const WRITE_STATS_EVENT_TYPE: &str = "sabre/namespace_writes";
 
-/// The namespace registry prefix for global state (00ec00)
-const NAMESPACE_REGISTRY_PREFIX: &str = "00ec00";
+/// The length of the namespace prefixes writes are counted under
+const WRITE_STATS_PREFIX_LENGTH: usize = 6;
 
-/// The contract registry prefix for global state (00ec01)
-const CONTRACT_REGISTRY_PREFIX: &str = "00ec01";
+/// The largest size a compressed execute contract payload may decompress to (64 MiB)
+const MAX_DECOMPRESSED_PAYLOAD_SIZE: usize = 64 * 1024 * 1024;
 
-/// The contract prefix for global state (00ec02)
-const CONTRACT_PREFIX: &str = "00ec02";
+struct Event {
+    event_type: String,
+    attributes: Vec<(String, String)>,
+    data: Vec<u8>,
+}
+
+/// The number of writes to a namespace, and the total bytes written
+#[derive(Default)]
+struct WriteStats {
+    writes: u64,
+    bytes: u64,
+}
 
+/// Events added by a contract are held until it has finished executing, so the event limit can
+/// be applied to all of them at once.
 struct SabreContext<'a> {
     sawtooth_context: &'a dyn TransactionContext,
+    transaction_id: &'a str,
+    events: RefCell<Vec<Event>>,
+    write_stats: RefCell<BTreeMap<String, WriteStats>>,
+    /// The host calls made by the transaction, if it is traced
+    host_calls: Option<RefCell<Vec<HostCall>>>,
+    /// The health the contract modules read are counted in
+    health: Option<&'a Health>,
+}
+
+impl<'a> SabreContext<'a> {
+    fn record(&self, function: &'static str, address: Option<&str>, bytes: usize, result: String) {
+        if let Some(host_calls) = &self.host_calls {
+            host_calls.borrow_mut().push(HostCall {
+                transaction_id: self.transaction_id.to_string(),
+                function,
+                address: address.map(String::from),
+                bytes,
+                result,
+            });
+        }
+    }
 }
 
 impl<'a> sawtooth::transact::handler::TransactionContext for SabreContext<'a> {
     fn get_state_entry(&self, address: &str) -> Result<Option<Vec<u8>>, ContextError> {
-        let results = self
-            .sawtooth_context
-            .get_state_entries(&[address.to_owned()])
-            .map_err(to_context_error)?;
+        let results = self.get_state_entries(&[address.to_owned()])?;
 
         // take the first item, if it exists
         Ok(results.into_iter().next().map(|(_, v)| v))
@@ -54,9 +115,32 @@ impl<'a> sawtooth::transact::handler::TransactionContext for SabreContext<'a> {
         &self,
         addresses: &[String],
     ) -> Result<Vec<(String, Vec<u8>)>, ContextError> {
-        self.sawtooth_context
-            .get_state_entries(addresses)
-            .map_err(to_context_error)
+        #[cfg(feature = "otel")]
+        let _span = tracing::debug_span!("sabre.state.get", entries = addresses.len()).entered();
+        let result = self.sawtooth_context.get_state_entries(addresses);
+
+        if let (Some(health), Ok(entries)) = (self.health, &result) {
+            for (address, data) in entries {
+                if address.starts_with(CONTRACT_ADDRESS_PREFIX) {
+                    health.record_module_load(address, data.len());
+                }
+            }
+        }
+
+        if self.host_calls.is_some() {
+            for address in addresses {
+                let (bytes, label) = match &result {
+                    Ok(entries) => match entries.iter().find(|(found, _)| found == address) {
+                        Some((_, data)) => (data.len(), "ok".to_string()),
+                        None => (0, "not_found".to_string()),
+                    },
+                    Err(err) => (0, format!("error: {}", err)),
+                };
+                self.record("get_state", Some(address.as_str()), bytes, label);
+            }
+        }
+
+        result.map_err(to_context_error)
     }
 
     fn set_state_entry(&self, address: String, data: Vec<u8>) -> Result<(), ContextError> {
@@ -64,9 +148,40 @@ impl<'a> sawtooth::transact::handler::TransactionContext for SabreContext<'a> {
     }
 
     fn set_state_entries(&self, entries: Vec<(String, Vec<u8>)>) -> Result<(), ContextError> {
-        self.sawtooth_context
-            .set_state_entries(entries)
-            .map_err(to_context_error)
+        #[cfg(feature = "otel")]
+        let _span = tracing::debug_span!("sabre.state.set", entries = entries.len()).entered();
+        {
+            let mut write_stats = self.write_stats.borrow_mut();
+            for (address, data) in &entries {
+                let prefix = address
+                    .get(..WRITE_STATS_PREFIX_LENGTH)
+                    .unwrap_or(address)
+                    .to_string();
+                let stats = write_stats.entry(prefix).or_default();
+                stats.writes += 1;
+                stats.bytes += data.len() as u64;
+            }
+        }
+
+        let traced = self.host_calls.as_ref().map(|_| {
+            entries
+                .iter()
+                .map(|(address, data)| (address.clone(), data.len()))
+                .collect::<Vec<_>>()
+        });
+
+        let result = self.sawtooth_context.set_state_entries(entries);
+
+        for (address, bytes) in traced.unwrap_or_default() {
+            self.record(
+                "set_state",
+                Some(address.as_str()),
+                bytes,
+                result_label(&result),
+            );
+        }
+
+        result.map_err(to_context_error)
     }
 
     fn delete_state_entry(&self, address: &str) -> Result<Option<String>, ContextError> {
@@ -77,15 +192,29 @@ impl<'a> sawtooth::transact::handler::TransactionContext for SabreContext<'a> {
     }
 
     fn delete_state_entries(&self, addresses: &[String]) -> Result<Vec<String>, ContextError> {
-        self.sawtooth_context
-            .delete_state_entries(addresses)
-            .map_err(to_context_error)
+        #[cfg(feature = "otel")]
+        let _span = tracing::debug_span!("sabre.state.delete", entries = addresses.len()).entered();
+        let result = self.sawtooth_context.delete_state_entries(addresses);
+
+        if self.host_calls.is_some() {
+            for address in addresses {
+                let label = match &result {
+                    Ok(deleted) if deleted.contains(address) => "ok".to_string(),
+                    Ok(_) => "not_found".to_string(),
+                    Err(err) => format!("error: {}", err),
+                };
+                self.record("delete_state", Some(address.as_str()), 0, label);
+            }
+        }
+
+        result.map_err(to_context_error)
     }
 
     fn add_receipt_data(&self, data: Vec<u8>) -> Result<(), ContextError> {
-        self.sawtooth_context
-            .add_receipt_data(&data)
-            .map_err(to_context_error)
+        let result = self.sawtooth_context.add_receipt_data(&data);
+        self.record("add_receipt_data", None, data.len(), result_label(&result));
+
+        result.map_err(to_context_error)
     }
 
     fn add_event(
@@ -94,9 +223,163 @@ impl<'a> sawtooth::transact::handler::TransactionContext for SabreContext<'a> {
         attributes: Vec<(String, String)>,
         data: Vec<u8>,
     ) -> Result<(), ContextError> {
-        self.sawtooth_context
-            .add_event(event_type, attributes, &data)
-            .map_err(to_context_error)
+        self.record("add_event", None, data.len(), "ok".into());
+        self.events.borrow_mut().push(Event {
+            event_type,
+            attributes,
+            data,
+        });
+        Ok(())
+    }
+}
+
+/// Returns the event reporting the writes per namespace, or None if there were no writes.
+fn write_stats_event(write_stats: BTreeMap<String, WriteStats>) -> Option<Event> {
+    if write_stats.is_empty() {
+        return None;
+    }
+
+    let attributes = write_stats
+        .keys()
+        .map(|prefix| ("namespace".to_string(), prefix.clone()))
+        .collect();
+    let data = write_stats
+        .iter()
+        .map(|(prefix, stats)| format!("{},{},{}\n", prefix, stats.writes, stats.bytes))
+        .collect::<String>()
+        .into_bytes();
+
+    Some(Event {
+        event_type: WRITE_STATS_EVENT_TYPE.to_string(),
+        attributes,
+        data,
+    })
+}
+
+/// How the events emitted by a transaction are limited, as configured in Sawtooth settings
+struct EventPolicy {
+    max_events: Option<usize>,
+    aggregate: bool,
+}
+
+impl EventPolicy {
+    fn load(context: &dyn TransactionContext) -> Result<Self, ApplyError> {
+        let max_events = match get_setting(context, MAX_EVENTS_SETTING_KEY)? {
+            Some(value) => Some(value.parse::<usize>().map_err(|_| {
+                ApplyError::InternalError(format!(
+                    "Setting {} is not a valid number: {}",
+                    MAX_EVENTS_SETTING_KEY, value
+                ))
+            })?),
+            None => None,
+        };
+        let aggregate = get_setting(context, AGGREGATE_EVENTS_SETTING_KEY)?
+            .map(|value| value == "true")
+            .unwrap_or(false);
+
+        Ok(EventPolicy {
+            max_events,
+            aggregate,
+        })
+    }
+
+    /// Returns the events to emit for a transaction, or an error if there are more than the
+    /// limit and they are not aggregated.
+    fn apply(&self, events: Vec<Event>) -> Result<Vec<Event>, ApplyError> {
+        let max_events = match self.max_events {
+            Some(max_events) if events.len() > max_events => max_events,
+            _ => return Ok(events),
+        };
+
+        if !self.aggregate {
+            return Err(ApplyError::InvalidTransaction(format!(
+                "Contract emitted {} events, more than the {} allowed by {}",
+                events.len(),
+                max_events,
+                MAX_EVENTS_SETTING_KEY
+            )));
+        }
+
+        let mut hasher = Sha512::new();
+        for event in &events {
+            hasher.update(event.event_type.as_bytes());
+            for (key, value) in &event.attributes {
+                hasher.update(key.as_bytes());
+                hasher.update(value.as_bytes());
+            }
+            hasher.update(&event.data);
+        }
+
+        Ok(vec![Event {
+            event_type: EVENTS_DIGEST_EVENT_TYPE.to_string(),
+            attributes: vec![("event_count".to_string(), events.len().to_string())],
+            data: hasher.finalize().to_vec(),
+        }])
+    }
+}
+
+/// Returns whether each transaction emits the number of writes and bytes written to each
+/// namespace.
+///
+/// The statistics limit nothing, so a transaction which leaves the setting out of its inputs,
+/// as those built before it was added to them do, is applied without them.
+fn write_stats_enabled(context: &dyn TransactionContext) -> Result<bool, ApplyError> {
+    Ok(read_setting(context, WRITE_STATS_SETTING_KEY)?
+        .flatten()
+        .map(|value| value == "true")
+        .unwrap_or(false))
+}
+
+/// Returns the value of the setting, or `None` if it is unset.
+///
+/// A setting which is not among the transaction's inputs cannot be read. It may hold a limit
+/// the submitter would escape by leaving it out, so the transaction is rejected.
+fn get_setting(context: &dyn TransactionContext, key: &str) -> Result<Option<String>, ApplyError> {
+    read_setting(context, key)?.ok_or_else(|| {
+        ApplyError::InvalidTransaction(format!(
+            "Setting {} is not among the transaction's inputs; its address {} must be added \
+             to them",
+            key,
+            setting_address(key).unwrap_or_default()
+        ))
+    })
+}
+
+/// Returns the value of the setting, `Some(None)` if it is unset, or `None` if it is not among
+/// the transaction's inputs.
+fn read_setting(
+    context: &dyn TransactionContext,
+    key: &str,
+) -> Result<Option<Option<String>>, ApplyError> {
+    let entry = match context.get_state_entries(&[setting_address(key)?]) {
+        Ok(entries) => entries.into_iter().next(),
+        Err(SdkContextError::AuthorizationError(_)) => return Ok(None),
+        Err(err) => return Err(ApplyError::InternalError(err.to_string())),
+    };
+
+    match entry {
+        Some((_, bytes)) => {
+            let setting = Setting::from_bytes(&bytes)
+                .map_err(|err| ApplyError::InternalError(err.to_string()))?;
+            Ok(Some(setting.get_value(key).map(String::from)))
+        }
+        None => Ok(Some(None)),
+    }
+}
+
+fn setting_address(key: &str) -> Result<String, ApplyError> {
+    Ok(compute_setting_address(key)
+        .map_err(|err| ApplyError::InternalError(err.to_string()))?
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>())
+}
+
+/// Returns how a host call is shown in the trace
+fn result_label<T>(result: &Result<T, sawtooth_sdk::processor::handler::ContextError>) -> String {
+    match result {
+        Ok(_) => "ok".to_string(),
+        Err(err) => format!("error: {}", err),
     }
 }
 
@@ -106,14 +389,30 @@ fn to_context_error(err: sawtooth_sdk::processor::handler::ContextError) -> Cont
 
 pub struct SabreHandler {
     transaction_handler: SabreTransactionHandler,
+    host_call_trace: Option<HostCallTrace>,
+    health: Option<Arc<Health>>,
 }
 
 impl SabreHandler {
     pub fn new(transaction_handler: SabreTransactionHandler) -> Self {
         Self {
             transaction_handler,
+            host_call_trace: None,
+            health: None,
         }
     }
+
+    /// Records the host calls of transactions to the trace.
+    pub fn with_host_call_trace(mut self, host_call_trace: HostCallTrace) -> Self {
+        self.host_call_trace = Some(host_call_trace);
+        self
+    }
+
+    /// Counts the transactions applied for the health endpoint.
+    pub fn with_health(mut self, health: Arc<Health>) -> Self {
+        self.health = Some(health);
+        self
+    }
 }
 
 impl TransactionHandler for SabreHandler {
@@ -126,11 +425,10 @@ impl TransactionHandler for SabreHandler {
     }
 
     fn namespaces(&self) -> Vec<String> {
-        vec![
-            NAMESPACE_REGISTRY_PREFIX.into(),
-            CONTRACT_REGISTRY_PREFIX.into(),
-            CONTRACT_PREFIX.into(),
-        ]
+        SabreNamespace::REGISTERED
+            .iter()
+            .map(|namespace| namespace.prefix().to_string())
+            .collect()
     }
 
     fn apply(
@@ -138,36 +436,614 @@ impl TransactionHandler for SabreHandler {
         request: &TpProcessRequest,
         context: &mut dyn TransactionContext,
     ) -> Result<(), ApplyError> {
+        // Transactions submitted by the sabre CLI carry a traceparent in their nonce
+        let trace = TraceParent::parse(request.get_header().get_nonce()).ok();
+
+        #[cfg(feature = "otel")]
+        let span = telemetry::apply_span(request.get_signature(), trace.as_ref());
+        #[cfg(feature = "otel")]
+        let _entered = span.enter();
+
+        let result = self.apply_transaction(request, context, trace);
+
+        #[cfg(feature = "otel")]
+        telemetry::record_result(&span, &result);
+
+        if let Some(health) = &self.health {
+            health.record_apply(match &result {
+                Ok(()) => ApplyOutcome::Applied,
+                Err(ApplyError::InvalidTransaction(_)) => ApplyOutcome::Invalid,
+                Err(_) => ApplyOutcome::InternalError,
+            });
+        }
+
+        result
+    }
+}
+
+impl SabreHandler {
+    fn apply_transaction(
+        &self,
+        request: &TpProcessRequest,
+        context: &mut dyn TransactionContext,
+        trace: Option<TraceParent>,
+    ) -> Result<(), ApplyError> {
+        let trace_tag = trace
+            .as_ref()
+            .map(|trace| format!(" [trace {}]", trace.trace_id()))
+            .unwrap_or_default();
+        debug!(
+            "Applying transaction {}{}",
+            request.get_signature(),
+            trace_tag
+        );
+
+        let payload = SabrePayload::from_bytes(request.get_payload())
+            .map_err(|err| ApplyError::InvalidTransaction(err.to_string()))?;
+        let (payload, payload_bytes) = decompress_payload(payload, request.get_payload())?;
+        #[cfg(feature = "otel")]
+        telemetry::record_action(&payload);
+        verify_contract_sha512(&payload, context)?;
+
+        let transaction_signer = request.get_header().get_signer_public_key();
+        let (signer, signature) = match verify_delegation(&payload, transaction_signer)? {
+            Some(delegation) => {
+                consume_delegation(delegation, request.get_signature(), context)?;
+                debug!(
+                    "Executing contract for delegated signer {} submitted by {}{}",
+                    delegation.signer_public_key(),
+                    transaction_signer,
+                    trace_tag
+                );
+                // The contract is only handed a signer and a signature, so the relayer is
+                // carried in the signature
+                (
+                    delegation.signer_public_key().to_string(),
+                    join_relayed_signature(request.get_signature(), transaction_signer),
+                )
+            }
+            None => (
+                transaction_signer.to_string(),
+                request.get_signature().to_string(),
+            ),
+        };
+
         let mut header = TransactionHeader::new();
-        header.set_signer_public_key(request.get_header().get_signer_public_key().to_string());
+        header.set_signer_public_key(signer);
 
         let header_bytes = header.write_to_bytes().map_err(|_| {
             ApplyError::InvalidTransaction("Unable to convert header to bytes".to_string())
         })?;
-        let txn = Transaction::new(
-            header_bytes,
-            request.get_signature().to_string(),
-            request.get_payload().to_vec(),
-        );
+        let txn = Transaction::new(header_bytes, signature, payload_bytes);
         let txn_pair = txn
             .into_pair()
             .map_err(|err| ApplyError::InvalidTransaction(err.to_string()))?;
 
+        let event_policy = EventPolicy::load(context)?;
+        let emit_write_stats = write_stats_enabled(context)?;
+
+        let traced = self
+            .host_call_trace
+            .as_ref()
+            .filter(|host_call_trace| host_call_trace.is_traced(request.get_signature()));
+
         let mut sabre_context = SabreContext {
             sawtooth_context: context,
+            transaction_id: request.get_signature(),
+            events: RefCell::new(Vec::new()),
+            write_stats: RefCell::new(BTreeMap::new()),
+            host_calls: traced.map(|_| RefCell::new(Vec::new())),
+            health: self.health.as_deref(),
         };
 
-        match self
-            .transaction_handler
-            .apply(&txn_pair, &mut sabre_context)
+        // The Sabre handler loads and runs the contract, so its span holds the contract's state
+        // reads and writes
+        let result = {
+            #[cfg(feature = "otel")]
+            let _span = tracing::info_span!("sabre.execute").entered();
+            self.transaction_handler
+                .apply(&txn_pair, &mut sabre_context)
+        };
+
+        // The trace is for debugging, so failing to write it does not fail the transaction
+        if let (Some(host_call_trace), Some(host_calls)) = (traced, sabre_context.host_calls.take())
         {
-            Ok(()) => Ok(()),
+            if let Err(err) = host_call_trace.write(&host_calls.into_inner()) {
+                error!(
+                    "Unable to write the host calls of transaction {}: {}",
+                    request.get_signature(),
+                    err
+                );
+            }
+        }
+
+        match result {
+            Ok(()) => (),
             Err(sawtooth::transact::handler::ApplyError::InvalidTransaction(msg)) => {
-                Err(ApplyError::InvalidTransaction(msg))
+                debug!(
+                    "Transaction {} is invalid{}: {}",
+                    request.get_signature(),
+                    trace_tag,
+                    msg
+                );
+                return Err(ApplyError::InvalidTransaction(msg));
             }
             Err(sawtooth::transact::handler::ApplyError::InternalError(msg)) => {
-                Err(ApplyError::InternalError(msg))
+                error!(
+                    "Transaction {} failed{}: {}",
+                    request.get_signature(),
+                    trace_tag,
+                    msg
+                );
+                return Err(ApplyError::InternalError(msg));
+            }
+        }
+
+        let mut events = event_policy.apply(sabre_context.events.into_inner())?;
+        // The statistics are the handler's own event, so they do not count toward the limit
+        if emit_write_stats {
+            events.extend(write_stats_event(sabre_context.write_stats.into_inner()));
+        }
+
+        for mut event in events {
+            if let Some(trace) = &trace {
+                event
+                    .attributes
+                    .push((TRACEPARENT.to_string(), trace.to_string()));
+            }
+            context
+                .add_event(event.event_type, event.attributes, &event.data)
+                .map_err(|err| ApplyError::InternalError(err.to_string()))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the payload with its execute contract payload decompressed, if it is compressed, and
+/// the bytes to hand to the Sabre handler, which executes the contract payload as it is given.
+fn decompress_payload(
+    payload: SabrePayload,
+    bytes: &[u8],
+) -> Result<(SabrePayload, Vec<u8>), ApplyError> {
+    let execute_contract = match payload.action() {
+        Action::ExecuteContract(execute_contract) if execute_contract.payload_compressed() => {
+            execute_contract.clone()
+        }
+        _ => return Ok((payload, bytes.to_vec())),
+    };
+
+    let execute_contract = execute_contract
+        .into_decompressed(MAX_DECOMPRESSED_PAYLOAD_SIZE)
+        .map_err(|err| ApplyError::InvalidTransaction(err.to_string()))?;
+    let payload = SabrePayloadBuilder::new()
+        .with_action(Action::from(execute_contract))
+        .build()
+        .map_err(|err| ApplyError::InternalError(err.to_string()))?;
+    let bytes = payload
+        .clone()
+        .into_bytes()
+        .map_err(|err| ApplyError::InternalError(err.to_string()))?;
+
+    Ok((payload, bytes))
+}
+
+/// Verifies the delegation attached to an execute contract action, if any, and returns it. The
+/// delegation must have been issued to the transaction signer, for the contract and payload of
+/// the action. The contract is executed with the delegated signer in place of the transaction
+/// signer.
+fn verify_delegation<'a>(
+    payload: &'a SabrePayload,
+    transaction_signer: &str,
+) -> Result<Option<&'a Delegation>, ApplyError> {
+    let execute_contract = match payload.action() {
+        Action::ExecuteContract(execute_contract) => execute_contract,
+        _ => return Ok(None),
+    };
+
+    let delegation = match execute_contract.delegation() {
+        Some(delegation) => delegation,
+        None => return Ok(None),
+    };
+
+    if delegation.relayer_public_key() != transaction_signer {
+        return Err(ApplyError::InvalidTransaction(format!(
+            "Delegation was issued to relayer {}, not transaction signer {}",
+            delegation.relayer_public_key(),
+            transaction_signer
+        )));
+    }
+
+    let verifier = Secp256k1Context::new().new_verifier();
+    let verified = delegation
+        .verify(
+            execute_contract.name(),
+            execute_contract.version(),
+            execute_contract.payload(),
+            &*verifier,
+        )
+        .map_err(|err| ApplyError::InvalidTransaction(format!("Invalid delegation: {}", err)))?;
+
+    if !verified {
+        return Err(ApplyError::InvalidTransaction(format!(
+            "Delegation signature is not valid for signer {}",
+            delegation.signer_public_key()
+        )));
+    }
+
+    Ok(Some(delegation))
+}
+
+/// Records in state that the delegation has been used, so it cannot be replayed. A delegation
+/// whose signer and nonce have already been used is rejected.
+fn consume_delegation(
+    delegation: &Delegation,
+    transaction_id: &str,
+    context: &dyn TransactionContext,
+) -> Result<(), ApplyError> {
+    let address = compute_delegation_address(delegation.signer_public_key(), delegation.nonce())
+        .map_err(|err| ApplyError::InternalError(err.to_string()))?
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+
+    let used = context
+        .get_state_entries(&[address.clone()])
+        .map_err(|err| ApplyError::InternalError(err.to_string()))?
+        .into_iter()
+        .next()
+        .is_some();
+    if used {
+        return Err(ApplyError::InvalidTransaction(format!(
+            "Delegation from signer {} with nonce {} has already been used",
+            delegation.signer_public_key(),
+            delegation.nonce()
+        )));
+    }
+
+    context
+        .set_state_entries(vec![(address, transaction_id.as_bytes().to_vec())])
+        .map_err(|err| ApplyError::InternalError(err.to_string()))
+}
+
+/// Verifies that the stored contract an execute contract action is pinned to, if it is pinned,
+/// has the expected sha512. A contract which is not stored is left to the Sabre handler to
+/// reject.
+fn verify_contract_sha512(
+    payload: &SabrePayload,
+    context: &dyn TransactionContext,
+) -> Result<(), ApplyError> {
+    let execute_contract = match payload.action() {
+        Action::ExecuteContract(execute_contract) => execute_contract,
+        _ => return Ok(()),
+    };
+
+    let expected = match execute_contract.contract_sha512() {
+        Some(expected) => expected,
+        None => return Ok(()),
+    };
+
+    // The version "latest" is resolved by the Sabre handler, so the contract it would execute
+    // is not known here
+    if execute_contract.version() == "latest" {
+        return Err(ApplyError::InvalidTransaction(
+            "A contract pinned to a sha512 must be executed by its version".to_string(),
+        ));
+    }
+
+    let address = compute_contract_address(execute_contract.name(), execute_contract.version())
+        .map_err(|err| ApplyError::InternalError(err.to_string()))?
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+
+    let bytes = match context
+        .get_state_entries(&[address])
+        .map_err(|err| ApplyError::InternalError(err.to_string()))?
+        .into_iter()
+        .next()
+    {
+        Some((_, bytes)) => bytes,
+        None => return Ok(()),
+    };
+
+    // The contract is borrowed from the entry rather than copied, as it may be megabytes
+    let contracts = ContractListRef::from_bytes(&bytes)
+        .map_err(|err| ApplyError::InternalError(err.to_string()))?;
+    let contract = match contracts.find(execute_contract.name(), execute_contract.version()) {
+        Some(contract) => contract,
+        None => return Ok(()),
+    };
+
+    let actual = Sha512::digest(contract.contract())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(ApplyError::InvalidTransaction(format!(
+            "Contract {}:{} has sha512 {}, not the pinned {}",
+            execute_contract.name(),
+            execute_contract.version(),
+            actual,
+            expected
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use cylinder::Signer;
+    use sabre_sdk::protocol::payload::{DelegationBuilder, ExecuteContractActionBuilder};
+    use sabre_sdk::protocol::settings::SettingEntry;
+    use sabre_sdk::protocol::SETTINGS_ADDRESS_PREFIX;
+    use sabre_sdk::protos::{IntoNative, IntoProto};
+
+    /// Global state held in memory
+    #[derive(Default)]
+    struct MockContext {
+        state: RefCell<HashMap<String, Vec<u8>>>,
+        /// The prefixes of the addresses which are not among the transaction's inputs
+        denied: Vec<String>,
+    }
+
+    impl TransactionContext for MockContext {
+        fn get_state_entries(
+            &self,
+            addresses: &[String],
+        ) -> Result<Vec<(String, Vec<u8>)>, SdkContextError> {
+            if let Some(address) = addresses.iter().find(|address| {
+                self.denied
+                    .iter()
+                    .any(|prefix| address.starts_with(prefix.as_str()))
+            }) {
+                return Err(SdkContextError::AuthorizationError(format!(
+                    "{} is not in the transaction's inputs",
+                    address
+                )));
             }
+
+            let state = self.state.borrow();
+            Ok(addresses
+                .iter()
+                .filter_map(|address| {
+                    state
+                        .get(address)
+                        .map(|data| (address.clone(), data.clone()))
+                })
+                .collect())
+        }
+
+        fn set_state_entries(
+            &self,
+            entries: Vec<(String, Vec<u8>)>,
+        ) -> Result<(), SdkContextError> {
+            self.state.borrow_mut().extend(entries);
+            Ok(())
+        }
+
+        fn delete_state_entries(
+            &self,
+            addresses: &[String],
+        ) -> Result<Vec<String>, SdkContextError> {
+            let mut state = self.state.borrow_mut();
+            Ok(addresses
+                .iter()
+                .filter(|address| state.remove(*address).is_some())
+                .cloned()
+                .collect())
+        }
+
+        fn add_receipt_data(&self, _data: &[u8]) -> Result<(), SdkContextError> {
+            Ok(())
+        }
+
+        fn add_event(
+            &self,
+            _event_type: String,
+            _attributes: Vec<(String, String)>,
+            _data: &[u8],
+        ) -> Result<(), SdkContextError> {
+            Ok(())
+        }
+    }
+
+    fn delegated_payload(delegated_signer: &dyn Signer, relayer: &str) -> SabrePayload {
+        let delegation = DelegationBuilder::new()
+            .with_name("intkey_multiply".to_string())
+            .with_version("1.0".to_string())
+            .with_payload(b"payload".to_vec())
+            .with_relayer_public_key(relayer.to_string())
+            .with_nonce("1".to_string())
+            .build(delegated_signer)
+            .unwrap();
+
+        ExecuteContractActionBuilder::new()
+            .with_name("intkey_multiply".to_string())
+            .with_version("1.0".to_string())
+            .with_payload(b"payload".to_vec())
+            .with_delegation(delegation)
+            .into_payload_builder()
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    // check that a delegation is accepted once from the relayer it was issued to, and that
+    // replaying it is rejected
+    fn check_delegation_replayed() {
+        let context = Secp256k1Context::new();
+        let delegated_signer = context.new_signer(context.new_random_private_key());
+        let payload = delegated_payload(&*delegated_signer, "02abcd");
+        let state = MockContext::default();
+
+        let delegation = verify_delegation(&payload, "02abcd").unwrap().unwrap();
+        assert_eq!(
+            delegation.signer_public_key(),
+            delegated_signer.public_key().unwrap().as_hex()
+        );
+        consume_delegation(delegation, "txn1", &state).unwrap();
+
+        match consume_delegation(delegation, "txn2", &state) {
+            Err(ApplyError::InvalidTransaction(msg)) => {
+                assert!(msg.contains("has already been used"))
+            }
+            _ => panic!("a replayed delegation was not rejected"),
+        }
+    }
+
+    #[test]
+    // check that a delegation submitted by a relayer other than the one it was issued to, or
+    // whose signature was rebound to that relayer, is rejected
+    fn check_delegation_rebound() {
+        let context = Secp256k1Context::new();
+        let delegated_signer = context.new_signer(context.new_random_private_key());
+        let payload = delegated_payload(&*delegated_signer, "02abcd");
+
+        match verify_delegation(&payload, "03ef01") {
+            Err(ApplyError::InvalidTransaction(msg)) => {
+                assert!(msg.contains("was issued to relayer 02abcd"))
+            }
+            _ => panic!("a delegation was accepted from another relayer"),
         }
+
+        // Substitute the other relayer's key while keeping the original signature
+        let original = match payload.action() {
+            Action::ExecuteContract(execute_contract) => execute_contract.delegation().unwrap(),
+            _ => unreachable!(),
+        };
+        let rebound = DelegationBuilder::new()
+            .with_name("intkey_multiply".to_string())
+            .with_version("1.0".to_string())
+            .with_payload(b"payload".to_vec())
+            .with_relayer_public_key("03ef01".to_string())
+            .with_nonce("1".to_string())
+            .build(&*delegated_signer)
+            .unwrap();
+        let mut proto = rebound.into_proto().unwrap();
+        proto.set_signature(original.signature().to_string());
+        let rebound = proto.into_native().unwrap();
+
+        let payload = ExecuteContractActionBuilder::new()
+            .with_name("intkey_multiply".to_string())
+            .with_version("1.0".to_string())
+            .with_payload(b"payload".to_vec())
+            .with_delegation(rebound)
+            .into_payload_builder()
+            .unwrap()
+            .build()
+            .unwrap();
+
+        match verify_delegation(&payload, "03ef01") {
+            Err(ApplyError::InvalidTransaction(msg)) => {
+                assert!(msg.contains("signature is not valid"))
+            }
+            _ => panic!("a rebound delegation was accepted"),
+        }
+    }
+
+    #[test]
+    // check that the event settings are read when they are among the transaction's inputs, and
+    // that the transaction is rejected when they are not
+    fn check_event_policy_settings_denied() {
+        let state = MockContext::default();
+        let address = compute_setting_address(MAX_EVENTS_SETTING_KEY)
+            .unwrap()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        let setting = Setting::new(vec![SettingEntry::new(
+            MAX_EVENTS_SETTING_KEY.to_string(),
+            "2".to_string(),
+        )]);
+        state
+            .state
+            .borrow_mut()
+            .insert(address, setting.into_bytes().unwrap());
+
+        let policy = EventPolicy::load(&state).unwrap();
+        assert_eq!(policy.max_events, Some(2));
+        assert!(!policy.aggregate);
+
+        let state = MockContext {
+            state: state.state,
+            denied: vec![SETTINGS_ADDRESS_PREFIX.to_string()],
+        };
+        match EventPolicy::load(&state) {
+            Err(ApplyError::InvalidTransaction(msg)) => {
+                assert!(msg.contains(MAX_EVENTS_SETTING_KEY))
+            }
+            _ => panic!("the event policy was loaded without its settings"),
+        }
+    }
+
+    #[test]
+    // check that a transaction which leaves the event settings out of its inputs is rejected
+    // before its contract is executed
+    fn check_execute_without_event_settings() {
+        let handler = SabreHandler::new(SabreTransactionHandler::new(Box::new(
+            crate::admin::AllowAllAdminPermission::default(),
+        )));
+        let payload = ExecuteContractActionBuilder::new()
+            .with_name("intkey_multiply".to_string())
+            .with_version("1.0".to_string())
+            .with_payload(b"payload".to_vec())
+            .into_payload_builder()
+            .unwrap()
+            .build()
+            .unwrap();
+        let mut header = sawtooth_sdk::messages::transaction::TransactionHeader::new();
+        header.set_signer_public_key("02abcd".to_string());
+        let mut request = TpProcessRequest::new();
+        request.set_header(header);
+        request.set_signature("txn1".to_string());
+        request.set_payload(payload.into_bytes().unwrap());
+        let mut state = MockContext {
+            denied: vec![SETTINGS_ADDRESS_PREFIX.to_string()],
+            ..MockContext::default()
+        };
+
+        // Executing the contract, which is not stored, would fail naming the contract
+        match handler.apply(&request, &mut state) {
+            Err(ApplyError::InvalidTransaction(msg)) => {
+                assert!(msg.contains(MAX_EVENTS_SETTING_KEY), "{}", msg)
+            }
+            _ => panic!("a transaction without the event settings was applied"),
+        }
+        assert!(state.state.borrow().is_empty());
+    }
+
+    #[test]
+    // check that write stats are enabled by their setting when it is among the transaction's
+    // inputs, and disabled when it is not
+    fn check_write_stats_setting_denied() {
+        let state = MockContext::default();
+        let address = compute_setting_address(WRITE_STATS_SETTING_KEY)
+            .unwrap()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        let setting = Setting::new(vec![SettingEntry::new(
+            WRITE_STATS_SETTING_KEY.to_string(),
+            "true".to_string(),
+        )]);
+        state
+            .state
+            .borrow_mut()
+            .insert(address, setting.into_bytes().unwrap());
+
+        assert!(write_stats_enabled(&state).unwrap());
+
+        let state = MockContext {
+            state: state.state,
+            denied: vec![SETTINGS_ADDRESS_PREFIX.to_string()],
+        };
+        assert!(!write_stats_enabled(&state).unwrap());
     }
 }
@@ -0,0 +1,342 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Serves the health of the transaction processor over HTTP
+//!
+//! * `GET /live` answers 200 while the process is up
+//! * `GET /ready` answers 200 once the processor is running and the validator accepts
+//!   connections, or 503 while it is standing by or the validator cannot be reached
+//! * `GET /health` answers 200 with the state, the validator's reachability, the transactions
+//!   applied and the contract modules loaded, as JSON
+//! * `POST /promote` connects a standing-by processor to the validator. It is only accepted with
+//!   the promote token as a bearer token or, when no token is configured, from a loopback address
+//!
+//! The validator connection is owned by the Sawtooth SDK's `TransactionProcessor`, which does
+//! not report its status, so the validator is checked by connecting to its endpoint. The time
+//! since the last applied transaction shows whether the validator is sending it work.
+//!
+//! The executor's cache of compiled modules lives in sawtooth-lib and is not exposed, so the
+//! contract modules the executor reads from state are counted instead. Repeat loads are those a
+//! cache holding every module would serve.
+
+use std::collections::HashSet;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a health request may take to arrive before its connection is dropped
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long connecting to the validator may take before it is reported unreachable
+const VALIDATOR_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The most health requests answered at once; further connections are dropped
+const MAX_CONCURRENT_REQUESTS: usize = 16;
+
+/// The state of the transaction processor and the transactions it has applied
+pub struct Health {
+    running: Mutex<bool>,
+    promoted: Condvar,
+    validator_endpoint: String,
+    promote_token: Option<String>,
+    applied: AtomicU64,
+    invalid: AtomicU64,
+    internal_errors: AtomicU64,
+    last_applied: Mutex<Option<Instant>>,
+    module_loads: AtomicU64,
+    module_bytes: AtomicU64,
+    modules: Mutex<HashSet<String>>,
+}
+
+#[derive(Serialize)]
+struct HealthReport {
+    state: &'static str,
+    validator_reachable: bool,
+    transactions_applied: u64,
+    transactions_invalid: u64,
+    internal_errors: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seconds_since_last_apply: Option<u64>,
+    module_cache: ModuleCacheReport,
+}
+
+#[derive(Serialize)]
+struct ModuleCacheReport {
+    loads: u64,
+    distinct: u64,
+    repeat_loads: u64,
+    bytes_loaded: u64,
+}
+
+/// The outcome of applying a transaction
+pub enum ApplyOutcome {
+    Applied,
+    Invalid,
+    InternalError,
+}
+
+impl Health {
+    /// Creates the health of a processor which is either standing by or about to connect to the
+    /// validator at the endpoint, such as tcp://localhost:4004. With a promote token, only
+    /// requests carrying it as a bearer token may promote the processor.
+    pub fn new(standby: bool, validator_endpoint: &str, promote_token: Option<&str>) -> Arc<Self> {
+        Arc::new(Health {
+            running: Mutex::new(!standby),
+            promoted: Condvar::new(),
+            validator_endpoint: validator_endpoint.to_string(),
+            promote_token: promote_token.map(String::from),
+            applied: AtomicU64::new(0),
+            invalid: AtomicU64::new(0),
+            internal_errors: AtomicU64::new(0),
+            last_applied: Mutex::new(None),
+            module_loads: AtomicU64::new(0),
+            module_bytes: AtomicU64::new(0),
+            modules: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// Blocks until the processor has been promoted, returning at once if it is not standing by.
+    pub fn wait_for_promotion(&self) {
+        let mut running = self.running.lock().unwrap_or_else(|err| err.into_inner());
+        while !*running {
+            running = self
+                .promoted
+                .wait(running)
+                .unwrap_or_else(|err| err.into_inner());
+        }
+    }
+
+    /// Promotes a standing-by processor. Returns false if it was already running.
+    pub fn promote(&self) -> bool {
+        let mut running = self.running.lock().unwrap_or_else(|err| err.into_inner());
+        if *running {
+            return false;
+        }
+        *running = true;
+        self.promoted.notify_all();
+        true
+    }
+
+    pub fn is_running(&self) -> bool {
+        *self.running.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Counts a transaction the handler has applied.
+    pub fn record_apply(&self, outcome: ApplyOutcome) {
+        let counter = match outcome {
+            ApplyOutcome::Applied => &self.applied,
+            ApplyOutcome::Invalid => &self.invalid,
+            ApplyOutcome::InternalError => &self.internal_errors,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        *self
+            .last_applied
+            .lock()
+            .unwrap_or_else(|err| err.into_inner()) = Some(Instant::now());
+    }
+
+    /// Returns whether the validator accepts connections at its endpoint.
+    pub fn is_validator_reachable(&self) -> bool {
+        let address = self
+            .validator_endpoint
+            .trim_start_matches("tcp://")
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addresses| addresses.next());
+        match address {
+            Some(address) => TcpStream::connect_timeout(&address, VALIDATOR_TIMEOUT).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Counts a contract module the executor read from state at the address.
+    pub fn record_module_load(&self, address: &str, bytes: usize) {
+        self.module_loads.fetch_add(1, Ordering::Relaxed);
+        self.module_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.modules
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .insert(address.to_string());
+    }
+
+    /// Returns whether a promote request from the peer, with the given Authorization header, is
+    /// allowed.
+    fn may_promote(&self, peer: Option<SocketAddr>, authorization: Option<&str>) -> bool {
+        match &self.promote_token {
+            Some(token) => authorization
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .map(|given| constant_time_eq(given.trim().as_bytes(), token.as_bytes()))
+                .unwrap_or(false),
+            None => peer.map(|peer| peer.ip().is_loopback()).unwrap_or(false),
+        }
+    }
+
+    fn report(&self) -> HealthReport {
+        let loads = self.module_loads.load(Ordering::Relaxed);
+        let distinct = self
+            .modules
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .len() as u64;
+
+        HealthReport {
+            state: if self.is_running() {
+                "running"
+            } else {
+                "standby"
+            },
+            validator_reachable: self.is_validator_reachable(),
+            transactions_applied: self.applied.load(Ordering::Relaxed),
+            transactions_invalid: self.invalid.load(Ordering::Relaxed),
+            internal_errors: self.internal_errors.load(Ordering::Relaxed),
+            seconds_since_last_apply: self
+                .last_applied
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .map(|instant| instant.elapsed().as_secs()),
+            module_cache: ModuleCacheReport {
+                loads,
+                distinct,
+                repeat_loads: loads.saturating_sub(distinct),
+                bytes_loaded: self.module_bytes.load(Ordering::Relaxed),
+            },
+        }
+    }
+}
+
+/// Compares the bytes in time which depends only on their lengths, so a token cannot be guessed
+/// from how long a comparison takes.
+fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
+    left.len() == right.len()
+        && left
+            .iter()
+            .zip(right)
+            .fold(0u8, |difference, (l, r)| difference | (l ^ r))
+            == 0
+}
+
+/// Binds the health endpoint to the address and serves it on a background thread, answering
+/// each request on a thread of its own so a slow client does not hold up probes.
+pub fn serve(address: &str, health: Arc<Health>) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    let active = Arc::new(AtomicUsize::new(0));
+
+    thread::Builder::new()
+        .name("health".into())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        debug!("Unable to accept health request: {}", err);
+                        continue;
+                    }
+                };
+                if active.fetch_add(1, Ordering::SeqCst) >= MAX_CONCURRENT_REQUESTS {
+                    active.fetch_sub(1, Ordering::SeqCst);
+                    debug!("Dropping health request: too many requests in progress");
+                    continue;
+                }
+
+                let health = health.clone();
+                let request_active = active.clone();
+                let spawned =
+                    thread::Builder::new()
+                        .name("health-request".into())
+                        .spawn(move || {
+                            if let Err(err) = handle_request(stream, &health) {
+                                debug!("Unable to answer health request: {}", err);
+                            }
+                            request_active.fetch_sub(1, Ordering::SeqCst);
+                        });
+                if let Err(err) = spawned {
+                    active.fetch_sub(1, Ordering::SeqCst);
+                    debug!("Unable to answer health request: {}", err);
+                }
+            }
+        })?;
+
+    Ok(())
+}
+
+fn handle_request(mut stream: TcpStream, health: &Health) -> io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let peer = stream.peer_addr().ok();
+
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // The headers are read so that the client sees its whole request consumed
+    let mut authorization = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("authorization") {
+                authorization = Some(value.trim().to_string());
+            }
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+
+    let (status, body) = match (method, path) {
+        ("GET", "/live") => ("200 OK", "{\"state\":\"live\"}".to_string()),
+        ("GET", "/ready") if !health.is_running() => (
+            "503 Service Unavailable",
+            "{\"state\":\"standby\"}".to_string(),
+        ),
+        ("GET", "/ready") if !health.is_validator_reachable() => (
+            "503 Service Unavailable",
+            "{\"state\":\"running\",\"error\":\"validator unreachable\"}".to_string(),
+        ),
+        ("GET", "/ready") => ("200 OK", "{\"state\":\"running\"}".to_string()),
+        ("GET", "/health") => (
+            "200 OK",
+            serde_json::to_string(&health.report()).map_err(io::Error::from)?,
+        ),
+        ("POST", "/promote") if !health.may_promote(peer, authorization.as_deref()) => (
+            "403 Forbidden",
+            "{\"error\":\"not allowed to promote\"}".to_string(),
+        ),
+        ("POST", "/promote") => {
+            if health.promote() {
+                info!("Promoted from standby by the health endpoint");
+            }
+            ("200 OK", "{\"state\":\"running\"}".to_string())
+        }
+        (_, "/live") | (_, "/ready") | (_, "/health") | (_, "/promote") => (
+            "405 Method Not Allowed",
+            "{\"error\":\"method not allowed\"}".to_string(),
+        ),
+        _ => ("404 Not Found", "{\"error\":\"not found\"}".to_string()),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
@@ -0,0 +1,75 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Records the host calls made while applying transactions, for debugging contracts
+//!
+//! Each state read, write and delete, event and receipt goes through the handler's context, so
+//! the context records them. The calls of a transaction are written to the trace file together,
+//! one JSON object per line, once it has been applied, whether or not it was valid. The file
+//! is read by `sabre trace show`.
+
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+/// A host call made by a transaction, written as one line of the trace file
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct HostCall {
+    pub transaction_id: String,
+    pub function: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    pub bytes: usize,
+    pub result: String,
+}
+
+/// Appends the host calls of the selected transactions to a trace file
+pub struct HostCallTrace {
+    file: Mutex<File>,
+    transaction_ids: HashSet<String>,
+}
+
+impl HostCallTrace {
+    /// Opens the trace file for appending. Only the given transactions are traced, or every
+    /// transaction if none are given.
+    pub fn open(path: &str, transaction_ids: HashSet<String>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(HostCallTrace {
+            file: Mutex::new(file),
+            transaction_ids,
+        })
+    }
+
+    /// Returns whether the host calls of the transaction should be recorded.
+    pub fn is_traced(&self, transaction_id: &str) -> bool {
+        self.transaction_ids.is_empty() || self.transaction_ids.contains(transaction_id)
+    }
+
+    /// Appends the host calls of a transaction to the trace file.
+    pub fn write(&self, calls: &[HostCall]) -> io::Result<()> {
+        let mut lines = String::new();
+        for call in calls {
+            lines.push_str(&serde_json::to_string(call).map_err(io::Error::from)?);
+            lines.push('\n');
+        }
+
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "trace file lock poisoned"))?;
+        file.write_all(lines.as_bytes())
+    }
+}
@@ -16,17 +16,28 @@
 extern crate clap;
 #[macro_use]
 extern crate log;
+#[macro_use]
+extern crate serde_derive;
 
 mod handler;
+mod health;
+mod host_trace;
+#[cfg(feature = "otel")]
+mod telemetry;
+
+use std::collections::HashSet;
 
 use clap::Arg;
 use log::LevelFilter;
 
 use sawtooth::families::sabre::admin;
 use sawtooth::families::sabre::handler::SabreTransactionHandler;
+use sawtooth_sdk::processor::handler::TransactionHandler;
 use sawtooth_sdk::processor::TransactionProcessor;
 
 use self::handler::SabreHandler;
+use self::health::Health;
+use self::host_trace::HostCallTrace;
 
 fn main() {
     let mut app = clap_app!(wasm_store_tp =>
@@ -43,6 +54,80 @@ fn main() {
             .long_help("Turns off the check for admin keys in Sawtooth Settings"),
     );
 
+    app = app
+        .arg(
+            Arg::with_name("host_call_trace")
+                .long("host-call-trace")
+                .takes_value(true)
+                .long_help(
+                    "Appends each state read, write and delete, event and receipt made by \
+                     transactions to this file, one JSON object per line, for `sabre trace show`",
+                ),
+        )
+        .arg(
+            Arg::with_name("host_call_trace_txn")
+                .long("host-call-trace-txn")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .requires("host_call_trace")
+                .long_help(
+                    "Traces only the transaction with this id; may be given more than once. \
+                     Every transaction is traced if none are given",
+                ),
+        );
+
+    app = app
+        .arg(
+            Arg::with_name("health_bind")
+                .long("health-bind")
+                .takes_value(true)
+                .long_help(
+                    "Serves /live, /ready, /health and /promote over HTTP at this address, \
+                     such as 0.0.0.0:8090",
+                ),
+        )
+        .arg(
+            Arg::with_name("standby")
+                .long("standby")
+                .requires("health_bind")
+                .long_help(
+                    "Loads the processor without connecting to the validator until it is \
+                     promoted with POST /promote on the health endpoint",
+                ),
+        )
+        .arg(
+            Arg::with_name("promote_token")
+                .long("promote-token")
+                .takes_value(true)
+                .requires("health_bind")
+                .long_help(
+                    "Accepts POST /promote only with this bearer token. Without it, only \
+                     requests from a loopback address may promote the processor",
+                ),
+        );
+
+    #[cfg(feature = "otel")]
+    {
+        app = app
+            .arg(
+                Arg::with_name("otlp_endpoint")
+                    .long("otlp-endpoint")
+                    .takes_value(true)
+                    .long_help(
+                        "Exports spans of transaction execution to the OTLP/HTTP collector at \
+                         this URL, such as http://localhost:4318/v1/traces",
+                    ),
+            )
+            .arg(
+                Arg::with_name("otlp_service_name")
+                    .long("otlp-service-name")
+                    .takes_value(true)
+                    .default_value("sawtooth-sabre")
+                    .long_help("The service name spans are exported under"),
+            );
+    }
+
     let matches = app.get_matches();
     let logger = simple_logger::SimpleLogger::new()
         // Switch to UTC timestamps, as local timestamps are not stable, by default. They are only
@@ -57,6 +142,20 @@ fn main() {
 
     logger.init().expect("Failed to create logger");
 
+    #[cfg(feature = "otel")]
+    if let Some(endpoint) = matches.value_of("otlp_endpoint") {
+        if let Err(err) = telemetry::init(
+            endpoint,
+            matches
+                .value_of("otlp_service_name")
+                .unwrap_or("sawtooth-sabre"),
+        ) {
+            error!("{}", err);
+            std::process::exit(1);
+        }
+        info!("Exporting spans to {}", endpoint);
+    }
+
     let connect = matches
         .value_of("connect")
         .unwrap_or("tcp://localhost:4004");
@@ -74,8 +173,63 @@ fn main() {
         }
     };
 
+    let handler = match matches.value_of("host_call_trace") {
+        Some(path) => {
+            let transaction_ids = matches
+                .values_of("host_call_trace_txn")
+                .map(|ids| ids.map(String::from).collect())
+                .unwrap_or_else(HashSet::new);
+            match HostCallTrace::open(path, transaction_ids) {
+                Ok(host_call_trace) => {
+                    warn!("Writing the host calls of transactions to {}", path);
+                    handler.with_host_call_trace(host_call_trace)
+                }
+                Err(err) => {
+                    error!("Unable to open host call trace {}: {}", path, err);
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => handler,
+    };
+
+    let health = Health::new(
+        matches.is_present("standby"),
+        connect,
+        matches.value_of("promote_token"),
+    );
+    let handler = match matches.value_of("health_bind") {
+        Some(address) => {
+            if let Err(err) = health::serve(address, health.clone()) {
+                error!(
+                    "Unable to serve the health endpoint at {}: {}",
+                    address, err
+                );
+                std::process::exit(1);
+            }
+            info!("Serving the health endpoint at {}", address);
+            handler.with_health(health.clone())
+        }
+        None => handler,
+    };
+
+    if !health.is_running() {
+        info!("Standing by until promoted");
+        health.wait_for_promotion();
+    }
+
+    // The validator does not publish the versions processors register, so operators declare them
+    // in the sabre.protocol.versions setting for clients to read
+    info!(
+        "Registering Sabre protocol versions {}",
+        handler.family_versions().join(", ")
+    );
+
     let mut processor = TransactionProcessor::new(connect);
 
     processor.add_handler(&handler);
     processor.start();
+
+    #[cfg(feature = "otel")]
+    telemetry::shutdown();
 }
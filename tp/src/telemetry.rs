@@ -0,0 +1,138 @@
+// Copyright 2018 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Exports spans of transaction execution to an OpenTelemetry collector over OTLP.
+//!
+//! Each transaction is traced as a `sabre.apply` span, holding a `sabre.execute` span for the
+//! Sabre handler, within which each state read, write and delete is a span of its own. When the
+//! transaction's nonce carries a traceparent, as those submitted by the sabre CLI do, the apply
+//! span continues the client's trace.
+
+use std::collections::HashMap;
+
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::sdk::propagation::TraceContextPropagator;
+use opentelemetry::sdk::{trace, Resource};
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use sabre_sdk::protocol::payload::{Action, SabrePayload};
+use sabre_sdk::trace::{TraceParent, TRACEPARENT};
+use sawtooth_sdk::processor::handler::ApplyError;
+use tracing::field::Empty;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::layer::SubscriberExt;
+
+/// Installs the exporter, sending spans to the OTLP/HTTP collector at the endpoint as the named
+/// service.
+pub fn init(endpoint: &str, service_name: &str) -> Result<(), String> {
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .http()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(
+            trace::config().with_resource(Resource::new(vec![KeyValue::new(
+                "service.name",
+                service_name.to_string(),
+            )])),
+        )
+        .install_simple()
+        .map_err(|err| format!("Unable to create the OTLP exporter: {}", err))?;
+
+    let subscriber =
+        tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+    tracing::subscriber::set_global_default(subscriber)
+        .map_err(|err| format!("Unable to install the tracing subscriber: {}", err))
+}
+
+/// Flushes the spans not yet exported.
+pub fn shutdown() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+/// Returns the span of a transaction's application, continuing the trace of its traceparent, if
+/// it has one.
+pub fn apply_span(signature: &str, trace: Option<&TraceParent>) -> Span {
+    let span = tracing::info_span!(
+        "sabre.apply",
+        "transaction.id" = signature,
+        "sabre.action" = Empty,
+        "contract.name" = Empty,
+        "contract.version" = Empty,
+        "otel.status_code" = Empty,
+        "otel.status_message" = Empty,
+    );
+
+    if let Some(trace) = trace {
+        let carrier: HashMap<String, String> = vec![(TRACEPARENT.to_string(), trace.to_string())]
+            .into_iter()
+            .collect();
+        span.set_parent(TraceContextPropagator::new().extract(&carrier));
+    }
+
+    span
+}
+
+/// Records the action of the transaction, and the contract it concerns, on the current span.
+pub fn record_action(payload: &SabrePayload) {
+    let span = Span::current();
+    let record = |action: &str, name: &str, version: Option<&str>| {
+        span.record("sabre.action", &action);
+        span.record("contract.name", &name);
+        if let Some(version) = version {
+            span.record("contract.version", &version);
+        }
+    };
+
+    match payload.action() {
+        Action::ExecuteContract(action) => {
+            record("execute_contract", action.name(), Some(action.version()))
+        }
+        Action::CreateContract(action) => {
+            record("create_contract", action.name(), Some(action.version()))
+        }
+        Action::DeleteContract(action) => {
+            record("delete_contract", action.name(), Some(action.version()))
+        }
+        Action::CreateContractRegistry(action) => {
+            record("create_contract_registry", action.name(), None)
+        }
+        Action::DeleteContractRegistry(action) => {
+            record("delete_contract_registry", action.name(), None)
+        }
+        Action::UpdateContractRegistryOwners(action) => {
+            record("update_contract_registry_owners", action.name(), None)
+        }
+        // Namespace registry actions do not concern a single contract
+        _ => {
+            span.record("sabre.action", &"namespace_registry");
+        }
+    }
+}
+
+/// Records the outcome of the transaction's application on its span.
+pub fn record_result(span: &Span, result: &Result<(), ApplyError>) {
+    match result {
+        Ok(()) => {
+            span.record("otel.status_code", &"OK");
+        }
+        Err(err) => {
+            span.record("otel.status_code", &"ERROR");
+            span.record("otel.status_message", &err.to_string().as_str());
+        }
+    }
+}